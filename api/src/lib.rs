@@ -125,14 +125,24 @@ struct WebSocketHandler {
 }
 
 impl WebSocketHandler {
-    fn new(peer: SocketAddr, sink: WsSink, stream: WsStream, wallet: Wallet, node: Node) -> Self {
+    fn new(
+        peer: SocketAddr,
+        sink: WsSink,
+        stream: WsStream,
+        wallet: Wallet,
+        node: Node,
+    ) -> Result<Self, WebSocketError> {
         let need_flush = false;
         let wallet_notifications = wallet.subscribe();
         let wallet_responses = Vec::new();
         let node_responses = Vec::new();
-        let node_block_added = node.subscribe_block_added();
-        let node_epoch_changed = node.subscribe_epoch_changed();
-        WebSocketHandler {
+        let node_unavailable = |e: Error| {
+            error!("[{}] Failed to subscribe to node events: {}", peer, e);
+            WebSocketError::RequestError("Node is not available")
+        };
+        let node_block_added = node.subscribe_block_added().map_err(node_unavailable)?;
+        let node_epoch_changed = node.subscribe_epoch_changed().map_err(node_unavailable)?;
+        Ok(WebSocketHandler {
             peer,
             sink,
             stream,
@@ -144,7 +154,7 @@ impl WebSocketHandler {
             node_responses,
             node_block_added,
             node_epoch_changed,
-        }
+        })
     }
 
     fn on_message(&mut self, text: String) -> Result<(), WebSocketError> {
@@ -161,8 +171,14 @@ impl WebSocketHandler {
                     .push((request.id, self.wallet.request(wallet_request)));
             }
             RequestKind::NodeRequest(node_request) => {
-                self.node_responses
-                    .push((request.id, self.node.request(node_request)));
+                let rx = match self.node.request(node_request) {
+                    Ok(rx) => rx,
+                    Err(e) => {
+                        error!("Failed to execute node request: {}", e);
+                        return Err(WebSocketError::RequestError("Node is not available"));
+                    }
+                };
+                self.node_responses.push((request.id, rx));
             }
         }
         Ok(())
@@ -358,6 +374,8 @@ impl WebSocketAPI {
                                     node3.clone(),
                                 )
                             })
+                            // Reject the connection if the node is gone.
+                            .and_then(|handler| handler)
                             .map_err(move |e| {
                                 error!("[{}] Error: {}", &peer, e);
                             })
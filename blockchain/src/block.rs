@@ -21,6 +21,7 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
+use crate::config::FeePolicy;
 use crate::merkle::*;
 use crate::output::*;
 use crate::transaction::Transaction;
@@ -224,8 +225,11 @@ impl MicroBlock {
         recipient_pkey: &curve1174::PublicKey,
         pkey: pbc::PublicKey,
         block_reward: i64,
+        fee_policy: FeePolicy,
     ) -> MicroBlock {
         let block_fee = transactions.iter().map(|tx| tx.fee()).sum();
+        // Burned fees are still recorded in the coinbase, but not paid out.
+        let paid_fee = fee_policy.paid_fee(block_fee);
 
         //
         // Coinbase.
@@ -235,7 +239,7 @@ impl MicroBlock {
         let mut gamma = Fr::zero();
 
         // Create outputs for fee and rewards.
-        for (amount, comment) in vec![(block_fee, "fee"), (block_reward, "reward")] {
+        for (amount, comment) in vec![(paid_fee, "fee"), (block_reward, "reward")] {
             if amount <= 0 {
                 continue;
             }
@@ -349,6 +353,23 @@ impl MacroBlock {
         Self::new(base, Fr::zero(), 0, &[], &[], None, pkey)
     }
 
+    /// Create a new macro block which pays block_reward to the leader.
+    pub fn with_reward(
+        base: BaseBlockHeader,
+        recipient_pkey: &curve1174::PublicKey,
+        pkey: pbc::PublicKey,
+        block_reward: i64,
+    ) -> MacroBlock {
+        if block_reward <= 0 {
+            return Self::empty(base, pkey);
+        }
+        let data = PaymentPayloadData::Comment("Block reward".to_string());
+        let (output, gamma) =
+            PaymentOutput::with_payload(recipient_pkey, block_reward, data).expect("invalid keys");
+        let outputs = [Output::PaymentOutput(output)];
+        Self::new(base, -gamma, block_reward, &[], &outputs, None, pkey)
+    }
+
     pub fn new(
         base: BaseBlockHeader,
         gamma: Fr,
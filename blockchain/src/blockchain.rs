@@ -37,7 +37,10 @@ use crate::transaction::{PaymentTransaction, Transaction};
 use crate::view_changes::ViewChangeProof;
 use failure::Error;
 use log::*;
+use serde_derive::Serialize;
+use std::cmp;
 use std::collections::BTreeMap;
+use std::ops::Bound;
 use std::time::{SystemTime, UNIX_EPOCH};
 use stegos_crypto::bulletproofs::fee_a;
 use stegos_crypto::curve1174::{ECp, Fr, PublicKey, SecretKey, G};
@@ -115,10 +118,23 @@ pub(crate) struct Balance {
     pub burned: ECp,
     /// The total sum of gamma adjustments.
     pub gamma: Fr,
-    /// The total sum of block rewards.
+    /// The total sum of block rewards, minus burned fees.
     pub block_reward: i64,
 }
 
+/// Information about an epoch.
+#[derive(Serialize, Clone, Debug)]
+pub struct EpochInfo {
+    /// 1-based identifier of the epoch.
+    pub epoch: u64,
+    /// Hash of the macro block which started this epoch.
+    pub macro_block_hash: Hash,
+    /// Facilitator of the transaction pool.
+    pub facilitator: pbc::PublicKey,
+    /// The number of validators.
+    pub validator_count: usize,
+}
+
 type BlockByHashMap = MultiVersionedMap<Hash, u64, u64>;
 type OutputByHashMap = MultiVersionedMap<Hash, OutputKey, u64>;
 type BalanceMap = MultiVersionedMap<(), Balance, u64>;
@@ -157,6 +173,12 @@ pub struct Blockchain {
     last_macro_block_timestamp: SystemTime,
     /// Last election result.
     election_result: ElectionResult,
+    /// Information about all epochs, ordered by epoch.
+    epochs: Vec<EpochInfo>,
+    /// The number of macro blocks in the genesis.
+    genesis_epochs: u64,
+    /// Zero-indexed identifier of the last macro block in the genesis.
+    last_genesis_macro_block_height: u64,
     //
     // Consensus information.
     //
@@ -225,6 +247,9 @@ impl Blockchain {
         let last_macro_block_height: u64 = 0;
         let last_macro_block_timestamp = UNIX_EPOCH;
         let election_result = ElectionResult::default();
+        let epochs: Vec<EpochInfo> = Vec::new();
+        let genesis_epochs: u64 = 0;
+        let last_genesis_macro_block_height: u64 = 0;
 
         //
         // Consensus information.
@@ -247,6 +272,9 @@ impl Blockchain {
             last_macro_block_height,
             last_macro_block_timestamp,
             election_result,
+            epochs,
+            genesis_epochs,
+            last_genesis_macro_block_height,
             view_change_proof,
             height,
             last_block_hash,
@@ -261,6 +289,13 @@ impl Blockchain {
     //----------------------------------------------------------------------------------------------
 
     fn recover(&mut self, genesis: Vec<Block>, timestamp: SystemTime) -> Result<(), Error> {
+        for block in &genesis {
+            if let Block::MacroBlock(block) = block {
+                self.genesis_epochs += 1;
+                self.last_genesis_macro_block_height = block.header.base.height;
+            }
+        }
+
         let mut blocks = self.database.iter();
 
         let block = blocks.next();
@@ -274,7 +309,7 @@ impl Blockchain {
                         self.push_micro_block(micro_block, timestamp)?;
                     }
                     Block::MacroBlock(macro_block) => {
-                        self.push_macro_block(macro_block, timestamp)?
+                        self.push_macro_block(macro_block, timestamp)?;
                     }
                 }
             }
@@ -415,6 +450,22 @@ impl Blockchain {
         self.output_by_hash.keys()
     }
 
+    /// Returns an iterator over UTXO hashes in ascending order, starting after `after`.
+    pub fn unspent_after(&self, after: Option<Hash>) -> impl Iterator<Item = &Hash> {
+        let start = match after {
+            Some(after) => Bound::Excluded(after),
+            None => Bound::Unbounded,
+        };
+        self.output_by_hash
+            .range((start, Bound::Unbounded))
+            .map(|(output_hash, _)| output_hash)
+    }
+
+    /// Returns the number of UTXO.
+    pub fn unspent_count(&self) -> usize {
+        self.output_by_hash.len()
+    }
+
     /// Returns true if blockchain contains unspent output.
     pub fn contains_output(&self, output_hash: &Hash) -> bool {
         self.output_by_hash.get(output_hash).is_some()
@@ -470,6 +521,18 @@ impl Blockchain {
         }
     }
 
+    /// Returns the height of the block which has created UTXO as a block reward.
+    /// Returns None for other outputs, including outputs of the genesis block.
+    pub fn coinbase_height(&self, output_hash: &Hash) -> Option<u64> {
+        match self.output_by_hash.get(output_hash) {
+            Some(OutputKey::MacroBlock { height, .. }) if *height > 0 => Some(*height),
+            Some(OutputKey::MicroBlock { height, tx_id, .. }) if *tx_id == std::u32::MAX => {
+                Some(*height)
+            }
+            _ => None,
+        }
+    }
+
     /// Checks whether a block exists or not.
     pub fn contains_block(&self, block_hash: &Hash) -> bool {
         if let Some(_height) = self.block_by_hash.get(block_hash) {
@@ -508,6 +571,15 @@ impl Blockchain {
         self.election_result.select_leader(view_change)
     }
 
+    /// Returns leaders for `count` consecutive view_changes, starting from the current one.
+    /// The chain state is not changed.
+    pub fn simulate_leaders(&self, count: u32) -> Vec<pbc::PublicKey> {
+        let view_change = self.view_change();
+        (0..count)
+            .map(|i| self.select_leader(view_change + i))
+            .collect()
+    }
+
     /// Returns public key of the active leader.
     pub fn leader(&self) -> pbc::PublicKey {
         self.select_leader(self.view_change())
@@ -540,6 +612,18 @@ impl Blockchain {
         self.last_macro_block_height
     }
 
+    /// Returns the number of epochs started by the genesis.
+    #[inline]
+    pub fn genesis_epochs(&self) -> u64 {
+        self.genesis_epochs
+    }
+
+    /// Returns the height of the last macro block in the genesis.
+    #[inline]
+    pub fn last_genesis_macro_block_height(&self) -> u64 {
+        self.last_genesis_macro_block_height
+    }
+
     /// Return the timestamp from the last macro block.
     #[inline]
     pub fn last_macro_block_timestamp(&self) -> SystemTime {
@@ -565,6 +649,14 @@ impl Blockchain {
         self.height
     }
 
+    /// Returns information about up to `limit` epochs, starting from `from_epoch`,
+    /// in ascending order.
+    pub fn epoch_history(&self, from_epoch: u64, limit: usize) -> &[EpochInfo] {
+        let start = cmp::min(from_epoch.saturating_sub(1) as usize, self.epochs.len());
+        let end = cmp::min(start.saturating_add(limit), self.epochs.len());
+        &self.epochs[start..end]
+    }
+
     /// Return the current blockchain epoch.
     #[inline(always)]
     pub fn epoch(&self) -> u64 {
@@ -581,12 +673,36 @@ impl Blockchain {
         self.escrow.get(validator_pkey, self.epoch)
     }
 
+    ///
+    /// Returns the last epoch when the stake is active, None if the stake doesn't exist.
+    ///
+    #[inline]
+    pub(crate) fn stake_active_until_epoch(
+        &self,
+        validator_pkey: &pbc::PublicKey,
+        output_hash: &Hash,
+    ) -> Option<u64> {
+        self.escrow.active_until_epoch(validator_pkey, output_hash)
+    }
+
     /// Return information about escrow.
     #[inline]
     pub fn escrow_info(&self) -> EscrowInfo {
         self.escrow.info(self.epoch)
     }
 
+    /// Returns the total amount of stakes in escrow.
+    #[inline]
+    pub fn total_stake(&self) -> i64 {
+        self.escrow.total()
+    }
+
+    /// Returns the blockchain configuration.
+    #[inline]
+    pub fn cfg(&self) -> &BlockchainConfig {
+        &self.cfg
+    }
+
     /// Returns balance.
     #[inline]
     pub(crate) fn balance(&self) -> &Balance {
@@ -629,6 +745,30 @@ impl Blockchain {
         self.election_result.clone()
     }
 
+    ///
+    /// Returns the election result used to select the leader of a block at `height`.
+    ///
+    /// Only heights of the current epoch are supported, including the next
+    /// block. `view_change` of the result is reset to 0 for past heights.
+    ///
+    pub fn election_result_at(&self, height: u64) -> Result<ElectionResult, Error> {
+        if height <= self.last_macro_block_height || height > self.height {
+            return Err(BlockchainError::HeightOutOfEpoch(
+                height,
+                self.last_macro_block_height,
+                self.height,
+            )
+            .into());
+        }
+        let mut election_result = self.election_result.clone();
+        if height < self.height {
+            let previous = self.block_by_height(height - 1)?;
+            election_result.random = previous.base_header().random;
+            election_result.view_change = 0;
+        }
+        Ok(election_result)
+    }
+
     //----------------------------------------------------------------------------------------------
     // Macro Blocks
     //----------------------------------------------------------------------------------------------
@@ -640,7 +780,7 @@ impl Blockchain {
         &mut self,
         block: MacroBlock,
         timestamp: SystemTime,
-    ) -> Result<(), BlockchainError> {
+    ) -> Result<(Vec<Output>, Vec<Output>), BlockchainError> {
         //
         // Validate the macro block.
         //
@@ -655,9 +795,9 @@ impl Blockchain {
         //
         // Update in-memory indexes and metadata.
         //
-        self.register_macro_block(block, timestamp)?;
+        let (inputs, outputs) = self.register_macro_block(block, timestamp)?;
 
-        Ok(())
+        Ok((inputs, outputs))
     }
 
     ///
@@ -725,6 +865,14 @@ impl Blockchain {
             self.cfg.max_slot_count,
         );
         metrics::EPOCH.inc();
+        let epoch_info = EpochInfo {
+            epoch: self.epoch,
+            macro_block_hash: block_hash,
+            facilitator: self.election_result.facilitator.clone(),
+            validator_count: self.election_result.validators.len(),
+        };
+        assert_eq!(self.epochs.len() as u64 + 1, self.epoch);
+        self.epochs.push(epoch_info);
 
         info!(
             "Registered a macro block: height={}, block={}",
@@ -929,6 +1077,7 @@ impl Blockchain {
         assert_eq!(self.height, version);
         metrics::HEIGHT.set(self.height as i64);
         metrics::UTXO_LEN.set(self.output_by_hash.len() as i64);
+        metrics::TOTAL_STAKE.set(self.escrow.total());
     }
 
     ///
@@ -992,6 +1141,10 @@ impl Blockchain {
         //
         // Register block.
         //
+        // Burned fees leave circulation, so they are subtracted from the emission.
+        let burned_fee =
+            block.coinbase.block_fee - self.cfg.fee_policy.paid_fee(block.coinbase.block_fee);
+        let block_reward = block.coinbase.block_reward - burned_fee;
         self.register_block(
             block_hash,
             input_hashes,
@@ -999,7 +1152,7 @@ impl Blockchain {
             output_keys,
             &outputs,
             gamma,
-            block.coinbase.block_reward,
+            block_reward,
             block.base.random,
             timestamp,
         );
@@ -1062,6 +1215,7 @@ impl Blockchain {
         self.reset_view_change();
         metrics::HEIGHT.set(self.height as i64);
         metrics::UTXO_LEN.set(self.output_by_hash.len() as i64);
+        metrics::TOTAL_STAKE.set(self.escrow.total());
 
         let mut created: Vec<Output> = Vec::new();
         let mut pruned: Vec<Output> = Vec::new();
@@ -1215,6 +1369,7 @@ pub fn create_fake_micro_block(
         &keys.wallet_pkey,
         keys.network_pkey,
         block_reward,
+        chain.cfg().fee_policy,
     );
     block.sign(&keys.network_skey, &keys.network_pkey);
     (block, input_hashes, output_hashes)
@@ -1395,6 +1550,23 @@ pub mod tests {
             assert_eq!(height + 1, chain.height());
         }
 
+        //
+        // Epoch history.
+        //
+        assert_eq!(chain.epoch(), 3);
+        let epochs = chain.epoch_history(0, 10).to_vec();
+        assert_eq!(epochs.len(), 3);
+        for (i, epoch_info) in epochs.iter().enumerate() {
+            assert_eq!(epoch_info.epoch, i as u64 + 1);
+            assert_eq!(epoch_info.validator_count, keychains.len());
+        }
+        assert_eq!(epochs[2].macro_block_hash, chain.last_block_hash());
+        assert_eq!(&epochs[2].facilitator, chain.facilitator());
+        let page = chain.epoch_history(2, 1);
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].epoch, 2);
+        assert!(chain.epoch_history(4, 10).is_empty());
+
         //
         // Recovery.
         //
@@ -1407,6 +1579,12 @@ pub mod tests {
             .expect("Failed to create blockchain");
         assert_eq!(height, chain.height());
         assert_eq!(block_hash, chain.last_block_hash());
+        let epochs2 = chain.epoch_history(0, 10);
+        assert_eq!(epochs2.len(), epochs.len());
+        for (a, b) in epochs.iter().zip(epochs2) {
+            assert_eq!(a.epoch, b.epoch);
+            assert_eq!(a.macro_block_hash, b.macro_block_hash);
+        }
         assert_eq!(chain.blocks().count() as u64, chain.height());
         assert_eq!(&balance, chain.balance());
     }
@@ -1435,11 +1613,19 @@ pub mod tests {
         let block_hash0 = chain.last_block_hash();
         let balance0 = chain.balance().clone();
         let escrow0 = chain.escrow_info().clone();
+        let unspent_count0 = chain.unspent_count();
+        let total_stake0: i64 = escrow0
+            .validators
+            .iter()
+            .map(|v| v.active_stake + v.expired_stake)
+            .sum();
+        assert_eq!(chain.total_stake(), total_stake0);
 
         // Register a micro block.
         timestamp += Duration::from_millis(1);
         let (block1, input_hashes1, output_hashes1) =
             create_fake_micro_block(&mut chain, &keychains, timestamp, 60);
+        let coinbase_outputs1 = block1.coinbase.outputs.len();
         chain
             .push_micro_block(block1, timestamp)
             .expect("block is valid");
@@ -1454,6 +1640,11 @@ pub mod tests {
         for output_hash in &output_hashes1 {
             assert!(chain.contains_output(output_hash));
         }
+        assert_eq!(
+            chain.unspent_count(),
+            unspent_count0 + coinbase_outputs1 + output_hashes1.len() - input_hashes1.len()
+        );
+        assert_eq!(chain.total_stake(), total_stake0);
         let height1 = chain.height();
         let block_hash1 = chain.last_block_hash();
         let balance1 = chain.balance().clone();
@@ -1501,6 +1692,8 @@ pub mod tests {
         assert_eq!(chain.blocks().count() as u64, chain.height());
         assert_eq!(&balance0, chain.balance());
         assert_eq!(escrow0, chain.escrow_info());
+        assert_eq!(chain.unspent_count(), unspent_count0);
+        assert_eq!(chain.total_stake(), total_stake0);
         for input_hash in &input_hashes1 {
             assert!(chain.contains_output(&input_hash));
         }
@@ -1552,4 +1745,111 @@ pub mod tests {
         // empty
         assert_eq!(blockchain.blocks_range(blockchain.height(), 1).len(), 0);
     }
+
+    #[test]
+    fn election_result_at() {
+        simple_logger::init_with_level(log::Level::Debug).unwrap_or_default();
+        let keychains = [
+            KeyChain::new_mem(),
+            KeyChain::new_mem(),
+            KeyChain::new_mem(),
+        ];
+
+        let mut timestamp = SystemTime::now();
+        let cfg: BlockchainConfig = Default::default();
+        let stake = cfg.min_stake_amount;
+        let blocks = genesis(&keychains, stake, 10 * cfg.min_stake_amount, timestamp);
+        let mut blockchain =
+            Blockchain::testing(cfg, blocks, timestamp).expect("Failed to create blockchain");
+        let starting_height = blockchain.height();
+        for _ in 0..10 {
+            timestamp += Duration::from_millis(1);
+            let block = create_empty_micro_block(&blockchain, &keychains, timestamp);
+            blockchain
+                .push_micro_block(block, timestamp)
+                .expect("Invalid block");
+        }
+
+        // Reproduces leaders of historical blocks.
+        for height in starting_height..blockchain.height() {
+            let block = match blockchain.block_by_height(height).unwrap() {
+                Block::MicroBlock(block) => block,
+                Block::MacroBlock(_) => panic!("Expected a micro block"),
+            };
+            let election_result = blockchain.election_result_at(height).unwrap();
+            assert_eq!(election_result.view_change, 0);
+            assert_eq!(
+                election_result.select_leader(block.base.view_change),
+                block.pkey
+            );
+        }
+
+        // The next block.
+        let election_result = blockchain.election_result_at(blockchain.height()).unwrap();
+        assert_eq!(
+            election_result.select_leader(blockchain.view_change()),
+            blockchain.leader()
+        );
+
+        // Out of the current epoch.
+        assert!(blockchain
+            .election_result_at(blockchain.last_macro_block_height())
+            .is_err());
+        assert!(blockchain
+            .election_result_at(blockchain.height() + 1)
+            .is_err());
+    }
+
+    #[test]
+    fn wrong_leader() {
+        simple_logger::init_with_level(log::Level::Debug).unwrap_or_default();
+        let keychains = [
+            KeyChain::new_mem(),
+            KeyChain::new_mem(),
+            KeyChain::new_mem(),
+        ];
+
+        let mut timestamp = SystemTime::now();
+        let cfg: BlockchainConfig = Default::default();
+        let stake = cfg.min_stake_amount;
+        let blocks = genesis(&keychains, stake, 10 * cfg.min_stake_amount, timestamp);
+        let mut blockchain =
+            Blockchain::testing(cfg, blocks, timestamp).expect("Failed to create blockchain");
+        let height = blockchain.height();
+
+        // A validator which is not the leader of the current view_change.
+        let view_change = blockchain.view_change();
+        let leader = blockchain.select_leader(view_change);
+        let keys = keychains.iter().find(|k| k.network_pkey != leader).unwrap();
+        timestamp += Duration::from_millis(1);
+        let seed = mix(blockchain.last_random(), view_change);
+        let random = pbc::make_VRF(&keys.network_skey, &seed);
+        let base = BaseBlockHeader::new(
+            VERSION,
+            blockchain.last_block_hash(),
+            height,
+            view_change,
+            timestamp,
+            random,
+        );
+        let mut block = MicroBlock::empty(base, None, keys.network_pkey);
+        block.sign(&keys.network_skey, &keys.network_pkey);
+
+        let e = blockchain.push_micro_block(block, timestamp).unwrap_err();
+        match e {
+            BlockchainError::BlockError(BlockError::DifferentPublicKey(elected, sender)) => {
+                assert_eq!(elected, leader);
+                assert_eq!(sender, keys.network_pkey);
+            }
+            e => panic!("Unexpected error: {}", e),
+        }
+        assert_eq!(blockchain.height(), height);
+
+        // The elected leader.
+        let block = create_empty_micro_block(&blockchain, &keychains, timestamp);
+        blockchain
+            .push_micro_block(block, timestamp)
+            .expect("block is valid");
+        assert_eq!(blockchain.height(), height + 1);
+    }
 }
@@ -23,6 +23,69 @@
 
 use serde_derive::{Deserialize, Serialize};
 
+/// What to do with transaction fees collected in a micro block.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FeePolicy {
+    /// Fees are destroyed and never re-enter circulation.
+    Burn,
+    /// Fees are paid to the leader in the coinbase.
+    RewardLeader,
+}
+
+impl FeePolicy {
+    /// Returns the part of `block_fee` which is paid to the leader in the coinbase.
+    pub fn paid_fee(&self, block_fee: i64) -> i64 {
+        match self {
+            FeePolicy::Burn => 0,
+            FeePolicy::RewardLeader => block_fee,
+        }
+    }
+}
+
+impl Default for FeePolicy {
+    fn default() -> Self {
+        FeePolicy::RewardLeader
+    }
+}
+
+/// The share of slots which must sign a block or a view change.
+///
+/// The weight of signers must be strictly greater than
+/// `numerator / denominator` of the total number of slots.
+/// The default is 2/3, which tolerates less than 1/3 of faulty slots.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BftThreshold {
+    pub numerator: i64,
+    pub denominator: i64,
+}
+
+impl BftThreshold {
+    /// Returns the minimal weight of signers out of `total_slots`.
+    pub fn required(&self, total_slots: i64) -> i64 {
+        assert!(self.denominator > 0);
+        assert!(self.numerator >= 0 && self.numerator < self.denominator);
+        assert!(total_slots > 0);
+        total_slots * self.numerator / self.denominator + 1
+    }
+
+    /// Returns true if `got_slots` out of `total_slots` reach the threshold.
+    pub fn is_reached(&self, got_slots: i64, total_slots: i64) -> bool {
+        assert!(got_slots <= total_slots);
+        assert!(got_slots >= 0);
+        got_slots >= self.required(total_slots)
+    }
+}
+
+impl Default for BftThreshold {
+    fn default() -> Self {
+        BftThreshold {
+            numerator: 2,
+            denominator: 3,
+        }
+    }
+}
+
 /// Blockchain configuration.
 #[derive(Debug, Clone)]
 pub struct BlockchainConfig {
@@ -32,6 +95,13 @@ pub struct BlockchainConfig {
     pub min_stake_amount: i64,
     /// How many epochs stake is valid.
     pub stake_epochs: u64,
+    /// What to do with transaction fees.
+    pub fee_policy: FeePolicy,
+    /// The share of slots required to sign blocks and view changes.
+    pub bft_threshold: BftThreshold,
+    /// The number of confirmations, counting the block itself,
+    /// required to spend rewards of a block. Zero disables the check.
+    pub coinbase_maturity: u64,
 }
 
 impl Default for BlockchainConfig {
@@ -40,6 +110,9 @@ impl Default for BlockchainConfig {
             max_slot_count: 1000,
             min_stake_amount: 1_000_000_000, // 1000 STG
             stake_epochs: 2,
+            fee_policy: FeePolicy::RewardLeader,
+            bft_threshold: BftThreshold::default(),
+            coinbase_maturity: 0,
         }
     }
 }
@@ -41,6 +41,16 @@ pub enum BlockchainError {
         _0, _1, _2
     )]
     StakeIsLocked(pbc::PublicKey, i64, i64),
+    #[fail(
+        display = "Stake is not matured: utxo={}, active_until_epoch={}, epoch={}",
+        _0, _1, _2
+    )]
+    StakeIsNotMatured(Hash, u64, u64),
+    #[fail(
+        display = "Height is not in the current epoch: height={}, last_macro_block_height={}, current_height={}",
+        _0, _1, _2
+    )]
+    HeightOutOfEpoch(u64, u64, u64),
     #[fail(display = "Internal storage error={}", _0)]
     StorageError(failure::Error),
     #[fail(display = "Transaction error={}", _0)]
@@ -72,6 +82,11 @@ pub enum TransactionError {
     DuplicateOutput(Hash, Hash),
     #[fail(display = "Output hash collision: tx={}, utxo={}", _0, _1)]
     OutputHashCollision(Hash, Hash),
+    #[fail(
+        display = "Immature block reward: tx={}, utxo={}, confirmations={}, required={}",
+        _0, _1, _2, _3
+    )]
+    ImmatureCoinbase(Hash, Hash, u64, u64),
 
     #[fail(display = "Non-StakeUTXO found in TXINs: tx = {}. utxo={}", _0, _1)]
     InvalidRestakingInput(Hash, Hash),
@@ -105,6 +120,11 @@ pub enum MultisignatureError {
         _0, _1
     )]
     TooBigBitmap(usize, usize),
+    #[fail(
+        display = "Signature bitmap has a bit out of range: bit={}, validators_len={} ",
+        _0, _1
+    )]
+    BitmapOutOfRange(usize, usize),
     #[fail(
         display = "Not enough votes in signature: votes={}, needed_votes={} ",
         _0, _1
@@ -139,6 +139,21 @@ impl Escrow {
         );
     }
 
+    ///
+    /// Returns the last epoch when the stake is active, None if the stake doesn't exist.
+    ///
+    pub fn active_until_epoch(
+        &self,
+        validator_pkey: &pbc::PublicKey,
+        output_hash: &Hash,
+    ) -> Option<u64> {
+        let key = EscrowKey {
+            validator_pkey: validator_pkey.clone(),
+            output_hash: output_hash.clone(),
+        };
+        self.escrow.get(&key).map(|value| value.active_until_epoch)
+    }
+
     ///
     /// Get staked value for validator.
     ///
@@ -195,6 +210,11 @@ impl Escrow {
             .collect()
     }
 
+    /// Returns the total amount of all stakes, both active and expired.
+    pub fn total(&self) -> i64 {
+        self.escrow.iter().map(|(_k, v)| v.amount).sum()
+    }
+
     /// Returns an object that represent printable part of the state.
     pub fn info(&self, epoch: u64) -> EscrowInfo {
         let mut validators: HashMap<pbc::PublicKey, ValidatorInfo> = HashMap::new();
@@ -38,6 +38,18 @@ pub fn genesis(
     coins: i64,
     timestamp: SystemTime,
 ) -> Vec<Block> {
+    let stakes = vec![stake; keychains.len()];
+    genesis_with_stakes(keychains, &stakes, coins, timestamp)
+}
+
+/// Genesis blocks with a different stake for each node.
+pub fn genesis_with_stakes(
+    keychains: &[KeyChain],
+    stakes: &[i64],
+    coins: i64,
+    timestamp: SystemTime,
+) -> Vec<Block> {
+    assert_eq!(keychains.len(), stakes.len());
     let mut blocks = Vec::with_capacity(2);
 
     // Both block are created at the same time in the same epoch.
@@ -60,19 +72,19 @@ pub fn genesis(
         //
 
         // Node #1 receives all moneys except stakes.
-        // All nodes gets `stake` money staked.
+        // Each node gets its `stake` money staked.
         //
         let mut outputs: Vec<Output> = Vec::with_capacity(1 + keychains.len());
 
         // Create PaymentOutput for node #1.
         let recipient_pkey = &keychains[0].wallet_pkey;
-        let mut coins1: i64 = coins - keychains.len() as i64 * stake;
+        let mut coins1: i64 = coins - stakes.iter().sum::<i64>();
         let (output, outputs_gamma) =
             Output::new_payment(recipient_pkey, coins1).expect("genesis has valid public keys");
         outputs.push(output);
 
         // Create StakeOutput for each node.
-        for keys in keychains {
+        for (keys, stake) in keychains.iter().zip(stakes.iter().cloned()) {
             let output = Output::new_stake(
                 &keys.wallet_pkey,
                 &keys.network_skey,
@@ -99,7 +111,7 @@ pub fn genesis(
         let block_hash = Hash::digest(&block);
         let mut signatures: BTreeMap<pbc::PublicKey, pbc::Signature> = BTreeMap::new();
         let mut validators: BTreeMap<pbc::PublicKey, i64> = BTreeMap::new();
-        for keychain in keychains.iter() {
+        for (keychain, stake) in keychains.iter().zip(stakes.iter().cloned()) {
             let sig = pbc::sign_hash(&block_hash, &keychain.network_skey);
             signatures.insert(keychain.network_pkey.clone(), sig);
             validators.insert(keychain.network_pkey.clone(), stake);
@@ -51,3 +51,4 @@ pub use crate::multisignature::*;
 pub use crate::output::*;
 pub use crate::storage::*;
 pub use crate::transaction::*;
+pub use crate::validation::{verify_sealed_block, StakingBalance};
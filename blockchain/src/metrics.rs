@@ -37,4 +37,19 @@ lazy_static! {
         register_int_gauge!("stegos_blockchain_height", "Blockchain blocks count").unwrap();
     pub static ref UTXO_LEN: IntGauge =
         register_int_gauge!("stegos_blockchain_utxo", "Size of UTXO map").unwrap();
+    pub static ref MULTISIG_CREATE_SECONDS: Histogram = register_histogram!(
+        "stegos_multisig_create_seconds",
+        "Time spent on aggregation of signatures into a multi-signature"
+    )
+    .unwrap();
+    pub static ref MULTISIG_VERIFY_SECONDS: Histogram = register_histogram!(
+        "stegos_multisig_verify_seconds",
+        "Time spent on aggregation of public keys for a multi-signature check"
+    )
+    .unwrap();
+    pub static ref TOTAL_STAKE: IntGauge = register_int_gauge!(
+        "stegos_blockchain_total_stake",
+        "Total amount of stakes in escrow"
+    )
+    .unwrap();
 }
@@ -21,22 +21,21 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
+use crate::config::BftThreshold;
 use crate::error::MultisignatureError;
+use crate::metrics;
 use crate::VALIDATORS_MAX;
 use bitvector::BitVector;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use stegos_crypto::hash::Hash;
 use stegos_crypto::pbc;
 
 ///
-/// Return true if supermajority of votes has been collected.
+/// Return true if supermajority of votes has been collected,
+/// using the default 2/3 threshold.
 ///
 pub fn check_supermajority(got_votes: i64, total_votes: i64) -> bool {
-    assert!(got_votes <= total_votes);
-    assert!(got_votes >= 0);
-    assert!(total_votes > 0);
-    let need_votes = (total_votes * 2 + 3) / 3;
-    (got_votes >= need_votes)
+    BftThreshold::default().is_reached(got_votes, total_votes)
 }
 
 ///
@@ -72,6 +71,7 @@ where
     let mut vec: Vec<_> = signatures.collect();
     vec.sort_by_key(|i| i.0);
 
+    let timer = metrics::MULTISIG_CREATE_SECONDS.start_timer();
     for (bit, sig) in vec {
         assert!(bit < VALIDATORS_MAX as u32);
         let sig: pbc::G1 = sig.clone().into();
@@ -79,12 +79,82 @@ where
         let ok = multisigmap.insert(bit as usize);
         assert!(ok);
     }
+    timer.observe_duration();
 
     let multisig: pbc::Signature = multisig.into();
 
     (multisig, multisigmap)
 }
 
+///
+/// Incrementally aggregates individual signatures into a multi-signature.
+/// Produces the same result as create_multi_signature() for the same signatures.
+///
+pub struct MultiSignatureBuilder {
+    /// Validator ids by public key.
+    ids: HashMap<pbc::PublicKey, u32>,
+    /// Aggregated signature.
+    multisig: pbc::G1,
+    /// Ids of signers.
+    multisigmap: BitVector,
+}
+
+impl MultiSignatureBuilder {
+    ///
+    /// Create an empty multi-signature for the given validators.
+    ///
+    pub fn new(validators: &Vec<(pbc::PublicKey, i64)>) -> Self {
+        let ids: HashMap<pbc::PublicKey, u32> = validators
+            .iter()
+            .enumerate()
+            .map(|(id, (pkey, _stake))| (pkey.clone(), id as u32))
+            .collect();
+        let multisig = pbc::G1::zero();
+        let multisigmap = BitVector::new(VALIDATORS_MAX);
+        MultiSignatureBuilder {
+            ids,
+            multisig,
+            multisigmap,
+        }
+    }
+
+    ///
+    /// Add a signature of the validator `pkey`.
+    ///
+    /// ## Panics
+    /// If `pkey` is not a validator or its signature has already been added.
+    ///
+    pub fn add(&mut self, pkey: &pbc::PublicKey, sig: &pbc::Signature) {
+        let id = *self.ids.get(pkey).expect("signer is a validator");
+        assert!(id < VALIDATORS_MAX as u32);
+        let ok = self.multisigmap.insert(id as usize);
+        assert!(ok, "duplicate signature: pkey={}", pkey);
+        let sig: pbc::G1 = sig.clone().into();
+        self.multisig += sig;
+    }
+
+    ///
+    /// Return the number of added signatures.
+    ///
+    pub fn len(&self) -> usize {
+        self.multisigmap.len()
+    }
+
+    ///
+    /// Return true if no signatures have been added.
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.multisigmap.len() == 0
+    }
+
+    ///
+    /// Return the aggregated signature and the bitmap of signers.
+    ///
+    pub fn finish(self) -> (pbc::Signature, BitVector) {
+        (self.multisig.into(), self.multisigmap)
+    }
+}
+
 ///
 /// Check multi-signature of group, each signature is weighted by stake.
 ///
@@ -94,6 +164,7 @@ pub fn check_multi_signature(
     multisigmap: &BitVector,
     validators: &Vec<(pbc::PublicKey, i64)>,
     total_slots: i64,
+    threshold: BftThreshold,
 ) -> Result<(), MultisignatureError> {
     // Check for trailing bits in the bitmap.
     if multisigmap.len() > validators.len() {
@@ -103,10 +174,21 @@ pub fn check_multi_signature(
         ));
     };
 
+    // Check that each bit corresponds to a validator.
+    if let Some(bit) = multisigmap.iter().find(|bit| *bit >= validators.len()) {
+        return Err(MultisignatureError::BitmapOutOfRange(bit, validators.len()));
+    }
+
+    // Nothing to aggregate.
+    if multisigmap.len() == 0 {
+        return Err(MultisignatureError::NotEnoughtVotes(0, total_slots));
+    }
+
     let mut multisigpkey = pbc::G2::zero();
     // total count of group slots
     let mut group_total_slots = 0;
 
+    let timer = metrics::MULTISIG_VERIFY_SECONDS.start_timer();
     for bit in multisigmap.iter() {
         let validator = &validators[bit];
         let pkey: pbc::G2 = validator.0.into();
@@ -116,9 +198,10 @@ pub fn check_multi_signature(
         multisigpkey += pkey;
         group_total_slots += slots;
     }
+    timer.observe_duration();
 
     // Multi-signature must be signed by the supermajority of validators.
-    if !check_supermajority(group_total_slots, total_slots) {
+    if !threshold.is_reached(group_total_slots, total_slots) {
         return Err(MultisignatureError::NotEnoughtVotes(
             group_total_slots,
             total_slots,
@@ -199,6 +282,279 @@ mod tests {
         }
 
         let multisig = create_multi_signature_index(signatures.iter().map(|p| (p.1, &p.0)));
-        assert!(check_multi_signature(hash, &multisig.0, &multisig.1, &validators, 1).is_ok())
+        assert!(check_multi_signature(
+            hash,
+            &multisig.0,
+            &multisig.1,
+            &validators,
+            1,
+            BftThreshold::default()
+        )
+        .is_ok())
+    }
+
+    #[test]
+    fn empty_multisig() {
+        let (_skey, pkey) = pbc::make_random_keys();
+        let validators = vec![(pkey, 1)];
+        let ref hash = Hash::digest("test");
+
+        let (multisig, multisigmap) = create_multi_signature_index(std::iter::empty());
+        assert_eq!(multisigmap.len(), 0);
+        match check_multi_signature(
+            hash,
+            &multisig,
+            &multisigmap,
+            &validators,
+            1,
+            BftThreshold::default(),
+        ) {
+            Err(MultisignatureError::NotEnoughtVotes(0, 1)) => {}
+            e => panic!("Unexpected result: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn bft_threshold() {
+        // The default threshold matches the classic 2/3 + 1 rule.
+        let threshold = BftThreshold::default();
+        for total_slots in 1..100 {
+            assert_eq!(threshold.required(total_slots), 2 * total_slots / 3 + 1);
+        }
+        assert_eq!(threshold.required(3), 3);
+        assert_eq!(threshold.required(4), 3);
+        assert_eq!(threshold.required(10), 7);
+
+        let ref hash = Hash::digest("test");
+        let mut validators = Vec::new();
+        let mut skeys = Vec::new();
+        for _ in 0..10 {
+            let (skey, pkey) = pbc::make_random_keys();
+            validators.push((pkey, 1));
+            skeys.push(skey);
+        }
+        let sign = |count: usize| {
+            let signatures: Vec<(u32, pbc::Signature)> = (0..count)
+                .map(|id| (id as u32, pbc::sign_hash(hash, &skeys[id])))
+                .collect();
+            create_multi_signature_index(signatures.iter().map(|(id, sig)| (*id, sig)))
+        };
+
+        for threshold in &[
+            BftThreshold::default(),
+            BftThreshold {
+                numerator: 1,
+                denominator: 2,
+            },
+            BftThreshold {
+                numerator: 4,
+                denominator: 5,
+            },
+        ] {
+            let required = threshold.required(10) as usize;
+            assert!(required <= validators.len());
+
+            // Threshold minus one.
+            let (multisig, multisigmap) = sign(required - 1);
+            match check_multi_signature(hash, &multisig, &multisigmap, &validators, 10, *threshold)
+            {
+                Err(MultisignatureError::NotEnoughtVotes(got, 10)) => {
+                    assert_eq!(got as usize, required - 1)
+                }
+                e => panic!("Unexpected result: {:?}", e),
+            }
+
+            // Threshold.
+            let (multisig, multisigmap) = sign(required);
+            check_multi_signature(hash, &multisig, &multisigmap, &validators, 10, *threshold)
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn multisig_errors() {
+        let ref hash = Hash::digest("test");
+        let ref other_hash = Hash::digest("other");
+        let mut validators = Vec::new();
+        let mut skeys = Vec::new();
+        for _ in 0..4 {
+            let (skey, pkey) = pbc::make_random_keys();
+            validators.push((pkey, 1));
+            skeys.push(skey);
+        }
+        let sign = |hash: &Hash, ids: &[u32]| {
+            let signatures: Vec<(u32, pbc::Signature)> = ids
+                .iter()
+                .map(|id| (*id, pbc::sign_hash(hash, &skeys[*id as usize])))
+                .collect();
+            create_multi_signature_index(signatures.iter().map(|(id, sig)| (*id, sig)))
+        };
+
+        // Valid.
+        let (multisig, multisigmap) = sign(hash, &[0, 1, 2]);
+        check_multi_signature(
+            hash,
+            &multisig,
+            &multisigmap,
+            &validators,
+            4,
+            BftThreshold::default(),
+        )
+        .unwrap();
+
+        // More signers than validators.
+        let (multisig, multisigmap) = sign(hash, &[0, 1, 2, 3]);
+        match check_multi_signature(
+            hash,
+            &multisig,
+            &multisigmap,
+            &validators[..3].to_vec(),
+            3,
+            BftThreshold::default(),
+        ) {
+            Err(MultisignatureError::TooBigBitmap(4, 3)) => {}
+            e => panic!("Unexpected result: {:?}", e),
+        }
+
+        // No supermajority.
+        let (multisig, multisigmap) = sign(hash, &[0, 1]);
+        match check_multi_signature(
+            hash,
+            &multisig,
+            &multisigmap,
+            &validators,
+            4,
+            BftThreshold::default(),
+        ) {
+            Err(MultisignatureError::NotEnoughtVotes(2, 4)) => {}
+            e => panic!("Unexpected result: {:?}", e),
+        }
+
+        // Signature of another hash.
+        let (multisig, multisigmap) = sign(other_hash, &[0, 1, 2]);
+        match check_multi_signature(
+            hash,
+            &multisig,
+            &multisigmap,
+            &validators,
+            4,
+            BftThreshold::default(),
+        ) {
+            Err(MultisignatureError::InvalidSignature(h)) => assert_eq!(&h, hash),
+            e => panic!("Unexpected result: {:?}", e),
+        }
+
+        // Bitmap doesn't match the signature.
+        let (multisig, _multisigmap) = sign(hash, &[0, 1, 2]);
+        let (_multisig, multisigmap) = sign(hash, &[1, 2, 3]);
+        match check_multi_signature(
+            hash,
+            &multisig,
+            &multisigmap,
+            &validators,
+            4,
+            BftThreshold::default(),
+        ) {
+            Err(MultisignatureError::InvalidSignature(h)) => assert_eq!(&h, hash),
+            e => panic!("Unexpected result: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn multisig_bitmap_out_of_range() {
+        let ref hash = Hash::digest("test");
+        let mut validators = Vec::new();
+        let mut signatures = Vec::new();
+        for i in 0..3 {
+            let (skey, pkey) = pbc::make_random_keys();
+            validators.push((pkey, 1));
+            signatures.push((i as u32, pbc::sign_hash(hash, &skey)));
+        }
+        // A stray bit beyond the validators list.
+        let (skey, _pkey) = pbc::make_random_keys();
+        signatures.push((10, pbc::sign_hash(hash, &skey)));
+
+        let (multisig, multisigmap) =
+            create_multi_signature_index(signatures.iter().map(|(id, sig)| (*id, sig)));
+        assert_eq!(multisigmap.len(), 4);
+        match check_multi_signature(
+            hash,
+            &multisig,
+            &multisigmap,
+            &validators,
+            3,
+            BftThreshold::default(),
+        ) {
+            Err(MultisignatureError::BitmapOutOfRange(10, 3)) => {}
+            e => panic!("Unexpected result: {:?}", e),
+        }
+
+        // The number of set bits doesn't exceed the number of validators.
+        signatures.remove(0);
+        let (multisig, multisigmap) =
+            create_multi_signature_index(signatures.iter().map(|(id, sig)| (*id, sig)));
+        assert_eq!(multisigmap.len(), 3);
+        match check_multi_signature(
+            hash,
+            &multisig,
+            &multisigmap,
+            &validators,
+            3,
+            BftThreshold::default(),
+        ) {
+            Err(MultisignatureError::BitmapOutOfRange(10, 3)) => {}
+            e => panic!("Unexpected result: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn multisig_builder() {
+        let ref hash = Hash::digest("test");
+        let mut validators = Vec::new();
+        let mut signatures: BTreeMap<pbc::PublicKey, pbc::Signature> = BTreeMap::new();
+        for i in 0..5 {
+            let (skey, pkey) = pbc::make_random_keys();
+            validators.push((pkey, 1));
+            // Validator #2 doesn't vote.
+            if i != 2 {
+                signatures.insert(pkey, pbc::sign_hash(hash, &skey));
+            }
+        }
+
+        // Signatures arrive in an arbitrary order.
+        let mut builder = MultiSignatureBuilder::new(&validators);
+        assert!(builder.is_empty());
+        for (pkey, sig) in signatures.iter().rev() {
+            builder.add(pkey, sig);
+        }
+        assert_eq!(builder.len(), signatures.len());
+        let (multisig, multisigmap) = builder.finish();
+
+        let (expected_multisig, expected_multisigmap) =
+            create_multi_signature(&validators, &signatures);
+        assert!(multisig == expected_multisig);
+        let bits: Vec<usize> = multisigmap.iter().collect();
+        let expected_bits: Vec<usize> = expected_multisigmap.iter().collect();
+        assert_eq!(bits, expected_bits);
+        check_multi_signature(
+            hash,
+            &multisig,
+            &multisigmap,
+            &validators,
+            5,
+            BftThreshold::default(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "duplicate signature")]
+    fn multisig_builder_duplicate() {
+        let (skey, pkey) = pbc::make_random_keys();
+        let validators = vec![(pkey, 1)];
+        let sig = pbc::sign_hash(&Hash::digest("test"), &skey);
+        let mut builder = MultiSignatureBuilder::new(&validators);
+        builder.add(&pkey, &sig);
+        builder.add(&pkey, &sig);
     }
 }
@@ -21,8 +21,9 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
-use crate::block::{MacroBlock, MicroBlock, VERSION};
+use crate::block::{Block, MacroBlock, MicroBlock, VERSION};
 use crate::blockchain::{Balance, Blockchain, ChainInfo};
+use crate::config::BftThreshold;
 use crate::election::mix;
 use crate::error::TransactionError;
 use crate::error::{BlockError, BlockchainError};
@@ -317,6 +318,44 @@ impl Transaction {
     }
 }
 
+///
+/// Verify signatures of a sealed block against the validators of its epoch.
+/// Macro blocks must carry a valid multisignature and micro blocks must be
+/// signed by one of the validators.
+///
+pub fn verify_sealed_block(
+    block: &Block,
+    validators: &Vec<(pbc::PublicKey, i64)>,
+    total_slots: i64,
+    threshold: BftThreshold,
+) -> Result<(), BlockError> {
+    let block_hash = Hash::digest(block);
+    let block_height = block.base_header().height;
+    match block {
+        Block::MacroBlock(ref block) => {
+            check_multi_signature(
+                &block_hash,
+                &block.body.multisig,
+                &block.body.multisigmap,
+                validators,
+                total_slots,
+                threshold,
+            )
+            .map_err(|e| BlockError::InvalidBlockSignature(e, block_height, block_hash))?;
+        }
+        Block::MicroBlock(ref block) => {
+            let leader = block.pkey;
+            if let Err(_e) = pbc::check_hash(&block_hash, &block.sig, &leader) {
+                return Err(BlockError::InvalidLeaderSignature(block_height, block_hash));
+            }
+            if !validators.iter().any(|(pkey, _slots)| *pkey == leader) {
+                return Err(BlockError::LeaderIsNotValidator(block_height, block_hash));
+            }
+        }
+    }
+    Ok(())
+}
+
 impl MacroBlock {
     ///
     /// Validate the block monetary balance.
@@ -363,13 +402,14 @@ impl MacroBlock {
 
 impl Blockchain {
     /// Check that the stake can be unstaked.
-    pub fn validate_staking_balance<'a, StakeIter>(
+    ///
+    /// Stakes spent by `inputs` can be withdrawn only after they have expired.
+    /// Active stakes can only be re-staked to the same validator.
+    pub fn validate_staking_balance(
         &self,
-        staking_balance: StakeIter,
-    ) -> Result<(), BlockchainError>
-    where
-        StakeIter: Iterator<Item = (&'a pbc::PublicKey, &'a i64)>,
-    {
+        inputs: &[Output],
+        staking_balance: &StakingBalance,
+    ) -> Result<(), BlockchainError> {
         for (validator_pkey, balance) in staking_balance {
             let (active_balance, expired_balance) = self.get_stake(validator_pkey);
             let expected_balance = active_balance + expired_balance + balance;
@@ -382,6 +422,29 @@ impl Blockchain {
             }
         }
 
+        // Check the unbonding period of withdrawn stakes.
+        for input in inputs {
+            let o = match input {
+                Output::StakeOutput(o) => o,
+                _ => continue,
+            };
+            let balance = staking_balance.get(&o.validator).cloned().unwrap_or(0);
+            if balance >= 0 {
+                continue; // re-stake
+            }
+            let output_hash = Hash::digest(input);
+            let active_until_epoch = self
+                .stake_active_until_epoch(&o.validator, &output_hash)
+                .expect("stake exists");
+            if active_until_epoch >= self.epoch() {
+                return Err(BlockchainError::StakeIsNotMatured(
+                    output_hash,
+                    active_until_epoch,
+                    self.epoch(),
+                ));
+            }
+        }
+
         Ok(())
     }
 
@@ -413,6 +476,21 @@ impl Blockchain {
                 return Err(TransactionError::MissingInput(tx_hash, input_hash.clone()).into());
             }
 
+            // Check that block rewards are matured.
+            let coinbase_maturity = self.cfg().coinbase_maturity;
+            if let Some(height) = self.coinbase_height(input_hash) {
+                let confirmations = self.height() - height;
+                if confirmations < coinbase_maturity {
+                    return Err(TransactionError::ImmatureCoinbase(
+                        tx_hash,
+                        input_hash.clone(),
+                        confirmations,
+                        coinbase_maturity,
+                    )
+                    .into());
+                }
+            }
+
             inputs.push(input);
         }
 
@@ -429,7 +507,7 @@ impl Blockchain {
         let staking_balance = tx.validate(&inputs)?;
 
         // Checks staking balance.
-        self.validate_staking_balance(staking_balance.iter())?;
+        self.validate_staking_balance(&inputs, &staking_balance)?;
 
         // Transaction is valid.
         debug!("Transaction is valid: tx={}", tx_hash);
@@ -532,6 +610,10 @@ impl Blockchain {
                 }
             };
 
+            if block.pkey != leader {
+                return Err(BlockError::DifferentPublicKey(leader, block.pkey).into());
+            }
+
             if let Err(_e) = pbc::check_hash(&block_hash, &block.sig, &leader) {
                 return Err(BlockError::InvalidLeaderSignature(height, block_hash).into());
             }
@@ -604,7 +686,9 @@ impl Blockchain {
         }
 
         // Validate coinbase monetary balance.
-        let total_fee = block.coinbase.block_reward + block.coinbase.block_fee;
+        // Burned fees are not paid out and must not be minted by the coinbase.
+        let paid_fee = self.cfg().fee_policy.paid_fee(block.coinbase.block_fee);
+        let total_fee = block.coinbase.block_reward + paid_fee;
         if mined + block.coinbase.gamma * (*G) != fee_a(total_fee) {
             return Err(BlockError::InvalidBlockBalance(height, block_hash).into());
         }
@@ -627,7 +711,8 @@ impl Blockchain {
 
         let mut burned = ECp::inf();
         let mut created = ECp::inf();
-        let mut staking_balance: HashMap<pbc::PublicKey, i64> = HashMap::new();
+        let mut staking_balance: StakingBalance = HashMap::new();
+        let mut stakes: Vec<Output> = Vec::new();
 
         //
         // Validate inputs.
@@ -658,9 +743,10 @@ impl Blockchain {
             match input {
                 Output::PaymentOutput(_o) => {}
                 Output::PublicPaymentOutput(_o) => {}
-                Output::StakeOutput(o) => {
+                Output::StakeOutput(ref o) => {
                     let entry = staking_balance.entry(o.validator).or_insert(0);
                     *entry -= o.amount;
+                    stakes.push(input.clone());
                 }
             }
             input_hash.hash(&mut hasher);
@@ -743,7 +829,7 @@ impl Blockchain {
         }
 
         // Checks staking balance.
-        self.validate_staking_balance(staking_balance.iter())?;
+        self.validate_staking_balance(&stakes, &staking_balance)?;
 
         Ok(())
     }
@@ -856,6 +942,7 @@ impl Blockchain {
                     &block.body.multisigmap,
                     self.validators(),
                     self.total_slots(),
+                    self.cfg().bft_threshold,
                 )
                 .map_err(|e| BlockError::InvalidBlockSignature(e, height, block_hash))?;
             }
@@ -875,8 +962,10 @@ impl Blockchain {
 pub mod tests {
     use super::*;
     use crate::block::{BaseBlockHeader, MacroBlock};
+    use crate::multisignature::create_multi_signature;
     use crate::output::OutputError;
     use crate::output::StakeOutput;
+    use std::collections::BTreeMap;
     use std::time::SystemTime;
     use stegos_crypto::pbc;
 
@@ -1521,4 +1610,104 @@ pub mod tests {
     fn burn_money() {
         create_burn_money(200, 100);
     }
+
+    #[test]
+    fn verify_sealed_blocks() {
+        let (skey, pkey) = pbc::make_random_keys();
+        let (other_skey, other_pkey) = pbc::make_random_keys();
+        let total_slots: i64 = 10;
+        let validators = vec![(pkey, total_slots)];
+        let threshold = BftThreshold::default();
+
+        let version: u64 = 1;
+        let height: u64 = 10;
+        let timestamp = SystemTime::now();
+        let view_change = 0;
+        let previous = Hash::digest("test");
+        let random = pbc::make_VRF(&skey, &mix(Hash::zero(), view_change));
+        let base = BaseBlockHeader::new(version, previous, height, view_change, timestamp, random);
+
+        //
+        // Valid macro block.
+        //
+        let mut block = MacroBlock::empty(base.clone(), pkey);
+        let block_hash = Hash::digest(&block);
+        let mut signatures = BTreeMap::new();
+        signatures.insert(pkey, pbc::sign_hash(&block_hash, &skey));
+        let (multisig, multisigmap) = create_multi_signature(&validators, &signatures);
+        block.body.multisig = multisig;
+        block.body.multisigmap = multisigmap;
+        verify_sealed_block(
+            &Block::MacroBlock(block.clone()),
+            &validators,
+            total_slots,
+            threshold,
+        )
+        .expect("block is valid");
+
+        //
+        // Tampered multisignature.
+        //
+        block.body.multisig = pbc::sign_hash(&Hash::digest("tampered"), &skey);
+        match verify_sealed_block(
+            &Block::MacroBlock(block),
+            &validators,
+            total_slots,
+            threshold,
+        ) {
+            Err(BlockError::InvalidBlockSignature(_e, block_height, hash)) => {
+                assert_eq!(block_height, height);
+                assert_eq!(hash, block_hash);
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        //
+        // Valid micro block.
+        //
+        let mut block = MicroBlock::empty(base.clone(), None, pkey);
+        block.sign(&skey, &pkey);
+        verify_sealed_block(
+            &Block::MicroBlock(block.clone()),
+            &validators,
+            total_slots,
+            threshold,
+        )
+        .expect("block is valid");
+
+        //
+        // Micro block with an invalid signature.
+        //
+        block.sig = pbc::sign_hash(&Hash::digest("tampered"), &skey);
+        match verify_sealed_block(
+            &Block::MicroBlock(block),
+            &validators,
+            total_slots,
+            threshold,
+        ) {
+            Err(BlockError::InvalidLeaderSignature(block_height, _hash)) => {
+                assert_eq!(block_height, height);
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        //
+        // Micro block from a non-validator.
+        //
+        let mut block = MicroBlock::empty(base, None, other_pkey);
+        block.sign(&other_skey, &other_pkey);
+        let block_hash = Hash::digest(&block);
+        match verify_sealed_block(
+            &Block::MicroBlock(block),
+            &validators,
+            total_slots,
+            threshold,
+        ) {
+            Err(BlockError::LeaderIsNotValidator(block_height, hash)) => {
+                assert_eq!(block_height, height);
+                assert_eq!(hash, block_hash);
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
 }
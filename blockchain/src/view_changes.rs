@@ -53,6 +53,7 @@ impl ViewChangeProof {
             &self.multimap,
             blockchain.validators(),
             blockchain.total_slots(),
+            blockchain.cfg().bft_threshold,
         )?;
         Ok(())
     }
@@ -29,6 +29,18 @@ use stegos_crypto::pbc;
 pub enum ConsensusError {
     #[fail(display = "Unknown peer: pkey={}", _0)]
     UnknownMessagePeer(pbc::PublicKey),
+    #[fail(
+        display = "Unsupported consensus message version: version={}, supported={}.",
+        _0, _1
+    )]
+    UnsupportedVersion(u32, u32),
+    #[fail(
+        display = "Message height is out of bounds: height={}, max={}.",
+        _0, _1
+    )]
+    HeightOutOfBounds(u64, u64),
+    #[fail(display = "Message round is out of bounds: round={}, max={}.", _0, _1)]
+    RoundOutOfBounds(u32, u32),
     #[fail(display = "Invalid message signature.")]
     InvalidMessageSignature,
     #[fail(
@@ -26,6 +26,19 @@ use failure::Error;
 use stegos_crypto::hash::{Hash, Hashable, Hasher};
 use stegos_crypto::pbc;
 
+/// The current version of consensus messages.
+/// Messages without a version are decoded as version 0.
+pub const CONSENSUS_PROTOCOL_VERSION: u32 = 1;
+
+/// Absolute bounds for height and round of consensus messages.
+#[derive(Clone, Debug)]
+pub struct MessageBounds {
+    /// The maximal acceptable height.
+    pub max_height: u64,
+    /// The maximal acceptable round.
+    pub max_round: u32,
+}
+
 /// Consensus Message Payload.
 #[derive(Clone, Debug)]
 pub enum ConsensusMessageBody<Request, Proof> {
@@ -59,6 +72,8 @@ impl<Request: Hashable, Proof: Hashable> Hashable for ConsensusMessageBody<Reque
 /// Consensus Message.
 #[derive(Clone, Debug)]
 pub struct ConsensusMessage<Request, Proof> {
+    /// Protocol version.
+    pub version: u32,
     /// Current round.
     pub round: u32,
     /// Current height.
@@ -95,7 +110,9 @@ impl<Request: Hashable, Proof: Hashable> ConsensusMessage<Request, Proof> {
         pkey: &pbc::PublicKey,
         body: ConsensusMessageBody<Request, Proof>,
     ) -> ConsensusMessage<Request, Proof> {
+        let version = CONSENSUS_PROTOCOL_VERSION;
         let mut hasher = Hasher::new();
+        version.hash(&mut hasher);
         height.hash(&mut hasher);
         round.hash(&mut hasher);
         request_hash.hash(&mut hasher);
@@ -103,6 +120,7 @@ impl<Request: Hashable, Proof: Hashable> ConsensusMessage<Request, Proof> {
         let hash = hasher.result();
         let sig = pbc::sign_hash(&hash, skey);
         ConsensusMessage {
+            version,
             height,
             round,
             request_hash,
@@ -114,12 +132,20 @@ impl<Request: Hashable, Proof: Hashable> ConsensusMessage<Request, Proof> {
 
     ///
     /// Validate signature of the message.
+    /// Messages out of `bounds` are rejected before any signature checks.
     ///
-    pub fn validate<F>(&self, mut validate_request: F) -> Result<(), ConsensusError>
+    pub fn validate<F>(
+        &self,
+        bounds: &MessageBounds,
+        mut validate_request: F,
+    ) -> Result<(), ConsensusError>
     where
         F: FnMut(Hash, &Request, u32) -> Result<(), Error>,
     {
+        self.validate_version()?;
+        self.validate_bounds(bounds)?;
         let mut hasher = Hasher::new();
+        self.version.hash(&mut hasher);
         self.height.hash(&mut hasher);
         self.round.hash(&mut hasher);
         self.request_hash.hash(&mut hasher);
@@ -140,11 +166,85 @@ impl<Request: Hashable, Proof: Hashable> ConsensusMessage<Request, Proof> {
         }
         Ok(())
     }
+
+    ///
+    /// Check that the message version is supported.
+    ///
+    pub fn validate_version(&self) -> Result<(), ConsensusError> {
+        if self.version != CONSENSUS_PROTOCOL_VERSION {
+            return Err(ConsensusError::UnsupportedVersion(
+                self.version,
+                CONSENSUS_PROTOCOL_VERSION,
+            ));
+        }
+        Ok(())
+    }
+
+    ///
+    /// Check that height and round of the message are within `bounds`.
+    ///
+    pub fn validate_bounds(&self, bounds: &MessageBounds) -> Result<(), ConsensusError> {
+        if self.height > bounds.max_height {
+            return Err(ConsensusError::HeightOutOfBounds(
+                self.height,
+                bounds.max_height,
+            ));
+        }
+        if self.round > bounds.max_round {
+            return Err(ConsensusError::RoundOutOfBounds(
+                self.round,
+                bounds.max_round,
+            ));
+        }
+        Ok(())
+    }
+}
+
+///
+/// Verify request_hash signatures of several pre-commits at once.
+///
+/// All signatures are aggregated with random weights and checked with a single pairing.
+/// Weights prevent invalid signatures which cancel each other out from passing.
+/// If the aggregated signature is not valid, each signature is checked
+/// individually to find the bad signers.
+///
+/// Returns public keys of validators with invalid signatures.
+///
+pub fn verify_precommits(
+    request_hash: &Hash,
+    precommits: &[(pbc::PublicKey, pbc::Signature)],
+) -> Vec<pbc::PublicKey> {
+    if precommits.is_empty() {
+        return Vec::new();
+    }
+
+    let mut multisig = pbc::G1::zero();
+    let mut multipkey = pbc::G2::zero();
+    for (pkey, sig) in precommits {
+        let weight = pbc::Zr::random();
+        let sig: pbc::G1 = (*sig).into();
+        let pkey: pbc::G2 = (*pkey).into();
+        multisig += sig * weight;
+        multipkey += pkey * weight;
+    }
+    let multisig: pbc::Signature = multisig.into();
+    let multipkey: pbc::PublicKey = multipkey.into();
+    if pbc::check_hash(request_hash, &multisig, &multipkey).is_ok() {
+        return Vec::new();
+    }
+
+    // Fall back to individual checks.
+    precommits
+        .iter()
+        .filter(|(pkey, sig)| pbc::check_hash(request_hash, sig, pkey).is_err())
+        .map(|(pkey, _sig)| *pkey)
+        .collect()
 }
 
 /// Used by protobuf tests.
 impl<Request: Hashable, Proof: Hashable> Hashable for ConsensusMessage<Request, Proof> {
     fn hash(&self, state: &mut Hasher) {
+        self.version.hash(state);
         self.height.hash(state);
         self.round.hash(state);
         self.request_hash.hash(state);
@@ -153,3 +253,105 @@ impl<Request: Hashable, Proof: Hashable> Hashable for ConsensusMessage<Request,
         self.sig.hash(state);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use stegos_crypto::pbc;
+
+    fn prevote(height: u64, round: u32) -> ConsensusMessage<Hash, Hash> {
+        let (skey, pkey) = pbc::make_random_keys();
+        let mut msg = ConsensusMessage::new(
+            height,
+            round,
+            Hash::digest("test"),
+            &skey,
+            &pkey,
+            ConsensusMessageBody::Prevote {},
+        );
+        // Any signature check would fail.
+        msg.sig = pbc::sign_hash(&Hash::digest("invalid"), &skey);
+        msg
+    }
+
+    #[test]
+    fn bounds() {
+        let bounds = MessageBounds {
+            max_height: 10,
+            max_round: 5,
+        };
+        let validate_request =
+            |_hash: Hash, _request: &Hash, _round: u32| -> Result<(), Error> { unreachable!() };
+
+        // Within bounds - the signature is checked.
+        match prevote(10, 5).validate(&bounds, validate_request) {
+            Err(ConsensusError::InvalidMessageSignature) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        // An absurd height is rejected before the signature check.
+        match prevote(u64::max_value(), 0).validate(&bounds, validate_request) {
+            Err(ConsensusError::HeightOutOfBounds(height, max_height)) => {
+                assert_eq!(height, u64::max_value());
+                assert_eq!(max_height, 10);
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        // An absurd round is rejected before the signature check.
+        match prevote(0, u32::max_value()).validate(&bounds, validate_request) {
+            Err(ConsensusError::RoundOutOfBounds(round, max_round)) => {
+                assert_eq!(round, u32::max_value());
+                assert_eq!(max_round, 5);
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+    #[test]
+    fn batch_precommits() {
+        let request_hash = Hash::digest("request");
+        let mut precommits: Vec<(pbc::PublicKey, pbc::Signature)> = (0..5)
+            .map(|_| {
+                let (skey, pkey) = pbc::make_random_keys();
+                (pkey, pbc::sign_hash(&request_hash, &skey))
+            })
+            .collect();
+
+        // Nothing to check.
+        assert!(verify_precommits(&request_hash, &[]).is_empty());
+
+        // All signatures are valid.
+        assert!(verify_precommits(&request_hash, &precommits).is_empty());
+
+        // One signature is made for another hash.
+        let (skey, pkey) = pbc::make_random_keys();
+        let sig = pbc::sign_hash(&Hash::digest("other"), &skey);
+        precommits.insert(2, (pkey, sig));
+        assert_eq!(verify_precommits(&request_hash, &precommits), vec![pkey]);
+
+        // One signature is made by another key.
+        precommits.remove(2);
+        let (skey, _pkey) = pbc::make_random_keys();
+        let pkey = precommits[4].0;
+        precommits[4].1 = pbc::sign_hash(&request_hash, &skey);
+        assert_eq!(verify_precommits(&request_hash, &precommits), vec![pkey]);
+
+        // Two invalid signatures cancel each other out in an unweighted sum.
+        let mut precommits: Vec<(pbc::PublicKey, pbc::Signature)> = (0..5)
+            .map(|_| {
+                let (skey, pkey) = pbc::make_random_keys();
+                (pkey, pbc::sign_hash(&request_hash, &skey))
+            })
+            .collect();
+        let delta = pbc::Zr::random();
+        let shift = |sig: pbc::Signature, delta: pbc::Zr| -> pbc::Signature {
+            let mut sig: pbc::G1 = sig.into();
+            sig += pbc::G1::generator() * delta;
+            sig.into()
+        };
+        precommits[1].1 = shift(precommits[1].1, delta);
+        precommits[3].1 = shift(precommits[3].1, -delta);
+        let expected = vec![precommits[1].0, precommits[3].0];
+        assert_eq!(verify_precommits(&request_hash, &precommits), expected);
+    }
+}
@@ -51,4 +51,10 @@ lazy_static! {
         "Current node consensus state (0 = Not in consensus, 1 = Proposing, 2 = Prevote, 3 = Propose, 4 = Commit)."
     )
     .unwrap();
+
+    pub static ref EQUIVOCATIONS: IntCounter = register_int_counter!(
+        "stegos_consensus_equivocations",
+        "The number of conflicting votes from the same validator in the same round."
+    )
+    .unwrap();
 }
@@ -28,7 +28,7 @@ use failure::Error;
 use log::{debug, info};
 use std::collections::HashMap;
 use stegos_blockchain::view_changes::*;
-use stegos_blockchain::{check_supermajority, Blockchain, ChainInfo, ValidatorId};
+use stegos_blockchain::{Blockchain, ChainInfo, ValidatorId};
 use stegos_crypto::hash::{Hash, Hashable, Hasher};
 use stegos_crypto::pbc;
 
@@ -159,8 +159,12 @@ impl ViewChangeCollector {
             self.collected_slots,
             blockchain.total_slots()
         );
-        // return proof only about first 2/3rd of validators
-        if check_supermajority(self.collected_slots, blockchain.total_slots()) {
+        // return proof as soon as the BFT threshold is reached
+        if blockchain
+            .cfg()
+            .bft_threshold
+            .is_reached(self.collected_slots, blockchain.total_slots())
+        {
             let signatures = self
                 .actual_view_changes
                 .iter()
@@ -88,6 +88,7 @@ impl ProtoConvert for BlockConsensusMessage {
     type Proto = consensus::ConsensusMessage;
     fn into_proto(&self) -> Self::Proto {
         let mut proto = consensus::ConsensusMessage::new();
+        proto.set_version(self.version);
         proto.set_height(self.height);
         proto.set_round(self.round);
         proto.set_request_hash(self.request_hash.into_proto());
@@ -97,6 +98,7 @@ impl ProtoConvert for BlockConsensusMessage {
         proto
     }
     fn from_proto(proto: &Self::Proto) -> Result<Self, Error> {
+        let version = proto.get_version();
         let height = proto.get_height();
         let round = proto.get_round();
         let request_hash = Hash::from_proto(proto.get_request_hash())?;
@@ -104,6 +106,7 @@ impl ProtoConvert for BlockConsensusMessage {
         let sig = pbc::Signature::from_proto(proto.get_sig())?;
         let pkey = pbc::PublicKey::from_proto(proto.get_pkey())?;
         Ok(ConsensusMessage {
+            version,
             height,
             round,
             request_hash,
@@ -180,7 +183,8 @@ mod tests {
             &network_pkey,
             body,
         );
-        roundtrip(&msg);
+        let msg2 = roundtrip(&msg);
+        assert_eq!(msg2.version, CONSENSUS_PROTOCOL_VERSION);
 
         let request_hash_sig = pbc::sign_hash(&Hash::digest("test"), &network_skey);
         let body = ConsensusMessageBody::Precommit { request_hash_sig };
@@ -29,13 +29,13 @@ use log::*;
 use std::collections::BTreeMap;
 use std::fmt::Debug;
 use std::mem;
-use stegos_blockchain::create_multi_signature;
-use stegos_blockchain::{check_supermajority, ElectionResult};
+use stegos_blockchain::{BftThreshold, ElectionResult, MultiSignatureBuilder};
 use stegos_crypto::hash::{Hash, Hashable};
 use stegos_crypto::pbc;
 
 struct LockedRound<Request, Proof> {
     precommits: BTreeMap<pbc::PublicKey, pbc::Signature>,
+    precommits_multisig: MultiSignatureBuilder,
     request: Request,
     proof: Proof,
 }
@@ -64,6 +64,15 @@ impl ConsensusState {
     }
 }
 
+/// Two different votes from the same validator in the same round and phase.
+#[derive(Clone, Debug)]
+pub struct EquivocationDetected<Request, Proof> {
+    /// The vote received first.
+    pub first: ConsensusMessage<Request, Proof>,
+    /// The conflicting vote.
+    pub second: ConsensusMessage<Request, Proof>,
+}
+
 /// Consensus State.
 pub struct Consensus<Request, Proof> {
     //
@@ -80,6 +89,8 @@ pub struct Consensus<Request, Proof> {
     validators: BTreeMap<pbc::PublicKey, i64>,
     /// total number of slots for specific node.
     total_slots: i64,
+    /// The share of slots required to commit.
+    threshold: BftThreshold,
     //
     // Current blockchain state
     //
@@ -108,6 +119,10 @@ pub struct Consensus<Request, Proof> {
     prevotes: BTreeMap<pbc::PublicKey, pbc::Signature>,
     /// Collected Precommits.
     precommits: BTreeMap<pbc::PublicKey, pbc::Signature>,
+    /// Multi-signature of collected Precommits, aggregated as they arrive.
+    precommits_multisig: MultiSignatureBuilder,
+    /// The first vote received from each validator per round and phase.
+    votes: BTreeMap<(pbc::PublicKey, u32, &'static str), ConsensusMessage<Request, Proof>>,
 
     //
     // External events
@@ -116,6 +131,8 @@ pub struct Consensus<Request, Proof> {
     inbox: Vec<ConsensusMessage<Request, Proof>>,
     /// Outgoing messages.
     pub outbox: Vec<ConsensusMessage<Request, Proof>>,
+    /// Detected protocol violations.
+    pub equivocations: Vec<EquivocationDetected<Request, Proof>>,
 }
 
 impl<Request: Hashable + Clone + Debug + Eq, Proof: Hashable + Clone + Debug>
@@ -133,6 +150,7 @@ impl<Request: Hashable + Clone + Debug + Eq, Proof: Hashable + Clone + Debug>
     /// * `starting_view_change` - blockchain view_change number.
     /// * `election_result` - result of the previous election.
     /// * `validators` - voting members of consensus.
+    /// * `threshold` - the share of slots required to commit.
     pub fn new(
         height: u64,
         epoch: u64,
@@ -140,24 +158,29 @@ impl<Request: Hashable + Clone + Debug + Eq, Proof: Hashable + Clone + Debug>
         pkey: pbc::PublicKey,
         election_result: ElectionResult,
         validators: BTreeMap<pbc::PublicKey, i64>,
+        threshold: BftThreshold,
     ) -> Self {
         assert!(validators.contains_key(&pkey));
         let state = ConsensusState::Propose;
         debug!("New => {}({}:{})", state.name(), height, 0);
         let prevotes: BTreeMap<pbc::PublicKey, pbc::Signature> = BTreeMap::new();
         let precommits: BTreeMap<pbc::PublicKey, pbc::Signature> = BTreeMap::new();
+        let precommits_multisig = new_multisig(&validators);
         let total_slots = validators.iter().map(|v| v.1).sum();
         let request = None;
         let proof = None;
         let locked_round = None;
         let round = 0;
+        let votes = BTreeMap::new();
         let inbox: Vec<ConsensusMessage<Request, Proof>> = Vec::new();
         let outbox: Vec<ConsensusMessage<Request, Proof>> = Vec::new();
+        let equivocations: Vec<EquivocationDetected<Request, Proof>> = Vec::new();
         Consensus {
             skey,
             pkey,
             validators,
             total_slots,
+            threshold,
             state,
             election_result,
             height,
@@ -168,8 +191,11 @@ impl<Request: Hashable + Clone + Debug + Eq, Proof: Hashable + Clone + Debug>
             locked_round,
             prevotes,
             precommits,
+            precommits_multisig,
+            votes,
             inbox,
             outbox,
+            equivocations,
         }
     }
 
@@ -179,6 +205,10 @@ impl<Request: Hashable + Clone + Debug + Eq, Proof: Hashable + Clone + Debug>
         self.state = ConsensusState::Propose;
         let locked_round = LockedRound {
             precommits: mem::replace(&mut self.precommits, BTreeMap::new()),
+            precommits_multisig: mem::replace(
+                &mut self.precommits_multisig,
+                new_multisig(&self.validators),
+            ),
             request: self.request.take().expect("expected some propose"),
             proof: self.proof.take().expect("expected some proof"),
         };
@@ -194,6 +224,7 @@ impl<Request: Hashable + Clone + Debug + Eq, Proof: Hashable + Clone + Debug>
         self.state = ConsensusState::Propose;
         self.prevotes.clear();
         self.precommits.clear();
+        self.precommits_multisig = new_multisig(&self.validators);
         self.request = None;
         self.proof = None;
         self.outbox.clear();
@@ -340,6 +371,18 @@ impl<Request: Hashable + Clone + Debug + Eq, Proof: Hashable + Clone + Debug>
     pub fn feed_message(
         &mut self,
         msg: ConsensusMessage<Request, Proof>,
+    ) -> Result<(), ConsensusError> {
+        self.handle_message(msg, false)
+    }
+
+    ///
+    /// Process a message, skipping the request_hash signature check
+    /// of a pre-commit if `precommit_verified` is set.
+    ///
+    fn handle_message(
+        &mut self,
+        msg: ConsensusMessage<Request, Proof>,
+        precommit_verified: bool,
     ) -> Result<(), ConsensusError> {
         trace!(
             "{}({}:{}): process message: msg={:?}",
@@ -396,6 +439,37 @@ impl<Request: Hashable + Clone + Debug + Eq, Proof: Hashable + Clone + Debug>
         }
         assert_eq!(msg.round, self.round);
 
+        // Check for equivocation.
+        let is_vote = match msg.body {
+            ConsensusMessageBody::Prevote { .. } | ConsensusMessageBody::Precommit { .. } => true,
+            ConsensusMessageBody::Proposal { .. } => false,
+        };
+        if is_vote {
+            let key = (msg.pkey, msg.round, msg.name());
+            if let Some(first) = self.votes.get(&key) {
+                if first.request_hash != msg.request_hash {
+                    warn!(
+                        "{}({}:{}): equivocation detected: first={:?}, second={:?}",
+                        self.state.name(),
+                        self.height,
+                        self.round,
+                        first,
+                        &msg
+                    );
+                    metrics::EQUIVOCATIONS.inc();
+                    let equivocation = EquivocationDetected {
+                        first: first.clone(),
+                        second: msg,
+                    };
+                    self.equivocations.push(equivocation);
+                    // Discard the second vote.
+                    return Ok(());
+                }
+            } else {
+                self.votes.insert(key, msg.clone());
+            }
+        }
+
         // Check request_hash.
         if self.state != ConsensusState::Propose {
             let expected_request_hash = Hash::digest(self.request.as_ref().unwrap());
@@ -529,6 +603,7 @@ impl<Request: Hashable + Clone + Debug + Eq, Proof: Hashable + Clone + Debug>
                         // Someone proposed a request that looks like our locked.
                         let locked = self.locked_round.take().unwrap();
                         self.precommits = locked.precommits;
+                        self.precommits_multisig = locked.precommits_multisig;
                         // repeat prevote
                         self.prevote(Hash::digest(&self.request));
                     } // don't vote for request that is different from our locked.
@@ -555,7 +630,9 @@ impl<Request: Hashable + Clone + Debug + Eq, Proof: Hashable + Clone + Debug>
 
                 // Check signature.
                 let request_hash = Hash::digest(self.request.as_ref().unwrap());
-                if let Err(_e) = pbc::check_hash(&request_hash, &request_hash_sig, &msg.pkey) {
+                if !precommit_verified
+                    && pbc::check_hash(&request_hash, &request_hash_sig, &msg.pkey).is_err()
+                {
                     error!(
                         "{}({}:{}): a pre-commit signature is not valid: from={:?}",
                         self.state.name(),
@@ -574,7 +651,9 @@ impl<Request: Hashable + Clone + Debug + Eq, Proof: Hashable + Clone + Debug>
                     self.round,
                     &msg.pkey
                 );
-                self.precommits.insert(msg.pkey, request_hash_sig);
+                if self.precommits.insert(msg.pkey, request_hash_sig).is_none() {
+                    self.precommits_multisig.add(&msg.pkey, &request_hash_sig);
+                }
             }
         }
 
@@ -631,8 +710,9 @@ impl<Request: Hashable + Clone + Debug + Eq, Proof: Hashable + Clone + Debug>
     /// Process pending messages received out-of-order.
     fn process_inbox(&mut self) {
         let inbox = std::mem::replace(&mut self.inbox, Vec::new());
-        for msg in inbox {
-            if let Err(e) = self.feed_message(msg) {
+        let verified = self.verify_inbox_precommits(&inbox);
+        for (msg, precommit_verified) in inbox.into_iter().zip(verified) {
+            if let Err(e) = self.handle_message(msg, precommit_verified) {
                 warn!(
                     "{}({}:{}): failed to process message: error={:?}",
                     self.state.name(),
@@ -644,6 +724,39 @@ impl<Request: Hashable + Clone + Debug + Eq, Proof: Hashable + Clone + Debug>
         }
     }
 
+    ///
+    /// Batch-verify queued pre-commits for the current request.
+    /// Returns true for each message whose request_hash signature is valid.
+    ///
+    fn verify_inbox_precommits(&self, inbox: &[ConsensusMessage<Request, Proof>]) -> Vec<bool> {
+        let mut verified = vec![false; inbox.len()];
+        let request_hash = match &self.request {
+            Some(request) => Hash::digest(request),
+            None => return verified,
+        };
+
+        let mut ids: Vec<usize> = Vec::new();
+        let mut precommits: Vec<(pbc::PublicKey, pbc::Signature)> = Vec::new();
+        for (id, msg) in inbox.iter().enumerate() {
+            if let ConsensusMessageBody::Precommit { request_hash_sig } = &msg.body {
+                if msg.round == self.round && msg.request_hash == request_hash {
+                    ids.push(id);
+                    precommits.push((msg.pkey, *request_hash_sig));
+                }
+            }
+        }
+        if precommits.len() < 2 {
+            return verified; // Nothing to batch.
+        }
+
+        let invalid = verify_precommits(&request_hash, &precommits);
+        for (id, (pkey, _sig)) in ids.into_iter().zip(precommits) {
+            // Invalid signatures are checked again to report the error.
+            verified[id] = !invalid.contains(&pkey);
+        }
+        verified
+    }
+
     ///
     /// Returns true if current node is leader.
     ///
@@ -665,6 +778,13 @@ impl<Request: Hashable + Clone + Debug + Eq, Proof: Hashable + Clone + Debug>
         self.epoch
     }
 
+    ///
+    /// Returns height of current consensus session.
+    ///
+    pub fn height(&self) -> u64 {
+        self.height
+    }
+
     ///
     /// Returns number of current consensus round.
     ///
@@ -686,6 +806,13 @@ impl<Request: Hashable + Clone + Debug + Eq, Proof: Hashable + Clone + Debug>
         self.state == ConsensusState::Propose && self.is_leader()
     }
 
+    ///
+    /// Returns true if a proposal has been accepted in the current round.
+    ///
+    pub fn is_proposed(&self) -> bool {
+        self.state != ConsensusState::Propose
+    }
+
     ///
     /// Returns true if current node should commit the request.
     ///
@@ -708,10 +835,13 @@ impl<Request: Hashable + Clone + Debug + Eq, Proof: Hashable + Clone + Debug>
     pub fn sign_and_commit(&mut self) -> (Request, Proof, pbc::Signature, BitVector) {
         assert!(self.should_commit());
 
-        // TODO: Use id instead of PublicKey.
-        let validators = self.validators.iter().map(|(k, v)| (*k, *v)).collect();
-        // Create multi-signature.
-        let (multisig, multisigmap) = create_multi_signature(&validators, &self.precommits);
+        // Take multi-signature, aggregated while collecting pre-commits.
+        assert_eq!(self.precommits_multisig.len(), self.precommits.len());
+        let precommits_multisig = mem::replace(
+            &mut self.precommits_multisig,
+            new_multisig(&self.validators),
+        );
+        let (multisig, multisigmap) = precommits_multisig.finish();
         let r = (
             self.request.take().unwrap(),
             self.proof.take().unwrap(),
@@ -738,6 +868,13 @@ impl<Request: Hashable + Clone + Debug + Eq, Proof: Hashable + Clone + Debug>
         for (pk, _sign) in accepts {
             stake += self.validators.get(pk).expect("vote from validator");
         }
-        check_supermajority(stake, self.total_slots)
+        self.threshold.is_reached(stake, self.total_slots)
     }
 }
+
+/// Create an empty multi-signature for the given validators.
+fn new_multisig(validators: &BTreeMap<pbc::PublicKey, i64>) -> MultiSignatureBuilder {
+    // TODO: Use id instead of PublicKey.
+    let validators = validators.iter().map(|(k, v)| (*k, *v)).collect();
+    MultiSignatureBuilder::new(&validators)
+}
@@ -40,7 +40,7 @@ use rand::Rng;
 use serde::ser::{Serialize, Serializer};
 use std::cmp::Ordering;
 use std::hash as stdhash;
-use std::ops::{Add, AddAssign, Neg};
+use std::ops::{Add, AddAssign, Mul, Neg};
 
 // --------------------------------------------------------------------------------
 
@@ -292,6 +292,13 @@ impl AddAssign<G1> for G1 {
     }
 }
 
+impl Mul<Zr> for G1 {
+    type Output = Self;
+    fn mul(self, other: Zr) -> Self {
+        mul_G1_Zr(&self, &other)
+    }
+}
+
 // -----------------------------------------
 #[derive(Copy, Clone)]
 #[repr(C)]
@@ -396,6 +403,13 @@ impl AddAssign<G2> for G2 {
     }
 }
 
+impl Mul<Zr> for G2 {
+    type Output = Self;
+    fn mul(self, other: Zr) -> Self {
+        mul_G2_Zr(&self, &other)
+    }
+}
+
 // -----------------------------------------
 #[derive(Copy, Clone)]
 #[repr(C)]
@@ -1148,6 +1162,18 @@ pub fn add_G1_G1(a: &G1, b: &G1) -> G1 {
     ans
 }
 
+pub fn mul_G1_Zr(a: &G1, b: &Zr) -> G1 {
+    let ans = a.clone();
+    unsafe {
+        rust_libpbc::exp_G1z(
+            *CONTEXT_FR256,
+            ans.base_vector().as_ptr() as *mut _,
+            b.base_vector().as_ptr() as *mut _,
+        );
+    }
+    ans
+}
+
 pub fn add_G2_G2(a: &G2, b: &G2) -> G2 {
     let ans = a.clone();
     unsafe {
@@ -1160,6 +1186,18 @@ pub fn add_G2_G2(a: &G2, b: &G2) -> G2 {
     ans
 }
 
+pub fn mul_G2_Zr(a: &G2, b: &Zr) -> G2 {
+    let ans = a.clone();
+    unsafe {
+        rust_libpbc::exp_G2z(
+            *CONTEXT_FR256,
+            ans.base_vector().as_ptr() as *mut _,
+            b.base_vector().as_ptr() as *mut _,
+        );
+    }
+    ans
+}
+
 pub fn compute_pairing(a: &G1, b: &G2) -> GT {
     let ans = GT::new();
     unsafe {
@@ -38,6 +38,8 @@ pub struct NetworkConfig {
     pub advertise_local_ips: bool,
     /// DNS name of pool of seed nodes
     pub seed_pool: String,
+    /// Interval between seed pool lookups (secs)
+    pub seed_pool_refresh_interval: u64,
     /// List of nodes to connect to on startup.
     pub seed_nodes: Vec<String>,
     /// Minimum active connections (try to keep at least so many established connections)
@@ -56,6 +58,7 @@ impl Default for NetworkConfig {
         NetworkConfig {
             bind_port: 0,
             seed_pool: "".to_string(),
+            seed_pool_refresh_interval: 600,
             seed_nodes: vec![],
             advertised_addresses: vec![],
             advertise_local_ips: false,
@@ -126,6 +126,11 @@ where
         self.known_nodes.insert(node_id, peer_id);
     }
 
+    /// Returns node_id of the known node with the given peer_id.
+    pub fn node_id(&self, peer_id: &PeerId) -> Option<pbc::PublicKey> {
+        self.known_nodes.peek_by_value(peer_id).cloned()
+    }
+
     pub fn deliver_unicast(&mut self, to: &pbc::PublicKey, payload: Vec<u8>) {
         let mut message = Unicast {
             to: to.clone(),
@@ -54,6 +54,15 @@ where
     /// Subscribe to topic, returns Stream<Vec<u8>> of messages incoming to topic
     fn subscribe(&self, topic: &str) -> Result<mpsc::UnboundedReceiver<Vec<u8>>, Error>;
 
+    /// Subscribe to topic, returns Stream<BroadcastMessage> of messages incoming to topic,
+    /// together with the public key of the publisher, if it is known.
+    /// The publisher is claimed by the message itself and is NOT authenticated,
+    /// so it can only be used as a hint, e.g. where to request missing data from.
+    fn subscribe_with_sender(
+        &self,
+        topic: &str,
+    ) -> Result<mpsc::UnboundedReceiver<BroadcastMessage>, Error>;
+
     /// Published message to topic
     fn publish(&self, topic: &str, data: Vec<u8>) -> Result<(), Error>;
 
@@ -75,6 +84,9 @@ where
         _new_pkey: pbc::PublicKey,
         _new_skey: pbc::SecretKey,
     ) -> Result<(), Error>;
+
+    /// Connect to a new peer, identified by multiaddr.
+    fn dial_address(&self, address: String) -> Result<(), Error>;
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -83,6 +95,14 @@ pub struct UnicastMessage {
     pub data: Vec<u8>,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BroadcastMessage {
+    /// Publisher of the message, None if it can't be resolved.
+    /// Unauthenticated: any peer can put an arbitrary source into a floodsub message.
+    pub from: Option<pbc::PublicKey>,
+    pub data: Vec<u8>,
+}
+
 impl Clone for Network {
     fn clone(&self) -> Network {
         self.box_clone()
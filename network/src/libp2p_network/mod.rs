@@ -49,7 +49,7 @@ use crate::discovery::{Discovery, DiscoveryOutEvent};
 use crate::gatekeeper::{Gatekeeper, GatekeeperOutEvent, PeerEvent};
 use crate::ncp::{Ncp, NcpOutEvent};
 use crate::pubsub::{Floodsub, FloodsubEvent, TopicBuilder, TopicHash};
-use crate::{Network, NetworkProvider, UnicastMessage};
+use crate::{BroadcastMessage, Network, NetworkProvider, UnicastMessage};
 
 mod proto;
 use self::proto::unicast_proto;
@@ -86,6 +86,18 @@ impl NetworkProvider for Libp2pNetwork {
         Ok(rx)
     }
 
+    /// Subscribe to topic, returns Stream<BroadcastMessage> of messages incoming to topic
+    fn subscribe_with_sender(
+        &self,
+        topic: &str,
+    ) -> Result<mpsc::UnboundedReceiver<BroadcastMessage>, Error> {
+        let topic: String = topic.clone().into();
+        let (tx, rx) = mpsc::unbounded();
+        let msg = ControlMessage::SubscribeWithSender { topic, handler: tx };
+        self.control_tx.unbounded_send(msg)?;
+        Ok(rx)
+    }
+
     /// Published message to topic
     fn publish(&self, topic: &str, data: Vec<u8>) -> Result<(), Error> {
         let topic: String = topic.clone().into();
@@ -139,6 +151,16 @@ impl NetworkProvider for Libp2pNetwork {
         self.control_tx.unbounded_send(msg)?;
         Ok(())
     }
+
+    // Connect to a new peer
+    fn dial_address(&self, address: String) -> Result<(), Error> {
+        let address: Multiaddr = address
+            .parse()
+            .map_err(|e| format_err!("failed to parse address: {}, error: {}", address, e))?;
+        let msg = ControlMessage::DialAddress { address };
+        self.control_tx.unbounded_send(msg)?;
+        Ok(())
+    }
 }
 
 fn new_service(
@@ -219,6 +241,8 @@ pub struct Libp2pBehaviour<TSubstream: AsyncRead + AsyncWrite> {
     #[behaviour(ignore)]
     consumers: HashMap<TopicHash, SmallVec<[mpsc::UnboundedSender<Vec<u8>>; 3]>>,
     #[behaviour(ignore)]
+    sender_consumers: HashMap<TopicHash, SmallVec<[mpsc::UnboundedSender<BroadcastMessage>; 3]>>,
+    #[behaviour(ignore)]
     unicast_consumers: HashMap<String, SmallVec<[mpsc::UnboundedSender<UnicastMessage>; 3]>>,
     #[behaviour(ignore)]
     my_pkey: pbc::PublicKey,
@@ -242,6 +266,7 @@ where
             delivery: Delivery::new(),
             discovery: Discovery::new(keychain.network_pkey.clone()),
             consumers: HashMap::new(),
+            sender_consumers: HashMap::new(),
             unicast_consumers: HashMap::new(),
             my_pkey: keychain.network_pkey.clone(),
             my_skey: keychain.network_skey.clone(),
@@ -279,6 +304,16 @@ where
                     .or_insert(SmallVec::new())
                     .push(handler);
             }
+            ControlMessage::SubscribeWithSender { topic, handler } => {
+                let floodsub_topic = TopicBuilder::new(topic.clone()).build();
+                let topic_hash = floodsub_topic.hash();
+                self.topics_map.insert(topic_hash.clone(), topic);
+                self.sender_consumers
+                    .entry(topic_hash.clone())
+                    .or_insert(SmallVec::new())
+                    .push(handler);
+                self.floodsub.subscribe(floodsub_topic);
+            }
             ControlMessage::Publish { topic, data } => {
                 debug!(target: "stegos_network::pubsub",
                     "Sending broadcast message: topic={}, size={}",
@@ -295,6 +330,10 @@ where
                 self.my_pkey = new_pkey;
                 self.my_skey = new_skey;
             }
+            ControlMessage::DialAddress { address } => {
+                debug!(target: "stegos_network::gatekeeper", "dialing peer with address {}", address);
+                self.gatekeeper.dial_address(address);
+            }
             ControlMessage::SubscribeUnicast {
                 protocol_id,
                 consumer,
@@ -467,6 +506,8 @@ where
                     }
                     return;
                 }
+                // The source is set by the publisher and isn't signed.
+                let from = self.discovery.node_id(&message.source);
                 for t in message.topics.into_iter() {
                     let topic = match self.topics_map.get(&t) {
                         Some(t) => t.clone(),
@@ -478,6 +519,22 @@ where
                         topic,
                         message.data.len(),
                     );
+                    if let Some(consumers) = self.sender_consumers.get_mut(&t) {
+                        let msg = BroadcastMessage {
+                            from: from.clone(),
+                            data: message.data.clone(),
+                        };
+                        consumers.retain({
+                            move |c| {
+                                if let Err(e) = c.unbounded_send(msg.clone()) {
+                                    error!(target: "stegos_network::pubsub", "Error sending data to consumer: {}", e);
+                                    false
+                                } else {
+                                    true
+                                }
+                            }
+                        })
+                    }
                     let consumers = self.consumers.entry(t).or_insert(SmallVec::new());
                     consumers.retain({
                         let data = &message.data;
@@ -653,6 +710,10 @@ pub enum ControlMessage {
         topic: String,
         handler: mpsc::UnboundedSender<Vec<u8>>,
     },
+    SubscribeWithSender {
+        topic: String,
+        handler: mpsc::UnboundedSender<BroadcastMessage>,
+    },
     Publish {
         topic: String,
         data: Vec<u8>,
@@ -670,6 +731,9 @@ pub enum ControlMessage {
         new_pkey: pbc::PublicKey,
         new_skey: pbc::SecretKey,
     },
+    DialAddress {
+        address: Multiaddr,
+    },
 }
 
 fn my_external_address(config: &NetworkConfig) -> Vec<Multiaddr> {
@@ -21,7 +21,7 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 #![allow(dead_code)]
-use crate::{Network, NetworkProvider, UnicastMessage};
+use crate::{BroadcastMessage, Network, NetworkProvider, UnicastMessage};
 use failure::Error;
 use futures::sync::mpsc;
 use log::*;
@@ -53,6 +53,24 @@ impl NetworkProvider for LoopbackNetwork {
         Ok(rx)
     }
 
+    fn subscribe_with_sender(
+        &self,
+        stopic: &str,
+    ) -> Result<mpsc::UnboundedReceiver<BroadcastMessage>, Error> {
+        let topic: String = stopic.to_string();
+        let (tx, rx) = mpsc::unbounded::<BroadcastMessage>();
+        assert!(
+            self.state
+                .lock()
+                .unwrap()
+                .sender_consumers
+                .insert(topic, tx)
+                .is_none(),
+            format!("multiple subscribe to topic {}", stopic)
+        );
+        Ok(rx)
+    }
+
     fn subscribe_unicast(
         &self,
         stopic: &str,
@@ -97,6 +115,10 @@ impl NetworkProvider for LoopbackNetwork {
         Ok(())
     }
 
+    fn dial_address(&self, _address: String) -> Result<(), Error> {
+        Ok(())
+    }
+
     // Clone self as a box
     fn box_clone(&self) -> Network {
         Box::new((*self).clone())
@@ -106,6 +128,7 @@ impl NetworkProvider for LoopbackNetwork {
 #[derive(Debug, Clone)]
 struct LoopbackState {
     consumers: HashMap<String, mpsc::UnboundedSender<Vec<u8>>>,
+    sender_consumers: HashMap<String, mpsc::UnboundedSender<BroadcastMessage>>,
     unicast_consumers: HashMap<String, mpsc::UnboundedSender<UnicastMessage>>,
     queue: VecDeque<MessageFromNode>,
 }
@@ -118,10 +141,12 @@ pub struct Loopback {
 impl Loopback {
     pub fn new() -> (Loopback, Network) {
         let consumers = HashMap::new();
+        let sender_consumers = HashMap::new();
         let unicast_consumers = HashMap::new();
         let queue = VecDeque::new();
         let state = LoopbackState {
             consumers,
+            sender_consumers,
             unicast_consumers,
             queue,
         };
@@ -248,19 +273,41 @@ impl Loopback {
         }
     }
 
-    pub fn receive_broadcast_raw(&mut self, topic: &str, data: Vec<u8>) {
+    fn deliver_broadcast(&mut self, from: Option<pbc::PublicKey>, topic: &str, data: Vec<u8>) {
         let ref mut state = self.state.lock().unwrap();
-        let ref mut node = state
-            .consumers
-            .get(topic)
-            .expect("Node didn't subscribe to broadcast");
-        node.unbounded_send(data).expect("channel error")
+        if let Some(node) = state.sender_consumers.get(topic) {
+            let message = BroadcastMessage { from, data };
+            node.unbounded_send(message).expect("channel error")
+        } else {
+            let ref mut node = state
+                .consumers
+                .get(topic)
+                .expect("Node didn't subscribe to broadcast");
+            node.unbounded_send(data).expect("channel error")
+        }
+    }
+
+    pub fn receive_broadcast_raw(&mut self, topic: &str, data: Vec<u8>) {
+        self.deliver_broadcast(None, topic, data);
     }
 
     pub fn receive_broadcast<M: ProtoConvert>(&mut self, topic: &str, msg: M) {
         self.receive_broadcast_raw(topic, msg.into_buffer().unwrap());
     }
 
+    pub fn receive_broadcast_from_raw(&mut self, peer: pbc::PublicKey, topic: &str, data: Vec<u8>) {
+        self.deliver_broadcast(Some(peer), topic, data);
+    }
+
+    pub fn receive_broadcast_from<M: ProtoConvert>(
+        &mut self,
+        peer: pbc::PublicKey,
+        topic: &str,
+        msg: M,
+    ) {
+        self.receive_broadcast_from_raw(peer, topic, msg.into_buffer().unwrap());
+    }
+
     pub fn receive_unicast_raw(&mut self, peer: pbc::PublicKey, topic: &str, data: Vec<u8>) {
         let ref mut state = self.state.lock().unwrap();
         let ref mut node = state
@@ -288,3 +335,215 @@ pub enum MessageFromNode {
         data: Vec<u8>,
     },
 }
+
+///
+/// In-process network which routes messages between several nodes.
+///
+/// Unlike `Loopback`, which only queues outgoing messages for inspection,
+/// the hub delivers published messages to all other connected nodes
+/// subscribed to the topic and unicast messages to the destination node.
+///
+#[derive(Debug, Clone, Default)]
+pub struct LoopbackHub {
+    state: Arc<Mutex<HubState>>,
+}
+
+#[derive(Debug, Default)]
+struct HubState {
+    nodes: HashMap<pbc::PublicKey, HubNode>,
+}
+
+#[derive(Debug, Default)]
+struct HubNode {
+    consumers: HashMap<String, Vec<mpsc::UnboundedSender<Vec<u8>>>>,
+    sender_consumers: HashMap<String, Vec<mpsc::UnboundedSender<BroadcastMessage>>>,
+    unicast_consumers: HashMap<String, mpsc::UnboundedSender<UnicastMessage>>,
+}
+
+impl LoopbackHub {
+    pub fn new() -> LoopbackHub {
+        Default::default()
+    }
+
+    /// Connect a new node identified by `pkey` to the hub.
+    pub fn connect(&self, pkey: pbc::PublicKey) -> Network {
+        let exists = self
+            .state
+            .lock()
+            .unwrap()
+            .nodes
+            .insert(pkey, HubNode::default());
+        assert!(exists.is_none(), "node is already connected");
+        let network = HubNetwork {
+            pkey,
+            state: self.state.clone(),
+        };
+        Box::new(network)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HubNetwork {
+    pkey: pbc::PublicKey,
+    state: Arc<Mutex<HubState>>,
+}
+
+impl NetworkProvider for HubNetwork {
+    fn subscribe(&self, topic: &str) -> Result<mpsc::UnboundedReceiver<Vec<u8>>, Error> {
+        let (tx, rx) = mpsc::unbounded();
+        let ref mut state = self.state.lock().unwrap();
+        let node = state.nodes.get_mut(&self.pkey).expect("connected");
+        node.consumers
+            .entry(topic.to_string())
+            .or_insert_with(Vec::new)
+            .push(tx);
+        Ok(rx)
+    }
+
+    fn subscribe_with_sender(
+        &self,
+        topic: &str,
+    ) -> Result<mpsc::UnboundedReceiver<BroadcastMessage>, Error> {
+        let (tx, rx) = mpsc::unbounded();
+        let ref mut state = self.state.lock().unwrap();
+        let node = state.nodes.get_mut(&self.pkey).expect("connected");
+        node.sender_consumers
+            .entry(topic.to_string())
+            .or_insert_with(Vec::new)
+            .push(tx);
+        Ok(rx)
+    }
+
+    fn subscribe_unicast(
+        &self,
+        protocol_id: &str,
+    ) -> Result<mpsc::UnboundedReceiver<UnicastMessage>, Error> {
+        let (tx, rx) = mpsc::unbounded::<UnicastMessage>();
+        let ref mut state = self.state.lock().unwrap();
+        let node = state.nodes.get_mut(&self.pkey).expect("connected");
+        assert!(
+            node.unicast_consumers
+                .insert(protocol_id.to_string(), tx)
+                .is_none(),
+            format!("multiple unicast subscribe to topic {}", protocol_id)
+        );
+        Ok(rx)
+    }
+
+    fn send(&self, to: pbc::PublicKey, protocol_id: &str, data: Vec<u8>) -> Result<(), Error> {
+        let ref mut state = self.state.lock().unwrap();
+        let consumer = state
+            .nodes
+            .get(&to)
+            .and_then(|node| node.unicast_consumers.get(protocol_id));
+        match consumer {
+            Some(consumer) => {
+                let msg = UnicastMessage {
+                    from: self.pkey,
+                    data,
+                };
+                consumer.unbounded_send(msg).ok(); // ignore errors.
+            }
+            None => {
+                debug!(
+                    "Dropped unicast message: to={}, protocol_id={}",
+                    to, protocol_id
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn publish(&self, topic: &str, data: Vec<u8>) -> Result<(), Error> {
+        trace!("Received publish for topic = {}", topic);
+        let ref mut state = self.state.lock().unwrap();
+        for (pkey, node) in state.nodes.iter_mut() {
+            if *pkey == self.pkey {
+                continue;
+            }
+            if let Some(consumers) = node.consumers.get_mut(topic) {
+                consumers.retain(|ch| ch.unbounded_send(data.clone()).is_ok());
+            }
+            if let Some(consumers) = node.sender_consumers.get_mut(topic) {
+                let msg = BroadcastMessage {
+                    from: Some(self.pkey),
+                    data: data.clone(),
+                };
+                consumers.retain(|ch| ch.unbounded_send(msg.clone()).is_ok());
+            }
+        }
+        Ok(())
+    }
+
+    fn change_network_keys(
+        &self,
+        _new_pkey: pbc::PublicKey,
+        _new_skey: pbc::SecretKey,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn dial_address(&self, _address: String) -> Result<(), Error> {
+        Ok(())
+    }
+
+    // Clone self as a box
+    fn box_clone(&self) -> Network {
+        Box::new((*self).clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{future, Async, Future, Stream};
+
+    fn try_recv<T>(rx: &mut mpsc::UnboundedReceiver<T>) -> Option<T> {
+        match future::lazy(|| rx.poll()).wait().unwrap() {
+            Async::Ready(item) => item,
+            Async::NotReady => None,
+        }
+    }
+
+    #[test]
+    fn hub_routing() {
+        let hub = LoopbackHub::new();
+        let (_skey1, pkey1) = pbc::make_random_keys();
+        let (_skey2, pkey2) = pbc::make_random_keys();
+        let (_skey3, pkey3) = pbc::make_random_keys();
+        let network1 = hub.connect(pkey1);
+        let network2 = hub.connect(pkey2);
+        let network3 = hub.connect(pkey3);
+
+        let mut rx1 = network1.subscribe("topic").unwrap();
+        let mut rx2 = network2.subscribe("topic").unwrap();
+        let mut rx3 = network3.subscribe("other").unwrap();
+
+        // Broadcast.
+        network1.publish("topic", vec![1]).unwrap();
+        assert_eq!(try_recv(&mut rx2), Some(vec![1]));
+        assert_eq!(try_recv(&mut rx1), None);
+        assert_eq!(try_recv(&mut rx3), None);
+
+        // Broadcast with sender.
+        let mut srx3 = network3.subscribe_with_sender("topic").unwrap();
+        network1.publish("topic", vec![4]).unwrap();
+        let msg = try_recv(&mut srx3).expect("delivered");
+        assert_eq!(msg.from, Some(pkey1));
+        assert_eq!(msg.data, vec![4]);
+        assert_eq!(try_recv(&mut rx2), Some(vec![4]));
+
+        // Unicast.
+        let mut urx2 = network2.subscribe_unicast("protocol").unwrap();
+        let mut urx3 = network3.subscribe_unicast("protocol").unwrap();
+        network1.send(pkey3, "protocol", vec![2]).unwrap();
+        let msg = try_recv(&mut urx3).expect("delivered");
+        assert_eq!(msg.from, pkey1);
+        assert_eq!(msg.data, vec![2]);
+        assert_eq!(try_recv(&mut urx2), None);
+
+        // Unknown destination.
+        let (_skey4, pkey4) = pbc::make_random_keys();
+        network1.send(pkey4, "protocol", vec![3]).unwrap();
+    }
+}
@@ -155,6 +155,13 @@ where
         }
         self.vk.get(value)
     }
+    /// Returns the key of `value` without refreshing or purging entries.
+    pub fn peek_by_value(&self, value: &V) -> Option<&K> {
+        match self.vk.get(value) {
+            Some(key) if self.kv.contains_key(key) => Some(key),
+            _ => None,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -169,6 +176,8 @@ mod tests {
         my_map.insert(1, 101);
         assert_eq!(*my_map.get_by_key(&1).unwrap(), 101);
         assert_eq!(*my_map.get_by_value(&101).unwrap(), 1);
+        assert_eq!(*my_map.peek_by_value(&101).unwrap(), 1);
+        assert_eq!(my_map.peek_by_value(&102), None);
     }
 
     #[test]
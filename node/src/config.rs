@@ -23,7 +23,7 @@
 
 use serde_derive::{Deserialize, Serialize};
 use std::time::Duration;
-use stegos_blockchain::BlockchainConfig;
+use stegos_blockchain::{BftThreshold, BlockchainConfig, FeePolicy};
 
 /// Chain configuration.
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -31,34 +31,122 @@ use stegos_blockchain::BlockchainConfig;
 pub struct ChainConfig {
     /// How long wait for transactions before starting to create a new block.
     pub tx_wait_timeout: Duration,
+    /// Create a new block without waiting for tx_wait_timeout
+    /// if mempool has at least this number of transactions.
+    /// Zero means that the leader always waits for tx_wait_timeout.
+    pub min_txs_for_immediate_block: usize,
+    /// How long the leader of a view change waits for transactions.
+    /// Zero means that a micro block is proposed immediately after the view change.
+    /// Must be less than micro_block_timeout, the node refuses to start otherwise.
+    pub view_change_tx_wait_timeout: Duration,
+    /// Don't create micro blocks without transactions until empty_block_timeout.
+    pub skip_empty_blocks: bool,
+    /// How long the leader can skip empty micro blocks.
+    /// Must be less than micro_block_timeout to avoid view changes,
+    /// the node refuses to start otherwise.
+    pub empty_block_timeout: Duration,
+    /// The number of blocks on top of a micro block before its transactions
+    /// are reported as committed. Macro blocks commit all transactions.
+    pub confirmation_depth: u64,
+    /// The number of confirmations, counting the block itself,
+    /// required to spend rewards of a block. Zero disables the check.
+    pub coinbase_maturity: u64,
     /// How long wait for micro blocks.
     pub micro_block_timeout: Duration,
     /// How long wait for the keu blocks.
     pub macro_block_timeout: Duration,
+    /// How long wait for a macro block proposal.
+    /// Defaults to macro_block_timeout.
+    pub macro_propose_timeout: Option<Duration>,
+    /// How long wait for a commit after the macro block has been proposed.
+    /// Defaults to macro_block_timeout.
+    pub macro_commit_timeout: Option<Duration>,
     /// Time to lock stakes.
     pub stake_epochs: u64,
     /// The number of blocks per epoch.
     pub blocks_in_epoch: u64,
     /// The maximal number of inputs + outputs in a transaction.
     pub max_utxo_in_tx: usize,
+    /// The maximal size of a transaction, in bytes.
+    pub max_tx_bytes: usize,
     /// The maximal number of inputs + outputs in a micro block.
     pub max_utxo_in_block: usize,
+    /// The maximal size of transactions in a micro block, in bytes.
+    pub max_block_bytes: usize,
     /// The maximal number of inputs + outputs in mempool.
     pub max_utxo_in_mempool: usize,
+    /// The maximal size of transactions in mempool, in bytes.
+    pub max_mempool_bytes: usize,
+    /// The maximal number of transactions being validated in background.
+    pub max_pending_transactions: usize,
     /// Loader will send maximum N epoch at time.
     pub chain_loader_speed_in_epoch: u64,
-    /// Fixed reward per block.
+    /// The maximal number of blocks in one loader response.
+    pub max_blocks_per_response: u64,
+    /// The maximal size of one loader response, in bytes.
+    pub max_response_bytes: usize,
+    /// Initial reward per block.
     pub block_reward: i64,
+    /// The number of blocks after which the block reward is halved.
+    /// Zero means that the block reward is never changed.
+    pub block_reward_halving_interval: u64,
+    /// Reward per macro block, paid to the leader of the epoch as a whole.
+    /// It is not split between the facilitator and validators.
+    /// Zero means that macro blocks carry no reward.
+    pub macro_block_reward: i64,
     /// Fixed fee for payment transactions.
     pub payment_fee: i64,
     /// Fixed fee for the stake transactions.
     pub stake_fee: i64,
+    /// What to do with transaction fees: burn them or pay them to the leader.
+    pub fee_policy: FeePolicy,
+    /// The share of slots required to sign blocks and view changes.
+    /// Test networks may change it, all validators must use the same value.
+    pub bft_threshold: BftThreshold,
+    /// Reject blocks with an unexpected reward.
+    /// Otherwise, such blocks are accepted and the violation is recorded for slashing.
+    pub strict_reward_validation: bool,
+    /// Never participate in consensus, even if the network key is in the validator set.
+    pub auditor_only: bool,
     /// Maximal number of slots for election.
     pub max_slot_count: i64,
     /// Minimal stake amount.
     pub min_stake_amount: i64,
     /// Minimal interval between loader runs.
     pub loader_timeout: Duration,
+    /// Maximal interval between loader runs if history requests don't make progress.
+    pub max_loader_timeout: Duration,
+    /// The number of unanswered history requests to a peer before trying another one.
+    pub loader_requests_per_peer: u32,
+    /// Minimal interval between manual re-syncs requested via API.
+    pub resync_timeout: Duration,
+    /// Maximal allowed difference between block timestamp and local time.
+    pub max_clock_drift: Duration,
+    /// The maximal rate of broadcast blocks from one producer, per second.
+    /// Only blocks with valid signatures are counted. Blocks of a producer which
+    /// has exceeded the rate are dropped before checking signatures. Zero means unlimited.
+    pub max_blocks_per_second: u32,
+    /// The maximal number of broadcast blocks from one producer at once.
+    pub max_blocks_burst: u32,
+    /// Where to keep mempool transactions.
+    pub mempool_storage: MempoolStorage,
+}
+
+/// Storage backend for mempool transactions.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type")]
+#[serde(rename_all = "snake_case")]
+pub enum MempoolStorage {
+    /// Keep transactions in memory. They are lost on restart.
+    Memory,
+    /// Keep transactions in a database at the given path.
+    Disk { path: String },
+}
+
+impl Default for MempoolStorage {
+    fn default() -> Self {
+        MempoolStorage::Memory
+    }
 }
 
 impl Default for ChainConfig {
@@ -71,30 +159,121 @@ impl Default for ChainConfig {
 
         ChainConfig {
             tx_wait_timeout,
+            min_txs_for_immediate_block: 0,
+            view_change_tx_wait_timeout: Duration::from_secs(0),
+            skip_empty_blocks: false,
+            empty_block_timeout: Duration::from_secs(20),
+            confirmation_depth: 2,
+            coinbase_maturity: blockchain_default.coinbase_maturity,
             micro_block_timeout,
             macro_block_timeout,
+            macro_propose_timeout: None,
+            macro_commit_timeout: None,
             stake_epochs: blockchain_default.stake_epochs,
             blocks_in_epoch: 5,
             max_utxo_in_tx: 10,
+            max_tx_bytes: 100 * 1024, // 100 KiB
             max_utxo_in_block: 1000,
+            max_block_bytes: 5 * 1024 * 1024, // 5 MiB
             max_utxo_in_mempool: 10000,
+            max_mempool_bytes: 50 * 1024 * 1024, // 50 MiB
+            max_pending_transactions: 1000,
             chain_loader_speed_in_epoch: 10,
-            block_reward: 60_000_000, // 60 STG
-            payment_fee: 1_000,       // 0.001 STG
-            stake_fee: 0,             // free
+            max_blocks_per_response: 10,
+            max_response_bytes: 5 * 1024 * 1024, // 5 MiB
+            block_reward: 60_000_000,            // 60 STG
+            block_reward_halving_interval: 0,    // never
+            macro_block_reward: 0,               // no reward
+            payment_fee: 1_000,                  // 0.001 STG
+            stake_fee: 0,                        // free
+            fee_policy: blockchain_default.fee_policy,
+            bft_threshold: blockchain_default.bft_threshold,
+            strict_reward_validation: true,
+            auditor_only: false,
             max_slot_count: blockchain_default.max_slot_count,
             min_stake_amount: blockchain_default.min_stake_amount,
             loader_timeout: Duration::from_millis(500),
+            max_loader_timeout: Duration::from_secs(30),
+            loader_requests_per_peer: 1,
+            resync_timeout: Duration::from_secs(10),
+            max_clock_drift: Duration::from_secs(30),
+            max_blocks_per_second: 2,
+            max_blocks_burst: 20,
+            mempool_storage: MempoolStorage::default(),
         }
     }
 }
 
+impl ChainConfig {
+    /// Returns the expected block reward for the block at `height`.
+    pub fn reward_at_height(&self, height: u64) -> i64 {
+        if self.block_reward_halving_interval == 0 {
+            return self.block_reward;
+        }
+        let halvings = height / self.block_reward_halving_interval;
+        if halvings >= 63 {
+            return 0;
+        }
+        self.block_reward >> halvings
+    }
+
+    /// Returns how long to wait for a macro block proposal.
+    pub fn macro_propose_timeout(&self) -> Duration {
+        self.macro_propose_timeout
+            .unwrap_or(self.macro_block_timeout)
+    }
+
+    /// Returns how long to wait for a commit of the proposed macro block.
+    pub fn macro_commit_timeout(&self) -> Duration {
+        self.macro_commit_timeout
+            .unwrap_or(self.macro_block_timeout)
+    }
+}
+
 impl Into<BlockchainConfig> for ChainConfig {
     fn into(self) -> BlockchainConfig {
         BlockchainConfig {
             max_slot_count: self.max_slot_count,
             min_stake_amount: self.min_stake_amount,
             stake_epochs: self.stake_epochs,
+            fee_policy: self.fee_policy,
+            bft_threshold: self.bft_threshold,
+            coinbase_maturity: self.coinbase_maturity,
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reward_at_height() {
+        let mut cfg: ChainConfig = Default::default();
+        assert_eq!(cfg.reward_at_height(0), cfg.block_reward);
+        assert_eq!(cfg.reward_at_height(u64::max_value()), cfg.block_reward);
+
+        cfg.block_reward = 60;
+        cfg.block_reward_halving_interval = 100;
+        assert_eq!(cfg.reward_at_height(0), 60);
+        assert_eq!(cfg.reward_at_height(99), 60);
+        assert_eq!(cfg.reward_at_height(100), 30);
+        assert_eq!(cfg.reward_at_height(199), 30);
+        assert_eq!(cfg.reward_at_height(200), 15);
+        assert_eq!(cfg.reward_at_height(300), 7);
+        assert_eq!(cfg.reward_at_height(100 * 63), 0);
+        assert_eq!(cfg.reward_at_height(u64::max_value()), 0);
+    }
+
+    #[test]
+    fn macro_block_timeouts() {
+        let mut cfg: ChainConfig = Default::default();
+        assert_eq!(cfg.macro_propose_timeout(), cfg.macro_block_timeout);
+        assert_eq!(cfg.macro_commit_timeout(), cfg.macro_block_timeout);
+
+        cfg.macro_propose_timeout = Some(Duration::from_secs(5));
+        cfg.macro_commit_timeout = Some(Duration::from_secs(60));
+        assert_eq!(cfg.macro_propose_timeout(), Duration::from_secs(5));
+        assert_eq!(cfg.macro_commit_timeout(), Duration::from_secs(60));
+    }
+}
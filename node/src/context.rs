@@ -0,0 +1,75 @@
+//! Logging Context.
+
+//
+// Copyright (c) 2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::fmt;
+use stegos_blockchain::Block;
+
+///
+/// Height and view_change of the block being processed.
+///
+/// Used as a prefix of log lines related to block processing and fork resolution,
+/// to make it possible to filter all messages about a particular height.
+///
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) struct BlockContext {
+    pub height: u64,
+    pub view_change: u32,
+}
+
+impl BlockContext {
+    pub fn new(height: u64, view_change: u32) -> Self {
+        BlockContext {
+            height,
+            view_change,
+        }
+    }
+
+    pub fn of(block: &Block) -> Self {
+        let header = block.base_header();
+        Self::new(header.height, header.view_change)
+    }
+}
+
+impl fmt::Display for BlockContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[height={}, view_change={}]",
+            self.height, self.view_change
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn display() {
+        let ctx = BlockContext::new(10, 2);
+        assert_eq!(
+            format!("{} Message", ctx),
+            "[height=10, view_change=2] Message"
+        );
+    }
+}
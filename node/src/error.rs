@@ -24,9 +24,9 @@
 // SOFTWARE.
 
 use failure::Fail;
-use std::time::SystemTime;
-use stegos_blockchain::{BlockError, BlockchainError};
+use std::time::{Duration, SystemTime};
 use stegos_crypto::hash::Hash;
+use stegos_crypto::pbc;
 
 #[derive(Debug, Fail, PartialEq, Eq)]
 pub enum NodeTransactionError {
@@ -38,12 +38,45 @@ pub enum NodeTransactionError {
     #[fail(display = "Transaction already exists in mempool: tx={}", _0)]
     AlreadyExists(Hash),
     #[fail(
-        display = "Transaction is too large: tx={}, got_inout={}, max_inout={}",
+        display = "Transaction is too large: tx={}, got={}, max={}",
         _0, _1, _2
     )]
     TooLarge(Hash, usize, usize),
+    #[fail(
+        display = "Transaction has too many bytes: tx={}, got={}, max={}",
+        _0, _1, _2
+    )]
+    TooManyBytes(Hash, usize, usize),
     #[fail(display = "Can't process transaction - mempool is full: tx={}", _0)]
     MempoolIsFull(Hash),
+    #[fail(
+        display = "Can't process transaction - validation queue is full: tx={}",
+        _0
+    )]
+    ValidationQueueIsFull(Hash),
+    #[fail(
+        display = "Transaction spends the same input twice: tx={}, utxo={}",
+        _0, _1
+    )]
+    DuplicateInput(Hash, Hash),
+    #[fail(
+        display = "Transaction spends an immature block reward: tx={}, utxo={}, confirmations={}, required={}",
+        _0, _1, _2, _3
+    )]
+    ImmatureCoinbase(Hash, Hash, u64, u64),
+    #[fail(
+        display = "Transaction doesn't pay more than conflicting transactions in mempool: tx={}, fee={}, conflicts_fee={}",
+        _0, _1, _2
+    )]
+    ConflictsWithMempool(Hash, i64, i64),
+}
+
+#[derive(Debug, Fail)]
+pub enum NodeMessageError {
+    #[fail(display = "Malformed message: topic={}, error={}", _0, _1)]
+    Malformed(&'static str, failure::Error),
+    #[fail(display = "Malformed message: topic={}, from={}, error={}", _0, _1, _2)]
+    MalformedFrom(&'static str, pbc::PublicKey, failure::Error),
 }
 
 #[derive(Debug, Fail, PartialEq, Eq)]
@@ -78,38 +111,42 @@ pub enum NodeBlockError {
         _0, _1
     )]
     OutdatedBlock(SystemTime, SystemTime),
+    #[fail(
+        display = "Block timestamp is too far in the future: height={}, block={}, drift={:?}",
+        _0, _1, _2
+    )]
+    TimestampInFuture(u64, Hash, Duration),
+    #[fail(
+        display = "Block timestamp is earlier than the previous one: height={}, previous_timestamp={:?}, block_timestamp={:?}",
+        _0, _1, _2
+    )]
+    NonMonotonicTimestamp(u64, SystemTime, SystemTime),
+    #[fail(display = "Block not found: height={}, current_height={}", _0, _1)]
+    NoSuchBlock(u64, u64),
 }
 
-#[derive(Debug, Fail)]
-pub enum ForkError {
-    #[fail(display = "Our branch is more significant, drop this block.")]
-    Canceled,
-    #[fail(display = "We have found a error processing fork: error={}.", _0)]
-    Error(failure::Error),
-}
-
-impl From<failure::Error> for ForkError {
-    fn from(err: failure::Error) -> ForkError {
-        ForkError::Error(err)
-    }
-}
-
-impl From<NodeBlockError> for ForkError {
-    fn from(err: NodeBlockError) -> ForkError {
-        ForkError::Error(err.into())
-    }
-}
-
-impl From<BlockError> for ForkError {
-    fn from(err: BlockError) -> ForkError {
-        ForkError::Error(err.into())
-    }
-}
-
-impl From<BlockchainError> for ForkError {
-    fn from(err: BlockchainError) -> ForkError {
-        ForkError::Error(err.into())
-    }
+#[derive(Debug, Fail, PartialEq, Eq)]
+pub enum NodeConfigError {
+    #[fail(display = "blocks_in_epoch must be positive")]
+    ZeroBlocksInEpoch,
+    #[fail(
+        display = "blocks_in_epoch doesn't match the chain: blocks_in_epoch={}, epoch={}, last_macro_block_height={}, height={}",
+        _0, _1, _2, _3
+    )]
+    BlocksInEpochMismatch(u64, u64, u64, u64),
+    #[fail(
+        display = "bft_threshold must be in [0, 1): numerator={}, denominator={}",
+        _0, _1
+    )]
+    InvalidBftThreshold(i64, i64),
+    #[fail(
+        display = "empty_block_timeout must be less than micro_block_timeout: empty_block_timeout={:?}, micro_block_timeout={:?}",
+        _0, _1
+    )]
+    EmptyBlockTimeoutTooLong(Duration, Duration),
+    #[fail(
+        display = "view_change_tx_wait_timeout must be less than micro_block_timeout: view_change_tx_wait_timeout={:?}, micro_block_timeout={:?}",
+        _0, _1
+    )]
+    ViewChangeTxWaitTimeoutTooLong(Duration, Duration),
 }
-
-pub type ForkResult = Result<(), ForkError>;
@@ -0,0 +1,294 @@
+//! Fee Estimation.
+
+//
+// Copyright (c) 2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use serde_derive::Serialize;
+use std::cmp;
+use std::collections::VecDeque;
+use stegos_blockchain::Transaction;
+
+/// The number of recently mined transactions used for fee estimation.
+const RECENT_FEES_LEN: usize = 100;
+
+///
+/// Advisory fee recommendation, per transaction output.
+///
+/// `low` is always the minimal fee accepted by the network.
+/// `medium` and `high` are the median and the 90th percentile of fees per output
+/// paid by recently mined transactions. They are raised proportionally to the
+/// mempool fullness, up to x2 for `medium` and up to x3 for `high` when the
+/// mempool is full. Any fee above `low` is valid, these values are just a hint.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FeeEstimate {
+    pub low: i64,
+    pub medium: i64,
+    pub high: i64,
+}
+
+///
+/// Predicted number of blocks until a new transaction is included.
+///
+/// The block builder takes transactions in arrival order, regardless of fees,
+/// so the new transaction waits for all queued ones. Blocks are filled the same
+/// way as the builder does, limited by both UTXO and bytes. The new transaction
+/// is assumed to be a minimal one, with a single input and a single output.
+/// Queued transactions which spend outputs of other queued transactions are
+/// skipped, as the builder does. Transactions which arrive later are not taken
+/// into account.
+///
+/// The fee distribution of queued transactions is intentionally not used:
+/// a higher fee doesn't move a transaction ahead of the queue, so an estimate
+/// based on fees would promise an earlier inclusion than the node delivers.
+///
+/// `blocks` is 1 for the next block, or None if the fee is below the minimal fee
+/// and the transaction would be rejected.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InclusionEstimate {
+    pub blocks: Option<u64>,
+    /// The number of inputs and outputs of all queued transactions.
+    pub utxo_ahead: usize,
+    /// The maximal number of inputs and outputs of transactions in a block.
+    pub block_capacity: usize,
+}
+
+/// The number of UTXO reserved for the coinbase of each block.
+const COINBASE_UTXO: usize = 2;
+/// The number of UTXO of a minimal transaction.
+const MIN_TX_UTXO: usize = 2;
+
+/// Estimates inclusion of a new transaction paying `fee` per output.
+/// `queued` yields the number of UTXO and the size in bytes of each mempool
+/// transaction, in the order they were queued.
+pub(crate) fn estimate_inclusion<I>(
+    fee: i64,
+    min_fee: i64,
+    queued: I,
+    max_utxo_in_block: usize,
+    max_block_bytes: usize,
+) -> InclusionEstimate
+where
+    I: Iterator<Item = (usize, usize)>,
+{
+    // Mirrors Mempool::create_block().
+    let fits = |utxo_in_block: usize, bytes_in_block: usize, utxo: usize, bytes: usize| {
+        utxo_in_block + utxo < max_utxo_in_block && bytes_in_block + bytes <= max_block_bytes
+    };
+    let mut blocks: u64 = 1;
+    let mut utxo_in_block: usize = COINBASE_UTXO;
+    let mut bytes_in_block: usize = 0;
+    let mut utxo_ahead: usize = 0;
+    for (utxo, bytes) in queued {
+        // A transaction which doesn't fit into an empty block still takes a block.
+        if utxo_in_block > COINBASE_UTXO && !fits(utxo_in_block, bytes_in_block, utxo, bytes) {
+            blocks += 1;
+            utxo_in_block = COINBASE_UTXO;
+            bytes_in_block = 0;
+        }
+        utxo_in_block += utxo;
+        bytes_in_block += bytes;
+        utxo_ahead += utxo;
+    }
+    if !fits(utxo_in_block, bytes_in_block, MIN_TX_UTXO, 0) {
+        blocks += 1;
+    }
+
+    let blocks = if fee < min_fee { None } else { Some(blocks) };
+    let block_capacity = max_utxo_in_block.saturating_sub(COINBASE_UTXO + 1);
+    InclusionEstimate {
+        blocks,
+        utxo_ahead,
+        block_capacity,
+    }
+}
+
+/// Returns the fee paid per output of the transaction.
+pub(crate) fn fee_per_output(tx: &Transaction) -> i64 {
+    let outputs = cmp::max(tx.txouts().len(), 1) as i64;
+    tx.fee() / outputs
+}
+
+/// A ring buffer of fees paid by recently mined transactions.
+pub(crate) struct RecentFees {
+    fees: VecDeque<i64>,
+}
+
+impl RecentFees {
+    ///
+    /// Creates an empty buffer.
+    ///
+    pub fn new() -> Self {
+        let fees: VecDeque<i64> = VecDeque::with_capacity(RECENT_FEES_LEN);
+        RecentFees { fees }
+    }
+
+    ///
+    /// Registers the fee per output of a mined transaction.
+    ///
+    pub fn push(&mut self, fee: i64) {
+        if self.fees.len() >= RECENT_FEES_LEN {
+            self.fees.pop_front();
+        }
+        self.fees.push_back(fee);
+    }
+
+    ///
+    /// Estimates fee using recently mined transactions and the mempool fullness.
+    ///
+    pub fn estimate(
+        &self,
+        min_fee: i64,
+        utxo_in_mempool: usize,
+        max_utxo_in_mempool: usize,
+    ) -> FeeEstimate {
+        let mut fees: Vec<i64> = self.fees.iter().cloned().collect();
+        fees.sort();
+        let percentile = |p: usize| -> i64 {
+            if fees.is_empty() {
+                min_fee
+            } else {
+                fees[(fees.len() - 1) * p / 100]
+            }
+        };
+        let medium = cmp::max(min_fee, percentile(50));
+        let high = cmp::max(medium, percentile(90));
+
+        // Mempool fullness, in percents.
+        let fullness = if max_utxo_in_mempool == 0 {
+            100
+        } else {
+            cmp::min(100, utxo_in_mempool * 100 / max_utxo_in_mempool)
+        } as i64;
+        let medium = medium.saturating_add(medium.saturating_mul(fullness) / 100);
+        let high = high.saturating_add(high.saturating_mul(2 * fullness) / 100);
+
+        FeeEstimate {
+            low: min_fee,
+            medium,
+            high,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn estimate() {
+        let min_fee: i64 = 10;
+        let mut recent_fees = RecentFees::new();
+
+        // No statistics.
+        let estimate = recent_fees.estimate(min_fee, 0, 100);
+        assert_eq!(
+            estimate,
+            FeeEstimate {
+                low: min_fee,
+                medium: min_fee,
+                high: min_fee
+            }
+        );
+
+        // Full mempool.
+        let estimate = recent_fees.estimate(min_fee, 200, 100);
+        assert_eq!(estimate.low, min_fee);
+        assert_eq!(estimate.medium, 2 * min_fee);
+        assert_eq!(estimate.high, 3 * min_fee);
+
+        // Recently mined transactions.
+        for fee in 1..=100 {
+            recent_fees.push(fee);
+        }
+        let estimate = recent_fees.estimate(min_fee, 0, 100);
+        assert_eq!(estimate.low, min_fee);
+        assert_eq!(estimate.medium, 50);
+        assert_eq!(estimate.high, 90);
+
+        // Half-full mempool.
+        let estimate = recent_fees.estimate(min_fee, 50, 100);
+        assert_eq!(estimate.low, min_fee);
+        assert_eq!(estimate.medium, 75);
+        assert_eq!(estimate.high, 180);
+
+        // Old fees are evicted.
+        for _ in 0..RECENT_FEES_LEN {
+            recent_fees.push(1000);
+        }
+        let estimate = recent_fees.estimate(min_fee, 0, 100);
+        assert_eq!(estimate.medium, 1000);
+        assert_eq!(estimate.high, 1000);
+    }
+
+    #[test]
+    fn inclusion() {
+        let min_fee: i64 = 10;
+        let max_utxo_in_block: usize = 100;
+        let max_block_bytes: usize = 10_000;
+        let estimate = |fee: i64, queued: &[(usize, usize)], max_utxo_in_block, max_block_bytes| {
+            estimate_inclusion(
+                fee,
+                min_fee,
+                queued.iter().cloned(),
+                max_utxo_in_block,
+                max_block_bytes,
+            )
+        };
+
+        // Empty mempool.
+        assert_eq!(
+            estimate(min_fee, &[], max_utxo_in_block, max_block_bytes),
+            InclusionEstimate {
+                blocks: Some(1),
+                utxo_ahead: 0,
+                block_capacity: max_utxo_in_block - 3,
+            }
+        );
+
+        // Fee below the minimum.
+        let e = estimate(min_fee - 1, &[], max_utxo_in_block, max_block_bytes);
+        assert_eq!(e.blocks, None);
+
+        // 20 transactions with 10 UTXO each, 9 of them fit into a block.
+        // Any fee waits for all of them.
+        let queued: Vec<(usize, usize)> = (0..20).map(|_| (10, 100)).collect();
+        for fee in &[min_fee, 1000 * min_fee] {
+            let e = estimate(*fee, &queued, max_utxo_in_block, max_block_bytes);
+            assert_eq!(e.utxo_ahead, 200);
+            assert_eq!(e.blocks, Some(3));
+        }
+
+        // The coinbase and the queued transaction leave no room for another one.
+        let queued = [(10, 100)];
+        let e = estimate(min_fee, &queued, 2 + 10 + 2, max_block_bytes);
+        assert_eq!(e.blocks, Some(2));
+        let e = estimate(min_fee, &queued, 2 + 10 + 2 + 1, max_block_bytes);
+        assert_eq!(e.blocks, Some(1));
+
+        // Blocks are also limited by size, two transactions per block.
+        let queued: Vec<(usize, usize)> = (0..5).map(|_| (10, 100)).collect();
+        let e = estimate(min_fee, &queued, max_utxo_in_block, 250);
+        assert_eq!(e.utxo_ahead, 50);
+        assert_eq!(e.blocks, Some(3));
+    }
+}
@@ -0,0 +1,157 @@
+//! Queue of consensus messages from the future.
+
+//
+// Copyright (c) 2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use log::*;
+use std::collections::{HashSet, VecDeque};
+use stegos_consensus::BlockConsensusMessage;
+use stegos_crypto::hash::Hash;
+
+/// The maximal number of queued consensus messages.
+pub(crate) const MAX_FUTURE_CONSENSUS_MESSAGES: usize = 1000;
+
+///
+/// A bounded queue of consensus messages received before the consensus was started.
+/// Duplicates are ignored and the oldest messages are dropped when the queue is full.
+///
+pub(crate) struct FutureConsensusMessages {
+    /// Maximal number of messages.
+    capacity: usize,
+    /// Messages in the insertion order.
+    messages: VecDeque<(Hash, BlockConsensusMessage)>,
+    /// Hashes of queued messages.
+    hashes: HashSet<Hash>,
+}
+
+impl FutureConsensusMessages {
+    ///
+    /// Creates an empty queue.
+    ///
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0);
+        let messages: VecDeque<(Hash, BlockConsensusMessage)> = VecDeque::new();
+        let hashes: HashSet<Hash> = HashSet::new();
+        FutureConsensusMessages {
+            capacity,
+            messages,
+            hashes,
+        }
+    }
+
+    ///
+    /// Queues a message.
+    ///
+    pub fn push(&mut self, msg: BlockConsensusMessage) {
+        let msg_hash = Hash::digest(&msg);
+        if !self.hashes.insert(msg_hash) {
+            debug!("Skip a duplicate consensus message: hash={}", msg_hash);
+            return;
+        }
+        if self.messages.len() >= self.capacity {
+            let (oldest_hash, _oldest) = self.messages.pop_front().unwrap();
+            self.hashes.remove(&oldest_hash);
+            debug!(
+                "Dropped the oldest consensus message, queue is full: hash={}",
+                oldest_hash
+            );
+        }
+        self.messages.push_back((msg_hash, msg));
+    }
+
+    ///
+    /// Removes all messages, sorted by height and round.
+    ///
+    pub fn drain(&mut self) -> Vec<BlockConsensusMessage> {
+        self.hashes.clear();
+        let mut messages: Vec<BlockConsensusMessage> =
+            self.messages.drain(..).map(|(_hash, msg)| msg).collect();
+        // The sort is stable, the insertion order is kept for the same round.
+        messages.sort_by_key(|msg| (msg.height, msg.round));
+        messages
+    }
+
+    ///
+    /// Removes all messages.
+    ///
+    pub fn clear(&mut self) {
+        self.hashes.clear();
+        self.messages.clear();
+    }
+
+    ///
+    /// Returns the number of queued messages.
+    ///
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use stegos_consensus::{ConsensusMessage, ConsensusMessageBody};
+    use stegos_crypto::pbc;
+
+    fn prevote(
+        skey: &pbc::SecretKey,
+        pkey: &pbc::PublicKey,
+        height: u64,
+        round: u32,
+    ) -> BlockConsensusMessage {
+        let request_hash = Hash::digest("test");
+        ConsensusMessage::new(
+            height,
+            round,
+            request_hash,
+            skey,
+            pkey,
+            ConsensusMessageBody::Prevote {},
+        )
+    }
+
+    #[test]
+    fn bounded() {
+        let (skey, pkey) = pbc::make_random_keys();
+        let mut queue = FutureConsensusMessages::new(3);
+
+        // Duplicates are ignored.
+        queue.push(prevote(&skey, &pkey, 10, 0));
+        queue.push(prevote(&skey, &pkey, 10, 0));
+        assert_eq!(queue.len(), 1);
+
+        // The oldest message is dropped.
+        queue.push(prevote(&skey, &pkey, 12, 0));
+        queue.push(prevote(&skey, &pkey, 11, 1));
+        queue.push(prevote(&skey, &pkey, 11, 0));
+        assert_eq!(queue.len(), 3);
+
+        // Messages are sorted by height and round.
+        let messages = queue.drain();
+        let order: Vec<(u64, u32)> = messages.iter().map(|m| (m.height, m.round)).collect();
+        assert_eq!(order, vec![(11, 0), (11, 1), (12, 0)]);
+        assert_eq!(queue.len(), 0);
+
+        // A dropped message can be queued again.
+        queue.push(prevote(&skey, &pkey, 10, 0));
+        assert_eq!(queue.len(), 1);
+    }
+}
@@ -22,20 +22,33 @@
 // SOFTWARE.
 
 mod config;
+mod context;
 mod error;
+mod fee;
+mod future_messages;
 mod loader;
 mod mempool;
+mod mempool_storage;
 pub mod metrics;
+mod participation;
 pub mod protos;
+mod ratelimit;
 #[cfg(test)]
 mod test;
 #[macro_use]
 pub mod timer;
 mod validation;
-pub use crate::config::ChainConfig;
+pub use crate::config::{ChainConfig, MempoolStorage};
+use crate::context::BlockContext;
 use crate::error::*;
+use crate::fee::{estimate_inclusion, fee_per_output, RecentFees};
+pub use crate::fee::{FeeEstimate, InclusionEstimate};
+use crate::future_messages::{FutureConsensusMessages, MAX_FUTURE_CONSENSUS_MESSAGES};
 use crate::loader::ChainLoaderMessage;
 use crate::mempool::Mempool;
+use crate::participation::Participation;
+pub use crate::participation::{ParticipationInfo, ValidatorParticipation};
+use crate::ratelimit::{RateLimiter, MAX_RATE_LIMITED_PEERS};
 use crate::timer::{Interval, TimerEvents};
 use crate::validation::*;
 use bitvector::BitVector;
@@ -43,24 +56,31 @@ use failure::Error;
 use futures::sync::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
 use futures::sync::oneshot;
 use futures::{Async, Future, Poll, Stream};
+use futures_cpupool::{CpuFuture, CpuPool};
 use futures_stream_select_all_send::select_all;
+use linked_hash_map::LinkedHashMap;
 use log::*;
 use protobuf;
 use protobuf::Message;
+use serde::ser::SerializeSeq;
 use serde_derive::Deserialize;
 use serde_derive::Serialize;
+use std::cmp;
 use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::VecDeque;
 use std::time::SystemTime;
 use std::time::{Duration, Instant};
 use stegos_blockchain::view_changes::ViewChangeProof;
 use stegos_blockchain::*;
 use stegos_consensus::optimistic::{SealedViewChangeProof, ViewChangeCollector, ViewChangeMessage};
-use stegos_consensus::{self as consensus, BlockConsensus, BlockConsensusMessage};
-use stegos_crypto::hash::Hash;
+use stegos_consensus::{self as consensus, BlockConsensus, BlockConsensusMessage, MessageBounds};
+use stegos_crypto::hash::{Hash, Hashable, Hasher};
 use stegos_crypto::pbc;
+use stegos_crypto::utils::u8v_to_hexstr;
 use stegos_keychain::KeyChain;
 use stegos_network::Network;
-use stegos_network::UnicastMessage;
+use stegos_network::{BroadcastMessage, UnicastMessage};
 use stegos_network::{NETWORK_READY_TOKEN, NETWORK_STATUS_TOPIC};
 use stegos_serialization::traits::ProtoConvert;
 use tokio_timer::clock;
@@ -88,42 +108,113 @@ impl Node {
         Ok(())
     }
 
+    /// Remove a pending transaction from the local mempool.
+    ///
+    /// This is a no-op if the transaction has already been included into a block.
+    /// Copies of the transaction which have already been broadcast to other nodes
+    /// are not affected and still can be mined by them.
+    pub fn cancel_transaction(&self, tx_hash: Hash) -> Result<(), Error> {
+        let msg = NodeMessage::CancelTransaction(tx_hash);
+        self.outbox.unbounded_send(msg)?;
+        Ok(())
+    }
+
+    /// Import a serialized mempool of another node into the local mempool.
+    ///
+    /// Duplicates are skipped. A transaction which double-spends local transactions
+    /// replaces them only if it pays a higher fee than all of them together.
+    pub fn merge_mempool(&self, data: Vec<u8>) -> Result<(), Error> {
+        let msg = NodeMessage::MergeMempool(data);
+        self.outbox.unbounded_send(msg)?;
+        Ok(())
+    }
+
     /// Execute a Node Request.
-    pub fn request(&self, request: NodeRequest) -> oneshot::Receiver<NodeResponse> {
+    pub fn request(&self, request: NodeRequest) -> Result<oneshot::Receiver<NodeResponse>, Error> {
         let (tx, rx) = oneshot::channel();
         let msg = NodeMessage::Request { request, tx };
-        self.outbox.unbounded_send(msg).expect("connected");
-        rx
+        self.outbox.unbounded_send(msg)?;
+        Ok(rx)
     }
 
     /// Subscribe to block changes.
-    pub fn subscribe_block_added(&self) -> UnboundedReceiver<BlockAdded> {
+    pub fn subscribe_block_added(&self) -> Result<UnboundedReceiver<BlockAdded>, Error> {
         let (tx, rx) = unbounded();
         let msg = NodeMessage::SubscribeBlockAdded(tx);
-        self.outbox.unbounded_send(msg).expect("connected");
-        rx
+        self.outbox.unbounded_send(msg)?;
+        Ok(rx)
     }
 
     /// Subscribe to epoch changes.
-    pub fn subscribe_epoch_changed(&self) -> UnboundedReceiver<EpochChanged> {
+    pub fn subscribe_epoch_changed(&self) -> Result<UnboundedReceiver<EpochChanged>, Error> {
         let (tx, rx) = unbounded();
         let msg = NodeMessage::SubscribeEpochChanged(tx);
-        self.outbox.unbounded_send(msg).expect("connected");
-        rx
+        self.outbox.unbounded_send(msg)?;
+        Ok(rx)
+    }
+
+    /// Subscribe to finalized epochs.
+    pub fn subscribe_epoch_finalized(&self) -> Result<UnboundedReceiver<EpochFinalized>, Error> {
+        let (tx, rx) = unbounded();
+        let msg = NodeMessage::SubscribeEpochFinalized(tx);
+        self.outbox.unbounded_send(msg)?;
+        Ok(rx)
     }
 
     /// Subscribe to UTXO changes.
-    pub fn subscribe_outputs_changed(&self) -> UnboundedReceiver<OutputsChanged> {
+    pub fn subscribe_outputs_changed(&self) -> Result<UnboundedReceiver<OutputsChanged>, Error> {
         let (tx, rx) = unbounded();
         let msg = NodeMessage::SubscribeOutputsChanged(tx);
-        self.outbox.unbounded_send(msg).expect("connected");
-        rx
+        self.outbox.unbounded_send(msg)?;
+        Ok(rx)
+    }
+
+    /// Subscribe to status changes of transactions.
+    pub fn subscribe_transaction_status(
+        &self,
+    ) -> Result<UnboundedReceiver<TransactionStatusChanged>, Error> {
+        let (tx, rx) = unbounded();
+        let msg = NodeMessage::SubscribeTransactionStatus(tx);
+        self.outbox.unbounded_send(msg)?;
+        Ok(rx)
+    }
+
+    /// Subscribe to outcomes of fork resolution.
+    pub fn subscribe_fork_resolved(&self) -> Result<UnboundedReceiver<ForkResolved>, Error> {
+        let (tx, rx) = unbounded();
+        let msg = NodeMessage::SubscribeForkResolved(tx);
+        self.outbox.unbounded_send(msg)?;
+        Ok(rx)
+    }
+
+    /// Subscribe to additions and removals of mempool transactions.
+    pub fn subscribe_mempool_changed(&self) -> Result<UnboundedReceiver<MempoolChanged>, Error> {
+        let (tx, rx) = unbounded();
+        let msg = NodeMessage::SubscribeMempoolChanged(tx);
+        self.outbox.unbounded_send(msg)?;
+        Ok(rx)
+    }
+
+    /// Subscribe to blocks reverted by fork resolution.
+    pub fn subscribe_reorg(&self) -> Result<UnboundedReceiver<ChainReorganized>, Error> {
+        let (tx, rx) = unbounded();
+        let msg = NodeMessage::SubscribeReorg(tx);
+        self.outbox.unbounded_send(msg)?;
+        Ok(rx)
     }
 
     /// Revert the latest block.
-    pub fn pop_block(&self) {
+    pub fn pop_block(&self) -> Result<(), Error> {
         let msg = NodeMessage::PopBlock;
-        self.outbox.unbounded_send(msg).expect("connected");
+        self.outbox.unbounded_send(msg)?;
+        Ok(())
+    }
+
+    /// Forbid reverting blocks at or below the specified height.
+    pub fn set_checkpoint(&self, height: u64) -> Result<(), Error> {
+        let msg = NodeMessage::SetCheckpoint(height);
+        self.outbox.unbounded_send(msg)?;
+        Ok(())
     }
 }
 
@@ -136,6 +227,22 @@ impl Node {
 pub enum NodeRequest {
     ElectionInfo {},
     EscrowInfo {},
+    FeeEstimate {},
+    ParticipationInfo {},
+    LeaderSchedule { count: u32 },
+    ValidatorSet {},
+    UtxoSnapshot { after: Option<Hash> },
+    ViewChangeProof {},
+    EpochHistory { from_epoch: u64, limit: u32 },
+    RawBlock { height: u64 },
+    OrphanBlocks {},
+    Resync {},
+    TransactionByHash { hash: Hash },
+    TransactionPending { tx_hash: Hash },
+    FeeParams {},
+    Health {},
+    InclusionEstimate { fee: i64 },
+    RewardViolations {},
 }
 
 ///
@@ -147,6 +254,267 @@ pub enum NodeRequest {
 pub enum NodeResponse {
     ElectionInfo(ElectionInfo),
     EscrowInfo(EscrowInfo),
+    FeeEstimate(FeeEstimate),
+    ParticipationInfo(ParticipationInfo),
+    LeaderSchedule(LeaderSchedule),
+    ValidatorSet(ValidatorSet),
+    UtxoSnapshot(UtxoSnapshot),
+    ViewChangeProof(ViewChangeProofInfo),
+    EpochHistory(EpochHistory),
+    RawBlock(RawBlock),
+    OrphanBlocks(OrphanBlocks),
+    Resync(ResyncInfo),
+    TransactionLocation(TransactionLocation),
+    Pending(PendingInfo),
+    FeeParams(FeeParams),
+    Health(HealthInfo),
+    InclusionEstimate(InclusionEstimate),
+    RewardViolations(RewardViolations),
+    Error { error: String },
+}
+
+///
+/// Leaders of the next view changes at the current height.
+///
+/// This is a best-effort projection. The leader of the next height depends on
+/// the randomness of the next block, which is unknown in advance. Therefore
+/// `leaders[i]` is the leader for `view_change + i` at `height` only, i.e.
+/// the sequence of leaders which will take over if the previous ones fail.
+///
+#[derive(Debug, Clone, Serialize)]
+pub struct LeaderSchedule {
+    pub height: u64,
+    pub view_change: u32,
+    pub leaders: Vec<pbc::PublicKey>,
+}
+
+///
+/// Validators of the current epoch.
+///
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidatorSet {
+    pub epoch: u64,
+    pub total_slots: i64,
+    pub facilitator: pbc::PublicKey,
+    pub validators: Vec<(pbc::PublicKey, i64)>,
+}
+
+///
+/// A page of the UTXO set, ordered by output hash.
+///
+/// Pages are produced on demand, so the chain can advance between requests.
+/// Clients should compare `height` and `block_hash` of all pages and restart
+/// the export if they differ.
+///
+#[derive(Debug, Clone, Serialize)]
+pub struct UtxoSnapshot {
+    /// Height of the block this page is consistent with.
+    pub height: u64,
+    /// Hash of the block this page is consistent with.
+    pub block_hash: Hash,
+    /// Unspent outputs, hex-encoded in protobuf format.
+    #[serde(serialize_with = "serialize_outputs")]
+    pub outputs: Vec<Output>,
+    /// Cursor for the next page, None if this is the last page.
+    pub next: Option<Hash>,
+}
+
+/// Returns the network key of the node which produced the block.
+fn block_producer(block: &Block) -> pbc::PublicKey {
+    match block {
+        Block::MacroBlock(ref block) => block.body.pkey,
+        Block::MicroBlock(ref block) => block.pkey,
+    }
+}
+
+/// Hash of a block together with its signatures.
+/// Unlike Hash::digest(), it differs for copies of the same block with forged signatures.
+fn sealed_block_hash(block: &Block) -> Hash {
+    let mut hasher = Hasher::new();
+    Hash::digest(block).hash(&mut hasher);
+    match block {
+        Block::MacroBlock(ref block) => {
+            block.body.multisig.hash(&mut hasher);
+            for bit in block.body.multisigmap.iter() {
+                (bit as u64).hash(&mut hasher);
+            }
+        }
+        Block::MicroBlock(ref block) => block.sig.hash(&mut hasher),
+    }
+    hasher.result()
+}
+
+fn serialize_outputs<S>(outputs: &[Output], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    let mut seq = serializer.serialize_seq(Some(outputs.len()))?;
+    for output in outputs {
+        let data = output
+            .into_buffer()
+            .map_err(|e| serde::ser::Error::custom(e))?;
+        seq.serialize_element(&u8v_to_hexstr(&data))?;
+    }
+    seq.end()
+}
+
+///
+/// The proof of the last view change at the current height.
+///
+#[derive(Debug, Clone, Serialize)]
+pub struct ViewChangeProofInfo {
+    pub height: u64,
+    pub view_change: u32,
+    /// Hex-encoded in protobuf format, None if view_change is 0.
+    #[serde(serialize_with = "serialize_proof")]
+    pub proof: Option<SealedViewChangeProof>,
+}
+
+fn serialize_proof<S>(
+    proof: &Option<SealedViewChangeProof>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    match proof {
+        Some(proof) => {
+            let data = proof
+                .into_buffer()
+                .map_err(|e| serde::ser::Error::custom(e))?;
+            serializer.serialize_some(&u8v_to_hexstr(&data))
+        }
+        None => serializer.serialize_none(),
+    }
+}
+
+///
+/// Past epochs in ascending order, starting from `from_epoch`.
+///
+#[derive(Debug, Clone, Serialize)]
+pub struct EpochHistory {
+    pub epochs: Vec<EpochInfo>,
+}
+
+///
+/// A block in protobuf format.
+///
+#[derive(Debug, Clone, Serialize)]
+pub struct RawBlock {
+    pub height: u64,
+    pub hash: Hash,
+    /// Base64-encoded protobuf of the block.
+    pub block: String,
+}
+
+///
+/// Blocks from the future waiting for their predecessors.
+///
+#[derive(Debug, Clone, Serialize)]
+pub struct OrphanBlocks {
+    pub height: u64,
+    pub last_block: Hash,
+    /// (height, hash, previous) in ascending order of height.
+    pub blocks: Vec<(u64, Hash, Hash)>,
+}
+
+///
+/// Acknowledgment of a manual history re-sync.
+///
+#[derive(Debug, Clone, Serialize)]
+pub struct ResyncInfo {
+    pub height: u64,
+    /// False if the request was throttled because of a recent re-sync.
+    pub requested: bool,
+}
+
+///
+/// Location of a transaction.
+///
+/// Only the mempool and the last MAX_TRANSACTION_SEARCH_BLOCKS (1000) blocks
+/// are searched, older transactions are reported as unknown.
+///
+#[derive(Debug, Clone, Serialize)]
+pub struct TransactionLocation {
+    pub tx_hash: Hash,
+    pub status: TransactionLocationStatus,
+    /// Height of the block with the transaction, None if it isn't in a block.
+    pub height: Option<u64>,
+}
+
+/// Where a transaction was found.
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionLocationStatus {
+    /// Waiting in the mempool.
+    Pending,
+    /// Included into a micro block, which still can be reverted.
+    InBlock,
+    /// Buried under confirmation_depth blocks or finalized by a macro block.
+    Committed,
+    /// Not found in the mempool and recent blocks, either unknown or too old.
+    Unknown,
+}
+
+///
+/// Presence of a transaction in the mempool.
+///
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingInfo {
+    pub tx_hash: Hash,
+    pub pending: bool,
+}
+
+///
+/// Minimal fees accepted by the node.
+///
+#[derive(Debug, Clone, Serialize)]
+pub struct FeeParams {
+    /// Minimal fee per output of a payment transaction.
+    pub payment_fee: i64,
+    /// Minimal fee of a stake transaction.
+    pub stake_fee: i64,
+}
+
+///
+/// Composite health check for liveness and readiness probes.
+///
+/// A response to the request is enough for liveness. The node is ready to
+/// serve when it is both `synchronized` and `network_ready`. `mempool_ok`
+/// and `validator_active` are informational and don't gate either probe.
+///
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthInfo {
+    /// The last macro block is recent, see is_synchronized().
+    pub synchronized: bool,
+    /// The current height of the blockchain.
+    pub height: u64,
+    /// False if the mempool is at capacity and new transactions are rejected.
+    pub mempool_ok: bool,
+    /// The network has reported readiness, i.e. the initial connection to peers.
+    /// Later disconnects are not reported by the network and don't reset it.
+    pub network_ready: bool,
+    /// The node is a validator of the current epoch.
+    pub validator_active: bool,
+}
+
+/// A block accepted with an unexpected reward, kept as evidence for slashing.
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct RewardViolation {
+    pub height: u64,
+    pub hash: Hash,
+    pub leader: pbc::PublicKey,
+    pub block_reward: i64,
+    pub expected_reward: i64,
+}
+
+///
+/// Recent blocks accepted with an unexpected reward.
+///
+#[derive(Debug, Clone, Serialize)]
+pub struct RewardViolations {
+    /// Oldest first, at most MAX_REWARD_VIOLATIONS.
+    pub violations: Vec<RewardViolation>,
 }
 
 /// Send when height is changed.
@@ -160,6 +528,7 @@ pub struct BlockAdded {
     pub remote_timestamp: i64,
     pub synchronized: bool,
     pub epoch: u64,
+    pub fees: i64,
 }
 
 /// Send when epoch is changed.
@@ -170,18 +539,133 @@ pub struct EpochChanged {
     pub validators: Vec<(pbc::PublicKey, i64)>,
 }
 
+/// Send when a macro block has been committed and its epoch is final.
+#[derive(Clone, Debug, Serialize)]
+pub struct EpochFinalized {
+    /// The finalized epoch.
+    pub epoch: u64,
+    /// The sum of block rewards of all blocks of the epoch.
+    pub total_reward: i64,
+    /// Validators of the next epoch.
+    pub validators: Vec<(pbc::PublicKey, i64)>,
+}
+
 /// Send when outputs created and/or pruned.
 #[derive(Debug, Clone)]
 pub struct OutputsChanged {
     pub epoch: u64,
     pub inputs: Vec<Output>,
     pub outputs: Vec<Output>,
+    /// True if this event was caused by reverting a block.
+    /// In this case `inputs` contains reverted (un-created) outputs
+    /// and `outputs` contains restored (un-spent) outputs.
+    pub rolled_back: bool,
+}
+
+/// Status of a transaction.
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+#[serde(tag = "status")]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionStatus {
+    /// Accepted to mempool or returned from a reverted block.
+    Accepted {},
+    /// Included into a micro block, which still can be reverted.
+    InBlock { height: u64 },
+    /// Buried under confirmation_depth blocks or finalized by a macro block.
+    /// Until the macro block, a revert still moves it back to Accepted.
+    Committed { height: u64 },
+    /// Removed from mempool by NodeRequest::CancelTransaction.
+    Cancelled {},
+    /// Removed from mempool by a merged transaction which pays a higher fee.
+    Replaced { by: Hash },
+}
+
+/// Result of fork resolution.
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+#[serde(tag = "outcome")]
+#[serde(rename_all = "snake_case")]
+pub enum ForkOutcome {
+    /// Remote chain is better, local blocks have been reverted.
+    RemoteWon { rolled_back: u64 },
+    /// Local chain is better.
+    LocalWon,
+    /// The remote block is a copy of the local one.
+    Duplicate,
+    /// The leader has produced two blocks for the same slot.
+    CheatDetected { offender: pbc::PublicKey },
+    /// Chains can't be compared without the missing blocks.
+    NeedHistory,
+}
+
+impl ForkOutcome {
+    /// Short name for metrics.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ForkOutcome::RemoteWon { .. } => "remote_won",
+            ForkOutcome::LocalWon => "local_won",
+            ForkOutcome::Duplicate => "duplicate",
+            ForkOutcome::CheatDetected { .. } => "cheat_detected",
+            ForkOutcome::NeedHistory => "need_history",
+        }
+    }
+}
+
+/// Send when a fork has been resolved.
+#[derive(Clone, Debug, Serialize)]
+pub struct ForkResolved {
+    pub height: u64,
+    pub outcome: ForkOutcome,
+}
+
+/// Send when transactions are added to or removed from the mempool.
+/// Changes are batched, so a transaction can be both in `added` and `removed`.
+#[derive(Clone, Debug, Serialize, Default)]
+pub struct MempoolChanged {
+    pub added: Vec<Hash>,
+    pub removed: Vec<Hash>,
+}
+
+/// Send when the status of a transaction is changed.
+#[derive(Clone, Debug, Serialize)]
+pub struct TransactionStatusChanged {
+    pub tx_hash: Hash,
+    pub status: TransactionStatus,
+}
+
+/// Send when blocks are reverted during fork resolution.
+#[derive(Clone, Debug, Serialize)]
+pub struct ChainReorganized {
+    /// Height before the fork resolution.
+    pub from_height: u64,
+    /// Height after the fork resolution.
+    pub to_height: u64,
+    /// Hashes of reverted blocks, starting from the latest one.
+    pub dropped_block_hashes: Vec<Hash>,
 }
 
 // ----------------------------------------------------------------
 // Internal Implementation.
 // ----------------------------------------------------------------
 
+/// The maximal number of leaders returned by NodeRequest::LeaderSchedule.
+const MAX_LEADER_SCHEDULE_LEN: u32 = 1000;
+/// The maximal number of epochs returned by NodeRequest::EpochHistory.
+const MAX_EPOCH_HISTORY_LEN: u32 = 1000;
+/// The maximal number of remembered rejected blocks.
+const MAX_REJECTED_BLOCKS: usize = 100;
+/// The maximal number of blocks with verified signatures to remember.
+const MAX_VERIFIED_BLOCKS: usize = 100;
+/// The maximal number of outputs returned by NodeRequest::UtxoSnapshot.
+const MAX_UTXO_SNAPSHOT_LEN: usize = 1000;
+/// The maximal number of blocks returned by NodeRequest::OrphanBlocks.
+const MAX_ORPHAN_BLOCKS_LEN: usize = 1000;
+/// The number of recent blocks searched by NodeRequest::TransactionByHash.
+const MAX_TRANSACTION_SEARCH_BLOCKS: u64 = 1000;
+/// The maximal number of rounds ahead of the local consensus to accept messages for.
+const MAX_CONSENSUS_ROUNDS_AHEAD: u32 = 100;
+/// The maximal number of remembered reward violations.
+const MAX_REWARD_VIOLATIONS: usize = 100;
+
 /// Topic used for sending transactions.
 const TX_TOPIC: &'static str = "tx";
 /// Topic used for consensus.
@@ -200,8 +684,16 @@ pub enum NodeMessage {
     //
     SubscribeBlockAdded(UnboundedSender<BlockAdded>),
     SubscribeEpochChanged(UnboundedSender<EpochChanged>),
+    SubscribeEpochFinalized(UnboundedSender<EpochFinalized>),
     SubscribeOutputsChanged(UnboundedSender<OutputsChanged>),
+    SubscribeReorg(UnboundedSender<ChainReorganized>),
+    SubscribeTransactionStatus(UnboundedSender<TransactionStatusChanged>),
+    SubscribeMempoolChanged(UnboundedSender<MempoolChanged>),
+    SubscribeForkResolved(UnboundedSender<ForkResolved>),
     PopBlock,
+    SetCheckpoint(u64),
+    CancelTransaction(Hash),
+    MergeMempool(Vec<u8>),
     Request {
         request: NodeRequest,
         tx: oneshot::Sender<NodeResponse>,
@@ -211,12 +703,32 @@ pub enum NodeMessage {
     //
     Transaction(Vec<u8>),
     Consensus(Vec<u8>),
-    SealedBlock(Vec<u8>),
+    SealedBlock(BroadcastMessage),
     ViewChangeMessage(Vec<u8>),
     ViewChangeProofMessage(UnicastMessage),
     ChainLoaderMessage(UnicastMessage),
 }
 
+///
+/// Decodes a network message received on `topic`.
+/// Malformed messages are counted and reported with the topic and the sender, if known.
+///
+fn decode_message<T: ProtoConvert>(
+    topic: &'static str,
+    from: Option<pbc::PublicKey>,
+    data: &[u8],
+) -> Result<T, Error> {
+    T::from_buffer(data).map_err(|e| {
+        metrics::MALFORMED_MESSAGES
+            .with_label_values(&[topic])
+            .inc();
+        match from {
+            Some(from) => NodeMessageError::MalformedFrom(topic, from, e).into(),
+            None => NodeMessageError::Malformed(topic, e).into(),
+        }
+    })
+}
+
 pub struct NodeService {
     /// Config.
     cfg: ChainConfig,
@@ -227,19 +739,57 @@ pub struct NodeService {
 
     /// A time when loader was started the last time
     last_sync_clock: Instant,
-
-    /// Orphan blocks sorted by height.
-    future_blocks: BTreeMap<u64, Block>,
+    /// The number of history requests since the last applied block.
+    sync_attempts: u32,
+    /// The number of history requests to each peer since the last applied block.
+    sync_targets: HashMap<pbc::PublicKey, u32>,
+    /// A time when re-sync was requested via API the last time.
+    last_resync_clock: Option<Instant>,
+
+    /// Blocks at or below this height can't be reverted by pop_block().
+    min_height: Option<u64>,
+
+    /// The chain was partially reverted because of an error, don't produce blocks.
+    chain_inconsistent: bool,
+
+    /// Orphan blocks sorted by height, together with the node which sent them.
+    future_blocks: BTreeMap<u64, (pbc::PublicKey, Block)>,
+
+    /// Recently rejected blocks which failed signature checks, oldest first.
+    /// Keyed by sealed_block_hash(), so a forged copy can't shadow the genuine block.
+    rejected_blocks: LinkedHashMap<Hash, ()>,
+    /// Recently received blocks with valid signatures, oldest first.
+    /// Keyed by sealed_block_hash(), so a forged copy can't reuse the result.
+    /// Cleared on every epoch because signatures depend on the validator set.
+    verified_blocks: LinkedHashMap<Hash, ()>,
+    /// Limits the rate of broadcast blocks from each producer.
+    block_rate_limiter: RateLimiter,
 
     /// A queue of consensus message from the future epoch.
     // TODO: Resolve unknown blocks using requests-responses.
-    future_consensus_messages: Vec<BlockConsensusMessage>,
+    future_consensus_messages: FutureConsensusMessages,
 
     //
     // Consensus
     //
     /// Memory pool of pending transactions.
     mempool: Mempool,
+    /// Thread pool for cryptographic checks of incoming transactions.
+    validation_pool: CpuPool,
+    /// Transactions being validated by validation_pool, in order of arrival.
+    pending_transactions: VecDeque<(Hash, CpuFuture<(Transaction, StakingBalance), Error>)>,
+    /// Fees paid by recently mined transactions.
+    recent_fees: RecentFees,
+    /// Transactions from micro blocks which are not committed yet, by height.
+    unconfirmed_transactions: BTreeMap<u64, Vec<Hash>>,
+    /// Transactions reported as committed by confirmation_depth,
+    /// which still can be reverted until the next macro block, by height.
+    committed_transactions: BTreeMap<u64, Vec<Hash>>,
+    /// Signatures of validators in committed macro blocks.
+    participation: Participation,
+    /// Recent blocks accepted with an unexpected reward, oldest first.
+    /// See strict_reward_validation.
+    reward_violations: VecDeque<RewardViolation>,
 
     /// Proof-of-stake consensus.
     consensus: Option<BlockConsensus>,
@@ -250,6 +800,9 @@ pub struct NodeService {
     /// Monotonic clock when the latest block was registered.
     last_block_clock: Instant,
 
+    /// Monotonic clock when the latest view change happened.
+    last_view_change_clock: Instant,
+
     //
     // Communication with environment.
     //
@@ -263,8 +816,20 @@ pub struct NodeService {
     on_block_added: Vec<UnboundedSender<BlockAdded>>,
     /// Triggered when epoch is changed.
     on_epoch_changed: Vec<UnboundedSender<EpochChanged>>,
+    /// Triggered when a macro block has been committed.
+    on_epoch_finalized: Vec<UnboundedSender<EpochFinalized>>,
     /// Triggered when outputs created and/or pruned.
     on_outputs_changed: Vec<UnboundedSender<OutputsChanged>>,
+    /// Triggered when blocks are reverted by fork resolution.
+    on_reorg: Vec<UnboundedSender<ChainReorganized>>,
+    /// Triggered when the status of a transaction is changed.
+    on_transaction_status: Vec<UnboundedSender<TransactionStatusChanged>>,
+    /// Triggered when transactions are added to or removed from the mempool.
+    on_mempool_changed: Vec<UnboundedSender<MempoolChanged>>,
+    /// Mempool changes since the last notification.
+    mempool_changes: MempoolChanged,
+    /// Triggered when a fork has been resolved.
+    on_fork_resolved: Vec<UnboundedSender<ForkResolved>>,
     /// Aggregated stream of events.
     events: Box<Stream<Item = NodeMessage, Error = ()> + Send>,
     /// timer events
@@ -281,20 +846,68 @@ impl NodeService {
         keys: KeyChain,
         network: Network,
     ) -> Result<(Self, Node), Error> {
+        validate_blocks_in_epoch(&cfg, &chain)?;
+        validate_bft_threshold(&cfg)?;
+        validate_timeouts(&cfg)?;
         let (outbox, inbox) = unbounded();
         let last_sync_clock = clock::now();
-        let future_consensus_messages = Vec::new();
-        let future_blocks: BTreeMap<u64, Block> = BTreeMap::new();
-        let mempool = Mempool::new();
+        let min_height = None;
+        let chain_inconsistent = false;
+        let future_consensus_messages = FutureConsensusMessages::new(MAX_FUTURE_CONSENSUS_MESSAGES);
+        let future_blocks: BTreeMap<u64, (pbc::PublicKey, Block)> = BTreeMap::new();
+        let rejected_blocks: LinkedHashMap<Hash, ()> = LinkedHashMap::new();
+        let verified_blocks: LinkedHashMap<Hash, ()> = LinkedHashMap::new();
+        let block_rate_limiter = RateLimiter::new(
+            cfg.max_blocks_per_second,
+            cfg.max_blocks_burst,
+            MAX_RATE_LIMITED_PEERS,
+        );
+        let mut mempool = Mempool::open(&cfg.mempool_storage);
+        // Drop persisted transactions which were processed while the node was down.
+        let stale: Vec<Hash> = mempool
+            .iter()
+            .filter(|(_tx_hash, tx)| {
+                tx.txins()
+                    .iter()
+                    .any(|input_hash| !chain.contains_output(input_hash))
+                    || tx
+                        .txouts()
+                        .iter()
+                        .any(|output| chain.contains_output(&Hash::digest(output)))
+            })
+            .map(|(tx_hash, _tx)| tx_hash.clone())
+            .collect();
+        for tx_hash in stale {
+            debug!("Dropped a stale transaction from mempool: tx={}", &tx_hash);
+            mempool.remove_tx(&tx_hash);
+        }
+        metrics::MEMPOOL_TRANSACTIONS.set(mempool.len() as i64);
+        metrics::MEMPOOL_INPUTS.set(mempool.inputs_len() as i64);
+        metrics::MEMPOOL_OUTPUTS.set(mempool.outputs_len() as i64);
+        metrics::MEMPOOL_BYTES.set(mempool.bytes_len() as i64);
+        let validation_pool = CpuPool::new_num_cpus();
+        let pending_transactions = VecDeque::new();
+        let recent_fees = RecentFees::new();
+        let unconfirmed_transactions: BTreeMap<u64, Vec<Hash>> = BTreeMap::new();
+        let committed_transactions: BTreeMap<u64, Vec<Hash>> = BTreeMap::new();
+        let participation = Participation::new();
+        let reward_violations = VecDeque::new();
 
         let consensus = None;
         let optimistic =
             ViewChangeCollector::new(&chain, keys.network_pkey, keys.network_skey.clone());
         let last_block_clock = clock::now();
+        let last_view_change_clock = last_block_clock;
 
         let on_block_added = Vec::<UnboundedSender<BlockAdded>>::new();
         let on_epoch_changed = Vec::<UnboundedSender<EpochChanged>>::new();
+        let on_epoch_finalized = Vec::<UnboundedSender<EpochFinalized>>::new();
         let on_outputs_changed = Vec::<UnboundedSender<OutputsChanged>>::new();
+        let on_reorg = Vec::<UnboundedSender<ChainReorganized>>::new();
+        let on_transaction_status = Vec::<UnboundedSender<TransactionStatusChanged>>::new();
+        let on_mempool_changed = Vec::<UnboundedSender<MempoolChanged>>::new();
+        let mempool_changes = MempoolChanged::default();
+        let on_fork_resolved = Vec::<UnboundedSender<ForkResolved>>::new();
 
         // Network Statuses
         let is_network_ready = false;
@@ -329,7 +942,7 @@ impl NodeService {
 
         // Sealed blocks broadcast topic.
         let block_rx = network
-            .subscribe(&SEALED_BLOCK_TOPIC)?
+            .subscribe_with_sender(&SEALED_BLOCK_TOPIC)?
             .map(|m| NodeMessage::SealedBlock(m));
         streams.push(Box::new(block_rx));
 
@@ -352,20 +965,42 @@ impl NodeService {
         let mut service = NodeService {
             cfg,
             last_sync_clock,
+            sync_attempts: 0,
+            sync_targets: HashMap::new(),
+            last_resync_clock: None,
+            min_height,
+            chain_inconsistent,
             future_blocks,
+            rejected_blocks,
+            verified_blocks,
+            block_rate_limiter,
             future_consensus_messages,
             chain,
             keys,
             mempool,
+            validation_pool,
+            pending_transactions,
+            recent_fees,
+            unconfirmed_transactions,
+            committed_transactions,
+            participation,
+            reward_violations,
             consensus,
             optimistic,
             last_block_clock,
+            last_view_change_clock,
             network: network.clone(),
             is_network_ready,
             network_status_rx,
             on_block_added,
             on_epoch_changed,
+            on_epoch_finalized,
             on_outputs_changed,
+            on_reorg,
+            on_transaction_status,
+            on_mempool_changed,
+            mempool_changes,
+            on_fork_resolved,
             events,
             macro_block_timer,
             propose_timer,
@@ -402,6 +1037,8 @@ impl NodeService {
         consensus::metrics::CONSENSUS_ROLE.set(consensus::metrics::ConsensusRole::Regular as i64);
         // Resign from Validator role.
         self.consensus = None;
+        // Signatures are checked against the validators of the previous epoch.
+        self.verified_blocks.clear();
         consensus::metrics::CONSENSUS_STATE
             .set(consensus::metrics::ConsensusState::NotInConsensus as i64);
 
@@ -414,12 +1051,127 @@ impl NodeService {
             .retain(move |ch| ch.unbounded_send(msg.clone()).is_ok());
         // clear consensus messages when new epoch starts
         self.future_consensus_messages.clear();
+        metrics::FUTURE_CONSENSUS_MESSAGES.set(0);
         self.optimistic.on_new_consensus(&self.chain);
     }
 
     /// Handle incoming transactions received from network.
     fn handle_transaction(&mut self, tx: Transaction) -> Result<(), Error> {
-        let tx_hash = Hash::digest(&tx);
+        if self.is_known_transaction(&Hash::digest(&tx)) {
+            return Ok(());
+        }
+        let tx_hash = self.check_transaction_limits(&tx)?;
+
+        // Validate transaction.
+        let timestamp = SystemTime::now();
+        validate_transaction(
+            &tx,
+            &self.mempool,
+            &self.chain,
+            timestamp,
+            self.cfg.payment_fee,
+            self.cfg.stake_fee,
+            self.cfg.coinbase_maturity,
+        )?;
+
+        self.push_transaction(tx_hash, tx);
+        self.check_immediate_micro_block();
+        Ok(())
+    }
+
+    /// Handle incoming transaction without blocking the event loop.
+    /// Cryptographic checks are executed on validation_pool, and
+    /// the transaction is queued to mempool by poll_pending_transactions().
+    fn handle_transaction_async(&mut self, tx: Transaction) -> Result<(), Error> {
+        if self.is_known_transaction(&Hash::digest(&tx)) {
+            return Ok(());
+        }
+        let tx_hash = self.check_transaction_limits(&tx)?;
+        if self.pending_transactions.len() >= self.cfg.max_pending_transactions {
+            return Err(NodeTransactionError::ValidationQueueIsFull(tx_hash).into());
+        }
+
+        // Cheap checks against the current state.
+        let inputs = validate_transaction_state(
+            &tx,
+            &self.mempool,
+            &self.chain,
+            self.cfg.payment_fee,
+            self.cfg.stake_fee,
+            self.cfg.coinbase_maturity,
+        )?;
+
+        // Check the monetary balance, Bulletpoofs/amounts and signature.
+        let future = self.validation_pool.spawn_fn(move || -> Result<_, Error> {
+            let staking_balance = tx.validate(&inputs)?;
+            Ok((tx, staking_balance))
+        });
+        self.pending_transactions.push_back((tx_hash, future));
+        Ok(())
+    }
+
+    /// Queue transactions validated by validation_pool to mempool.
+    /// Returns Async::Ready when there are no more pending transactions.
+    fn poll_pending_transactions(&mut self) -> Async<()> {
+        while let Some((_tx_hash, future)) = self.pending_transactions.front_mut() {
+            let result = match future.poll() {
+                Ok(Async::NotReady) => return Async::NotReady,
+                Ok(Async::Ready(validated)) => Ok(validated),
+                Err(e) => Err(e),
+            };
+            let (tx_hash, _future) = self.pending_transactions.pop_front().unwrap();
+            let result = result.and_then(|(tx, staking_balance)| {
+                self.handle_transaction_validated(tx_hash, tx, staking_balance)
+            });
+            if let Err(e) = result {
+                error!("Error: {}", e);
+            }
+        }
+        Async::Ready(())
+    }
+
+    /// Handle transaction validated by validation_pool.
+    fn handle_transaction_validated(
+        &mut self,
+        tx_hash: Hash,
+        tx: Transaction,
+        staking_balance: StakingBalance,
+    ) -> Result<(), Error> {
+        // Mempool and blockchain may have changed during validation.
+        let inputs = validate_transaction_state(
+            &tx,
+            &self.mempool,
+            &self.chain,
+            self.cfg.payment_fee,
+            self.cfg.stake_fee,
+            self.cfg.coinbase_maturity,
+        )?;
+        self.chain
+            .validate_staking_balance(&inputs, &staking_balance)?;
+        // Mempool may have been filled up during validation.
+        self.check_mempool_limits(&tx_hash, &tx)?;
+
+        self.push_transaction(tx_hash, tx);
+        self.check_immediate_micro_block();
+        Ok(())
+    }
+
+    /// Returns true if the transaction is already in the mempool or is being validated.
+    /// Such duplicates are common because of gossip, so they are skipped quietly.
+    fn is_known_transaction(&self, tx_hash: &Hash) -> bool {
+        if !self.mempool.contains(tx_hash)
+            && !self.pending_transactions.iter().any(|(h, _)| h == tx_hash)
+        {
+            return false;
+        }
+        trace!("Skip a known transaction: tx={}", tx_hash);
+        metrics::DUPLICATE_TRANSACTIONS.inc();
+        true
+    }
+
+    /// Check size limits of incoming transaction.
+    fn check_transaction_limits(&self, tx: &Transaction) -> Result<Hash, Error> {
+        let tx_hash = Hash::digest(tx);
         info!(
             "Received transaction from the network: tx={}, inputs={}, outputs={}, fee={}",
             &tx_hash,
@@ -439,37 +1191,313 @@ impl NodeService {
             .into());
         }
 
-        // Limit the maximum size of mempool.
+        // Limit the serialized size, which also accounts for large outputs.
+        let tx_bytes = tx.into_proto().compute_size() as usize;
+        if tx_bytes > self.cfg.max_tx_bytes {
+            return Err(NodeTransactionError::TooManyBytes(
+                tx_hash,
+                tx_bytes,
+                self.cfg.max_tx_bytes,
+            )
+            .into());
+        }
+
+        self.check_mempool_limits(&tx_hash, tx)?;
+        Ok(tx_hash)
+    }
+
+    /// Check that mempool has room for the transaction.
+    fn check_mempool_limits(&self, tx_hash: &Hash, tx: &Transaction) -> Result<(), Error> {
         let utxo_in_mempool = self.mempool.inputs_len() + self.mempool.outputs_len();
         if utxo_in_mempool > self.cfg.max_utxo_in_mempool {
-            return Err(NodeTransactionError::MempoolIsFull(tx_hash).into());
+            return Err(NodeTransactionError::MempoolIsFull(*tx_hash).into());
+        }
+        let tx_bytes = tx.into_proto().compute_size() as usize;
+        if self.mempool.bytes_len() + tx_bytes > self.cfg.max_mempool_bytes {
+            return Err(NodeTransactionError::MempoolIsFull(*tx_hash).into());
         }
+        Ok(())
+    }
 
-        // Validate transaction.
-        let timestamp = SystemTime::now();
+    /// Queue valid transaction to mempool.
+    fn push_transaction(&mut self, tx_hash: Hash, tx: Transaction) {
+        info!("Transaction is valid, adding to mempool: tx={}", &tx_hash);
+        self.mempool.push_tx(tx_hash, tx);
+        metrics::MEMPOOL_TRANSACTIONS.set(self.mempool.len() as i64);
+        metrics::MEMPOOL_INPUTS.set(self.mempool.inputs_len() as i64);
+        metrics::MEMPOOL_OUTPUTS.set(self.mempool.inputs_len() as i64);
+        metrics::MEMPOOL_BYTES.set(self.mempool.bytes_len() as i64);
+        self.notify_transaction_status(tx_hash, TransactionStatus::Accepted {});
+        if !self.on_mempool_changed.is_empty() {
+            self.mempool_changes.added.push(tx_hash);
+        }
+    }
+
+    /// Remember transactions removed from the mempool for subscribers.
+    fn mempool_removed(&mut self, tx_hashes: Vec<Hash>) {
+        if !self.on_mempool_changed.is_empty() {
+            self.mempool_changes.removed.extend(tx_hashes);
+        }
+    }
+
+    /// Notify subscribers about mempool changes since the last call.
+    fn flush_mempool_changes(&mut self) {
+        if self.mempool_changes.added.is_empty() && self.mempool_changes.removed.is_empty() {
+            return;
+        }
+        let msg = std::mem::replace(&mut self.mempool_changes, MempoolChanged::default());
+        self.on_mempool_changed
+            .retain(move |ch| ch.unbounded_send(msg.clone()).is_ok());
+    }
+
+    /// Notify subscribers about the new status of a transaction.
+    fn notify_transaction_status(&mut self, tx_hash: Hash, status: TransactionStatus) {
+        let msg = TransactionStatusChanged { tx_hash, status };
+        self.on_transaction_status
+            .retain(move |ch| ch.unbounded_send(msg.clone()).is_ok());
+    }
+
+    /// Report transactions as committed, if they are deep enough or finalized by a macro block.
+    fn commit_transactions(&mut self, finalized: bool) {
+        let height = self.chain.height();
+        let committed: Vec<u64> = self
+            .unconfirmed_transactions
+            .keys()
+            .cloned()
+            .take_while(|h| finalized || h + 1 + self.cfg.confirmation_depth <= height)
+            .collect();
+        for h in committed {
+            let tx_hashes = self.unconfirmed_transactions.remove(&h).unwrap();
+            for tx_hash in &tx_hashes {
+                self.notify_transaction_status(
+                    *tx_hash,
+                    TransactionStatus::Committed { height: h },
+                );
+            }
+            self.committed_transactions.insert(h, tx_hashes);
+        }
+        if finalized {
+            self.committed_transactions.clear();
+        }
+    }
+
+    /// Revert the latest micro block.
+    /// Its transactions are forgotten until they are returned to the mempool.
+    fn pop_micro_block(&mut self) -> Result<(Vec<Output>, Vec<Output>), BlockchainError> {
+        let (inputs, outputs) = self.chain.pop_micro_block()?;
+        let height = self.chain.height();
+        self.reward_violations.retain(|v| v.height < height);
+        self.unconfirmed_transactions.remove(&height);
+        self.committed_transactions.remove(&height);
+        Ok((inputs, outputs))
+    }
+
+    /// Return transactions from reverted micro blocks to the mempool.
+    /// They are reported as Accepted again, even if they were reported as Committed before.
+    fn requeue_transactions(&mut self, transactions: Vec<Transaction>) {
+        for tx in transactions {
+            let tx_hash = Hash::digest(&tx);
+            if self.mempool.contains(&tx_hash) {
+                continue;
+            }
+            if !self.mempool.conflicts(&tx).is_empty() {
+                warn!(
+                    "Dropped a reverted transaction which conflicts with the mempool: tx={}",
+                    tx_hash
+                );
+                continue;
+            }
+            if let Err(e) = self.check_mempool_limits(&tx_hash, &tx) {
+                warn!(
+                    "Dropped a reverted transaction: tx={}, error={}",
+                    tx_hash, e
+                );
+                continue;
+            }
+            self.push_transaction(tx_hash, tx);
+        }
+    }
+
+    /// Handler for NodeMessage::MergeMempool.
+    fn handle_merge_mempool(&mut self, msg: Vec<u8>) -> Result<(), Error> {
+        let other = Mempool::from_buffer(&msg)?;
+        let merged = self.merge_mempool(&other);
+        info!(
+            "Merged mempool: received={}, merged={}",
+            other.len(),
+            merged.len()
+        );
+        Ok(())
+    }
+
+    /// Import transactions from another mempool, e.g. from a node which is taken over.
+    /// Each transaction goes through the same checks as a transaction received from the network.
+    /// Returns hashes of the imported transactions.
+    fn merge_mempool(&mut self, other: &Mempool) -> Vec<Hash> {
+        let mut merged: Vec<Hash> = Vec::new();
+        for (tx_hash, tx) in other.iter() {
+            if self.is_known_transaction(tx_hash) {
+                continue;
+            }
+            match self.merge_transaction(tx) {
+                Ok(tx_hash) => merged.push(tx_hash),
+                Err(e) => warn!("Failed to merge transaction: tx={}, error={}", tx_hash, e),
+            }
+        }
+        self.check_immediate_micro_block();
+        merged
+    }
+
+    /// Queue a transaction from another mempool.
+    /// Conflicting transactions are replaced if they pay less in total.
+    fn merge_transaction(&mut self, tx: Transaction) -> Result<Hash, Error> {
+        let tx_hash = self.check_transaction_limits(&tx)?;
+        let conflicts = self.mempool.conflicts(&tx);
+        if conflicts.is_empty() {
+            validate_transaction(
+                &tx,
+                &self.mempool,
+                &self.chain,
+                SystemTime::now(),
+                self.cfg.payment_fee,
+                self.cfg.stake_fee,
+                self.cfg.coinbase_maturity,
+            )?;
+            self.push_transaction(tx_hash, tx);
+            return Ok(tx_hash);
+        }
+
+        let conflicts_fee: i64 = conflicts
+            .iter()
+            .map(|h| self.mempool.get(h).expect("conflict exists").fee())
+            .sum();
+        if tx.fee() <= conflicts_fee {
+            return Err(NodeTransactionError::ConflictsWithMempool(
+                tx_hash,
+                tx.fee(),
+                conflicts_fee,
+            )
+            .into());
+        }
+
+        // The mempool is only checked for conflicts, which are replaced below.
         validate_transaction(
             &tx,
-            &self.mempool,
+            &Mempool::new(),
             &self.chain,
-            timestamp,
+            SystemTime::now(),
             self.cfg.payment_fee,
             self.cfg.stake_fee,
+            self.cfg.coinbase_maturity,
         )?;
+        let replaced: Vec<Hash> = conflicts.into_iter().collect();
+        for replaced_hash in &replaced {
+            self.mempool.remove_tx(replaced_hash);
+            info!(
+                "Replaced transaction: tx={}, by={}",
+                replaced_hash, &tx_hash
+            );
+            self.notify_transaction_status(
+                *replaced_hash,
+                TransactionStatus::Replaced { by: tx_hash },
+            );
+        }
+        self.mempool_removed(replaced);
+        self.push_transaction(tx_hash, tx);
+        Ok(tx_hash)
+    }
 
-        // Queue to mempool.
-        info!("Transaction is valid, adding to mempool: tx={}", &tx_hash);
-        self.mempool.push_tx(tx_hash, tx);
-        metrics::MEMPOOL_TRANSACTIONS.set(self.mempool.len() as i64);
-        metrics::MEMPOOL_INPUTS.set(self.mempool.inputs_len() as i64);
-        metrics::MEMPOOL_OUTPUTS.set(self.mempool.inputs_len() as i64);
+    /// Revert micro blocks down to the given height and return hashes of reverted blocks.
+    /// All blocks are checked first, so the chain is either reverted or left intact.
+    fn revert_micro_blocks(&mut self, height: u64) -> Result<Vec<Hash>, Error> {
+        assert!(height <= self.chain.height());
 
-        Ok(())
+        // Check that all blocks can be reverted.
+        let mut block_hashes: Vec<Hash> = Vec::new();
+        let mut transactions: Vec<Vec<Transaction>> = Vec::new();
+        for block_height in (height..self.chain.height()).rev() {
+            let block = self.chain.block_by_height(block_height)?;
+            let block_hash = Hash::digest(&block);
+            match block {
+                Block::MacroBlock(_) => {
+                    return Err(NodeBlockError::ExpectedMicroBlock(block_height, block_hash).into());
+                }
+                Block::MicroBlock(block) => transactions.push(block.transactions),
+            }
+            block_hashes.push(block_hash);
+        }
+
+        for block_hash in &block_hashes {
+            let (inputs, outputs) = match self.pop_micro_block() {
+                Ok(r) => r,
+                Err(e) => {
+                    error!(
+                        "Failed to revert a block, stopped producing blocks: height={}, block={}, target_height={}, error={}",
+                        self.chain.height(),
+                        block_hash,
+                        height,
+                        e
+                    );
+                    self.chain_inconsistent = true;
+                    return Err(e.into());
+                }
+            };
+            let msg = OutputsChanged {
+                epoch: self.chain.epoch(),
+                inputs,
+                outputs,
+                rolled_back: true,
+            };
+            self.on_outputs_changed
+                .retain(move |ch| ch.unbounded_send(msg.clone()).is_ok());
+        }
+        assert_eq!(height, self.chain.height());
+
+        // Keep the original order of transactions.
+        for transactions in transactions.into_iter().rev() {
+            self.requeue_transactions(transactions);
+        }
+        Ok(block_hashes)
+    }
+
+    /// Notify subscribers that blocks of `epoch` since `epoch_start` have been finalized.
+    fn notify_epoch_finalized(&mut self, epoch: u64, epoch_start: u64) {
+        if self.on_epoch_finalized.is_empty() {
+            return;
+        }
+        let total_reward: i64 = self
+            .chain
+            .blocks_range(epoch_start, self.chain.height() - epoch_start)
+            .iter()
+            .map(|block| match block {
+                Block::MacroBlock(block) => block.header.block_reward,
+                Block::MicroBlock(block) => block.coinbase.block_reward,
+            })
+            .sum();
+        let msg = EpochFinalized {
+            epoch,
+            total_reward,
+            validators: self.chain.validators().clone(),
+        };
+        self.on_epoch_finalized
+            .retain(move |ch| ch.unbounded_send(msg.clone()).is_ok());
+    }
+
+    /// Report the outcome of fork resolution to metrics and subscribers.
+    fn notify_fork_resolved(&mut self, height: u64, outcome: ForkOutcome) {
+        metrics::FORK_OUTCOMES
+            .with_label_values(&[outcome.name()])
+            .inc();
+        let msg = ForkResolved { height, outcome };
+        self.on_fork_resolved
+            .retain(move |ch| ch.unbounded_send(msg.clone()).is_ok());
     }
 
     ///
     /// Resolve a fork using a duplicate micro block from the current epoch.
     ///
-    fn resolve_fork(&mut self, remote: &Block) -> ForkResult {
+    fn resolve_fork(&mut self, remote: &Block) -> Result<ForkOutcome, Error> {
+        let ctx = BlockContext::of(remote);
         let height = remote.base_header().height;
         assert!(height < self.chain.height());
         assert!(height > 0);
@@ -495,9 +1523,7 @@ impl NodeService {
         };
 
         // check that validator is really leader for provided view_change.
-        let previous_block = self.chain.block_by_height(height - 1)?;
-        let mut election_result = self.chain.election_result();
-        election_result.random = previous_block.base_header().random;
+        let election_result = self.chain.election_result_at(height)?;
         let leader = election_result.select_leader(remote_view_change);
         if leader != remote.pkey {
             return Err(BlockError::DifferentPublicKey(leader, remote.pkey).into());
@@ -505,16 +1531,16 @@ impl NodeService {
 
         // check multiple blocks with same view_change
         if remote_view_change == local.base.view_change {
-            if local_hash == local_hash {
+            if remote_hash == local_hash {
                 debug!(
-                    "Skip a duplicate block with the same hash: height={}, block={}, current_height={}, last_block={}",
-                    height, local_hash, self.chain.height(), self.chain.last_block_hash(),
+                    "{} Skip a duplicate block with the same hash: block={}, current_height={}, last_block={}",
+                    ctx, local_hash, self.chain.height(), self.chain.last_block_hash(),
                 );
-                return Err(ForkError::Canceled);
+                return Ok(ForkOutcome::Duplicate);
             }
 
-            warn!("Two micro-blocks from the same leader detected: height={}, local_block={}, remote_block={}, local_previous={}, remote_previous={}, local_view_change={}, remote_view_change={}, current_height={}, last_block={}",
-                  height,
+            warn!("{} Two micro-blocks from the same leader detected: local_block={}, remote_block={}, local_previous={}, remote_previous={}, local_view_change={}, remote_view_change={}, current_height={}, last_block={}",
+                  ctx,
                   local_hash,
                   remote_hash,
                   local.base.previous,
@@ -527,15 +1553,17 @@ impl NodeService {
             metrics::CHEATS.inc();
             // TODO: implement slashing.
 
-            return Err(ForkError::Canceled);
+            return Ok(ForkOutcome::CheatDetected {
+                offender: remote.pkey,
+            });
         } else if remote_view_change <= local.base.view_change {
             debug!(
-                "Found a fork with lower view_change, sending blocks: pkey={}",
-                leader
+                "{} Found a fork with lower view_change, sending blocks: pkey={}, local_view_change={}",
+                ctx, leader, local.base.view_change
             );
 
             self.send_blocks(leader, height)?;
-            return Err(ForkError::Canceled);
+            return Ok(ForkOutcome::LocalWon);
         }
 
         // Check the proof.
@@ -560,16 +1588,21 @@ impl NodeService {
     }
 
     /// We receive info about view_change, rollback the blocks, and set view_change to new.
-    fn try_rollback(&mut self, pkey: pbc::PublicKey, proof: SealedViewChangeProof) -> ForkResult {
+    fn try_rollback(
+        &mut self,
+        pkey: pbc::PublicKey,
+        proof: SealedViewChangeProof,
+    ) -> Result<ForkOutcome, Error> {
+        let ctx = BlockContext::new(proof.chain.height, proof.chain.view_change);
         let height = proof.chain.height;
         // Check height.
         if height <= self.chain.last_macro_block_height() {
             debug!(
-                "Skip an outdated proof: height={}, current_height={}",
-                height,
+                "{} Skip an outdated proof: current_height={}",
+                ctx,
                 self.chain.height()
             );
-            return Err(ForkError::Canceled);
+            return Ok(ForkOutcome::LocalWon);
         }
 
         let local = if height < self.chain.height() {
@@ -591,16 +1624,19 @@ impl NodeService {
         } else if height == self.chain.height() {
             ChainInfo::from_blockchain(&self.chain)
         } else {
-            debug!("Received proof with future height, ignoring for now: proof_height={}, our_height={}",
-            height, self.chain.height());
-            return Err(ForkError::Canceled);
+            debug!(
+                "{} Received proof with future height, ignoring for now: our_height={}",
+                ctx,
+                self.chain.height()
+            );
+            return Ok(ForkOutcome::NeedHistory);
         };
 
         let local_view_change = local.view_change;
         let remote_view_change = proof.chain.view_change;
 
-        debug!("Started fork resolution: height={}, local_previous={}, remote_previous={}, local_view_change={}, remote_view_change={}, remote_proof={:?}, current_height={}",
-               height,
+        debug!("{} Started fork resolution: local_previous={}, remote_previous={}, local_view_change={}, remote_view_change={}, remote_proof={:?}, current_height={}",
+               ctx,
                local.last_block,
                proof.chain.last_block,
                local_view_change,
@@ -611,18 +1647,18 @@ impl NodeService {
 
         // Check view_change.
         if remote_view_change < local_view_change {
-            warn!("View change proof with lesser or equal view_change: height={}, local_view_change={}, remote_view_change={}, current_height={}",
-                  height,
+            warn!("{} View change proof with lesser or equal view_change: local_view_change={}, remote_view_change={}, current_height={}",
+                  ctx,
                   local_view_change,
                   remote_view_change,
                   self.chain.height(),
             );
-            return Err(ForkError::Canceled);
+            return Ok(ForkOutcome::LocalWon);
         }
         // Check previous hash.
         if proof.chain.last_block != local.last_block {
-            warn!("Found a proof with invalid previous hash: height={}, local_previous={}, remote_previous={}, local_view_change={}, remote_view_change={}, current_height={}, last_block={}",
-                  height,
+            warn!("{} Found a proof with invalid previous hash: local_previous={}, remote_previous={}, local_view_change={}, remote_view_change={}, current_height={}, last_block={}",
+                  ctx,
                   local.last_block,
                   proof.chain.last_block,
                   local_view_change,
@@ -631,7 +1667,7 @@ impl NodeService {
                   self.chain.last_block_hash());
             // Request history from that node.
             self.request_history_from(pkey)?;
-            return Err(ForkError::Canceled);
+            return Ok(ForkOutcome::NeedHistory);
         }
 
         assert!(remote_view_change >= local_view_change);
@@ -644,8 +1680,8 @@ impl NodeService {
         metrics::FORKS.inc();
 
         warn!(
-            "A fork detected: height={}, local_previous={}, remote_previous={}, local_view_change={}, remote_view_change={}, current_height={}, last_block={}",
-            height,
+            "{} A fork detected: local_previous={}, remote_previous={}, local_view_change={}, remote_view_change={}, current_height={}, last_block={}",
+            ctx,
             local.last_block,
             proof.chain.last_block,
             local_view_change,
@@ -654,42 +1690,83 @@ impl NodeService {
             self.chain.last_block_hash());
 
         // Truncate the blockchain.
-        while self.chain.height() > height {
-            let (inputs, outputs) = self.chain.pop_micro_block()?;
-            let msg = OutputsChanged {
-                epoch: self.chain.epoch(),
-                inputs,
-                outputs,
+        let from_height = self.chain.height();
+        let dropped_block_hashes = self.revert_micro_blocks(height)?;
+        let rolled_back = dropped_block_hashes.len() as u64;
+        if !dropped_block_hashes.is_empty() {
+            let msg = ChainReorganized {
+                from_height,
+                to_height: height,
+                dropped_block_hashes,
             };
-            self.on_outputs_changed
+            self.on_reorg
                 .retain(move |ch| ch.unbounded_send(msg.clone()).is_ok());
         }
-        assert_eq!(height, self.chain.height());
 
         self.chain
             .set_view_change(proof.chain.view_change + 1, proof.proof);
-        Ok(())
+        self.last_view_change_clock = clock::now();
+        Ok(ForkOutcome::RemoteWon { rolled_back })
     }
 
-    /// Handle incoming blocks received from network.
-    fn handle_sealed_block(&mut self, block: Block) -> Result<(), Error> {
+    /// Handle blocks received from the broadcast topic.
+    /// Blocks from peers which exceeded the rate limit are dropped.
+    fn handle_broadcast_block(
+        &mut self,
+        from: Option<pbc::PublicKey>,
+        block: Block,
+    ) -> Result<(), Error> {
+        self.process_sealed_block(from, block, true)
+    }
+
+    /// Handle incoming blocks received from the `from` node.
+    /// The producer of the block is used as the sender if `from` is unknown.
+    fn handle_sealed_block(
+        &mut self,
+        from: Option<pbc::PublicKey>,
+        block: Block,
+    ) -> Result<(), Error> {
+        self.process_sealed_block(from, block, false)
+    }
+
+    /// Implementation of handle_sealed_block() and handle_broadcast_block().
+    /// If `rate_limited` is set, blocks with valid signatures are limited per producer.
+    /// The sender of a broadcast is unauthenticated and can't be used as the key.
+    fn process_sealed_block(
+        &mut self,
+        from: Option<pbc::PublicKey>,
+        block: Block,
+        rate_limited: bool,
+    ) -> Result<(), Error> {
+        let from = from.unwrap_or_else(|| block_producer(&block));
+        let ctx = BlockContext::of(&block);
         let block_hash = Hash::digest(&block);
         let block_height = block.base_header().height;
         debug!(
-            "Received a new block: height={}, block={}, view_change={}, current_height={}, last_block={}",
-            block_height,
+            "{} Received a new block: block={}, current_height={}, last_block={}",
+            ctx,
             block_hash,
-            block.base_header().view_change,
             self.chain.height(),
             self.chain.last_block_hash()
         );
 
+        // Check blocks which were rejected before.
+        let sealed_hash = sealed_block_hash(&block);
+        if self.rejected_blocks.get_refresh(&sealed_hash).is_some() {
+            metrics::REJECTED_BLOCK_CACHE_HITS.inc();
+            debug!(
+                "{} Skip a block which was rejected before: block={}",
+                ctx, block_hash
+            );
+            return Ok(());
+        }
+
         // Check height.
         if block_height <= self.chain.last_macro_block_height() {
             // A duplicate block from a finalized epoch - ignore.
             debug!(
-                "Skip an outdated block: height={}, block={}, current_height={}, last_block={}",
-                block_height,
+                "{} Skip an outdated block: block={}, current_height={}, last_block={}",
+                ctx,
                 block_hash,
                 self.chain.height(),
                 self.chain.last_block_hash()
@@ -697,8 +1774,8 @@ impl NodeService {
             return Ok(());
         } else if block_height > self.chain.last_macro_block_height() + self.cfg.blocks_in_epoch {
             // An orphan block from later epochs - ignore.
-            warn!("Skipped an orphan block from the future: height={}, block={}, current_height={}, last_block={}",
-                  block_height,
+            warn!("{} Skipped an orphan block from the future: block={}, current_height={}, last_block={}",
+                  ctx,
                   block_hash,
                   self.chain.height(),
                   self.chain.last_block_hash()
@@ -710,48 +1787,65 @@ impl NodeService {
         assert!(block_height > self.chain.last_macro_block_height());
         assert!(block_height <= self.chain.last_macro_block_height() + self.cfg.blocks_in_epoch);
 
+        // Drop blocks of a rate limited producer before checking signatures.
+        // The producer is not verified yet, so its budget is taken only below.
+        let producer = block_producer(&block);
+        if rate_limited && !self.block_rate_limiter.is_allowed(&producer, clock::now()) {
+            metrics::RATE_LIMITED_BLOCKS.inc();
+            debug!(
+                "{} Dropped a block from a rate limited producer: block={}, producer={}, from={}",
+                ctx, block_hash, producer, from
+            );
+            return Ok(());
+        }
+
         // Check block consistency.
-        match block {
-            Block::MacroBlock(ref block) => {
-                check_multi_signature(
-                    &block_hash,
-                    &block.body.multisig,
-                    &block.body.multisigmap,
-                    self.chain.validators(),
-                    self.chain.total_slots(),
-                )
-                .map_err(|e| BlockError::InvalidBlockSignature(e, block_height, block_hash))?;
-            }
-            Block::MicroBlock(ref block) => {
-                let leader = block.pkey;
-                if let Err(_e) = pbc::check_hash(&block_hash, &block.sig, &leader) {
-                    return Err(BlockError::InvalidLeaderSignature(block_height, block_hash).into());
-                }
-                if !self.chain.is_validator(&leader) {
-                    return Err(BlockError::LeaderIsNotValidator(block_height, block_hash).into());
-                }
+        if self.verified_blocks.get_refresh(&sealed_hash).is_some() {
+            metrics::VERIFIED_BLOCK_CACHE_HITS.inc();
+            debug!(
+                "{} Skip signature checks for a block which was verified before: block={}",
+                ctx, block_hash
+            );
+        } else if let Err(e) = self.check_block_signature(&block) {
+            // Signatures are checked against validators of the current epoch,
+            // so this block will never become valid.
+            self.reject_block(sealed_hash);
+            return Err(e);
+        } else {
+            self.verified_blocks.insert(sealed_hash, ());
+            while self.verified_blocks.len() > MAX_VERIFIED_BLOCKS {
+                self.verified_blocks.pop_front();
             }
         }
 
+        // Limit the rate of blocks from each producer.
+        if rate_limited && !self.block_rate_limiter.check(&producer, clock::now()) {
+            metrics::RATE_LIMITED_BLOCKS.inc();
+            debug!(
+                "{} Dropped a block from a rate limited producer: block={}, producer={}, from={}",
+                ctx, block_hash, producer, from
+            );
+            return Ok(());
+        }
+
         // A duplicate block from the current epoch - try to resolve forks.
         if block_height < self.chain.height() {
-            match self.resolve_fork(&block) {
+            let outcome = self.resolve_fork(&block)?;
+            self.notify_fork_resolved(block_height, outcome.clone());
+            match outcome {
                 //TODO: Notify sender about our blocks?
-                Ok(()) => {
-                    debug!(
-                        "Fork resolution decide that remote chain is better: fork_height={}",
-                        block_height
-                    );
+                ForkOutcome::RemoteWon { .. } => {
+                    debug!("{} Fork resolution decide that remote chain is better", ctx);
                     assert_eq!(
                         block_height,
                         self.chain.height(),
                         "Fork resolution rollback our chain"
                     );
                 }
-                Err(ForkError::Canceled) => {
+                _ => {
                     debug!(
-                        "Fork resolution decide that our chain is better: fork_height={}",
-                        block_height
+                        "{} Fork resolution decide that our chain is better: outcome={:?}",
+                        ctx, outcome
                     );
                     assert!(
                         block_height < self.chain.height(),
@@ -759,24 +1853,20 @@ impl NodeService {
                     );
                     return Ok(());
                 }
-                Err(ForkError::Error(e)) => return Err(e),
             }
         }
 
         // Add this block to a queue.
-        self.future_blocks.insert(block_height, block);
+        self.future_blocks.insert(block_height, (from, block));
+        self.update_future_blocks_metrics();
 
         // Process pending blocks.
-        while let Some(block) = self.future_blocks.remove(&self.chain.height()) {
+        while let Some((_from, block)) = self.future_blocks.remove(&self.chain.height()) {
+            let ctx = BlockContext::of(&block);
             let hash = Hash::digest(&block);
             let view_change = block.base_header().view_change;
             if let Err(e) = self.apply_new_block(block) {
-                error!(
-                    "Failed to apply block: height={}, block={}, error={}",
-                    self.chain.height(),
-                    hash,
-                    e
-                );
+                error!("{} Failed to apply block: block={}, error={}", ctx, hash, e);
 
                 if let Ok(BlockError::InvalidPreviousHash(_, _, _, _)) = e.downcast::<BlockError>()
                 {
@@ -788,23 +1878,110 @@ impl NodeService {
                 break; // Stop processing.
             }
         }
+        self.update_future_blocks_metrics();
+
+        // Queue is not empty - request history from the sender of the lowest orphan.
+        let lowest_sender = self
+            .future_blocks
+            .values()
+            .next()
+            .map(|(from, _block)| *from);
+        if let Some(from) = lowest_sender {
+            for (_from, block) in self.future_blocks.values() {
+                debug!(
+                    "{} Orphan block: block={}, previous={}, current_height={}, last_block={}",
+                    BlockContext::of(block),
+                    Hash::digest(block),
+                    block.base_header().previous,
+                    self.chain.height(),
+                    self.chain.last_block_hash()
+                );
+            }
+            if let Err(e) = self.request_history_from(from) {
+                warn!(
+                    "Failed to request history from the sender of orphan blocks: from={}, error={}",
+                    from, e
+                );
+                self.request_history()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check the signature of a block from the current epoch.
+    fn check_block_signature(&self, block: &Block) -> Result<(), Error> {
+        metrics::BLOCK_SIGNATURE_CHECKS.inc();
+        verify_sealed_block(
+            block,
+            self.chain.validators(),
+            self.chain.total_slots(),
+            self.chain.cfg().bft_threshold,
+        )?;
+        Ok(())
+    }
+
+    /// Remember a block which failed validation and will never become valid.
+    fn reject_block(&mut self, sealed_hash: Hash) {
+        self.rejected_blocks.insert(sealed_hash, ());
+        while self.rejected_blocks.len() > MAX_REJECTED_BLOCKS {
+            self.rejected_blocks.pop_front();
+        }
+    }
+
+    /// Update metrics about the queue of orphan blocks.
+    fn update_future_blocks_metrics(&self) {
+        metrics::FUTURE_BLOCKS.set(self.future_blocks.len() as i64);
+        let max_height = self.future_blocks.keys().next_back().cloned().unwrap_or(0);
+        metrics::FUTURE_BLOCKS_MAX_HEIGHT.set(max_height as i64);
+    }
+
+    /// Check the reward of a new block.
+    /// Returns a violation to record if the reward is unexpected in the lenient mode.
+    fn check_block_reward(
+        &self,
+        height: u64,
+        hash: Hash,
+        leader: pbc::PublicKey,
+        block_reward: i64,
+        expected_reward: i64,
+    ) -> Result<Option<RewardViolation>, Error> {
+        if self.chain.epoch() == 0 || block_reward == expected_reward {
+            return Ok(None);
+        }
+        if self.cfg.strict_reward_validation {
+            return Err(NodeBlockError::InvalidBlockReward(
+                height,
+                hash,
+                block_reward,
+                expected_reward,
+            )
+            .into());
+        }
+        Ok(Some(RewardViolation {
+            height,
+            hash,
+            leader,
+            block_reward,
+            expected_reward,
+        }))
+    }
 
-        // Queue is not empty - request history from the current leader.
-        if !self.future_blocks.is_empty() {
-            for (height, block) in self.future_blocks.iter() {
-                debug!(
-                    "Orphan block: height={}, block={}, previous={}, current_height={}, last_block={}",
-                    height,
-                    Hash::digest(block),
-                    block.base_header().previous,
-                    self.chain.height(),
-                    self.chain.last_block_hash()
-                );
-            }
-            self.request_history()?;
+    /// Record a block with an unexpected reward for slashing.
+    fn register_reward_violation(&mut self, violation: RewardViolation) {
+        warn!(
+            "Accepted a block with an invalid reward: height={}, block={}, leader={}, reward={}, expected={}",
+            violation.height,
+            violation.hash,
+            violation.leader,
+            violation.block_reward,
+            violation.expected_reward
+        );
+        metrics::REWARD_VIOLATIONS.inc();
+        self.reward_violations.push_back(violation);
+        while self.reward_violations.len() > MAX_REWARD_VIOLATIONS {
+            self.reward_violations.pop_front();
         }
-
-        Ok(())
     }
 
     /// Try to apply a new block to the blockchain.
@@ -813,7 +1990,13 @@ impl NodeService {
         let timestamp = block.base_header().timestamp;
         let height = block.base_header().height;
         let view_change = block.base_header().view_change;
-        match block {
+        let ctx = BlockContext::new(height, view_change);
+
+        // Check that the block is not from the future and time doesn't go backwards.
+        let previous_timestamp = self.chain.last_block()?.base_header().timestamp;
+        validate_block_timestamp(&self.cfg, &block, previous_timestamp)?;
+
+        let fees: i64 = match block {
             Block::MacroBlock(macro_block) => {
                 let was_synchronized = self.is_synchronized();
 
@@ -824,17 +2007,13 @@ impl NodeService {
                     );
                 }
 
-                // TODO: add rewards for MacroBlocks.
-                if self.chain.epoch() > 0 && macro_block.header.block_reward != 0 {
-                    // TODO: support slashing.
-                    return Err(NodeBlockError::InvalidBlockReward(
-                        height,
-                        hash,
-                        macro_block.header.block_reward,
-                        self.cfg.block_reward,
-                    )
-                    .into());
-                }
+                let reward_violation = self.check_block_reward(
+                    height,
+                    hash,
+                    macro_block.body.pkey,
+                    macro_block.header.block_reward,
+                    self.cfg.macro_block_reward,
+                )?;
                 // Check consensus.
                 if let Some(consensus) = &mut self.consensus {
                     if consensus.should_commit() {
@@ -843,17 +2022,39 @@ impl NodeService {
                         let consensus_block_hash = Hash::digest(consensus_block);
                         if hash != consensus_block_hash {
                             panic!(
-                                "Network fork: received_block={:?}, consensus_block={:?}",
-                                &hash, &consensus_block_hash
+                                "{} Network fork: received_block={:?}, consensus_block={:?}",
+                                ctx, &hash, &consensus_block_hash
                             );
                         }
                     }
                 }
-                self.chain.push_macro_block(macro_block, timestamp)?;
+                let epoch = self.chain.epoch();
+                let epoch_start = self.chain.last_macro_block_height() + 1;
+                // Validators must be taken before the election of the next epoch.
+                let validators = self.chain.validators().clone();
+                let multisigmap = macro_block.body.multisigmap.clone();
+                let (inputs, outputs) = self.chain.push_macro_block(macro_block, timestamp)?;
+                self.participation.register(&validators, &multisigmap);
+                if let Some(reward_violation) = reward_violation {
+                    self.register_reward_violation(reward_violation);
+                }
+
+                // Notify subscribers about the block reward.
+                if !inputs.is_empty() || !outputs.is_empty() {
+                    let msg = OutputsChanged {
+                        epoch,
+                        inputs,
+                        outputs,
+                        rolled_back: false,
+                    };
+                    self.on_outputs_changed
+                        .retain(move |ch| ch.unbounded_send(msg.clone()).is_ok());
+                }
 
                 if !was_synchronized && self.is_synchronized() {
                     info!(
-                        "Synchronized with the network: height={}, last_block={}",
+                        "{} Synchronized with the network: current_height={}, last_block={}",
+                        ctx,
                         self.chain.height(),
                         self.chain.last_block_hash()
                     );
@@ -861,6 +2062,9 @@ impl NodeService {
                 }
 
                 self.on_new_epoch();
+                self.commit_transactions(true);
+                self.notify_epoch_finalized(epoch, epoch_start);
+                0
             }
             Block::MicroBlock(micro_block) => {
                 // Check for the correct block order.
@@ -875,42 +2079,39 @@ impl NodeService {
                 let timestamp = SystemTime::now();
 
                 // Check block reward.
-                if self.chain.epoch() > 0
-                    && micro_block.coinbase.block_reward != self.cfg.block_reward
-                {
-                    // TODO: support slashing.
-                    return Err(NodeBlockError::InvalidBlockReward(
-                        height,
-                        hash,
-                        micro_block.coinbase.block_reward,
-                        self.cfg.block_reward,
-                    )
-                    .into());
-                }
-
+                let reward_violation = self.check_block_reward(
+                    height,
+                    hash,
+                    micro_block.pkey,
+                    micro_block.coinbase.block_reward,
+                    self.cfg.reward_at_height(height),
+                )?;
+
+                let tx_fees: Vec<i64> = micro_block
+                    .transactions
+                    .iter()
+                    .map(fee_per_output)
+                    .collect();
+                let fees: i64 = micro_block.transactions.iter().map(|tx| tx.fee()).sum();
+                let tx_hashes: Vec<Hash> =
+                    micro_block.transactions.iter().map(Hash::digest).collect();
+                let block_reward = micro_block.coinbase.block_reward;
                 let leader = micro_block.pkey;
-                let block_view_change = micro_block.base.view_change;
                 let (inputs, outputs) = match self.chain.push_micro_block(micro_block, timestamp) {
                     Err(e @ BlockchainError::BlockError(BlockError::InvalidViewChange(..))) => {
-                        warn!("Discarded a block with lesser view_change: block_view_change={}, our_view_change={}",
-                              block_view_change, self.chain.view_change());
+                        warn!(
+                            "{} Discarded a block with lesser view_change: our_view_change={}",
+                            ctx,
+                            self.chain.view_change()
+                        );
 
-                        let mut chain = ChainInfo::from_blockchain(&self.chain);
                         let proof = self
-                            .chain
-                            .view_change_proof()
-                            .clone()
+                            .sealed_view_change_proof()
                             .expect("last view_change proof.");
                         debug!(
-                            "Sending view change proof to block sender: sender={}, proof={:?}",
-                            leader, proof
+                            "{} Sending view change proof to block sender: sender={}, proof={:?}",
+                            ctx, leader, proof.proof
                         );
-                        // correct information about proof, to refer previous on view_change;
-                        chain.view_change -= 1;
-                        let proof = SealedViewChangeProof {
-                            chain,
-                            proof: proof.clone(),
-                        };
 
                         self.network
                             .send(leader, VIEW_CHANGE_DIRECT, proof.into_buffer()?)?;
@@ -919,19 +2120,33 @@ impl NodeService {
                     Err(e) => return Err(e.into()),
                     Ok(v) => v,
                 };
+                if let Some(reward_violation) = reward_violation {
+                    self.register_reward_violation(reward_violation);
+                }
                 // Remove old transactions from the mempool.
                 let input_hashes: Vec<Hash> = inputs.iter().map(|o| Hash::digest(o)).collect();
                 let output_hashes: Vec<Hash> = outputs.iter().map(|o| Hash::digest(o)).collect();
-                self.mempool.prune(&input_hashes, &output_hashes);
+                let pruned = self.mempool.prune(&input_hashes, &output_hashes);
+                self.mempool_removed(pruned);
                 metrics::MEMPOOL_TRANSACTIONS.set(self.mempool.len() as i64);
                 metrics::MEMPOOL_INPUTS.set(self.mempool.inputs_len() as i64);
                 metrics::MEMPOOL_OUTPUTS.set(self.mempool.inputs_len() as i64);
+                metrics::MEMPOOL_BYTES.set(self.mempool.bytes_len() as i64);
+                for fee in tx_fees {
+                    self.recent_fees.push(fee);
+                }
+                for tx_hash in &tx_hashes {
+                    self.notify_transaction_status(*tx_hash, TransactionStatus::InBlock { height });
+                }
+                self.unconfirmed_transactions.insert(height, tx_hashes);
+                self.commit_transactions(false);
 
                 // Notify subscribers.
                 let msg = OutputsChanged {
                     epoch: self.chain.epoch(),
                     inputs,
                     outputs,
+                    rolled_back: false,
                 };
                 self.on_outputs_changed
                     .retain(move |ch| ch.unbounded_send(msg.clone()).is_ok());
@@ -939,11 +2154,16 @@ impl NodeService {
                 if self.chain.blocks_in_epoch() >= self.cfg.blocks_in_epoch {
                     self.on_change_group()?;
                 }
-                self.optimistic.on_new_payment_block(&self.chain)
+                self.optimistic.on_new_payment_block(&self.chain);
+                metrics::BLOCK_REWARD.set(block_reward);
+                fees
             }
-        }
+        };
 
         self.last_block_clock = clock::now();
+        // The node made progress, reset the loader backoff.
+        self.sync_attempts = 0;
+        self.sync_targets.clear();
 
         let local_timestamp = metrics::time_to_timestamp_ms(SystemTime::now());
         let remote_timestamp = metrics::time_to_timestamp_ms(timestamp);
@@ -951,6 +2171,8 @@ impl NodeService {
         metrics::BLOCK_REMOTE_TIMESTAMP.set(remote_timestamp);
         metrics::BLOCK_LOCAL_TIMESTAMP.set(local_timestamp);
         metrics::BLOCK_LAG.set(lag); // can be negative.
+        metrics::BLOCK_FEES_TOTAL.set(fees);
+        metrics::BLOCK_FEES_CUMULATIVE.inc_by(fees);
 
         let msg = BlockAdded {
             height,
@@ -961,6 +2183,7 @@ impl NodeService {
             remote_timestamp,
             synchronized: self.is_synchronized(),
             epoch: self.chain.epoch(),
+            fees,
         };
         self.on_block_added
             .retain(move |ch| ch.unbounded_send(msg.clone()).is_ok());
@@ -986,6 +2209,15 @@ impl NodeService {
         Ok(())
     }
 
+    /// Handler for NodeMessage::SubscribeEpochFinalized.
+    fn handle_subscribe_epoch_finalized(
+        &mut self,
+        tx: UnboundedSender<EpochFinalized>,
+    ) -> Result<(), Error> {
+        self.on_epoch_finalized.push(tx);
+        Ok(())
+    }
+
     /// Handler for NodeMessage::SubscribeOutputs.
     fn handle_subscribe_outputs(
         &mut self,
@@ -995,18 +2227,57 @@ impl NodeService {
         Ok(())
     }
 
+    /// Handler for NodeMessage::SubscribeReorg.
+    fn handle_subscribe_reorg(
+        &mut self,
+        tx: UnboundedSender<ChainReorganized>,
+    ) -> Result<(), Error> {
+        self.on_reorg.push(tx);
+        Ok(())
+    }
+
+    /// Handler for NodeMessage::SubscribeTransactionStatus.
+    fn handle_subscribe_transaction_status(
+        &mut self,
+        tx: UnboundedSender<TransactionStatusChanged>,
+    ) -> Result<(), Error> {
+        self.on_transaction_status.push(tx);
+        Ok(())
+    }
+
+    /// Handler for NodeMessage::SubscribeForkResolved.
+    fn handle_subscribe_fork_resolved(
+        &mut self,
+        tx: UnboundedSender<ForkResolved>,
+    ) -> Result<(), Error> {
+        self.on_fork_resolved.push(tx);
+        Ok(())
+    }
+
+    /// Handler for NodeMessage::SubscribeMempoolChanged.
+    fn handle_subscribe_mempool_changed(
+        &mut self,
+        tx: UnboundedSender<MempoolChanged>,
+    ) -> Result<(), Error> {
+        self.on_mempool_changed.push(tx);
+        Ok(())
+    }
+
     /// Handler for NodeMessage::PopBlock.
     fn handle_pop_block(&mut self) -> Result<(), Error> {
         warn!("Received a request to revert the latest block");
+        if let Some(min_height) = self.min_height {
+            let last_height = self.chain.height() - 1;
+            if last_height <= min_height {
+                error!(
+                    "Attempt to revert a block below the checkpoint: height={}, checkpoint={}",
+                    last_height, min_height
+                );
+                return Ok(());
+            }
+        }
         if self.chain.blocks_in_epoch() > 1 {
-            let (inputs, outputs) = self.chain.pop_micro_block()?;
-            let msg = OutputsChanged {
-                epoch: self.chain.epoch(),
-                inputs,
-                outputs,
-            };
-            self.on_outputs_changed
-                .retain(move |ch| ch.unbounded_send(msg.clone()).is_ok());
+            self.revert_micro_blocks(self.chain.height() - 1)?;
             self.recover_consensus_state()?
         } else {
             error!(
@@ -1017,6 +2288,292 @@ impl NodeService {
         Ok(())
     }
 
+    /// Handler for NodeMessage::SetCheckpoint.
+    fn handle_set_checkpoint(&mut self, height: u64) -> Result<(), Error> {
+        info!("Set checkpoint: height={}", height);
+        self.min_height = Some(height);
+        Ok(())
+    }
+
+    /// Handler for NodeRequest::FeeEstimate.
+    fn fee_estimate(&self) -> FeeEstimate {
+        let utxo_in_mempool = self.mempool.inputs_len() + self.mempool.outputs_len();
+        self.recent_fees.estimate(
+            self.cfg.payment_fee,
+            utxo_in_mempool,
+            self.cfg.max_utxo_in_mempool,
+        )
+    }
+
+    /// Handler for NodeRequest::InclusionEstimate.
+    fn inclusion_estimate(&self, fee: i64) -> InclusionEstimate {
+        // Transactions which spend outputs of other mempool transactions
+        // are skipped by Mempool::create_block().
+        let queued = self
+            .mempool
+            .iter()
+            .filter(|(_tx_hash, tx)| {
+                !tx.txins()
+                    .iter()
+                    .any(|input_hash| self.mempool.contains_output(input_hash))
+            })
+            .map(|(tx_hash, tx)| {
+                let utxo = tx.txins().len() + tx.txouts().len();
+                (utxo, self.mempool.tx_bytes_len(tx_hash))
+            });
+        estimate_inclusion(
+            fee,
+            self.cfg.payment_fee,
+            queued,
+            self.cfg.max_utxo_in_block,
+            self.cfg.max_block_bytes,
+        )
+    }
+
+    /// Handler for NodeRequest::LeaderSchedule.
+    fn leader_schedule(&self, count: u32) -> LeaderSchedule {
+        let count = cmp::min(count, MAX_LEADER_SCHEDULE_LEN);
+        let view_change = self.chain.view_change();
+        let leaders = (0..count)
+            .map(|i| self.chain.select_leader(view_change + i))
+            .collect();
+        LeaderSchedule {
+            height: self.chain.height(),
+            view_change,
+            leaders,
+        }
+    }
+
+    /// Handler for NodeRequest::EpochHistory.
+    fn epoch_history(&self, from_epoch: u64, limit: u32) -> EpochHistory {
+        let limit = cmp::min(limit, MAX_EPOCH_HISTORY_LEN);
+        let epochs = self
+            .chain
+            .epoch_history(from_epoch, limit as usize)
+            .to_vec();
+        EpochHistory { epochs }
+    }
+
+    /// Handler for NodeRequest::RawBlock.
+    fn raw_block(&self, height: u64) -> Result<RawBlock, Error> {
+        if height >= self.chain.height() {
+            return Err(NodeBlockError::NoSuchBlock(height, self.chain.height()).into());
+        }
+        let block = self.chain.block_by_height(height)?;
+        let hash = Hash::digest(&block);
+        let block = base64::encode(&block.into_buffer()?);
+        Ok(RawBlock {
+            height,
+            hash,
+            block,
+        })
+    }
+
+    /// Handler for NodeRequest::RewardViolations.
+    fn reward_violations(&self) -> RewardViolations {
+        let violations = self.reward_violations.iter().cloned().collect();
+        RewardViolations { violations }
+    }
+
+    /// Handler for NodeRequest::OrphanBlocks.
+    fn orphan_blocks(&self) -> OrphanBlocks {
+        let blocks = self
+            .future_blocks
+            .iter()
+            .take(MAX_ORPHAN_BLOCKS_LEN)
+            .map(|(height, (_from, block))| {
+                (*height, Hash::digest(block), block.base_header().previous)
+            })
+            .collect();
+        OrphanBlocks {
+            height: self.chain.height(),
+            last_block: self.chain.last_block_hash(),
+            blocks,
+        }
+    }
+
+    /// Handler for NodeRequest::TransactionByHash.
+    fn transaction_by_hash(&self, tx_hash: Hash) -> Result<TransactionLocation, Error> {
+        if self.mempool.contains(&tx_hash) {
+            return Ok(TransactionLocation {
+                tx_hash,
+                status: TransactionLocationStatus::Pending,
+                height: None,
+            });
+        }
+
+        // Walk backwards from the tip, most lookups are for recent transactions.
+        let height = self.chain.height();
+        let start = height.saturating_sub(MAX_TRANSACTION_SEARCH_BLOCKS);
+        for block_height in (start..height).rev() {
+            let block = match self.chain.block_by_height(block_height)? {
+                Block::MicroBlock(block) => block,
+                Block::MacroBlock(_) => continue,
+            };
+            if !block
+                .transactions
+                .iter()
+                .any(|tx| Hash::digest(tx) == tx_hash)
+            {
+                continue;
+            }
+            let status = if block_height < self.chain.last_macro_block_height()
+                || block_height + 1 + self.cfg.confirmation_depth <= height
+            {
+                TransactionLocationStatus::Committed
+            } else {
+                TransactionLocationStatus::InBlock
+            };
+            return Ok(TransactionLocation {
+                tx_hash,
+                status,
+                height: Some(block_height),
+            });
+        }
+
+        Ok(TransactionLocation {
+            tx_hash,
+            status: TransactionLocationStatus::Unknown,
+            height: None,
+        })
+    }
+
+    /// Handler for NodeRequest::TransactionPending.
+    fn transaction_pending(&self, tx_hash: Hash) -> PendingInfo {
+        let pending = self.mempool.contains(&tx_hash);
+        PendingInfo { tx_hash, pending }
+    }
+
+    /// Handler for NodeRequest::FeeParams.
+    fn fee_params(&self) -> FeeParams {
+        FeeParams {
+            payment_fee: self.cfg.payment_fee,
+            stake_fee: self.cfg.stake_fee,
+        }
+    }
+
+    /// Handler for NodeRequest::Health.
+    fn health(&self) -> HealthInfo {
+        let utxo_in_mempool = self.mempool.inputs_len() + self.mempool.outputs_len();
+        let mempool_ok = utxo_in_mempool < self.cfg.max_utxo_in_mempool
+            && self.mempool.bytes_len() < self.cfg.max_mempool_bytes;
+        HealthInfo {
+            synchronized: self.is_synchronized(),
+            height: self.chain.height(),
+            mempool_ok,
+            network_ready: self.is_network_ready,
+            validator_active: self.chain.is_validator(&self.keys.network_pkey),
+        }
+    }
+
+    /// Handler for NodeRequest::Resync.
+    fn resync(&mut self) -> Result<ResyncInfo, Error> {
+        let height = self.chain.height();
+        let now = clock::now();
+        if let Some(last_resync_clock) = self.last_resync_clock {
+            let elapsed = now.duration_since(last_resync_clock);
+            if elapsed < self.cfg.resync_timeout {
+                debug!(
+                    "Throttling resync: elapsed={:?}, min_interval={:?}",
+                    elapsed, self.cfg.resync_timeout
+                );
+                return Ok(ResyncInfo {
+                    height,
+                    requested: false,
+                });
+            }
+        }
+
+        info!("Resync requested: height={}", height);
+        self.last_resync_clock = Some(now);
+        // Bypass the loader throttling and backoff.
+        self.sync_attempts = 0;
+        self.sync_targets.clear();
+        self.last_sync_clock = now - self.cfg.loader_timeout;
+        self.request_history()?;
+        Ok(ResyncInfo {
+            height,
+            requested: true,
+        })
+    }
+
+    /// Handler for NodeRequest::ValidatorSet.
+    fn validator_set(&self) -> ValidatorSet {
+        ValidatorSet {
+            epoch: self.chain.epoch(),
+            total_slots: self.chain.total_slots(),
+            facilitator: self.chain.facilitator().clone(),
+            validators: self.chain.validators().clone(),
+        }
+    }
+
+    /// Handler for NodeRequest::UtxoSnapshot.
+    fn utxo_snapshot(&self, after: Option<Hash>) -> Result<UtxoSnapshot, Error> {
+        let mut output_hashes: Vec<Hash> = self
+            .chain
+            .unspent_after(after)
+            .take(MAX_UTXO_SNAPSHOT_LEN + 1)
+            .cloned()
+            .collect();
+        let next = if output_hashes.len() > MAX_UTXO_SNAPSHOT_LEN {
+            output_hashes.truncate(MAX_UTXO_SNAPSHOT_LEN);
+            output_hashes.last().cloned()
+        } else {
+            None
+        };
+        let mut outputs = Vec::with_capacity(output_hashes.len());
+        for output_hash in &output_hashes {
+            let output = self.chain.output_by_hash(output_hash)?.expect("exists");
+            outputs.push(output);
+        }
+        Ok(UtxoSnapshot {
+            height: self.chain.height(),
+            block_hash: self.chain.last_block_hash(),
+            outputs,
+            next,
+        })
+    }
+
+    /// Returns the proof of the last view change at the current height.
+    fn sealed_view_change_proof(&self) -> Option<SealedViewChangeProof> {
+        if self.chain.view_change() == 0 {
+            return None;
+        }
+        let proof = self.chain.view_change_proof().clone()?;
+        let mut chain = ChainInfo::from_blockchain(&self.chain);
+        // correct information about proof, to refer previous on view_change;
+        chain.view_change -= 1;
+        Some(SealedViewChangeProof { chain, proof })
+    }
+
+    /// Handler for NodeRequest::ViewChangeProof.
+    fn view_change_proof_info(&self) -> ViewChangeProofInfo {
+        ViewChangeProofInfo {
+            height: self.chain.height(),
+            view_change: self.chain.view_change(),
+            proof: self.sealed_view_change_proof(),
+        }
+    }
+
+    /// Handler for NodeMessage::CancelTransaction.
+    fn handle_cancel_transaction(&mut self, tx_hash: Hash) -> Result<(), Error> {
+        if self.mempool.remove_tx(&tx_hash).is_none() {
+            debug!(
+                "Transaction is not in the mempool, ignoring cancel request: tx={}",
+                &tx_hash
+            );
+            return Ok(());
+        }
+        info!("Cancelled transaction: tx={}", &tx_hash);
+        self.mempool_removed(vec![tx_hash]);
+        self.notify_transaction_status(tx_hash, TransactionStatus::Cancelled {});
+        metrics::MEMPOOL_TRANSACTIONS.set(self.mempool.len() as i64);
+        metrics::MEMPOOL_INPUTS.set(self.mempool.inputs_len() as i64);
+        metrics::MEMPOOL_OUTPUTS.set(self.mempool.outputs_len() as i64);
+        metrics::MEMPOOL_BYTES.set(self.mempool.bytes_len() as i64);
+        Ok(())
+    }
+
     /// Handler for new epoch creation procedure.
     /// This method called only on leader side, and when consensus is active.
     /// Leader should create a KeyBlock based on last random provided by VRF.
@@ -1029,6 +2586,7 @@ impl NodeService {
         let leader = consensus.leader();
         let blockchain = &self.chain;
         let keys = &self.keys;
+        let block_reward = self.cfg.macro_block_reward;
         assert_eq!(&leader, &self.keys.network_pkey);
 
         let create_macro_block = || {
@@ -1048,7 +2606,8 @@ impl NodeService {
             );
 
             let validators = blockchain.validators();
-            let mut block = MacroBlock::empty(base, keys.network_pkey);
+            let mut block =
+                MacroBlock::with_reward(base, &keys.wallet_pkey, keys.network_pkey, block_reward);
 
             let block_hash = Hash::digest(&block);
 
@@ -1097,6 +2656,14 @@ impl NodeService {
     /// Request for changing group received from VRF system.
     /// Restars consensus with new params, and send new keyblock.
     fn on_change_group(&mut self) -> Result<(), Error> {
+        if self.cfg.auditor_only {
+            debug!("I am auditor, waiting for consensus to produce blocks");
+            return Ok(());
+        }
+        if self.chain_inconsistent {
+            error!("The chain is inconsistent, skipping consensus");
+            return Ok(());
+        }
         if self
             .chain
             .validators()
@@ -1115,6 +2682,7 @@ impl NodeService {
             self.keys.network_pkey.clone(),
             self.chain.election_result(),
             self.chain.validators().iter().cloned().collect(),
+            self.chain.cfg().bft_threshold,
         );
         self.consensus = Some(consensus);
         self.on_new_consensus()?;
@@ -1160,11 +2728,8 @@ impl NodeService {
         }
 
         // update timer, set current_time to now().
+        self.reset_macro_block_timer();
         let consensus = self.consensus.as_ref().unwrap();
-        assert!(self.chain.view_change() <= consensus.round());
-        let relevant_round = 1 + consensus.round() - self.chain.view_change();
-        self.macro_block_timer
-            .reset(self.cfg.macro_block_timeout * relevant_round);
         if consensus.should_propose() {
             info!(
                 "I'm leader, proposing a new macro block: height={}, last_block={}, epoch={}",
@@ -1173,6 +2738,7 @@ impl NodeService {
                 self.chain.epoch(),
             );
             self.create_new_epoch()?;
+            self.reset_macro_block_timer();
         } else {
             info!(
                 "I'm validator, waiting for a new macro block: height={}, last_block={}, epoch={}, leader={}",
@@ -1183,7 +2749,8 @@ impl NodeService {
             );
         }
 
-        let outbox = std::mem::replace(&mut self.future_consensus_messages, Vec::new());
+        let outbox = self.future_consensus_messages.drain();
+        metrics::FUTURE_CONSENSUS_MESSAGES.set(0);
         for msg in outbox {
             if let Err(e) = self.handle_consensus_message(msg) {
                 debug!("Error in future consensus message: {}", e);
@@ -1214,19 +2781,63 @@ impl NodeService {
     /// Handles incoming consensus requests received from network.
     ///
     fn handle_consensus_message(&mut self, msg: BlockConsensusMessage) -> Result<(), Error> {
+        // Reject messages from incompatible nodes early, even for future heights.
+        msg.validate_version()?;
         // if our consensus state is outdated, push message to future_consensus_messages.
         // TODO: remove queue and use request-responses to get message from other nodes.
         if self.consensus.is_none() {
+            // Don't let absurd heights and rounds occupy the queue.
+            let height = self.chain.last_macro_block_height() + self.cfg.blocks_in_epoch;
+            let bounds = MessageBounds {
+                max_height: height + self.cfg.blocks_in_epoch,
+                max_round: MAX_CONSENSUS_ROUNDS_AHEAD,
+            };
+            msg.validate_bounds(&bounds)?;
             self.future_consensus_messages.push(msg);
+            metrics::FUTURE_CONSENSUS_MESSAGES.set(self.future_consensus_messages.len() as i64);
             return Ok(());
         }
+        // Drop stale messages before doing expensive validation.
+        let consensus = self.consensus.as_ref().unwrap();
+        if msg.height < consensus.height() || msg.round < consensus.round() {
+            debug!(
+                "Dropped a stale consensus message: height={}, round={}, our_height={}, our_round={}",
+                msg.height,
+                msg.round,
+                consensus.height(),
+                consensus.round()
+            );
+            metrics::STALE_CONSENSUS_MESSAGES.inc();
+            return Ok(());
+        }
+        let bounds = MessageBounds {
+            max_height: consensus.height() + self.cfg.blocks_in_epoch,
+            max_round: consensus.round() + MAX_CONSENSUS_ROUNDS_AHEAD,
+        };
         let validate_request = |request_hash: Hash, block: &MacroBlock, round| {
             validate_proposed_macro_block(&self.cfg, &self.chain, round, request_hash, block)
         };
         // Validate signature and content.
-        msg.validate(validate_request)?;
+        msg.validate(&bounds, validate_request)?;
         let consensus = self.consensus.as_mut().unwrap();
+        let was_proposed = consensus.is_proposed();
         consensus.feed_message(msg)?;
+        if !was_proposed && consensus.is_proposed() {
+            // Proposal has been accepted, wait for a commit.
+            self.reset_macro_block_timer();
+        }
+        let consensus = self.consensus.as_mut().unwrap();
+        // Report protocol violations.
+        for equivocation in consensus.equivocations.drain(..) {
+            error!(
+                "Validator sent conflicting votes: pkey={}, height={}, round={}, first={}, second={}",
+                equivocation.first.pkey,
+                equivocation.first.height,
+                equivocation.first.round,
+                equivocation.first.request_hash,
+                equivocation.second.request_hash
+            );
+        }
         // Flush pending messages.
         NodeService::flush_consensus_messages(consensus, &mut self.network)?;
 
@@ -1244,22 +2855,84 @@ impl NodeService {
     /// Returns true if current node is a leader.
     ///
     fn is_leader(&self) -> bool {
-        self.chain.leader() == self.keys.network_pkey
+        !self.cfg.auditor_only
+            && !self.chain_inconsistent
+            && self.chain.leader() == self.keys.network_pkey
+    }
+
+    /// Returns the time when the leader should propose a new micro block.
+    fn micro_block_propose_deadline(&self) -> Instant {
+        if self.chain.view_change() == 0 {
+            self.last_block_clock + self.cfg.tx_wait_timeout
+        } else {
+            self.last_view_change_clock + self.cfg.view_change_tx_wait_timeout
+        }
     }
 
     /// Сhecks if it's time to create a micro block.
     fn handle_micro_block_propose_timer(&mut self) -> Result<(), Error> {
-        let elapsed: Duration = clock::now().duration_since(self.last_block_clock);
-
         // Check that a new payment block should be created.
-        if self.consensus.is_none() && elapsed >= self.cfg.tx_wait_timeout && self.is_leader() {
+        if self.consensus.is_none()
+            && (clock::now() >= self.micro_block_propose_deadline()
+                || self.has_enough_transactions())
+            && self.is_leader()
+        {
             assert!(self.chain.blocks_in_epoch() < self.cfg.blocks_in_epoch);
-            self.create_micro_block(None)?;
+            if self.should_skip_empty_block() {
+                debug!(
+                    "Mempool is empty, skipping the micro block: height={}, last_block={}",
+                    self.chain.height(),
+                    self.chain.last_block_hash()
+                );
+                return Ok(());
+            }
+            let proof = self.chain.view_change_proof().clone();
+            self.create_micro_block(proof)?;
+            self.propose_timer.reset(self.cfg.tx_wait_timeout);
         }
 
         Ok(())
     }
 
+    /// True if the leader should wait for transactions instead of creating an empty block.
+    /// Blocks after view changes are never skipped.
+    fn should_skip_empty_block(&self) -> bool {
+        let elapsed: Duration = clock::now().duration_since(self.last_block_clock);
+        self.cfg.skip_empty_blocks
+            && self.mempool.len() == 0
+            && self.chain.view_change() == 0
+            && elapsed < self.cfg.empty_block_timeout
+    }
+
+    /// True if mempool has enough transactions to create a micro block without waiting.
+    fn has_enough_transactions(&self) -> bool {
+        self.cfg.min_txs_for_immediate_block > 0
+            && self.mempool.len() >= self.cfg.min_txs_for_immediate_block
+    }
+
+    /// Creates a micro block without waiting for tx_wait_timeout,
+    /// if mempool has enough transactions.
+    /// Errors are logged here, because they aren't caused by the incoming transaction.
+    fn check_immediate_micro_block(&mut self) {
+        if self.consensus.is_some()
+            || !self.has_enough_transactions()
+            || !self.is_leader()
+            || self.chain.blocks_in_epoch() >= self.cfg.blocks_in_epoch
+        {
+            return;
+        }
+        let proof = self.chain.view_change_proof().clone();
+        if let Err(e) = self.create_micro_block(proof) {
+            error!(
+                "Failed to create a micro block: height={}, error={}",
+                self.chain.height(),
+                e
+            );
+            return;
+        }
+        self.propose_timer.reset(self.cfg.tx_wait_timeout);
+    }
+
     /// True if the node is synchronized with the network.
     fn is_synchronized(&self) -> bool {
         let timestamp = SystemTime::now();
@@ -1270,6 +2943,19 @@ impl NodeService {
             >= timestamp
     }
 
+    /// Restart the macro block timer, depending on the phase of consensus.
+    fn reset_macro_block_timer(&mut self) {
+        let consensus = self.consensus.as_ref().unwrap();
+        assert!(self.chain.view_change() <= consensus.round());
+        let relevant_round = 1 + consensus.round() - self.chain.view_change();
+        let timeout = if consensus.is_proposed() {
+            self.cfg.macro_commit_timeout()
+        } else {
+            self.cfg.macro_propose_timeout()
+        };
+        self.macro_block_timer.reset(timeout * relevant_round);
+    }
+
     /// Checks if it's time to perform a view change on a micro block.
     fn handle_macro_block_viewchange_timer(&mut self) -> Result<(), Error> {
         if self.consensus.is_none() {
@@ -1277,8 +2963,9 @@ impl NodeService {
         }
 
         warn!(
-            "Timed out while waiting for a macro block: height={}",
+            "Timed out while waiting for a macro block: height={}, proposed={}",
             self.chain.height(),
+            self.consensus.as_ref().unwrap().is_proposed(),
         );
 
         // Check that a block has been committed but haven't send by the leader.
@@ -1327,28 +3014,42 @@ impl NodeService {
         pkey: pbc::PublicKey,
     ) -> Result<(), Error> {
         debug!("Received sealed view change proof: proof = {:?}", proof);
-        self.try_rollback(pkey, proof)?;
+        let height = proof.chain.height;
+        let outcome = self.try_rollback(pkey, proof)?;
+        self.notify_fork_resolved(height, outcome);
         return Ok(());
     }
     fn handle_view_change(&mut self, msg: ViewChangeMessage) -> Result<(), Error> {
         if let Some(proof) = self.optimistic.handle_message(&self.chain, msg)? {
+            let ctx = BlockContext::new(self.chain.height(), self.chain.view_change());
             debug!(
-                "Received enough messages for change leader: height={}, last_block={}, view_change={}",
-                self.chain.height(), self.chain.last_block_hash(), self.chain.view_change()
+                "{} Received enough messages for change leader: last_block={}",
+                ctx,
+                self.chain.last_block_hash()
             );
             self.chain
                 .set_view_change(self.chain.view_change() + 1, proof.clone());
+            self.last_view_change_clock = clock::now();
 
-            //TODO: save proof if you are not leader.
             if self.is_leader() {
-                debug!(
-                    "We are leader, producing new micro block: height={}, last_block={}",
-                    self.chain.height(),
-                    self.chain.last_block_hash()
-                );
-                self.create_micro_block(Some(proof))?;
-            } else {
-                //save proof for future usage.
+                let ctx = BlockContext::new(self.chain.height(), self.chain.view_change());
+                let timeout = self.cfg.view_change_tx_wait_timeout;
+                if timeout == Duration::from_secs(0) {
+                    debug!(
+                        "{} We are leader, producing new micro block: last_block={}",
+                        ctx,
+                        self.chain.last_block_hash()
+                    );
+                    self.create_micro_block(Some(proof))?;
+                } else {
+                    debug!(
+                        "{} We are leader, waiting for transactions: last_block={}, timeout={:?}",
+                        ctx,
+                        self.chain.last_block_hash(),
+                        timeout
+                    );
+                    self.propose_timer.reset(timeout);
+                }
             }
         }
         Ok(())
@@ -1363,8 +3064,8 @@ impl NodeService {
         }
 
         warn!(
-            "Timed out while waiting for a micro block: height={}, elapsed={:?}",
-            self.chain.height(),
+            "{} Timed out while waiting for a micro block: elapsed={:?}",
+            BlockContext::new(self.chain.height(), self.chain.view_change()),
             elapsed
         );
 
@@ -1372,6 +3073,11 @@ impl NodeService {
         metrics::SYNCHRONIZED.set(0);
         self.request_history()?;
 
+        // Auditors don't vote for view changes.
+        if self.cfg.auditor_only {
+            return Ok(());
+        }
+
         // Try to perform the view change.
         metrics::MICRO_BLOCK_VIEW_CHANGES.inc();
         if let Some(msg) = self.optimistic.handle_timeout(&self.chain)? {
@@ -1407,12 +3113,14 @@ impl NodeService {
             previous,
             VERSION,
             self.chain.height(),
-            self.cfg.block_reward,
+            self.cfg.reward_at_height(height),
+            self.cfg.fee_policy,
             &self.keys,
             self.chain.last_random(),
             self.chain.view_change(),
             proof,
             self.cfg.max_utxo_in_block,
+            self.cfg.max_block_bytes,
         );
         let block_hash = Hash::digest(&block);
 
@@ -1420,10 +3128,11 @@ impl NodeService {
         block.sign(&self.keys.network_skey, &self.keys.network_pkey);
 
         info!(
-            "Created a micro block: height={}, block={}, transactions={}",
+            "Created a micro block: height={}, block={}, transactions={}, fee={}",
             height,
             &block_hash,
             block.transactions.len(),
+            block.coinbase.block_fee,
         );
 
         // TODO: swap send_sealed_block() and apply_new_block() order after removing VRF.
@@ -1512,10 +3221,28 @@ impl Future for NodeService {
                     let result: Result<(), Error> = match event {
                         NodeMessage::SubscribeBlockAdded(tx) => self.handle_block_added(tx),
                         NodeMessage::SubscribeEpochChanged(tx) => self.handle_subscribe_epoch(tx),
+                        NodeMessage::SubscribeEpochFinalized(tx) => {
+                            self.handle_subscribe_epoch_finalized(tx)
+                        }
                         NodeMessage::SubscribeOutputsChanged(tx) => {
                             self.handle_subscribe_outputs(tx)
                         }
+                        NodeMessage::SubscribeReorg(tx) => self.handle_subscribe_reorg(tx),
+                        NodeMessage::SubscribeTransactionStatus(tx) => {
+                            self.handle_subscribe_transaction_status(tx)
+                        }
+                        NodeMessage::SubscribeMempoolChanged(tx) => {
+                            self.handle_subscribe_mempool_changed(tx)
+                        }
+                        NodeMessage::SubscribeForkResolved(tx) => {
+                            self.handle_subscribe_fork_resolved(tx)
+                        }
                         NodeMessage::PopBlock => self.handle_pop_block(),
+                        NodeMessage::SetCheckpoint(height) => self.handle_set_checkpoint(height),
+                        NodeMessage::CancelTransaction(tx_hash) => {
+                            self.handle_cancel_transaction(tx_hash)
+                        }
+                        NodeMessage::MergeMempool(msg) => self.handle_merge_mempool(msg),
                         NodeMessage::Request { request, tx } => {
                             let response = match request {
                                 NodeRequest::ElectionInfo {} => {
@@ -1524,25 +3251,92 @@ impl Future for NodeService {
                                 NodeRequest::EscrowInfo {} => {
                                     NodeResponse::EscrowInfo(self.chain.escrow_info())
                                 }
+                                NodeRequest::FeeEstimate {} => {
+                                    NodeResponse::FeeEstimate(self.fee_estimate())
+                                }
+                                NodeRequest::ParticipationInfo {} => {
+                                    NodeResponse::ParticipationInfo(self.participation.info())
+                                }
+                                NodeRequest::RewardViolations {} => {
+                                    NodeResponse::RewardViolations(self.reward_violations())
+                                }
+                                NodeRequest::LeaderSchedule { count } => {
+                                    NodeResponse::LeaderSchedule(self.leader_schedule(count))
+                                }
+                                NodeRequest::ValidatorSet {} => {
+                                    NodeResponse::ValidatorSet(self.validator_set())
+                                }
+                                NodeRequest::EpochHistory { from_epoch, limit } => {
+                                    NodeResponse::EpochHistory(
+                                        self.epoch_history(from_epoch, limit),
+                                    )
+                                }
+                                NodeRequest::ViewChangeProof {} => {
+                                    NodeResponse::ViewChangeProof(self.view_change_proof_info())
+                                }
+                                NodeRequest::UtxoSnapshot { after } => {
+                                    match self.utxo_snapshot(after) {
+                                        Ok(snapshot) => NodeResponse::UtxoSnapshot(snapshot),
+                                        Err(e) => NodeResponse::Error {
+                                            error: format!("{}", e),
+                                        },
+                                    }
+                                }
+                                NodeRequest::RawBlock { height } => match self.raw_block(height) {
+                                    Ok(block) => NodeResponse::RawBlock(block),
+                                    Err(e) => NodeResponse::Error {
+                                        error: format!("{}", e),
+                                    },
+                                },
+                                NodeRequest::OrphanBlocks {} => {
+                                    NodeResponse::OrphanBlocks(self.orphan_blocks())
+                                }
+                                NodeRequest::Resync {} => match self.resync() {
+                                    Ok(info) => NodeResponse::Resync(info),
+                                    Err(e) => NodeResponse::Error {
+                                        error: format!("{}", e),
+                                    },
+                                },
+                                NodeRequest::TransactionByHash { hash } => {
+                                    match self.transaction_by_hash(hash) {
+                                        Ok(location) => NodeResponse::TransactionLocation(location),
+                                        Err(e) => NodeResponse::Error {
+                                            error: format!("{}", e),
+                                        },
+                                    }
+                                }
+                                NodeRequest::TransactionPending { tx_hash } => {
+                                    NodeResponse::Pending(self.transaction_pending(tx_hash))
+                                }
+                                NodeRequest::FeeParams {} => {
+                                    NodeResponse::FeeParams(self.fee_params())
+                                }
+                                NodeRequest::Health {} => NodeResponse::Health(self.health()),
+                                NodeRequest::InclusionEstimate { fee } => {
+                                    NodeResponse::InclusionEstimate(self.inclusion_estimate(fee))
+                                }
                             };
                             tx.send(response).ok(); // ignore errors.
                             Ok(())
                         }
-                        NodeMessage::Transaction(msg) => Transaction::from_buffer(&msg)
-                            .and_then(|msg| self.handle_transaction(msg)),
-                        NodeMessage::Consensus(msg) => BlockConsensusMessage::from_buffer(&msg)
+                        NodeMessage::Transaction(msg) => decode_message(TX_TOPIC, None, &msg)
+                            .and_then(|msg| self.handle_transaction_async(msg)),
+                        NodeMessage::Consensus(msg) => decode_message(CONSENSUS_TOPIC, None, &msg)
                             .and_then(|msg| self.handle_consensus_message(msg)),
-                        NodeMessage::ViewChangeMessage(msg) => ViewChangeMessage::from_buffer(&msg)
-                            .and_then(|msg| self.handle_view_change(msg)),
+                        NodeMessage::ViewChangeMessage(msg) => {
+                            decode_message(VIEW_CHANGE_TOPIC, None, &msg)
+                                .and_then(|msg| self.handle_view_change(msg))
+                        }
                         NodeMessage::ViewChangeProofMessage(msg) => {
-                            SealedViewChangeProof::from_buffer(&msg.data)
+                            decode_message(VIEW_CHANGE_DIRECT, Some(msg.from), &msg.data)
                                 .and_then(|proof| self.handle_view_change_direct(proof, msg.from))
                         }
                         NodeMessage::SealedBlock(msg) => {
-                            Block::from_buffer(&msg).and_then(|msg| self.handle_sealed_block(msg))
+                            decode_message(SEALED_BLOCK_TOPIC, msg.from, &msg.data)
+                                .and_then(|block| self.handle_broadcast_block(msg.from, block))
                         }
                         NodeMessage::ChainLoaderMessage(msg) => {
-                            ChainLoaderMessage::from_buffer(&msg.data)
+                            decode_message(loader::CHAIN_LOADER_TOPIC, Some(msg.from), &msg.data)
                                 .and_then(|data| self.handle_chain_loader_message(msg.from, data))
                         }
                     };
@@ -1551,7 +3345,13 @@ impl Future for NodeService {
                     }
                 }
                 Async::Ready(None) => unreachable!(), // never happens
-                Async::NotReady => return Ok(Async::NotReady),
+                Async::NotReady => {
+                    // Transactions validated by the thread pool.
+                    let _ = self.poll_pending_transactions();
+                    // Send all mempool changes of this iteration at once.
+                    self.flush_mempool_changes();
+                    return Ok(Async::NotReady);
+                }
             }
         }
     }
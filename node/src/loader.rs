@@ -22,7 +22,11 @@
 use crate::NodeService;
 use failure::{format_err, Error};
 use log::*;
+use protobuf::Message;
 use rand::seq::IteratorRandom;
+use std::cmp;
+use std::mem;
+use std::time::Duration;
 use stegos_blockchain::Block;
 use stegos_crypto::hash::{Hashable, Hasher};
 use stegos_crypto::pbc;
@@ -50,6 +54,8 @@ impl RequestBlocks {
 pub struct ResponseBlocks {
     pub height: u64,
     pub blocks: Vec<Block>,
+    /// More chunks of the same response follow.
+    pub more: bool,
 }
 
 impl Hashable for ResponseBlocks {
@@ -58,12 +64,17 @@ impl Hashable for ResponseBlocks {
         for block in &self.blocks {
             block.hash(state);
         }
+        self.more.hash(state);
     }
 }
 
 impl ResponseBlocks {
-    pub fn new(height: u64, blocks: Vec<Block>) -> ResponseBlocks {
-        Self { height, blocks }
+    pub fn new(height: u64, blocks: Vec<Block>, more: bool) -> ResponseBlocks {
+        Self {
+            height,
+            blocks,
+            more,
+        }
     }
 }
 
@@ -125,16 +136,56 @@ impl NodeService {
         self.request_history_from(from)
     }
 
+    /// Returns the minimal interval before the next history request.
+    /// The interval doubles with every request which hasn't led to a new block.
+    pub(crate) fn loader_backoff(&self) -> Duration {
+        let shift = cmp::min(self.sync_attempts.saturating_sub(1), 16);
+        let backoff = self.cfg.loader_timeout * (1u32 << shift);
+        cmp::min(backoff, self.cfg.max_loader_timeout)
+    }
+
+    /// Returns `from` or, if it hasn't answered the previous requests,
+    /// another validator which hasn't been asked since the last applied block.
+    fn rotate_sync_target(&mut self, from: pbc::PublicKey) -> pbc::PublicKey {
+        let requests = self.sync_targets.get(&from).cloned().unwrap_or(0);
+        if requests < self.cfg.loader_requests_per_peer {
+            return from;
+        }
+
+        let next = self
+            .chain
+            .validators()
+            .iter()
+            .map(|(k, _)| *k)
+            .find(|key| self.keys.network_pkey != *key && !self.sync_targets.contains_key(key));
+        match next {
+            Some(next) => {
+                debug!(
+                    "Peer doesn't respond, trying another one: peer={}, next={}",
+                    from, next
+                );
+                next
+            }
+            None => {
+                // All validators have been tried, start over.
+                self.sync_targets.clear();
+                from
+            }
+        }
+    }
+
     pub fn request_history_from(&mut self, from: pbc::PublicKey) -> Result<(), Error> {
         let elapsed = clock::now().duration_since(self.last_sync_clock);
-        if elapsed < self.cfg.loader_timeout {
+        let backoff = self.loader_backoff();
+        if elapsed < backoff {
             debug!(
-                "Throttling loader: elapsed={:?}, min_interval={:?}",
-                elapsed, self.cfg.loader_timeout
+                "Throttling loader: elapsed={:?}, min_interval={:?}, attempts={}",
+                elapsed, backoff, self.sync_attempts
             );
             return Ok(());
         }
 
+        let from = self.rotate_sync_target(from);
         let start_height = self.chain.last_macro_block_height();
         info!(
             "Downloading blocks: from={}, start_height={}, our_height={}",
@@ -144,6 +195,8 @@ impl NodeService {
         );
         let msg = ChainLoaderMessage::Request(RequestBlocks::new(start_height));
         self.last_sync_clock = clock::now();
+        self.sync_attempts = self.sync_attempts.saturating_add(1);
+        *self.sync_targets.entry(from).or_insert(0) += 1;
         self.network
             .send(from, CHAIN_LOADER_TOPIC, msg.into_buffer()?)
     }
@@ -166,34 +219,81 @@ impl NodeService {
 
     pub fn send_blocks(&mut self, pkey: pbc::PublicKey, starting_height: u64) -> Result<(), Error> {
         assert!(starting_height < self.chain.height());
-        // Send one epoch.
+        // Send up to chain_loader_speed_in_epoch epochs.
         let blocks = self.chain.blocks_range(
             starting_height,
             self.cfg.blocks_in_epoch * self.cfg.chain_loader_speed_in_epoch,
         );
         info!("Feeding blocks: to={}, num_blocks={}", pkey, blocks.len());
-        let msg = ChainLoaderMessage::Response(ResponseBlocks::new(self.chain.height(), blocks));
-        self.network
-            .send(pkey, CHAIN_LOADER_TOPIC, msg.into_buffer()?)?;
+
+        // Split blocks into chunks bounded by the number of blocks and the size in bytes.
+        let mut chunks: Vec<Vec<Block>> = Vec::new();
+        let mut chunk: Vec<Block> = Vec::new();
+        let mut chunk_bytes: usize = 0;
+        for block in blocks {
+            let block_bytes = block.into_proto().compute_size() as usize;
+            if !chunk.is_empty()
+                && (chunk.len() as u64 >= self.cfg.max_blocks_per_response
+                    || chunk_bytes + block_bytes > self.cfg.max_response_bytes)
+            {
+                chunks.push(mem::replace(&mut chunk, Vec::new()));
+                chunk_bytes = 0;
+            }
+            chunk_bytes += block_bytes;
+            chunk.push(block);
+        }
+        if !chunk.is_empty() {
+            chunks.push(chunk);
+        }
+
+        let last = chunks.len().saturating_sub(1);
+        for (i, chunk) in chunks.into_iter().enumerate() {
+            self.send_blocks_chunk(pkey, chunk, i < last)?;
+        }
         Ok(())
     }
 
+    fn send_blocks_chunk(
+        &mut self,
+        pkey: pbc::PublicKey,
+        blocks: Vec<Block>,
+        more: bool,
+    ) -> Result<(), Error> {
+        debug!(
+            "Sending blocks: to={}, first_height={}, num_blocks={}, more={}",
+            pkey,
+            blocks[0].base_header().height,
+            blocks.len(),
+            more
+        );
+        let msg =
+            ChainLoaderMessage::Response(ResponseBlocks::new(self.chain.height(), blocks, more));
+        self.network
+            .send(pkey, CHAIN_LOADER_TOPIC, msg.into_buffer()?)
+    }
+
     fn handle_response_blocks(
         &mut self,
         pkey: pbc::PublicKey,
         response: ResponseBlocks,
     ) -> Result<(), Error> {
         info!(
-            "Received blocks: from={}, num_blocks={}, remote_height={}",
+            "Received blocks: from={}, num_blocks={}, remote_height={}, more={}",
             pkey,
             response.blocks.len(),
             response.height,
+            response.more,
         );
 
         let initial_height = self.chain.height();
         for block in response.blocks {
             // Fail on the first error.
-            self.handle_sealed_block(block)?;
+            self.handle_sealed_block(Some(pkey), block)?;
+        }
+
+        // The rest of the requested range is on the way.
+        if response.more {
+            return Ok(());
         }
 
         //
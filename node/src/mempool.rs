@@ -21,8 +21,10 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
-use linked_hash_map::LinkedHashMap;
+use crate::config::MempoolStorage;
+use crate::mempool_storage::{DiskStorage, MemoryStorage, TransactionStorage};
 use log::*;
+use protobuf::Message;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::time::SystemTime;
@@ -31,12 +33,17 @@ use stegos_blockchain::*;
 use stegos_crypto::hash::Hash;
 use stegos_crypto::pbc;
 use stegos_keychain::KeyChain;
+use stegos_serialization::traits::ProtoConvert;
 
 /// Memory Pool of Transactions.
 pub struct Mempool {
-    pool: LinkedHashMap<Hash, Transaction>,
+    pool: Box<dyn TransactionStorage>,
     inputs: HashMap<Hash, Hash>,
     outputs: HashMap<Hash, Hash>,
+    /// Serialized size of each transaction.
+    tx_bytes: HashMap<Hash, usize>,
+    /// Total serialized size of all transactions.
+    bytes: usize,
 }
 
 impl Mempool {
@@ -44,14 +51,43 @@ impl Mempool {
     /// Creates a new mempool instance.
     ///
     pub fn new() -> Self {
-        let pool: LinkedHashMap<Hash, Transaction> = LinkedHashMap::new();
+        Self::with_storage(Box::new(MemoryStorage::new()))
+    }
+
+    ///
+    /// Creates a new mempool instance with the storage backend selected by `storage`.
+    ///
+    pub fn open(storage: &MempoolStorage) -> Self {
+        match storage {
+            MempoolStorage::Memory => Self::new(),
+            MempoolStorage::Disk { path } => Self::with_storage(Box::new(DiskStorage::new(path))),
+        }
+    }
+
+    ///
+    /// Creates a new mempool instance on top of `pool`.
+    /// Transactions which already exist in `pool` are indexed.
+    ///
+    pub fn with_storage(pool: Box<dyn TransactionStorage>) -> Self {
         let inputs: HashMap<Hash, Hash> = HashMap::new();
         let outputs: HashMap<Hash, Hash> = HashMap::new();
-        return Self {
+        let tx_bytes: HashMap<Hash, usize> = HashMap::new();
+        let bytes: usize = 0;
+        let mut mempool = Self {
             pool,
             inputs,
             outputs,
+            tx_bytes,
+            bytes,
         };
+        let persisted: Vec<(Hash, Transaction)> = mempool
+            .iter()
+            .map(|(tx_hash, tx)| (tx_hash.clone(), tx))
+            .collect();
+        for (tx_hash, tx) in persisted {
+            mempool.index_tx(&tx_hash, &tx);
+        }
+        mempool
     }
 
     ///
@@ -71,8 +107,45 @@ impl Mempool {
     ///
     /// Checks if the mempool contains the given transaction.
     ///
-    pub fn contains_tx(&self, tx_hash: &Hash) -> bool {
-        self.pool.contains_key(tx_hash)
+    pub fn contains(&self, tx_hash: &Hash) -> bool {
+        self.pool.contains(tx_hash)
+    }
+
+    ///
+    /// Returns the transaction with the given hash.
+    ///
+    pub fn get(&self, tx_hash: &Hash) -> Option<Transaction> {
+        self.pool.get(tx_hash)
+    }
+
+    ///
+    /// Returns hashes of transactions which spend the same inputs or
+    /// create the same outputs as `tx`.
+    ///
+    pub fn conflicts(&self, tx: &Transaction) -> HashSet<Hash> {
+        let mut tx_hashes: HashSet<Hash> = HashSet::new();
+        for input_hash in tx.txins() {
+            if let Some(tx_hash) = self.inputs.get(input_hash) {
+                tx_hashes.insert(tx_hash.clone());
+            }
+        }
+        for output in tx.txouts() {
+            let output_hash = Hash::digest(output);
+            if let Some(tx_hash) = self.outputs.get(&output_hash) {
+                tx_hashes.insert(tx_hash.clone());
+            }
+        }
+        tx_hashes
+    }
+
+    ///
+    /// Returns an iterator over transactions in the order they were queued.
+    ///
+    pub fn iter(&self) -> impl Iterator<Item = (&Hash, Transaction)> {
+        self.pool.hashes().map(move |tx_hash| {
+            let tx = self.pool.get(tx_hash).expect("transaction exists");
+            (tx_hash, tx)
+        })
     }
 
     ///
@@ -80,6 +153,14 @@ impl Mempool {
     ///
     pub fn push_tx(&mut self, tx_hash: Hash, tx: Transaction) {
         debug_assert_eq!(&tx_hash, &Hash::digest(&tx));
+        self.index_tx(&tx_hash, &tx);
+        self.pool.insert(tx_hash, tx);
+    }
+
+    ///
+    /// Registers inputs, outputs and size of a transaction.
+    ///
+    fn index_tx(&mut self, tx_hash: &Hash, tx: &Transaction) {
         for input_hash in tx.txins() {
             let exists = self.inputs.insert(input_hash.clone(), tx_hash.clone());
             assert!(exists.is_none());
@@ -89,12 +170,34 @@ impl Mempool {
             let exists = self.outputs.insert(output_hash, tx_hash.clone());
             assert!(exists.is_none());
         }
-        let exists = self.pool.insert(tx_hash, tx);
+        let bytes = tx.into_proto().compute_size() as usize;
+        let exists = self.tx_bytes.insert(tx_hash.clone(), bytes);
         assert!(exists.is_none());
+        self.bytes += bytes;
+    }
+
+    ///
+    /// Removes a transaction from the mempool.
+    /// Returns the removed transaction, if it was found.
+    ///
+    pub fn remove_tx(&mut self, tx_hash: &Hash) -> Option<Transaction> {
+        let tx = self.pool.remove(tx_hash)?;
+        self.bytes -= self.tx_bytes.remove(tx_hash).expect("transaction exists");
+        for input_hash in tx.txins() {
+            let tx_hash2 = self.inputs.remove(input_hash).expect("input exists");
+            assert_eq!(&tx_hash2, tx_hash);
+        }
+        for output in tx.txouts() {
+            let output_hash = Hash::digest(output);
+            let tx_hash2 = self.outputs.remove(&output_hash).expect("output exists");
+            assert_eq!(&tx_hash2, tx_hash);
+        }
+        Some(tx)
     }
 
     /// Prune old transactions contains tx_hash from the mempool.
-    pub fn prune(&mut self, input_hashes: &[Hash], output_hashes: &[Hash]) {
+    /// Returns hashes of pruned transactions.
+    pub fn prune(&mut self, input_hashes: &[Hash], output_hashes: &[Hash]) -> Vec<Hash> {
         let mut tx_hashes: HashSet<Hash> = HashSet::new();
 
         // Collect transactions affected by inputs.
@@ -112,8 +215,10 @@ impl Mempool {
         }
 
         // Prune transactions.
+        let mut pruned: Vec<Hash> = Vec::with_capacity(tx_hashes.len());
         for tx_hash in tx_hashes {
             let tx = self.pool.remove(&tx_hash).expect("transaction exists");
+            self.bytes -= self.tx_bytes.remove(&tx_hash).expect("transaction exists");
             for input_hash in tx.txins() {
                 if let Some(tx_hash2) = self.inputs.remove(input_hash) {
                     assert_eq!(tx_hash2, tx_hash);
@@ -125,7 +230,9 @@ impl Mempool {
                     assert_eq!(tx_hash2, tx_hash);
                 }
             }
+            pruned.push(tx_hash);
         }
+        pruned
     }
 
     ///
@@ -150,6 +257,20 @@ impl Mempool {
         self.outputs.len()
     }
 
+    ///
+    /// Returns the size of the given transaction, in bytes.
+    ///
+    pub fn tx_bytes_len(&self, tx_hash: &Hash) -> usize {
+        self.tx_bytes[tx_hash]
+    }
+
+    ///
+    /// Returns the total size of transactions in this mempool, in bytes.
+    ///
+    pub fn bytes_len(&self) -> usize {
+        self.bytes
+    }
+
     ///
     /// Process transactions in mempool and create a new monetary block.
     ///
@@ -159,11 +280,13 @@ impl Mempool {
         version: u64,
         height: u64,
         block_reward: i64,
+        fee_policy: FeePolicy,
         keychain: &KeyChain,
         last_random: Hash,
         view_change: u32,
         view_change_proof: Option<ViewChangeProof>,
         max_utxo_in_block: usize,
+        max_block_bytes: usize,
     ) -> MicroBlock {
         let timestamp = SystemTime::now();
         let seed = mix(last_random, view_change);
@@ -173,21 +296,39 @@ impl Mempool {
         // Transactions.
         //
         let mut utxo_in_block: usize = 2;
+        let mut bytes_in_block: usize = 0;
         let mut transactions: Vec<Transaction> = Vec::new();
-        for entry in self.pool.entries() {
-            let tx_hash = entry.key();
-            let tx = entry.get();
+        for (tx_hash, tx) in self.iter() {
             debug_assert_eq!(tx_hash, &Hash::digest(&tx));
 
+            // Inputs are resolved against the blockchain, therefore outputs
+            // created by other transactions from the mempool can't be spent
+            // in the same block. Such transactions wait for the next block.
+            if tx
+                .txins()
+                .iter()
+                .any(|input_hash| self.outputs.contains_key(input_hash))
+            {
+                debug!("Skipped a dependent transaction: hash={}", &tx_hash);
+                continue;
+            }
+
             // Check the maximum number of UTXO in block.
             if utxo_in_block + tx.txins().len() + tx.txouts().len() >= max_utxo_in_block {
                 break;
             }
 
+            // Check the maximum size of block in bytes.
+            let tx_bytes = self.tx_bytes[tx_hash];
+            if bytes_in_block + tx_bytes > max_block_bytes {
+                break;
+            }
+
             debug!("Processing transaction: hash={}", &tx_hash);
-            transactions.push(tx.clone());
             utxo_in_block += tx.txins().len();
             utxo_in_block += tx.txouts().len();
+            bytes_in_block += tx_bytes;
+            transactions.push(tx);
         }
 
         debug!(
@@ -205,6 +346,7 @@ impl Mempool {
             &keychain.wallet_pkey,
             keychain.network_pkey,
             block_reward,
+            fee_policy,
         )
     }
 }
@@ -213,6 +355,7 @@ impl Mempool {
 mod test {
     use super::*;
     use stegos_crypto::curve1174::make_random_keys;
+    use tempdir::TempDir;
 
     #[test]
     fn basic() {
@@ -230,8 +373,16 @@ mod test {
 
         mempool.push_tx(tx_hash1.clone(), tx1.clone().into());
         mempool.push_tx(tx_hash2.clone(), tx2.clone().into());
-        assert!(mempool.contains_tx(&tx_hash1));
-        assert!(mempool.contains_tx(&tx_hash2));
+        assert!(mempool.contains(&tx_hash1));
+        assert!(mempool.contains(&tx_hash2));
+        assert_eq!(
+            mempool.get(&tx_hash1).map(|tx| Hash::digest(&tx)),
+            Some(tx_hash1)
+        );
+        assert_eq!(
+            mempool.get(&tx_hash2).map(|tx| Hash::digest(&tx)),
+            Some(tx_hash2)
+        );
         assert_eq!(mempool.len(), 2);
 
         for input in inputs1.iter().chain(inputs2.iter()) {
@@ -250,7 +401,8 @@ mod test {
         let input1_hashes: Vec<Hash> = inputs1.iter().map(|o| Hash::digest(o)).collect();
         let output1_hashes: Vec<Hash> = outputs1.iter().map(|o| Hash::digest(o)).collect();
         mempool.prune(&input1_hashes, &output1_hashes);
-        assert!(!mempool.contains_tx(&tx_hash1));
+        assert!(!mempool.contains(&tx_hash1));
+        assert!(mempool.get(&tx_hash1).is_none());
         for input in &inputs1 {
             let input_hash = Hash::digest(input);
             assert!(!mempool.contains_input(&input_hash));
@@ -259,7 +411,7 @@ mod test {
             let output_hash = Hash::digest(output);
             assert!(!mempool.contains_output(&output_hash));
         }
-        assert!(mempool.contains_tx(&tx_hash2));
+        assert!(mempool.contains(&tx_hash2));
         for input in &inputs2 {
             let input_hash = Hash::digest(input);
             assert!(mempool.contains_input(&input_hash));
@@ -271,16 +423,52 @@ mod test {
         assert_eq!(mempool.len(), 1);
 
         mempool.push_tx(tx_hash1.clone(), tx1.clone().into());
-        assert!(mempool.contains_tx(&tx_hash1));
+        assert!(mempool.contains(&tx_hash1));
         assert_eq!(mempool.len(), 2);
 
         // Prune nothing.
         mempool.prune(&vec![Hash::digest(&1u64)], &vec![Hash::digest(&1u64)]);
-        assert!(mempool.contains_tx(&tx_hash1));
-        assert!(mempool.contains_tx(&tx_hash2));
+        assert!(mempool.contains(&tx_hash1));
+        assert!(mempool.contains(&tx_hash2));
         assert_eq!(mempool.len(), 2);
     }
 
+    #[test]
+    fn bytes_len() {
+        let (skey, pkey) = make_random_keys();
+        let mut mempool = Mempool::new();
+        assert_eq!(mempool.bytes_len(), 0);
+
+        let (tx1, inputs1, outputs1) =
+            PaymentTransaction::new_test(&skey, &pkey, 100, 2, 200, 1, 0)
+                .expect("transaction valid");
+        let (tx2, _inputs2, _outputs2) =
+            PaymentTransaction::new_test(&skey, &pkey, 300, 1, 100, 3, 0)
+                .expect("transaction valid");
+        let tx1: Transaction = tx1.into();
+        let tx2: Transaction = tx2.into();
+        let tx_bytes1 = tx1.into_proto().compute_size() as usize;
+        let tx_bytes2 = tx2.into_proto().compute_size() as usize;
+        let tx_hash1 = Hash::digest(&tx1);
+        let tx_hash2 = Hash::digest(&tx2);
+
+        // Rises on insert.
+        mempool.push_tx(tx_hash1.clone(), tx1);
+        assert_eq!(mempool.bytes_len(), tx_bytes1);
+        mempool.push_tx(tx_hash2.clone(), tx2);
+        assert_eq!(mempool.bytes_len(), tx_bytes1 + tx_bytes2);
+
+        // Falls on prune.
+        let input1_hashes: Vec<Hash> = inputs1.iter().map(|o| Hash::digest(o)).collect();
+        let output1_hashes: Vec<Hash> = outputs1.iter().map(|o| Hash::digest(o)).collect();
+        mempool.prune(&input1_hashes, &output1_hashes);
+        assert_eq!(mempool.bytes_len(), tx_bytes2);
+
+        // Falls on remove.
+        mempool.remove_tx(&tx_hash2).expect("transaction exists");
+        assert_eq!(mempool.bytes_len(), 0);
+    }
+
     #[test]
     pub fn partial_pruning1() {
         let (skey, pkey) = make_random_keys();
@@ -291,7 +479,7 @@ mod test {
         let tx_hash = Hash::digest(&tx);
         mempool.push_tx(tx_hash.clone(), tx.clone().into());
         mempool.prune(&vec![Hash::digest(&inputs[0])], &vec![]);
-        assert!(!mempool.contains_tx(&tx_hash));
+        assert!(!mempool.contains(&tx_hash));
         for input in inputs {
             let input_hash = Hash::digest(&input);
             assert!(!mempool.contains_input(&input_hash));
@@ -312,7 +500,7 @@ mod test {
         let tx_hash = Hash::digest(&tx);
         mempool.push_tx(tx_hash.clone(), tx.clone().into());
         mempool.prune(&vec![], &vec![Hash::digest(&outputs[0])]);
-        assert!(!mempool.contains_tx(&tx_hash));
+        assert!(!mempool.contains(&tx_hash));
         for input in inputs {
             let input_hash = Hash::digest(&input);
             assert!(!mempool.contains_input(&input_hash));
@@ -323,6 +511,87 @@ mod test {
         }
     }
 
+    #[test]
+    fn serialization() {
+        let (skey, pkey) = make_random_keys();
+        let mut mempool = Mempool::new();
+
+        let (tx1, _inputs1, _outputs1) =
+            PaymentTransaction::new_test(&skey, &pkey, 100, 2, 200, 1, 0)
+                .expect("transaction valid");
+        let (tx2, _inputs2, _outputs2) =
+            PaymentTransaction::new_test(&skey, &pkey, 300, 1, 100, 3, 0)
+                .expect("transaction valid");
+        let tx_hash1 = Hash::digest(&tx1);
+        let tx_hash2 = Hash::digest(&tx2);
+        mempool.push_tx(tx_hash1.clone(), tx1.clone().into());
+        mempool.push_tx(tx_hash2.clone(), tx2.clone().into());
+
+        let data = mempool.into_buffer().unwrap();
+        let mempool2 = Mempool::from_buffer(&data).unwrap();
+        let tx_hashes: Vec<Hash> = mempool2.iter().map(|(tx_hash, _)| *tx_hash).collect();
+        assert_eq!(tx_hashes, vec![tx_hash1, tx_hash2]);
+        assert_eq!(mempool2.bytes_len(), mempool.bytes_len());
+
+        // Conflicting transactions are rejected.
+        let mut proto = mempool.into_proto();
+        let tx1: Transaction = tx1.into();
+        proto.mut_transactions().push(tx1.into_proto());
+        assert!(Mempool::from_proto(&proto).is_err());
+    }
+
+    #[test]
+    fn disk_storage() {
+        let (skey, pkey) = make_random_keys();
+        let temp_dir = TempDir::new("mempool").expect("couldn't create temp dir");
+        let storage = MempoolStorage::Disk {
+            path: temp_dir.path().to_str().unwrap().to_string(),
+        };
+
+        let (tx1, _inputs1, _outputs1) =
+            PaymentTransaction::new_test(&skey, &pkey, 100, 2, 200, 1, 0)
+                .expect("transaction valid");
+        let (tx2, inputs2, outputs2) =
+            PaymentTransaction::new_test(&skey, &pkey, 300, 1, 100, 3, 0)
+                .expect("transaction valid");
+        let tx_hash1 = Hash::digest(&tx1);
+        let tx_hash2 = Hash::digest(&tx2);
+        let (len, inputs_len, outputs_len, bytes_len) = {
+            let mut mempool = Mempool::open(&storage);
+            mempool.push_tx(tx_hash1.clone(), tx1.into());
+            mempool.push_tx(tx_hash2.clone(), tx2.into());
+            (
+                mempool.len(),
+                mempool.inputs_len(),
+                mempool.outputs_len(),
+                mempool.bytes_len(),
+            )
+        };
+
+        // Transactions and indexes are restored after reopening.
+        let mut mempool = Mempool::open(&storage);
+        assert_eq!(mempool.len(), len);
+        assert_eq!(mempool.inputs_len(), inputs_len);
+        assert_eq!(mempool.outputs_len(), outputs_len);
+        assert_eq!(mempool.bytes_len(), bytes_len);
+        let tx_hashes: Vec<Hash> = mempool.iter().map(|(tx_hash, _)| *tx_hash).collect();
+        assert_eq!(tx_hashes, vec![tx_hash1, tx_hash2]);
+        for input in &inputs2 {
+            assert!(mempool.contains_input(&Hash::digest(input)));
+        }
+        for output in &outputs2 {
+            assert!(mempool.contains_output(&Hash::digest(output)));
+        }
+
+        // Removals are persisted too.
+        mempool.remove_tx(&tx_hash1).expect("transaction exists");
+        drop(mempool);
+        let mempool = Mempool::open(&storage);
+        assert_eq!(mempool.len(), 1);
+        assert!(!mempool.contains(&tx_hash1));
+        assert!(mempool.contains(&tx_hash2));
+    }
+
     #[test]
     fn create_block() {
         let keys = KeyChain::new_mem();
@@ -356,11 +625,13 @@ mod test {
             version,
             height,
             reward,
+            FeePolicy::RewardLeader,
             &keys,
             Hash::digest("test"),
             view_change,
             None,
             max_utxo_in_block,
+            usize::max_value(),
         );
 
         // Used transactions - tx3 is not used because of max_utxo_in_block.
@@ -388,4 +659,122 @@ mod test {
             unreachable!();
         }
     }
+
+    #[test]
+    fn create_block_dependent() {
+        let keys = KeyChain::new_mem();
+        let mut mempool = Mempool::new();
+
+        // tx2 spends the output of tx1.
+        let (tx1, _inputs1, outputs1) =
+            PaymentTransaction::new_test(&keys.wallet_skey, &keys.wallet_pkey, 3, 1, 2, 1, 1)
+                .expect("transaction valid");
+        let (output2, gamma2) = Output::new_payment(&keys.wallet_pkey, 1).expect("keys are valid");
+        let tx2 = PaymentTransaction::new(&keys.wallet_skey, &outputs1, &[output2], gamma2, 1)
+            .expect("transaction valid");
+        let tx_hash1 = Hash::digest(&tx1);
+        let tx_hash2 = Hash::digest(&tx2);
+        mempool.push_tx(tx_hash2.clone(), tx2.clone().into());
+        mempool.push_tx(tx_hash1.clone(), tx1.clone().into());
+
+        let create_block = |mempool: &mut Mempool, max_utxo_in_block: usize| {
+            mempool.create_block(
+                Hash::digest(&1u64),
+                1,
+                0,
+                10,
+                FeePolicy::RewardLeader,
+                &keys,
+                Hash::digest("test"),
+                0,
+                None,
+                max_utxo_in_block,
+                usize::max_value(),
+            )
+        };
+
+        // tx1 doesn't fit into the block - tx2 is dropped too.
+        let block = create_block(&mut mempool, 4);
+        assert_eq!(block.transactions.len(), 0);
+
+        // tx2 waits for the block with tx1.
+        let block = create_block(&mut mempool, 100);
+        assert_eq!(block.transactions.len(), 1);
+        assert_eq!(Hash::digest(&block.transactions[0]), tx_hash1);
+
+        // tx1 is committed - tx2 goes to the next block.
+        mempool.remove_tx(&tx_hash1).expect("transaction exists");
+        let block = create_block(&mut mempool, 100);
+        assert_eq!(block.transactions.len(), 1);
+        assert_eq!(Hash::digest(&block.transactions[0]), tx_hash2);
+    }
+
+    #[test]
+    fn create_block_max_bytes() {
+        let keys = KeyChain::new_mem();
+        let mut mempool = Mempool::new();
+
+        // Every payment output carries a large encrypted payload.
+        let (tx1, _inputs1, _outputs1) =
+            PaymentTransaction::new_test(&keys.wallet_skey, &keys.wallet_pkey, 6, 1, 2, 2, 2)
+                .expect("transaction valid");
+        let (tx2, _inputs2, _outputs2) =
+            PaymentTransaction::new_test(&keys.wallet_skey, &keys.wallet_pkey, 6, 1, 2, 2, 2)
+                .expect("transaction valid");
+        let (tx3, _inputs3, _outputs3) =
+            PaymentTransaction::new_test(&keys.wallet_skey, &keys.wallet_pkey, 6, 1, 2, 2, 2)
+                .expect("transaction valid");
+        let tx1: Transaction = tx1.into();
+        let tx2: Transaction = tx2.into();
+        let tx3: Transaction = tx3.into();
+        let tx_bytes1 = tx1.into_proto().compute_size() as usize;
+        let tx_bytes2 = tx2.into_proto().compute_size() as usize;
+        let tx_hash1 = Hash::digest(&tx1);
+        let tx_hash2 = Hash::digest(&tx2);
+        let tx_hash3 = Hash::digest(&tx3);
+        mempool.push_tx(tx_hash1.clone(), tx1);
+        mempool.push_tx(tx_hash2.clone(), tx2);
+        mempool.push_tx(tx_hash3.clone(), tx3);
+
+        // Only two transactions fit into the block.
+        let max_block_bytes = tx_bytes1 + tx_bytes2 + 1;
+        let block = mempool.create_block(
+            Hash::digest(&1u64),
+            1,
+            0,
+            10,
+            FeePolicy::RewardLeader,
+            &keys,
+            Hash::digest("test"),
+            0,
+            None,
+            1000,
+            max_block_bytes,
+        );
+        assert_eq!(block.transactions.len(), 2);
+        assert_eq!(Hash::digest(&block.transactions[0]), tx_hash1);
+        assert_eq!(Hash::digest(&block.transactions[1]), tx_hash2);
+        let bytes: usize = block
+            .transactions
+            .iter()
+            .map(|tx| tx.into_proto().compute_size() as usize)
+            .sum();
+        assert!(bytes <= max_block_bytes);
+
+        // A transaction larger than the limit is never included.
+        let block = mempool.create_block(
+            Hash::digest(&1u64),
+            1,
+            0,
+            10,
+            FeePolicy::RewardLeader,
+            &keys,
+            Hash::digest("test"),
+            0,
+            None,
+            1000,
+            tx_bytes1 - 1,
+        );
+        assert_eq!(block.transactions.len(), 0);
+    }
 }
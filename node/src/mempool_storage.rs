@@ -0,0 +1,275 @@
+//! Storage backends for the Memory Pool.
+
+//
+// Copyright (c) 2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use byteorder::{BigEndian, ByteOrder};
+use linked_hash_map::LinkedHashMap;
+use rocksdb::{IteratorMode, DB};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use stegos_blockchain::Transaction;
+use stegos_crypto::hash::Hash;
+use stegos_serialization::traits::ProtoConvert;
+
+/// Storage of mempool transactions.
+///
+/// Transactions are returned in the order they were inserted.
+pub trait TransactionStorage: Send {
+    /// Stores a transaction. The transaction must not exist in the storage.
+    fn insert(&mut self, tx_hash: Hash, tx: Transaction);
+
+    /// Removes a transaction and returns it, if it was found.
+    fn remove(&mut self, tx_hash: &Hash) -> Option<Transaction>;
+
+    /// Returns the transaction with the given hash.
+    fn get(&self, tx_hash: &Hash) -> Option<Transaction>;
+
+    /// Checks if the storage contains the given transaction.
+    fn contains(&self, tx_hash: &Hash) -> bool;
+
+    /// Returns hashes of all transactions in the order they were inserted.
+    fn hashes<'a>(&'a self) -> Box<dyn Iterator<Item = &'a Hash> + 'a>;
+
+    /// Returns the number of transactions in the storage.
+    fn len(&self) -> usize;
+}
+
+/// Keeps transactions in memory.
+pub struct MemoryStorage {
+    pool: LinkedHashMap<Hash, Transaction>,
+}
+
+impl MemoryStorage {
+    /// Creates an empty storage.
+    pub fn new() -> Self {
+        let pool: LinkedHashMap<Hash, Transaction> = LinkedHashMap::new();
+        MemoryStorage { pool }
+    }
+}
+
+impl TransactionStorage for MemoryStorage {
+    fn insert(&mut self, tx_hash: Hash, tx: Transaction) {
+        let exists = self.pool.insert(tx_hash, tx);
+        assert!(exists.is_none());
+    }
+
+    fn remove(&mut self, tx_hash: &Hash) -> Option<Transaction> {
+        self.pool.remove(tx_hash)
+    }
+
+    fn get(&self, tx_hash: &Hash) -> Option<Transaction> {
+        self.pool.get(tx_hash).cloned()
+    }
+
+    fn contains(&self, tx_hash: &Hash) -> bool {
+        self.pool.contains_key(tx_hash)
+    }
+
+    fn hashes<'a>(&'a self) -> Box<dyn Iterator<Item = &'a Hash> + 'a> {
+        Box::new(self.pool.keys())
+    }
+
+    fn len(&self) -> usize {
+        self.pool.len()
+    }
+}
+
+/// Keeps transactions in RocksDB, so they survive restarts.
+///
+/// Each record is keyed by the transaction hash and holds the insertion
+/// sequence number followed by the serialized transaction.
+/// Only the hashes are kept in memory.
+pub struct DiskStorage {
+    /// RocksDB database object.
+    database: DB,
+    /// Transaction hashes by insertion sequence number.
+    order: BTreeMap<u64, Hash>,
+    /// Insertion sequence numbers by transaction hash.
+    seqs: HashMap<Hash, u64>,
+    /// The next sequence number.
+    next_seq: u64,
+}
+
+impl DiskStorage {
+    /// Opens the storage at `path`, loading the list of persisted transactions.
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        let database = DB::open_default(path).expect("couldn't open mempool database");
+        let mut order: BTreeMap<u64, Hash> = BTreeMap::new();
+        let mut seqs: HashMap<Hash, u64> = HashMap::new();
+        for (key, value) in database.full_iterator(IteratorMode::Start) {
+            let tx_hash = Hash::try_from_bytes(&key).expect("couldn't deserialize tx_hash");
+            let seq = BigEndian::read_u64(&value[..8]);
+            order.insert(seq, tx_hash);
+            seqs.insert(tx_hash, seq);
+        }
+        let next_seq = order.keys().next_back().map(|seq| seq + 1).unwrap_or(0);
+        DiskStorage {
+            database,
+            order,
+            seqs,
+            next_seq,
+        }
+    }
+}
+
+impl TransactionStorage for DiskStorage {
+    fn insert(&mut self, tx_hash: Hash, tx: Transaction) {
+        assert!(!self.seqs.contains_key(&tx_hash));
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        let mut data = vec![0u8; 8];
+        BigEndian::write_u64(&mut data, seq);
+        data.extend(tx.into_buffer().expect("couldn't serialize transaction"));
+        self.database
+            .put(&tx_hash.to_bytes(), &data)
+            .expect("couldn't write to mempool database");
+        self.order.insert(seq, tx_hash);
+        self.seqs.insert(tx_hash, seq);
+    }
+
+    fn remove(&mut self, tx_hash: &Hash) -> Option<Transaction> {
+        let tx = self.get(tx_hash)?;
+        let seq = self.seqs.remove(tx_hash).expect("transaction exists");
+        self.order.remove(&seq).expect("transaction exists");
+        self.database
+            .delete(&tx_hash.to_bytes())
+            .expect("couldn't write to mempool database");
+        Some(tx)
+    }
+
+    fn get(&self, tx_hash: &Hash) -> Option<Transaction> {
+        if !self.seqs.contains_key(tx_hash) {
+            return None;
+        }
+        let data = self
+            .database
+            .get(&tx_hash.to_bytes())
+            .expect("couldn't read from mempool database")
+            .expect("transaction exists");
+        let tx = Transaction::from_buffer(&data[8..]).expect("couldn't deserialize transaction");
+        Some(tx)
+    }
+
+    fn contains(&self, tx_hash: &Hash) -> bool {
+        self.seqs.contains_key(tx_hash)
+    }
+
+    fn hashes<'a>(&'a self) -> Box<dyn Iterator<Item = &'a Hash> + 'a> {
+        Box::new(self.order.values())
+    }
+
+    fn len(&self) -> usize {
+        self.seqs.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use stegos_blockchain::PaymentTransaction;
+    use stegos_crypto::curve1174::make_random_keys;
+    use tempdir::TempDir;
+
+    fn make_transactions(n: usize) -> Vec<(Hash, Transaction)> {
+        let (skey, pkey) = make_random_keys();
+        (0..n)
+            .map(|_| {
+                let (tx, _inputs, _outputs) =
+                    PaymentTransaction::new_test(&skey, &pkey, 100, 1, 100, 1, 0)
+                        .expect("transaction valid");
+                let tx: Transaction = tx.into();
+                (Hash::digest(&tx), tx)
+            })
+            .collect()
+    }
+
+    fn check_storage(storage: &mut dyn TransactionStorage) {
+        let txs = make_transactions(3);
+        assert_eq!(storage.len(), 0);
+        for (tx_hash, tx) in &txs {
+            assert!(!storage.contains(tx_hash));
+            storage.insert(tx_hash.clone(), tx.clone());
+            assert!(storage.contains(tx_hash));
+        }
+        assert_eq!(storage.len(), 3);
+        for (tx_hash, _tx) in &txs {
+            let tx = storage.get(tx_hash).expect("transaction exists");
+            assert_eq!(&Hash::digest(&tx), tx_hash);
+        }
+
+        // Insertion order is preserved.
+        let hashes: Vec<Hash> = storage.hashes().cloned().collect();
+        let expected: Vec<Hash> = txs.iter().map(|(tx_hash, _)| tx_hash.clone()).collect();
+        assert_eq!(hashes, expected);
+
+        // Removal.
+        let tx = storage.remove(&txs[1].0).expect("transaction exists");
+        assert_eq!(Hash::digest(&tx), txs[1].0);
+        assert!(!storage.contains(&txs[1].0));
+        assert!(storage.get(&txs[1].0).is_none());
+        assert!(storage.remove(&txs[1].0).is_none());
+        assert_eq!(storage.len(), 2);
+
+        // Re-inserted transactions go to the end.
+        storage.insert(txs[1].0.clone(), txs[1].1.clone());
+        let hashes: Vec<Hash> = storage.hashes().cloned().collect();
+        assert_eq!(hashes, vec![txs[0].0, txs[2].0, txs[1].0]);
+    }
+
+    #[test]
+    fn memory() {
+        let mut storage = MemoryStorage::new();
+        check_storage(&mut storage);
+    }
+
+    #[test]
+    fn disk() {
+        let temp_dir = TempDir::new("mempool").expect("couldn't create temp dir");
+        let mut storage = DiskStorage::new(temp_dir.path());
+        check_storage(&mut storage);
+    }
+
+    #[test]
+    fn disk_persistence() {
+        let temp_dir = TempDir::new("mempool").expect("couldn't create temp dir");
+        let txs = make_transactions(3);
+        {
+            let mut storage = DiskStorage::new(temp_dir.path());
+            for (tx_hash, tx) in &txs {
+                storage.insert(tx_hash.clone(), tx.clone());
+            }
+            storage.remove(&txs[0].0).expect("transaction exists");
+        }
+
+        let mut storage = DiskStorage::new(temp_dir.path());
+        assert_eq!(storage.len(), 2);
+        let hashes: Vec<Hash> = storage.hashes().cloned().collect();
+        assert_eq!(hashes, vec![txs[1].0, txs[2].0]);
+        let tx = storage.get(&txs[2].0).expect("transaction exists");
+        assert_eq!(Hash::digest(&tx), txs[2].0);
+
+        // New transactions are appended after the persisted ones.
+        storage.insert(txs[0].0.clone(), txs[0].1.clone());
+        let hashes: Vec<Hash> = storage.hashes().cloned().collect();
+        assert_eq!(hashes, vec![txs[1].0, txs[2].0, txs[0].0]);
+    }
+}
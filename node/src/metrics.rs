@@ -49,6 +49,16 @@ lazy_static! {
         "The number of forced view_changes for the micro blocks."
     )
     .unwrap();
+    pub static ref FUTURE_CONSENSUS_MESSAGES: IntGauge = register_int_gauge!(
+        "stegos_future_consensus_messages",
+        "The number of queued consensus messages received before the consensus was started"
+    )
+    .unwrap();
+    pub static ref STALE_CONSENSUS_MESSAGES: IntCounter = register_int_counter!(
+        "stegos_stale_consensus_messages",
+        "The number of dropped consensus messages from past heights or rounds"
+    )
+    .unwrap();
     pub static ref FORKS: IntCounter = register_int_counter!(
         "stegos_forks",
         "The number of forks detected"
@@ -69,6 +79,15 @@ lazy_static! {
         register_int_gauge!("stegos_block_lag_ms", "The last block creation + validation + propagation time, i.e. it is the time difference between the local time at a remote leader when the last block began to be created and the local time at this node when this block was registered.").unwrap();
     pub static ref BLOCK_IDLE: IntGauge =
         register_int_gauge!("stegos_block_idle_ms", "The elapsed time since the last block, i.e. it is the time difference between the local time at this node and the time when the last block was registered.").unwrap();
+    pub static ref BLOCK_REWARD: IntGauge =
+        register_int_gauge!("stegos_block_reward", "The block reward of the last micro block.").unwrap();
+    pub static ref BLOCK_FEES_TOTAL: IntGauge =
+        register_int_gauge!("stegos_block_fees", "The total fee of transactions in the last block.").unwrap();
+    pub static ref BLOCK_FEES_CUMULATIVE: IntCounter = register_int_counter!(
+        "stegos_block_fees_total",
+        "The total fee of transactions in all registered blocks."
+    )
+    .unwrap();
 
     pub static ref MEMPOOL_INPUTS: IntGauge =
         register_int_gauge!("stegos_mempool_inputs", "The number of inputs in mempool.").unwrap();
@@ -76,6 +95,71 @@ lazy_static! {
         register_int_gauge!("stegos_mempool_outputs", "The number of outputs in mempool.").unwrap();
     pub static ref MEMPOOL_TRANSACTIONS: IntGauge =
         register_int_gauge!("stegos_mempool_transactions", "The number of transactions in mempool.").unwrap();
+    pub static ref MEMPOOL_BYTES: IntGauge =
+        register_int_gauge!("stegos_mempool_bytes", "The total size of transactions in mempool, in bytes.").unwrap();
+    pub static ref DUPLICATE_TRANSACTIONS: IntCounter = register_int_counter!(
+        "stegos_duplicate_transactions",
+        "The number of received transactions skipped because they are already in mempool"
+    )
+    .unwrap();
+
+    pub static ref FUTURE_BLOCKS: IntGauge =
+        register_int_gauge!("stegos_future_blocks", "The number of orphan blocks waiting in the queue.").unwrap();
+    pub static ref FUTURE_BLOCKS_MAX_HEIGHT: IntGauge =
+        register_int_gauge!("stegos_future_blocks_max_height", "The maximal height of orphan blocks waiting in the queue.").unwrap();
+    pub static ref REJECTED_BLOCK_CACHE_HITS: IntCounter = register_int_counter!(
+        "stegos_rejected_block_cache_hits",
+        "The number of received blocks skipped because they were rejected before"
+    )
+    .unwrap();
+    pub static ref BLOCK_SIGNATURE_CHECKS: IntCounter = register_int_counter!(
+        "stegos_block_signature_checks",
+        "The number of signature checks of received blocks"
+    )
+    .unwrap();
+    pub static ref VERIFIED_BLOCK_CACHE_HITS: IntCounter = register_int_counter!(
+        "stegos_verified_block_cache_hits",
+        "The number of received blocks with signatures verified before"
+    )
+    .unwrap();
+    pub static ref RATE_LIMITED_BLOCKS: IntCounter = register_int_counter!(
+        "stegos_rate_limited_blocks",
+        "The number of received blocks dropped because the producer exceeded the rate limit"
+    )
+    .unwrap();
+
+    pub static ref REWARD_VIOLATIONS: IntCounter = register_int_counter!(
+        "stegos_reward_violations",
+        "The number of accepted blocks with an unexpected reward"
+    )
+    .unwrap();
+
+    pub static ref MALFORMED_MESSAGES: IntCounterVec = register_int_counter_vec!(
+        "stegos_malformed_messages",
+        "The number of network messages which can't be decoded, by topic.",
+        &["topic"]
+    )
+    .unwrap();
+
+    pub static ref FORK_OUTCOMES: IntCounterVec = register_int_counter_vec!(
+        "stegos_fork_outcomes",
+        "The number of resolved forks by outcome.",
+        &["outcome"]
+    )
+    .unwrap();
+
+    pub static ref VALIDATOR_PARTICIPATION: IntCounterVec = register_int_counter_vec!(
+        "stegos_validator_participation",
+        "The number of committed macro blocks signed by validator.",
+        &["validator"]
+    )
+    .unwrap();
+    pub static ref VALIDATOR_MISSED_ROUNDS: IntCounterVec = register_int_counter_vec!(
+        "stegos_validator_missed_rounds",
+        "The number of committed macro blocks not signed by validator.",
+        &["validator"]
+    )
+    .unwrap();
 }
 
 pub mod vrf {
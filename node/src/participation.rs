@@ -0,0 +1,130 @@
+//! Validator Participation.
+
+//
+// Copyright (c) 2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::metrics;
+use bitvector::BitVector;
+use serde_derive::Serialize;
+use std::collections::BTreeMap;
+use stegos_crypto::pbc;
+
+/// Participation statistics of a single validator.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ValidatorParticipation {
+    pub pkey: pbc::PublicKey,
+    /// The number of committed macro blocks signed by this validator.
+    pub signed: u64,
+    /// The number of committed macro blocks missing the signature of this validator.
+    pub missed: u64,
+}
+
+/// Participation statistics of all validators seen by this node since startup.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ParticipationInfo {
+    pub validators: Vec<ValidatorParticipation>,
+}
+
+/// Tracks how often validators sign committed macro blocks.
+pub(crate) struct Participation {
+    /// (signed, missed) per validator.
+    stats: BTreeMap<pbc::PublicKey, (u64, u64)>,
+}
+
+impl Participation {
+    ///
+    /// Creates an empty tracker.
+    ///
+    pub fn new() -> Self {
+        let stats: BTreeMap<pbc::PublicKey, (u64, u64)> = BTreeMap::new();
+        Participation { stats }
+    }
+
+    ///
+    /// Registers signers of a committed macro block.
+    ///
+    /// `validators` must be the validators of the epoch the block was
+    /// committed in, in the order used to build `multisigmap`.
+    ///
+    pub fn register(&mut self, validators: &[(pbc::PublicKey, i64)], multisigmap: &BitVector) {
+        for (bit, (pkey, _slots)) in validators.iter().enumerate() {
+            let key_str = pkey.to_string();
+            let entry = self.stats.entry(*pkey).or_insert((0, 0));
+            if multisigmap.contains(bit) {
+                entry.0 += 1;
+                metrics::VALIDATOR_PARTICIPATION
+                    .with_label_values(&[key_str.as_str()])
+                    .inc();
+            } else {
+                entry.1 += 1;
+                metrics::VALIDATOR_MISSED_ROUNDS
+                    .with_label_values(&[key_str.as_str()])
+                    .inc();
+            }
+        }
+    }
+
+    ///
+    /// Returns the aggregated statistics.
+    ///
+    pub fn info(&self) -> ParticipationInfo {
+        let validators = self
+            .stats
+            .iter()
+            .map(|(pkey, (signed, missed))| ValidatorParticipation {
+                pkey: *pkey,
+                signed: *signed,
+                missed: *missed,
+            })
+            .collect();
+        ParticipationInfo { validators }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn register() {
+        let (_skey0, pkey0) = pbc::make_random_keys();
+        let (_skey1, pkey1) = pbc::make_random_keys();
+        let validators = vec![(pkey0, 1), (pkey1, 1)];
+        let mut participation = Participation::new();
+
+        let mut multisigmap = BitVector::new(validators.len());
+        multisigmap.insert(0);
+        participation.register(&validators, &multisigmap);
+        multisigmap.insert(1);
+        participation.register(&validators, &multisigmap);
+
+        let info = participation.info();
+        assert_eq!(info.validators.len(), 2);
+        for stat in info.validators {
+            if stat.pkey == pkey0 {
+                assert_eq!((stat.signed, stat.missed), (2, 0));
+            } else {
+                assert_eq!(stat.pkey, pkey1);
+                assert_eq!((stat.signed, stat.missed), (1, 1));
+            }
+        }
+    }
+}
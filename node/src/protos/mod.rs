@@ -27,8 +27,11 @@ use stegos_blockchain::protos::*;
 include!(concat!(env!("OUT_DIR"), "/protos/mod.rs"));
 
 use crate::loader::{ChainLoaderMessage, RequestBlocks, ResponseBlocks};
+use crate::mempool::Mempool;
 use failure::{format_err, Error};
 use protobuf::RepeatedField;
+use stegos_blockchain::Transaction;
+use stegos_crypto::hash::Hash;
 
 impl ProtoConvert for RequestBlocks {
     type Proto = loader::RequestBlocks;
@@ -48,6 +51,7 @@ impl ProtoConvert for ResponseBlocks {
     fn into_proto(&self) -> Self::Proto {
         let mut proto = loader::ResponseBlocks::new();
         proto.set_height(self.height);
+        proto.set_more(self.more);
         let blocks: Vec<_> = self.blocks.iter().map(ProtoConvert::into_proto).collect();
         proto.set_blocks(RepeatedField::from_vec(blocks));
         proto
@@ -60,7 +64,12 @@ impl ProtoConvert for ResponseBlocks {
             .map(ProtoConvert::from_proto)
             .collect();
         let blocks = blocks?;
-        Ok(ResponseBlocks { height, blocks })
+        let more = proto.get_more();
+        Ok(ResponseBlocks {
+            height,
+            blocks,
+            more,
+        })
     }
 }
 
@@ -91,6 +100,31 @@ impl ProtoConvert for ChainLoaderMessage {
     }
 }
 
+impl ProtoConvert for Mempool {
+    type Proto = mempool::Mempool;
+    fn into_proto(&self) -> Self::Proto {
+        let mut proto = mempool::Mempool::new();
+        let transactions: Vec<_> = self.iter().map(|(_, tx)| tx.into_proto()).collect();
+        proto.set_transactions(RepeatedField::from_vec(transactions));
+        proto
+    }
+    fn from_proto(proto: &Self::Proto) -> Result<Self, Error> {
+        let mut mempool = Mempool::new();
+        for tx in proto.get_transactions() {
+            let tx = Transaction::from_proto(tx)?;
+            let tx_hash = Hash::digest(&tx);
+            if mempool.contains(&tx_hash) || !mempool.conflicts(&tx).is_empty() {
+                return Err(format_err!(
+                    "Conflicting transaction in mempool: tx={}",
+                    tx_hash
+                ));
+            }
+            mempool.push_tx(tx_hash, tx);
+        }
+        Ok(mempool)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,182 @@
+//! Rate limiter of incoming messages.
+
+//
+// Copyright (c) 2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use linked_hash_map::LinkedHashMap;
+use std::cmp;
+use std::time::Instant;
+use stegos_crypto::pbc;
+
+/// The maximal number of tracked peers.
+pub(crate) const MAX_RATE_LIMITED_PEERS: usize = 1000;
+
+struct Bucket {
+    /// Available tokens.
+    tokens: f64,
+    /// The last time when tokens were added.
+    updated: Instant,
+}
+
+///
+/// A token bucket rate limiter keyed by a network key.
+/// Each peer can send up to `burst` messages at once and then `rate` messages per second.
+/// The least recently seen peers are forgotten when the limiter is full.
+///
+pub(crate) struct RateLimiter {
+    /// Messages per second, zero means unlimited.
+    rate: u32,
+    /// The maximal number of tokens in a bucket.
+    burst: u32,
+    /// Maximal number of tracked peers.
+    capacity: usize,
+    /// Buckets in the LRU order.
+    buckets: LinkedHashMap<pbc::PublicKey, Bucket>,
+}
+
+impl RateLimiter {
+    ///
+    /// Creates a new rate limiter.
+    ///
+    pub fn new(rate: u32, burst: u32, capacity: usize) -> Self {
+        assert!(capacity > 0);
+        let burst = cmp::max(burst, 1);
+        let buckets: LinkedHashMap<pbc::PublicKey, Bucket> = LinkedHashMap::new();
+        RateLimiter {
+            rate,
+            burst,
+            capacity,
+            buckets,
+        }
+    }
+
+    ///
+    /// Takes a token from the bucket of `peer`.
+    /// Returns false if the peer has exceeded the rate.
+    ///
+    pub fn check(&mut self, peer: &pbc::PublicKey, now: Instant) -> bool {
+        if self.rate == 0 {
+            return true;
+        }
+        if !self.buckets.contains_key(peer) {
+            if self.buckets.len() >= self.capacity {
+                self.buckets.pop_front();
+            }
+            let bucket = Bucket {
+                tokens: self.burst as f64,
+                updated: now,
+            };
+            self.buckets.insert(*peer, bucket);
+        }
+        let bucket = self.buckets.get_refresh(peer).unwrap();
+        Self::refill(bucket, self.rate, self.burst, now);
+        if bucket.tokens < 1.0 {
+            return false;
+        }
+        bucket.tokens -= 1.0;
+        true
+    }
+
+    ///
+    /// Returns false if the peer has exceeded the rate, without taking a token.
+    ///
+    pub fn is_allowed(&mut self, peer: &pbc::PublicKey, now: Instant) -> bool {
+        if self.rate == 0 {
+            return true;
+        }
+        match self.buckets.get_mut(peer) {
+            Some(bucket) => {
+                Self::refill(bucket, self.rate, self.burst, now);
+                bucket.tokens >= 1.0
+            }
+            None => true,
+        }
+    }
+
+    fn refill(bucket: &mut Bucket, rate: u32, burst: u32, now: Instant) {
+        if now > bucket.updated {
+            let elapsed = now.duration_since(bucket.updated);
+            let elapsed = elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 * 1e-9;
+            bucket.tokens = (bucket.tokens + elapsed * rate as f64).min(burst as f64);
+            bucket.updated = now;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn token_bucket() {
+        let peer1 = pbc::make_random_keys().1;
+        let peer2 = pbc::make_random_keys().1;
+        let mut limiter = RateLimiter::new(1, 2, 10);
+        let now = Instant::now();
+
+        // Burst.
+        assert!(limiter.check(&peer1, now));
+        assert!(limiter.check(&peer1, now));
+        assert!(!limiter.check(&peer1, now));
+
+        // Peers are limited independently.
+        assert!(!limiter.is_allowed(&peer1, now));
+        assert!(limiter.is_allowed(&peer2, now));
+        assert!(limiter.check(&peer2, now));
+
+        // Tokens are refilled with the rate.
+        assert!(!limiter.check(&peer1, now + Duration::from_millis(500)));
+        assert!(limiter.check(&peer1, now + Duration::from_millis(1000)));
+        assert!(!limiter.check(&peer1, now + Duration::from_millis(1000)));
+
+        // But never above the burst.
+        let later = now + Duration::from_secs(60);
+        assert!(limiter.check(&peer1, later));
+        assert!(limiter.check(&peer1, later));
+        assert!(!limiter.check(&peer1, later));
+    }
+
+    #[test]
+    fn unlimited() {
+        let peer = pbc::make_random_keys().1;
+        let mut limiter = RateLimiter::new(0, 1, 10);
+        let now = Instant::now();
+        for _ in 0..100 {
+            assert!(limiter.check(&peer, now));
+        }
+    }
+
+    #[test]
+    fn bounded() {
+        let peer1 = pbc::make_random_keys().1;
+        let peer2 = pbc::make_random_keys().1;
+        let mut limiter = RateLimiter::new(1, 1, 1);
+        let now = Instant::now();
+
+        assert!(limiter.check(&peer1, now));
+        assert!(!limiter.check(&peer1, now));
+
+        // The first peer is forgotten and gets a full bucket again.
+        assert!(limiter.check(&peer2, now));
+        assert!(limiter.check(&peer1, now));
+    }
+}
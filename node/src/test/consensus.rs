@@ -24,7 +24,7 @@
 use super::*;
 use crate::*;
 use stegos_blockchain::Block;
-use stegos_consensus::ConsensusMessageBody;
+use stegos_consensus::{ConsensusMessage, ConsensusMessageBody};
 use stegos_crypto::pbc;
 
 #[test]
@@ -594,3 +594,206 @@ fn out_of_order_micro_block() {
             .filter_broadcast(&[crate::CONSENSUS_TOPIC]);
     });
 }
+
+#[test]
+fn stale_message() {
+    let mut cfg: ChainConfig = Default::default();
+    cfg.blocks_in_epoch = 1;
+    let config = SandboxConfig {
+        chain: cfg,
+        num_nodes: 3,
+        ..Default::default()
+    };
+
+    Sandbox::start(config, |mut s| {
+        let topic = crate::CONSENSUS_TOPIC;
+        s.poll();
+
+        let height = s.nodes[0].node_service.chain.height();
+        let leader_pk = s.nodes[0].node_service.chain.leader();
+        let leader_node = s.node(&leader_pk).unwrap();
+        let proposal: BlockConsensusMessage = leader_node.network_service.get_broadcast(topic);
+        assert_matches!(proposal.body, ConsensusMessageBody::Proposal { .. });
+
+        // Send this proposal to other nodes.
+        for node in s.iter_except(&[leader_pk]) {
+            node.network_service
+                .receive_broadcast(topic, proposal.clone());
+        }
+        s.poll();
+
+        let mut prevotes: Vec<BlockConsensusMessage> = Vec::with_capacity(s.num_nodes());
+        for node in s.nodes.iter_mut() {
+            let prevote: BlockConsensusMessage = node.network_service.get_broadcast(topic);
+            assert_matches!(prevote.body, ConsensusMessageBody::Prevote { .. });
+            prevotes.push(prevote);
+        }
+
+        // A pre-vote from the past height is dropped without validation,
+        // even though its signature doesn't match anymore.
+        let mut stale = prevotes[1].clone();
+        assert_eq!(stale.height, height);
+        stale.height = height - 1;
+        let stale_messages = metrics::STALE_CONSENSUS_MESSAGES.get();
+        s.nodes[0]
+            .node_service
+            .handle_consensus_message(stale)
+            .expect("stale message is ignored");
+        assert!(metrics::STALE_CONSENSUS_MESSAGES.get() > stale_messages);
+        s.nodes[0].network_service.assert_empty_queue();
+    });
+}
+
+#[test]
+fn stale_round_precommit() {
+    let mut cfg: ChainConfig = Default::default();
+    cfg.blocks_in_epoch = 1;
+    let config = SandboxConfig {
+        chain: cfg,
+        num_nodes: 3,
+        ..Default::default()
+    };
+
+    Sandbox::start(config, |mut s| {
+        let topic = crate::CONSENSUS_TOPIC;
+        s.poll();
+
+        let leader_pk = s.nodes[0].node_service.chain.leader();
+        let leader_node = s.node(&leader_pk).unwrap();
+        let proposal: BlockConsensusMessage = leader_node.network_service.get_broadcast(topic);
+        assert_matches!(proposal.body, ConsensusMessageBody::Proposal { .. });
+
+        // Send this proposal to other nodes.
+        for node in s.iter_except(&[leader_pk]) {
+            node.network_service
+                .receive_broadcast(topic, proposal.clone());
+        }
+        s.poll();
+
+        // Send pre-votes to nodes.
+        for i in 0..s.num_nodes() {
+            let prevote: BlockConsensusMessage = s.nodes[i].network_service.get_broadcast(topic);
+            assert_matches!(prevote.body, ConsensusMessageBody::Prevote { .. });
+            for j in 0..s.num_nodes() {
+                if i != j {
+                    s.nodes[j]
+                        .network_service
+                        .receive_broadcast(topic, prevote.clone());
+                }
+            }
+        }
+        s.poll();
+
+        let mut precommits: Vec<BlockConsensusMessage> = Vec::with_capacity(s.num_nodes());
+        for node in s.nodes.iter_mut() {
+            let precommit: BlockConsensusMessage = node.network_service.get_broadcast(topic);
+            assert_matches!(precommit.body, ConsensusMessageBody::Precommit { .. });
+            precommits.push(precommit);
+        }
+
+        // Pick a non-leader to receive votes, it never commits on its own.
+        let receiver = (0..s.num_nodes())
+            .find(|i| s.nodes[*i].node_service.keys.network_pkey != leader_pk)
+            .unwrap();
+        let sender = (receiver + 1) % s.num_nodes();
+
+        // The receiver moves to the next round before getting the pre-commit.
+        let node = &mut s.nodes[receiver];
+        let consensus = node.node_service.consensus.as_mut().unwrap();
+        consensus.next_round();
+        let round = consensus.round();
+        assert_eq!(precommits[sender].round + 1, round);
+
+        // A valid pre-commit from the previous round is ignored.
+        let stale_messages = metrics::STALE_CONSENSUS_MESSAGES.get();
+        node.node_service
+            .handle_consensus_message(precommits[sender].clone())
+            .expect("stale pre-commit is ignored");
+        assert!(metrics::STALE_CONSENSUS_MESSAGES.get() > stale_messages);
+        let consensus = node.node_service.consensus.as_ref().unwrap();
+        assert_eq!(consensus.round(), round);
+        assert!(!consensus.should_commit());
+        node.network_service.assert_empty_queue();
+    });
+}
+
+#[test]
+fn equivocation() {
+    let mut cfg: ChainConfig = Default::default();
+    cfg.blocks_in_epoch = 1;
+    let config = SandboxConfig {
+        chain: cfg,
+        num_nodes: 3,
+        ..Default::default()
+    };
+
+    Sandbox::start(config, |mut s| {
+        let topic = crate::CONSENSUS_TOPIC;
+        s.poll();
+
+        let leader_pk = s.nodes[0].node_service.chain.leader();
+        let leader_node = s.node(&leader_pk).unwrap();
+        let proposal: BlockConsensusMessage = leader_node.network_service.get_broadcast(topic);
+        assert_matches!(proposal.body, ConsensusMessageBody::Proposal { .. });
+
+        // Send this proposal to other nodes.
+        for node in s.iter_except(&[leader_pk]) {
+            node.network_service
+                .receive_broadcast(topic, proposal.clone());
+        }
+        s.poll();
+
+        // Send pre-votes to nodes.
+        for i in 0..s.num_nodes() {
+            let prevote: BlockConsensusMessage = s.nodes[i].network_service.get_broadcast(topic);
+            assert_matches!(prevote.body, ConsensusMessageBody::Prevote { .. });
+            for j in 0..s.num_nodes() {
+                if i != j {
+                    s.nodes[j]
+                        .network_service
+                        .receive_broadcast(topic, prevote.clone());
+                }
+            }
+        }
+        s.poll();
+
+        let mut precommits: Vec<BlockConsensusMessage> = Vec::with_capacity(s.num_nodes());
+        for node in s.nodes.iter_mut() {
+            let precommit: BlockConsensusMessage = node.network_service.get_broadcast(topic);
+            assert_matches!(precommit.body, ConsensusMessageBody::Precommit { .. });
+            precommits.push(precommit);
+        }
+
+        // Pick a non-leader to receive votes, it never commits on its own.
+        let receiver = (0..s.num_nodes())
+            .find(|i| s.nodes[*i].node_service.keys.network_pkey != leader_pk)
+            .unwrap();
+        let sender = (receiver + 1) % s.num_nodes();
+
+        // The sender pre-commits for two different requests.
+        let precommit = precommits[sender].clone();
+        let skey = s.nodes[sender].node_service.keys.network_skey.clone();
+        let pkey = s.nodes[sender].node_service.keys.network_pkey;
+        let request_hash = Hash::digest("conflicting request");
+        let request_hash_sig = pbc::sign_hash(&request_hash, &skey);
+        let conflicting: BlockConsensusMessage = ConsensusMessage::new(
+            precommit.height,
+            precommit.round,
+            request_hash,
+            &skey,
+            &pkey,
+            ConsensusMessageBody::Precommit { request_hash_sig },
+        );
+
+        let equivocations = stegos_consensus::metrics::EQUIVOCATIONS.get();
+        let node = &mut s.nodes[receiver];
+        node.node_service
+            .handle_consensus_message(precommit)
+            .expect("valid pre-commit");
+        node.node_service
+            .handle_consensus_message(conflicting)
+            .expect("conflicting pre-commit is ignored");
+        assert!(stegos_consensus::metrics::EQUIVOCATIONS.get() > equivocations);
+        node.network_service.assert_empty_queue();
+    });
+}
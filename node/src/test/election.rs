@@ -0,0 +1,127 @@
+//
+// MIT License
+//
+// Copyright (c) 2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use super::*;
+use crate::*;
+use std::collections::HashMap;
+use std::time::SystemTime;
+use stegos_blockchain::*;
+
+/// Creates a blockchain with one validator per stake.
+fn chain_with_stakes(cfg: &ChainConfig, stakes: &[i64]) -> (Vec<KeyChain>, Blockchain) {
+    let keychains: Vec<KeyChain> = stakes.iter().map(|_| KeyChain::new_mem()).collect();
+    let total_stake: i64 = stakes.iter().sum();
+    let timestamp = SystemTime::now();
+    let genesis = genesis_with_stakes(&keychains, stakes, 1000 * total_stake, timestamp);
+    let chain = Blockchain::testing(cfg.clone().into(), genesis, timestamp)
+        .expect("Failed to create blockchain");
+    (keychains, chain)
+}
+
+/// Counts how many times each validator is selected as the leader over `view_changes`.
+fn leader_distribution(chain: &Blockchain, view_changes: u32) -> HashMap<pbc::PublicKey, u32> {
+    let mut leaders: HashMap<pbc::PublicKey, u32> = HashMap::new();
+    for leader in chain.simulate_leaders(view_changes) {
+        *leaders.entry(leader).or_insert(0) += 1;
+    }
+    leaders
+}
+
+/// Checks that leaders are selected proportionally to the stakes.
+fn assert_stake_weighted(stakes: &[i64]) {
+    const VIEW_CHANGES: u32 = 10000;
+    let cfg: ChainConfig = Default::default();
+    let (keychains, chain) = chain_with_stakes(&cfg, stakes);
+    let total_stake: i64 = stakes.iter().sum();
+    let slots: HashMap<pbc::PublicKey, i64> = chain.validators().iter().cloned().collect();
+    let total_slots: i64 = slots.values().sum();
+    assert_eq!(total_slots, cfg.max_slot_count);
+
+    let leaders = leader_distribution(&chain, VIEW_CHANGES);
+    assert_eq!(leaders.len(), stakes.len(), "each validator leads");
+    for (keys, stake) in keychains.iter().zip(stakes.iter()) {
+        let pkey = &keys.network_pkey;
+        let observed = leaders[pkey] as f64 / VIEW_CHANGES as f64;
+        // Leaders are selected proportionally to the slots.
+        let expected = slots[pkey] as f64 / total_slots as f64;
+        assert!(
+            (observed - expected).abs() < 0.03,
+            "observed={}, expected={}",
+            observed,
+            expected
+        );
+        // Slots are allocated proportionally to the stakes.
+        let expected = *stake as f64 / total_stake as f64;
+        assert!(
+            (observed - expected).abs() < 0.07,
+            "observed={}, expected={}",
+            observed,
+            expected
+        );
+    }
+}
+
+#[test]
+fn equal_stakes() {
+    let cfg: ChainConfig = Default::default();
+    let stakes = vec![cfg.min_stake_amount; 4];
+    assert_stake_weighted(&stakes);
+}
+
+#[test]
+fn weighted_stakes() {
+    let cfg: ChainConfig = Default::default();
+    let stakes: Vec<i64> = (1..5).map(|i| i * cfg.min_stake_amount).collect();
+    assert_stake_weighted(&stakes);
+}
+
+#[test]
+fn proportional_stakes() {
+    let cfg: ChainConfig = Default::default();
+    let stakes: Vec<i64> = vec![1, 2, 3]
+        .into_iter()
+        .map(|i| i * cfg.min_stake_amount)
+        .collect();
+    assert_stake_weighted(&stakes);
+}
+
+#[test]
+fn simulate_leaders() {
+    let cfg: ChainConfig = Default::default();
+    let stakes: Vec<i64> = vec![1, 2, 3]
+        .into_iter()
+        .map(|i| i * cfg.min_stake_amount)
+        .collect();
+    let (_keychains, chain) = chain_with_stakes(&cfg, &stakes);
+    let view_change = chain.view_change();
+    let leaders = chain.simulate_leaders(100);
+    assert_eq!(leaders.len(), 100);
+    for (i, leader) in leaders.iter().enumerate() {
+        assert_eq!(leader, &chain.select_leader(view_change + i as u32));
+    }
+    assert_eq!(leaders[0], chain.leader());
+
+    // Deterministic and read-only.
+    assert_eq!(chain.simulate_leaders(100), leaders);
+    assert_eq!(chain.view_change(), view_change);
+}
@@ -100,6 +100,171 @@ fn dead_leader() {
     });
 }
 
+// CASE view change with tx_wait_timeout:
+// Nodes [A, B, C, D]
+//
+// 1. Node A leader of view_change 1, didn't broadcast micro block (B1) to [B,C,D]
+// 2. Nodes [B, C, D] receive 2/3rd of view_change messages.
+//
+// Asserts that the leader of view_change 1 waits for view_change_tx_wait_timeout
+// before proposing a new micro block.
+#[test]
+fn view_change_tx_wait_timeout() {
+    let mut cfg: ChainConfig = Default::default();
+    cfg.blocks_in_epoch = 2000;
+    cfg.view_change_tx_wait_timeout = Duration::from_secs(2);
+    let config = SandboxConfig {
+        num_nodes: 4,
+        chain: cfg,
+        ..Default::default()
+    };
+
+    Sandbox::start(config, |mut s| {
+        s.poll();
+
+        let leader_pk = s.nodes[0].node_service.chain.leader();
+        // let leader shoot his block
+        s.wait(s.cfg().tx_wait_timeout);
+        s.poll();
+        // emulate timeout on other nodes, and wait for request
+        s.wait(s.cfg().micro_block_timeout);
+        s.poll();
+        let view_change_tx_wait_timeout = s.cfg().view_change_tx_wait_timeout;
+        let mut r = s.split(&[leader_pk]);
+        r.parts
+            .1
+            .filter_unicast(&[crate::loader::CHAIN_LOADER_TOPIC]);
+
+        let mut msgs = Vec::new();
+        for node in &mut r.parts.1.nodes {
+            let msg: ViewChangeMessage = node.network_service.get_broadcast(VIEW_CHANGE_TOPIC);
+            msgs.push(msg);
+        }
+        for node in r.parts.1.iter_mut() {
+            for msg in &msgs {
+                node.network_service
+                    .receive_broadcast(crate::VIEW_CHANGE_TOPIC, msg.clone())
+            }
+        }
+
+        let next_leader = r.parts.1.next_view_change_leader();
+        r.parts.1.poll();
+        for node in r.parts.1.iter_mut() {
+            assert_eq!(node.node_service.chain.view_change(), 1);
+        }
+
+        let leader = r
+            .parts
+            .1
+            .iter_mut()
+            .find(|node| node.node_service.keys.network_pkey == next_leader)
+            .unwrap();
+        // The leader doesn't propose the block immediately.
+        let now = tokio_timer::clock::now();
+        let deadline = leader.node_service.micro_block_propose_deadline();
+        assert!(deadline > now);
+
+        r.wait(view_change_tx_wait_timeout);
+        let leader = r
+            .parts
+            .1
+            .iter_mut()
+            .find(|node| node.node_service.keys.network_pkey == next_leader)
+            .unwrap();
+        leader.poll();
+        let block: Block = leader.network_service.get_broadcast(SEALED_BLOCK_TOPIC);
+        assert_eq!(block.base_header().view_change, 1);
+        assert_eq!(leader.node_service.chain.view_change(), 0);
+
+        let first_leader = r.parts.0.first_mut();
+        first_leader
+            .network_service
+            .filter_broadcast(&[crate::VIEW_CHANGE_TOPIC, crate::SEALED_BLOCK_TOPIC]);
+        first_leader
+            .network_service
+            .filter_unicast(&[crate::loader::CHAIN_LOADER_TOPIC]);
+    });
+}
+
+// CASE view change proof request:
+// Nodes [A, B, C, D]
+//
+// 1. Node A leader of view_change 1, didn't broadcast micro block (B1) to [B,C,D]
+// 2. Nodes [B, C, D] receive 2/3rd of view_change messages.
+//
+// Asserts that non-leaders among [B, C, D] return the proof of the view change.
+#[test]
+fn view_change_proof_request() {
+    let mut cfg: ChainConfig = Default::default();
+    cfg.blocks_in_epoch = 2000;
+    let config = SandboxConfig {
+        num_nodes: 4,
+        chain: cfg,
+        ..Default::default()
+    };
+
+    Sandbox::start(config, |mut s| {
+        s.poll();
+
+        for node in s.nodes.iter_mut() {
+            let info = node.node_service.view_change_proof_info();
+            assert_eq!(info.view_change, 0);
+            assert!(info.proof.is_none());
+        }
+
+        let leader_pk = s.nodes[0].node_service.chain.leader();
+        // let leader shoot his block
+        s.wait(s.cfg().tx_wait_timeout);
+        s.poll();
+        // emulate timeout on other nodes, and wait for request
+        s.wait(s.cfg().micro_block_timeout);
+        s.poll();
+        let mut r = s.split(&[leader_pk]);
+        r.parts
+            .1
+            .filter_unicast(&[crate::loader::CHAIN_LOADER_TOPIC]);
+
+        let mut msgs = Vec::new();
+        for node in &mut r.parts.1.nodes {
+            let msg: ViewChangeMessage = node.network_service.get_broadcast(VIEW_CHANGE_TOPIC);
+            msgs.push(msg);
+        }
+        for node in r.parts.1.iter_mut() {
+            for msg in &msgs {
+                node.network_service
+                    .receive_broadcast(crate::VIEW_CHANGE_TOPIC, msg.clone())
+            }
+        }
+
+        let next_leader = r.parts.1.next_view_change_leader();
+        r.parts.1.poll();
+        for node in r.parts.1.iter_mut() {
+            if next_leader == node.node_service.keys.network_pkey {
+                let _: Block = node.network_service.get_broadcast(SEALED_BLOCK_TOPIC);
+                continue;
+            }
+            let info = node.node_service.view_change_proof_info();
+            assert_eq!(info.height, node.node_service.chain.height());
+            assert_eq!(info.view_change, 1);
+            let proof = info.proof.expect("view change proof");
+            assert_eq!(proof.chain.height, info.height);
+            assert_eq!(proof.chain.view_change, 0);
+            assert_eq!(
+                proof.chain.last_block,
+                node.node_service.chain.last_block_hash()
+            );
+        }
+
+        let first_leader = r.parts.0.first_mut();
+        first_leader
+            .network_service
+            .filter_broadcast(&[crate::VIEW_CHANGE_TOPIC, crate::SEALED_BLOCK_TOPIC]);
+        first_leader
+            .network_service
+            .filter_unicast(&[crate::loader::CHAIN_LOADER_TOPIC]);
+    });
+}
+
 // CASE partition:
 // Nodes [A, B, C, D]
 //
@@ -485,6 +650,7 @@ fn resolve_fork_without_block() {
         let first_block: Block = leader
             .network_service
             .get_broadcast(crate::SEALED_BLOCK_TOPIC);
+        let first_block_hash = Hash::digest(&first_block);
 
         s.wait(s.cfg().micro_block_timeout);
         s.poll();
@@ -551,6 +717,11 @@ fn resolve_fork_without_block() {
             .filter_broadcast(&[crate::SEALED_BLOCK_TOPIC]);
 
         first_leader.poll();
+        let (tx, mut reorg_rx) = unbounded();
+        first_leader
+            .node_service
+            .handle_subscribe_reorg(tx)
+            .unwrap();
         // unicast view_change proof to old leader.
         first_leader
             .network_service
@@ -568,6 +739,19 @@ fn resolve_fork_without_block() {
             starting_view_changes + 1
         );
         assert_eq!(first_leader.node_service.chain.height(), starting_height);
+
+        // assert reorg notification
+        let reorg = (&mut reorg_rx).wait().next().unwrap().unwrap();
+        assert!(reorg.from_height > starting_height);
+        assert_eq!(reorg.to_height, starting_height);
+        assert_eq!(
+            reorg.dropped_block_hashes.len() as u64,
+            reorg.from_height - reorg.to_height
+        );
+        assert_eq!(
+            reorg.dropped_block_hashes.last().unwrap(),
+            &first_block_hash
+        );
     });
 }
 
@@ -693,6 +877,102 @@ fn micro_block_without_signature() {
     });
 }
 
+// CASE orphan block relayed by another node:
+// Nodes [A, B, C, D]
+//
+// 1. Node A is the leader of height 1, and broadcasts B1 to all nodes except C.
+// 2. Node B is the leader of height 2, and broadcasts B2.
+// 3. Node C receives B2 relayed by node D.
+//
+// Asserts that C attributes B2 to D and requests the history from D.
+
+#[test]
+fn sealed_block_sender() {
+    let mut cfg: ChainConfig = Default::default();
+    cfg.blocks_in_epoch = 2000;
+    let config = SandboxConfig {
+        num_nodes: 4,
+        chain: cfg,
+        ..Default::default()
+    };
+
+    Sandbox::start(config, |mut s| {
+        s.poll();
+        let starting_height = s.nodes[0].node_service.chain.height();
+        let max_loader_timeout = s.cfg().max_loader_timeout;
+
+        let leader_pk = s.nodes[0].node_service.chain.leader();
+        s.wait(s.cfg().tx_wait_timeout);
+        s.poll();
+
+        let leader = s.node(&leader_pk).unwrap();
+        let first_block: Block = leader
+            .network_service
+            .get_broadcast(crate::SEALED_BLOCK_TOPIC);
+        let new_leader_pk = leader.node_service.chain.leader();
+
+        let keys: Vec<pbc::PublicKey> = s
+            .nodes
+            .iter()
+            .map(|node| node.node_service.keys.network_pkey)
+            .collect();
+        let lagging_pk = *keys
+            .iter()
+            .find(|pk| **pk != leader_pk && **pk != new_leader_pk)
+            .unwrap();
+        let sender_pk = *keys
+            .iter()
+            .find(|pk| **pk != new_leader_pk && **pk != lagging_pk)
+            .unwrap();
+
+        info!("======= BROADCAST FIRST BLOCK =======");
+        for node in s.iter_except(&[leader_pk, lagging_pk]) {
+            node.network_service
+                .receive_broadcast(crate::SEALED_BLOCK_TOPIC, first_block.clone());
+            node.poll();
+        }
+
+        s.wait(s.cfg().tx_wait_timeout);
+        s.poll();
+        let new_leader = s.node(&new_leader_pk).unwrap();
+        let second_block: Block = new_leader
+            .network_service
+            .get_broadcast(crate::SEALED_BLOCK_TOPIC);
+        assert_eq!(second_block.base_header().height, starting_height + 1);
+
+        info!("======= RELAY SECOND BLOCK =======");
+        let node = s.node(&lagging_pk).unwrap();
+        node.network_service
+            .filter_unicast(&[crate::loader::CHAIN_LOADER_TOPIC]);
+        node.node_service.last_sync_clock = tokio_timer::clock::now() - max_loader_timeout;
+        node.network_service.receive_broadcast_from(
+            sender_pk,
+            crate::SEALED_BLOCK_TOPIC,
+            second_block,
+        );
+        node.poll();
+        assert_eq!(node.node_service.chain.height(), starting_height);
+        let (from, _block) = node
+            .node_service
+            .future_blocks
+            .get(&(starting_height + 1))
+            .expect("orphan is queued");
+        assert_eq!(*from, sender_pk);
+        let _msg: ChainLoaderMessage = node
+            .network_service
+            .get_unicast(crate::loader::CHAIN_LOADER_TOPIC, &sender_pk);
+
+        // The missing block unlocks the queue.
+        node.network_service
+            .receive_broadcast(crate::SEALED_BLOCK_TOPIC, first_block);
+        node.poll();
+        assert_eq!(node.node_service.chain.height(), starting_height + 2);
+
+        s.filter_unicast(&[crate::loader::CHAIN_LOADER_TOPIC]);
+        s.filter_broadcast(&[crate::SEALED_BLOCK_TOPIC]);
+    });
+}
+
 fn precondition_2_different_leaderers(s: &mut Sandbox) {
     let mut ready = false;
     for _ in 0..(s.cfg().blocks_in_epoch - 2) {
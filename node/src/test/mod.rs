@@ -26,6 +26,7 @@ pub mod time;
 pub use stegos_network::loopback::Loopback;
 use time::{start_test, wait, TestTimer};
 mod consensus;
+mod election;
 mod microblocks;
 mod requests;
 use crate::*;
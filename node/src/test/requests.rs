@@ -64,3 +64,64 @@ fn request_on_timeout() {
             .filter_broadcast(&[crate::SEALED_BLOCK_TOPIC]);
     });
 }
+
+#[test]
+fn malformed_messages() {
+    let config = SandboxConfig {
+        num_nodes: 3,
+        ..Default::default()
+    };
+
+    Sandbox::start(config, |mut s| {
+        s.poll();
+        let peer = s.nodes[1].node_service.keys.network_pkey;
+        let node = s.first_mut();
+        // An overlong varint, which can't be decoded on any topic.
+        let garbage = vec![0xFFu8; 16];
+
+        let broadcast_topics = [
+            crate::TX_TOPIC,
+            crate::CONSENSUS_TOPIC,
+            crate::VIEW_CHANGE_TOPIC,
+            crate::SEALED_BLOCK_TOPIC,
+        ];
+        for &topic in &broadcast_topics {
+            let malformed = metrics::MALFORMED_MESSAGES.with_label_values(&[topic]);
+            let before = malformed.get();
+            node.network_service
+                .receive_broadcast_raw(topic, garbage.clone());
+            node.poll();
+            assert!(malformed.get() > before, "topic={}", topic);
+        }
+
+        let unicast_topics = [crate::VIEW_CHANGE_DIRECT, crate::loader::CHAIN_LOADER_TOPIC];
+        for &topic in &unicast_topics {
+            let malformed = metrics::MALFORMED_MESSAGES.with_label_values(&[topic]);
+            let before = malformed.get();
+            node.network_service
+                .receive_unicast_raw(peer, topic, garbage.clone());
+            node.poll();
+            assert!(malformed.get() > before, "topic={}", topic);
+        }
+
+        // Errors carry the topic and the sender.
+        let e = decode_message::<Transaction>(crate::TX_TOPIC, None, &garbage).unwrap_err();
+        match e.downcast::<NodeMessageError>().unwrap() {
+            NodeMessageError::Malformed(topic, _e) => assert_eq!(topic, crate::TX_TOPIC),
+            e => panic!("Unexpected error: {}", e),
+        }
+        let e = decode_message::<ChainLoaderMessage>(
+            crate::loader::CHAIN_LOADER_TOPIC,
+            Some(peer),
+            &garbage,
+        )
+        .unwrap_err();
+        match e.downcast::<NodeMessageError>().unwrap() {
+            NodeMessageError::MalformedFrom(topic, from, _e) => {
+                assert_eq!(topic, crate::loader::CHAIN_LOADER_TOPIC);
+                assert_eq!(from, peer);
+            }
+            e => panic!("Unexpected error: {}", e),
+        }
+    });
+}
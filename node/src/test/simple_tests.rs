@@ -21,8 +21,13 @@
 
 use super::Loopback;
 use crate::*;
+use futures::future;
 use std::time::SystemTime;
 use stegos_blockchain::*;
+use stegos_consensus::{
+    ConsensusError, ConsensusMessage, ConsensusMessageBody, CONSENSUS_PROTOCOL_VERSION,
+};
+use stegos_crypto::curve1174::{self, Fr};
 
 #[test]
 pub fn init() {
@@ -53,11 +58,35 @@ pub fn init() {
     );
 }
 
+/// Creates a node for the first of `keys` on a new chain, where all of `keys` are validators.
+fn new_node_with_validators(cfg: ChainConfig, keys: &[KeyChain]) -> (NodeService, Node, Loopback) {
+    simple_logger::init_with_level(log::Level::Debug).unwrap_or_default();
+    let (loopback, network) = Loopback::new();
+    let timestamp = SystemTime::now();
+    let genesis = genesis(
+        keys,
+        cfg.min_stake_amount,
+        1000 * cfg.min_stake_amount,
+        timestamp,
+    );
+    let chain = Blockchain::testing(cfg.clone().into(), genesis, timestamp)
+        .expect("Failed to create blockchain");
+    let (node, node_api) = NodeService::new(cfg, chain, keys[0].clone(), network).unwrap();
+    (node, node_api, loopback)
+}
+
+/// Creates a node which is the only validator of a new chain.
+fn new_node(cfg: ChainConfig) -> (NodeService, Node, Loopback) {
+    new_node_with_validators(cfg, &[KeyChain::new_mem()])
+}
+
 fn simulate_consensus(node: &mut NodeService) {
     node.create_micro_block(None).unwrap();
 }
 
-fn simulate_payment(node: &mut NodeService, amount: i64) -> Result<(), Error> {
+/// Creates a payment which spends all payment outputs of the node and pays `extra_fee`
+/// on top of the standard fee.
+fn create_payment(node: &NodeService, amount: i64, extra_fee: i64) -> Result<Transaction, Error> {
     let sender_skey = &node.keys.wallet_skey;
     let sender_pkey = &node.keys.wallet_pkey;
     let mut inputs: Vec<Output> = Vec::new();
@@ -75,7 +104,7 @@ fn simulate_payment(node: &mut NodeService, amount: i64) -> Result<(), Error> {
         }
     }
 
-    let fee: i64 = node.cfg.payment_fee * inputs.len() as i64;
+    let fee: i64 = node.cfg.payment_fee * inputs.len() as i64 + extra_fee;
     assert!(inputs_amount >= amount + fee);
     let change = inputs_amount - amount - fee;
     let mut outputs: Vec<Output> = Vec::<Output>::with_capacity(2);
@@ -89,25 +118,23 @@ fn simulate_payment(node: &mut NodeService, amount: i64) -> Result<(), Error> {
     }
 
     let tx = PaymentTransaction::new(sender_skey, &inputs, &outputs, outputs_gamma, fee)?;
-    node.handle_transaction(tx.into())?;
-    Ok(())
+    Ok(tx.into())
+}
+
+fn simulate_payment(node: &mut NodeService, amount: i64) -> Result<Hash, Error> {
+    let tx = create_payment(node, amount, 0)?;
+    let tx_hash = Hash::digest(&tx);
+    node.handle_transaction(tx)?;
+    Ok(tx_hash)
 }
 
 #[test]
 pub fn payments() {
-    simple_logger::init_with_level(log::Level::Debug).unwrap_or_default();
-    let keys = KeyChain::new_mem();
-    let (_loopback, network) = Loopback::new();
-
     let cfg: ChainConfig = Default::default();
     let total: i64 = 1000 * cfg.min_stake_amount;
     let stake: i64 = cfg.min_stake_amount;
-    let timestamp = SystemTime::now();
-    let genesis = genesis(&[keys.clone()], stake, total, timestamp);
-    let chain = Blockchain::testing(cfg.clone().into(), genesis, timestamp)
-        .expect("Failed to create blockchain");
-    let (mut node, _node_api) =
-        NodeService::new(cfg.clone(), chain, keys.clone(), network).unwrap();
+    let (mut node, _node_api, _loopback) = new_node(cfg.clone());
+    let keys = node.keys.clone();
     let mut block_count = node.chain.height();
 
     // Payment without a change.
@@ -184,3 +211,2182 @@ pub fn payments() {
 
     assert_eq!(block_count, 2);
 }
+
+#[test]
+pub fn outputs_rolled_back() {
+    let cfg: ChainConfig = Default::default();
+    let (mut node, _node_api, _loopback) = new_node(cfg);
+    let (tx, mut rx) = unbounded();
+    node.handle_subscribe_outputs(tx).unwrap();
+
+    // Apply a block.
+    simulate_payment(&mut node, 100).unwrap();
+    simulate_consensus(&mut node);
+    let applied = (&mut rx).wait().next().unwrap().unwrap();
+    assert!(!applied.rolled_back);
+    assert_eq!(applied.inputs.len(), 1);
+
+    // Revert this block.
+    node.handle_pop_block().unwrap();
+    let reverted = (&mut rx).wait().next().unwrap().unwrap();
+    assert!(reverted.rolled_back);
+    assert_eq!(reverted.inputs.len(), applied.outputs.len());
+    assert_eq!(reverted.outputs.len(), applied.inputs.len());
+    assert_eq!(
+        Hash::digest(&reverted.outputs[0]),
+        Hash::digest(&applied.inputs[0])
+    );
+}
+
+#[test]
+pub fn pop_block_checkpoint() {
+    let cfg: ChainConfig = Default::default();
+    let (mut node, _node_api, _loopback) = new_node(cfg);
+    let block_count = node.chain.height();
+
+    // Apply two blocks.
+    simulate_payment(&mut node, 100).unwrap();
+    simulate_consensus(&mut node);
+    simulate_payment(&mut node, 100).unwrap();
+    simulate_consensus(&mut node);
+    assert_eq!(node.chain.height(), block_count + 2);
+
+    // Checkpoint at the first block.
+    node.handle_set_checkpoint(block_count).unwrap();
+
+    // The second block can be reverted.
+    let last_block_hash = node.chain.last_block_hash();
+    node.handle_pop_block().unwrap();
+    assert_eq!(node.chain.height(), block_count + 1);
+    assert_ne!(node.chain.last_block_hash(), last_block_hash);
+
+    // The first block is protected by the checkpoint.
+    let last_block_hash = node.chain.last_block_hash();
+    node.handle_pop_block().unwrap();
+    assert_eq!(node.chain.height(), block_count + 1);
+    assert_eq!(node.chain.last_block_hash(), last_block_hash);
+}
+
+#[test]
+pub fn leader_schedule() {
+    let cfg: ChainConfig = Default::default();
+    let (node, _node_api, _loopback) = new_node(cfg);
+    let keys = node.keys.clone();
+
+    let schedule = node.leader_schedule(5);
+    assert_eq!(schedule.height, node.chain.height());
+    assert_eq!(schedule.view_change, node.chain.view_change());
+    assert_eq!(schedule.leaders.len(), 5);
+    assert_eq!(schedule.leaders[0], node.chain.leader());
+    for leader in schedule.leaders {
+        assert_eq!(leader, keys.network_pkey);
+    }
+
+    let schedule = node.leader_schedule(u32::max_value());
+    assert_eq!(schedule.leaders.len(), MAX_LEADER_SCHEDULE_LEN as usize);
+}
+
+#[test]
+pub fn cancel_transaction() {
+    let cfg: ChainConfig = Default::default();
+    let (mut node, _node_api, _loopback) = new_node(cfg);
+    let (tx, mut rx) = unbounded();
+    node.handle_subscribe_transaction_status(tx).unwrap();
+    let block_count = node.chain.height();
+
+    // Cancel before mine.
+    let tx_hash = simulate_payment(&mut node, 100).unwrap();
+    assert!(node.mempool.contains(&tx_hash));
+    let msg = (&mut rx).wait().next().unwrap().unwrap();
+    assert_eq!(msg.status, TransactionStatus::Accepted {});
+    node.handle_cancel_transaction(tx_hash).unwrap();
+    assert!(!node.mempool.contains(&tx_hash));
+    assert_eq!(node.mempool.len(), 0);
+    assert_eq!(node.mempool.inputs_len(), 0);
+    assert_eq!(node.mempool.outputs_len(), 0);
+    let msg = (&mut rx).wait().next().unwrap().unwrap();
+    assert_eq!(msg.tx_hash, tx_hash);
+    assert_eq!(msg.status, TransactionStatus::Cancelled {});
+
+    // Cancel after mine - no-op.
+    let tx_hash = simulate_payment(&mut node, 100).unwrap();
+    simulate_consensus(&mut node);
+    assert_eq!(node.chain.height(), block_count + 1);
+    assert_eq!(node.mempool.len(), 0);
+    node.handle_cancel_transaction(tx_hash).unwrap();
+    assert_eq!(node.mempool.len(), 0);
+    assert_eq!(node.chain.height(), block_count + 1);
+}
+
+fn create_micro_block_with_timestamp(node: &NodeService, timestamp: SystemTime) -> Block {
+    create_micro_block_with_reward(node, timestamp, node.cfg.block_reward)
+}
+
+fn create_micro_block_with_reward(
+    node: &NodeService,
+    timestamp: SystemTime,
+    block_reward: i64,
+) -> Block {
+    let view_change = node.chain.view_change();
+    let seed = mix(node.chain.last_random(), view_change);
+    let random = pbc::make_VRF(&node.keys.network_skey, &seed);
+    let base = BaseBlockHeader::new(
+        VERSION,
+        node.chain.last_block_hash(),
+        node.chain.height(),
+        view_change,
+        timestamp,
+        random,
+    );
+    let mut block = MicroBlock::with_reward(
+        base,
+        None,
+        Vec::new(),
+        &node.keys.wallet_pkey,
+        node.keys.network_pkey,
+        block_reward,
+        node.cfg.fee_policy,
+    );
+    block.sign(&node.keys.network_skey, &node.keys.network_pkey);
+    Block::MicroBlock(block)
+}
+
+#[test]
+pub fn block_timestamp_in_future() {
+    let cfg: ChainConfig = Default::default();
+    let (mut node, _node_api, _loopback) = new_node(cfg.clone());
+    let height = node.chain.height();
+
+    // A block stamped 10 minutes ahead.
+    let timestamp = SystemTime::now() + Duration::from_secs(10 * 60);
+    let block = create_micro_block_with_timestamp(&node, timestamp);
+    let e = node.apply_new_block(block).unwrap_err();
+    match e.downcast::<NodeBlockError>().unwrap() {
+        NodeBlockError::TimestampInFuture(block_height, _hash, drift) => {
+            assert_eq!(block_height, height);
+            assert!(drift > cfg.max_clock_drift);
+        }
+        e => panic!("Unexpected error: {}", e),
+    }
+    assert_eq!(node.chain.height(), height);
+
+    // A small drift is tolerated.
+    let timestamp = SystemTime::now() + cfg.max_clock_drift / 2;
+    let block = create_micro_block_with_timestamp(&node, timestamp);
+    node.apply_new_block(block).unwrap();
+    assert_eq!(node.chain.height(), height + 1);
+}
+
+#[test]
+pub fn block_timestamp_non_monotonic() {
+    let cfg: ChainConfig = Default::default();
+    let (mut node, _node_api, _loopback) = new_node(cfg);
+    let height = node.chain.height();
+    let previous_timestamp = node.chain.last_block().unwrap().base_header().timestamp;
+
+    // The first block after the macro block is backdated.
+    let timestamp = previous_timestamp - Duration::from_secs(60);
+    let block = create_micro_block_with_timestamp(&node, timestamp);
+    let e = node.apply_new_block(block).unwrap_err();
+    match e.downcast::<NodeBlockError>().unwrap() {
+        NodeBlockError::NonMonotonicTimestamp(block_height, previous, this) => {
+            assert_eq!(block_height, height);
+            assert_eq!(previous, previous_timestamp);
+            assert_eq!(this, timestamp);
+        }
+        e => panic!("Unexpected error: {}", e),
+    }
+    assert_eq!(node.chain.height(), height);
+
+    // Equal timestamps are allowed.
+    let block = create_micro_block_with_timestamp(&node, previous_timestamp);
+    node.apply_new_block(block).unwrap();
+    assert_eq!(node.chain.height(), height + 1);
+
+    // A backdated micro block after a micro block.
+    let block = create_micro_block_with_timestamp(&node, timestamp);
+    let e = node.apply_new_block(block).unwrap_err();
+    match e.downcast::<NodeBlockError>().unwrap() {
+        NodeBlockError::NonMonotonicTimestamp(..) => {}
+        e => panic!("Unexpected error: {}", e),
+    }
+    assert_eq!(node.chain.height(), height + 1);
+}
+
+#[test]
+pub fn chain_loader_chunks() {
+    simple_logger::init_with_level(log::Level::Debug).unwrap_or_default();
+    let keys1 = KeyChain::new_mem();
+    let keys2 = KeyChain::new_mem();
+    let (mut loopback1, network1) = Loopback::new();
+    let (mut loopback2, network2) = Loopback::new();
+
+    let mut cfg: ChainConfig = Default::default();
+    cfg.blocks_in_epoch = 20;
+    cfg.max_blocks_per_response = 3;
+    let timestamp = SystemTime::now();
+    let genesis = genesis(
+        &[keys1.clone()],
+        cfg.min_stake_amount,
+        1000 * cfg.min_stake_amount,
+        timestamp,
+    );
+    let chain1 = Blockchain::testing(cfg.clone().into(), genesis.clone(), timestamp)
+        .expect("Failed to create blockchain");
+    let chain2 = Blockchain::testing(cfg.clone().into(), genesis, timestamp)
+        .expect("Failed to create blockchain");
+    let (mut node1, _node_api1) =
+        NodeService::new(cfg.clone(), chain1, keys1.clone(), network1).unwrap();
+    let (mut node2, _node_api2) =
+        NodeService::new(cfg.clone(), chain2, keys2.clone(), network2).unwrap();
+
+    for _ in 0..10 {
+        simulate_consensus(&mut node1);
+    }
+    loopback1.filter_broadcast(&[crate::SEALED_BLOCK_TOPIC]);
+    let height = node1.chain.height();
+    assert_eq!(height, 11);
+
+    // Request more blocks than one chunk allows.
+    let request = ChainLoaderMessage::Request(crate::loader::RequestBlocks::new(0));
+    node1
+        .handle_chain_loader_message(keys2.network_pkey, request)
+        .unwrap();
+
+    let mut chunks = 0;
+    while node2.chain.height() < height {
+        let msg: ChainLoaderMessage =
+            loopback1.get_unicast(crate::loader::CHAIN_LOADER_TOPIC, &keys2.network_pkey);
+        match &msg {
+            ChainLoaderMessage::Response(response) => {
+                assert!(response.blocks.len() as u64 <= cfg.max_blocks_per_response);
+                assert_eq!(response.height, height);
+                assert_eq!(response.more, chunks < 3);
+            }
+            _ => panic!("Expected a response"),
+        }
+        node2
+            .handle_chain_loader_message(keys1.network_pkey, msg)
+            .unwrap();
+        chunks += 1;
+        // The next range is requested only after the last chunk.
+        if chunks < 4 {
+            loopback2.assert_empty_queue();
+        }
+    }
+    assert_eq!(chunks, 4);
+    assert_eq!(node2.chain.height(), height);
+    assert_eq!(node2.chain.last_block_hash(), node1.chain.last_block_hash());
+}
+
+#[test]
+pub fn mempool_bytes_limit() {
+    let mut cfg: ChainConfig = Default::default();
+
+    // The byte limit triggers before the count limit.
+    cfg.max_mempool_bytes = 100;
+    assert!(cfg.max_utxo_in_mempool > 0);
+    let (mut node, _node_api, _loopback) = new_node(cfg);
+    let e = simulate_payment(&mut node, 100).unwrap_err();
+    match e.downcast::<NodeTransactionError>().unwrap() {
+        NodeTransactionError::MempoolIsFull(_tx_hash) => {}
+        e => panic!("Unexpected error: {}", e),
+    }
+    assert_eq!(node.mempool.len(), 0);
+    assert_eq!(node.mempool.bytes_len(), 0);
+}
+
+#[test]
+pub fn tx_bytes_limit() {
+    let mut cfg: ChainConfig = Default::default();
+
+    // A few UTXOs, but too many bytes.
+    cfg.max_tx_bytes = 1000;
+    let (mut node, _node_api, _loopback) = new_node(cfg.clone());
+    let e = simulate_payment(&mut node, 100).unwrap_err();
+    match e.downcast::<NodeTransactionError>().unwrap() {
+        NodeTransactionError::TooManyBytes(_tx_hash, got, max) => {
+            assert!(got > max);
+            assert_eq!(max, cfg.max_tx_bytes);
+        }
+        e => panic!("Unexpected error: {}", e),
+    }
+    assert_eq!(node.mempool.len(), 0);
+}
+
+#[test]
+pub fn transactions_validated_in_background() {
+    // Split the genesis payment into several outputs.
+    const COUNT: usize = 8;
+    let mut cfg: ChainConfig = Default::default();
+    cfg.max_pending_transactions = COUNT + 1;
+    let (mut node, _node_api, _loopback) = new_node(cfg.clone());
+    let keys = node.keys.clone();
+    let sender_skey = &keys.wallet_skey;
+    let sender_pkey = &keys.wallet_pkey;
+
+    let mut inputs: Vec<Output> = Vec::new();
+    let mut inputs_amount: i64 = 0;
+    for hash in node.chain.unspent() {
+        let output = node
+            .chain
+            .output_by_hash(&hash)
+            .expect("no disk errors")
+            .expect("utxo exists");
+        if let Output::PaymentOutput(ref o) = output {
+            let PaymentPayload { amount, .. } = o.decrypt_payload(sender_skey).unwrap();
+            inputs.push(output);
+            inputs_amount += amount;
+        }
+    }
+    let fee = cfg.payment_fee * COUNT as i64;
+    let amount = (inputs_amount - fee) / COUNT as i64;
+    let mut split: Vec<(Output, i64)> = Vec::with_capacity(COUNT);
+    let mut outputs_gamma = Fr::zero();
+    for i in 0..COUNT {
+        let amount = if i + 1 < COUNT {
+            amount
+        } else {
+            inputs_amount - fee - amount * (COUNT - 1) as i64
+        };
+        let (output, gamma) = PaymentOutput::new(sender_pkey, amount).unwrap();
+        split.push((Output::PaymentOutput(output), amount));
+        outputs_gamma += gamma;
+    }
+    let outputs: Vec<Output> = split.iter().map(|(o, _)| o.clone()).collect();
+    let tx = PaymentTransaction::new(sender_skey, &inputs, &outputs, outputs_gamma, fee).unwrap();
+    node.handle_transaction(tx.into()).unwrap();
+    simulate_consensus(&mut node);
+    assert_eq!(node.mempool.len(), 0);
+
+    // A burst of transactions, each spending one of the outputs above.
+    let mut txs: Vec<Transaction> = Vec::with_capacity(COUNT);
+    let mut tx_hashes: Vec<Hash> = Vec::with_capacity(COUNT);
+    for (input, amount) in &split {
+        let (output, gamma) = PaymentOutput::new(sender_pkey, amount - cfg.payment_fee).unwrap();
+        let tx = PaymentTransaction::new(
+            sender_skey,
+            &[input.clone()],
+            &[Output::PaymentOutput(output)],
+            gamma,
+            cfg.payment_fee,
+        )
+        .unwrap();
+        tx_hashes.push(Hash::digest(&tx));
+        txs.push(tx.clone().into());
+        node.handle_transaction_async(tx.into()).unwrap();
+    }
+
+    // A conflicting transaction, which arrived after the first one.
+    let (input, amount) = &split[0];
+    let (output, gamma) = PaymentOutput::new(sender_pkey, amount - cfg.payment_fee).unwrap();
+    let tx = PaymentTransaction::new(
+        sender_skey,
+        &[input.clone()],
+        &[Output::PaymentOutput(output)],
+        gamma,
+        cfg.payment_fee,
+    )
+    .unwrap();
+    let conflicting_tx_hash = Hash::digest(&tx);
+    node.handle_transaction_async(tx.into()).unwrap();
+
+    // The event loop is not blocked by validation.
+    assert_eq!(node.mempool.len(), 0);
+    assert_eq!(node.pending_transactions.len(), COUNT + 1);
+
+    // Duplicates of transactions being validated are skipped.
+    node.handle_transaction_async(txs[1].clone()).unwrap();
+    assert_eq!(node.pending_transactions.len(), COUNT + 1);
+
+    // The validation queue is full.
+    let (output, gamma) = PaymentOutput::new(sender_pkey, amount - cfg.payment_fee).unwrap();
+    let tx = PaymentTransaction::new(
+        sender_skey,
+        &[input.clone()],
+        &[Output::PaymentOutput(output)],
+        gamma,
+        cfg.payment_fee,
+    )
+    .unwrap();
+    let e = node.handle_transaction_async(tx.into()).unwrap_err();
+    match e.downcast::<NodeTransactionError>().unwrap() {
+        NodeTransactionError::ValidationQueueIsFull(_) => {}
+        e => panic!("Unexpected error: {}", e),
+    }
+    assert_eq!(node.pending_transactions.len(), COUNT + 1);
+
+    // Wait for validation.
+    future::poll_fn(|| Ok::<_, ()>(node.poll_pending_transactions()))
+        .wait()
+        .unwrap();
+    assert_eq!(node.pending_transactions.len(), 0);
+    assert_eq!(node.mempool.len(), COUNT);
+    for tx_hash in &tx_hashes {
+        assert!(node.mempool.contains(tx_hash));
+    }
+    assert!(!node.mempool.contains(&conflicting_tx_hash));
+}
+
+#[test]
+pub fn immediate_micro_block() {
+    let mut cfg: ChainConfig = Default::default();
+
+    // Don't wait for tx_wait_timeout if there is at least one transaction.
+    cfg.min_txs_for_immediate_block = 1;
+    cfg.tx_wait_timeout = Duration::from_secs(3600);
+    let (mut node, _node_api, _loopback) = new_node(cfg);
+    let height = node.chain.height();
+    assert!(node.is_leader());
+
+    let tx_hash = simulate_payment(&mut node, 100).unwrap();
+    assert_eq!(node.mempool.len(), 0);
+    assert_eq!(node.chain.height(), height + 1);
+    assert!(!node.mempool.contains(&tx_hash));
+}
+
+#[test]
+pub fn request_after_shutdown() {
+    let cfg: ChainConfig = Default::default();
+    let (node, node_api, _loopback) = new_node(cfg);
+
+    // The service is alive.
+    assert!(node_api.request(NodeRequest::FeeEstimate {}).is_ok());
+    assert!(node_api.subscribe_block_added().is_ok());
+
+    // The service has been shut down.
+    drop(node);
+    assert!(node_api.request(NodeRequest::FeeEstimate {}).is_err());
+    assert!(node_api.subscribe_block_added().is_err());
+    assert!(node_api.subscribe_epoch_changed().is_err());
+    assert!(node_api.subscribe_outputs_changed().is_err());
+    assert!(node_api.subscribe_reorg().is_err());
+}
+
+#[test]
+pub fn validator_set() {
+    let keys1 = KeyChain::new_mem();
+    let keys2 = KeyChain::new_mem();
+
+    let cfg: ChainConfig = Default::default();
+    let (mut node, _node_api, _loopback) = new_node_with_validators(cfg, &[keys1, keys2]);
+
+    let (tx, mut rx) = unbounded();
+    node.handle_subscribe_epoch(tx).unwrap();
+    let epoch_changed = (&mut rx).wait().next().unwrap().unwrap();
+
+    let validator_set = node.validator_set();
+    assert_eq!(validator_set.epoch, epoch_changed.epoch);
+    assert_eq!(validator_set.facilitator, epoch_changed.facilitator);
+    assert_eq!(validator_set.validators, epoch_changed.validators);
+    assert_eq!(validator_set.validators.len(), 2);
+    assert_eq!(validator_set.total_slots, node.chain.total_slots());
+    let slots: i64 = validator_set.validators.iter().map(|(_, s)| *s).sum();
+    assert_eq!(slots, validator_set.total_slots);
+}
+
+#[test]
+pub fn utxo_snapshot() {
+    let cfg: ChainConfig = Default::default();
+    let (node, _node_api, _loopback) = new_node(cfg);
+
+    let mut expected: Vec<Hash> = node.chain.unspent().cloned().collect();
+    expected.sort();
+    assert!(expected.len() > 1);
+
+    // Full set fits into one page.
+    let snapshot = node.utxo_snapshot(None).unwrap();
+    assert_eq!(snapshot.height, node.chain.height());
+    assert_eq!(snapshot.block_hash, node.chain.last_block_hash());
+    assert_eq!(snapshot.next, None);
+    let hashes: Vec<Hash> = snapshot.outputs.iter().map(Hash::digest).collect();
+    assert_eq!(hashes, expected);
+
+    // Resume after the first output.
+    let snapshot = node.utxo_snapshot(Some(expected[0])).unwrap();
+    assert_eq!(snapshot.next, None);
+    let hashes: Vec<Hash> = snapshot.outputs.iter().map(Hash::digest).collect();
+    assert_eq!(&hashes[..], &expected[1..]);
+
+    // Resume after the last output.
+    let snapshot = node.utxo_snapshot(expected.last().cloned()).unwrap();
+    assert!(snapshot.outputs.is_empty());
+    assert_eq!(snapshot.next, None);
+}
+
+#[test]
+pub fn rejected_blocks_cache() {
+    let cfg: ChainConfig = Default::default();
+    let (mut node, _node_api, _loopback) = new_node(cfg);
+    let height = node.chain.height();
+
+    // A block from the future can become valid later - not cached.
+    let timestamp = SystemTime::now() + Duration::from_secs(10 * 60);
+    let block = create_micro_block_with_timestamp(&node, timestamp);
+    let sealed_hash = sealed_block_hash(&block);
+    node.handle_sealed_block(None, block).unwrap();
+    assert!(!node.rejected_blocks.contains_key(&sealed_hash));
+
+    // A block with an invalid signature - cached.
+    let mut block = create_micro_block_with_timestamp(&node, SystemTime::now());
+    if let Block::MicroBlock(ref mut block) = block {
+        block.base.timestamp += Duration::from_secs(1);
+    }
+    let block_hash = Hash::digest(&block);
+    let sealed_hash = sealed_block_hash(&block);
+    let e = node.handle_sealed_block(None, block.clone()).unwrap_err();
+    match e.downcast::<BlockError>().unwrap() {
+        BlockError::InvalidLeaderSignature(block_height, hash) => {
+            assert_eq!(block_height, height);
+            assert_eq!(hash, block_hash);
+        }
+        e => panic!("Unexpected error: {}", e),
+    }
+    assert!(node.rejected_blocks.contains_key(&sealed_hash));
+
+    // The second try hits the cache.
+    let hits = metrics::REJECTED_BLOCK_CACHE_HITS.get();
+    node.handle_sealed_block(None, block).unwrap();
+    assert!(metrics::REJECTED_BLOCK_CACHE_HITS.get() > hits);
+    assert_eq!(node.chain.height(), height);
+
+    // A forged signature doesn't shadow the genuine block.
+    let block = create_micro_block_with_timestamp(&node, SystemTime::now());
+    let mut forged = block.clone();
+    if let Block::MicroBlock(ref mut forged) = forged {
+        forged.sig = pbc::Signature::zero();
+    }
+    assert_eq!(Hash::digest(&forged), Hash::digest(&block));
+    let forged_hash = sealed_block_hash(&forged);
+    let sealed_hash = sealed_block_hash(&block);
+    node.handle_sealed_block(None, forged).unwrap_err();
+    assert!(node.rejected_blocks.contains_key(&forged_hash));
+    assert!(!node.rejected_blocks.contains_key(&sealed_hash));
+    node.handle_sealed_block(None, block).unwrap();
+    assert_eq!(node.chain.height(), height + 1);
+}
+
+#[test]
+pub fn verified_blocks_cache() {
+    let mut cfg: ChainConfig = Default::default();
+    cfg.blocks_in_epoch = 2;
+    let (mut node, _node_api, _loopback) = new_node(cfg.clone());
+    let height = node.chain.height();
+
+    // A block from the future passes signature checks, but can't be applied yet.
+    let timestamp = SystemTime::now() + Duration::from_secs(10 * 60);
+    let block = create_micro_block_with_timestamp(&node, timestamp);
+    let sealed_hash = sealed_block_hash(&block);
+    let checks = metrics::BLOCK_SIGNATURE_CHECKS.get();
+    node.handle_sealed_block(None, block.clone()).unwrap();
+    assert!(metrics::BLOCK_SIGNATURE_CHECKS.get() > checks);
+    assert!(node.verified_blocks.contains_key(&sealed_hash));
+    assert_eq!(node.chain.height(), height);
+
+    // The same block is not verified again.
+    let hits = metrics::VERIFIED_BLOCK_CACHE_HITS.get();
+    node.handle_sealed_block(None, block.clone()).unwrap();
+    assert!(metrics::VERIFIED_BLOCK_CACHE_HITS.get() > hits);
+    assert_eq!(node.chain.height(), height);
+
+    // A forged copy of the verified block doesn't skip signature checks.
+    let mut forged = block;
+    if let Block::MicroBlock(ref mut forged) = forged {
+        forged.sig = pbc::Signature::zero();
+    }
+    node.handle_sealed_block(None, forged).unwrap_err();
+    assert_eq!(node.chain.height(), height);
+
+    // The cache is cleared on a new epoch.
+    let epoch = node.chain.epoch();
+    simulate_consensus(&mut node);
+    assert!(!node.verified_blocks.is_empty());
+    let block = create_macro_block_with_reward(&node, cfg.macro_block_reward);
+    node.apply_new_block(Block::MacroBlock(block)).unwrap();
+    assert_eq!(node.chain.epoch(), epoch + 1);
+    assert!(node.verified_blocks.is_empty());
+}
+
+#[test]
+pub fn block_fees() {
+    let cfg: ChainConfig = Default::default();
+    let (mut node, _node_api, _loopback) = new_node(cfg.clone());
+    let keys = node.keys.clone();
+    let (tx, mut rx) = unbounded();
+    node.handle_block_added(tx).unwrap();
+
+    // Split the genesis output into two.
+    simulate_payment(&mut node, 100).unwrap();
+    simulate_consensus(&mut node);
+    let added = (&mut rx).wait().next().unwrap().unwrap();
+    assert_eq!(added.fees, cfg.payment_fee);
+
+    // Two independent fee-paying transactions.
+    let mut tx_count = 0;
+    let unspent: Vec<Hash> = node.chain.unspent().cloned().collect();
+    for hash in unspent {
+        let output = node
+            .chain
+            .output_by_hash(&hash)
+            .expect("no disk errors")
+            .expect("utxo exists");
+        let amount = match output {
+            Output::PaymentOutput(ref o) => {
+                let PaymentPayload { amount, .. } = o.decrypt_payload(&keys.wallet_skey).unwrap();
+                amount
+            }
+            _ => continue,
+        };
+        if amount <= cfg.payment_fee {
+            continue;
+        }
+        let (output2, gamma2) =
+            PaymentOutput::new(&keys.wallet_pkey, amount - cfg.payment_fee).unwrap();
+        let tx = PaymentTransaction::new(
+            &keys.wallet_skey,
+            &[output],
+            &[Output::PaymentOutput(output2)],
+            gamma2,
+            cfg.payment_fee,
+        )
+        .unwrap();
+        node.handle_transaction(tx.into()).unwrap();
+        tx_count += 1;
+        if tx_count == 2 {
+            break;
+        }
+    }
+    assert_eq!(tx_count, 2);
+    assert_eq!(node.mempool.len(), 2);
+
+    let fees_before = metrics::BLOCK_FEES_CUMULATIVE.get();
+    simulate_consensus(&mut node);
+    let added = (&mut rx).wait().next().unwrap().unwrap();
+    assert_eq!(added.fees, 2 * cfg.payment_fee);
+    assert_eq!(metrics::BLOCK_FEES_TOTAL.get(), 2 * cfg.payment_fee);
+    assert!(metrics::BLOCK_FEES_CUMULATIVE.get() >= fees_before + 2 * cfg.payment_fee);
+}
+
+#[test]
+pub fn skip_empty_blocks() {
+    let mut cfg: ChainConfig = Default::default();
+    cfg.skip_empty_blocks = true;
+    let (mut node, _node_api, _loopback) = new_node(cfg.clone());
+    let height = node.chain.height();
+    assert!(node.is_leader());
+
+    // The mempool is empty - wait.
+    node.last_block_clock = clock::now() - cfg.tx_wait_timeout;
+    node.handle_micro_block_propose_timer().unwrap();
+    assert_eq!(node.chain.height(), height);
+
+    // A transaction has arrived.
+    simulate_payment(&mut node, 100).unwrap();
+    node.handle_micro_block_propose_timer().unwrap();
+    assert_eq!(node.mempool.len(), 0);
+    assert_eq!(node.chain.height(), height + 1);
+
+    // An empty block is created after empty_block_timeout.
+    node.last_block_clock = clock::now() - cfg.tx_wait_timeout;
+    node.handle_micro_block_propose_timer().unwrap();
+    assert_eq!(node.chain.height(), height + 1);
+    node.last_block_clock = clock::now() - cfg.empty_block_timeout;
+    node.handle_micro_block_propose_timer().unwrap();
+    assert_eq!(node.chain.height(), height + 2);
+}
+
+#[test]
+pub fn transaction_status() {
+    let mut cfg: ChainConfig = Default::default();
+    cfg.confirmation_depth = 1;
+    let (mut node, _node_api, _loopback) = new_node(cfg);
+    let (tx, mut rx) = unbounded();
+    node.handle_subscribe_transaction_status(tx).unwrap();
+    let height = node.chain.height();
+
+    let tx_hash = simulate_payment(&mut node, 100).unwrap();
+    let msg = (&mut rx).wait().next().unwrap().unwrap();
+    assert_eq!(msg.tx_hash, tx_hash);
+    assert_eq!(msg.status, TransactionStatus::Accepted {});
+
+    // Included into a block.
+    simulate_consensus(&mut node);
+    let msg = (&mut rx).wait().next().unwrap().unwrap();
+    assert_eq!(msg.tx_hash, tx_hash);
+    assert_eq!(msg.status, TransactionStatus::InBlock { height });
+
+    // Reverted and returned to the mempool.
+    node.handle_pop_block().unwrap();
+    let msg = (&mut rx).wait().next().unwrap().unwrap();
+    assert_eq!(msg.tx_hash, tx_hash);
+    assert_eq!(msg.status, TransactionStatus::Accepted {});
+    assert!(node.mempool.contains(&tx_hash));
+
+    // Included again and buried under confirmation_depth blocks.
+    simulate_consensus(&mut node);
+    let msg = (&mut rx).wait().next().unwrap().unwrap();
+    assert_eq!(msg.status, TransactionStatus::InBlock { height });
+    simulate_consensus(&mut node);
+    let msg = (&mut rx).wait().next().unwrap().unwrap();
+    assert_eq!(msg.tx_hash, tx_hash);
+    assert_eq!(msg.status, TransactionStatus::Committed { height });
+    assert_eq!(node.chain.height(), height + 2);
+
+    // Committed, but not finalized yet - still can be reverted.
+    node.handle_pop_block().unwrap();
+    node.handle_pop_block().unwrap();
+    let msg = (&mut rx).wait().next().unwrap().unwrap();
+    assert_eq!(msg.tx_hash, tx_hash);
+    assert_eq!(msg.status, TransactionStatus::Accepted {});
+    assert!(node.mempool.contains(&tx_hash));
+}
+
+#[test]
+pub fn raw_block() {
+    let cfg: ChainConfig = Default::default();
+    let (node, _node_api, _loopback) = new_node(cfg);
+    let height = node.chain.height();
+
+    let raw = node.raw_block(height - 1).unwrap();
+    assert_eq!(raw.height, height - 1);
+    assert_eq!(raw.hash, node.chain.last_block_hash());
+    let block = Block::from_buffer(&base64::decode(&raw.block).unwrap()).unwrap();
+    assert_eq!(Hash::digest(&block), raw.hash);
+
+    // Beyond the chain tip.
+    let e = node.raw_block(height).unwrap_err();
+    match e.downcast::<NodeBlockError>().unwrap() {
+        NodeBlockError::NoSuchBlock(block_height, current_height) => {
+            assert_eq!(block_height, height);
+            assert_eq!(current_height, height);
+        }
+        e => panic!("Unexpected error: {}", e),
+    }
+}
+
+fn create_macro_block_with_reward(node: &NodeService, block_reward: i64) -> MacroBlock {
+    let view_change = node.chain.view_change();
+    let seed = mix(node.chain.last_random(), view_change);
+    let random = pbc::make_VRF(&node.keys.network_skey, &seed);
+    let base = BaseBlockHeader::new(
+        VERSION,
+        node.chain.last_block_hash(),
+        node.chain.height(),
+        view_change,
+        SystemTime::now(),
+        random,
+    );
+    let mut block = MacroBlock::with_reward(
+        base,
+        &node.keys.wallet_pkey,
+        node.keys.network_pkey,
+        block_reward,
+    );
+    // The only validator has the supermajority.
+    let (multisig, multisigmap) = create_proposal_signature(
+        &Hash::digest(&block),
+        &node.keys.network_skey,
+        &node.keys.network_pkey,
+        node.chain.validators(),
+    );
+    block.body.multisig = multisig;
+    block.body.multisigmap = multisigmap;
+    block
+}
+
+#[test]
+pub fn macro_block_reward() {
+    let mut cfg: ChainConfig = Default::default();
+    cfg.blocks_in_epoch = 2;
+    cfg.macro_block_reward = 1_000_000;
+    let (mut node, _node_api, _loopback) = new_node(cfg.clone());
+    let keys = node.keys.clone();
+    simulate_consensus(&mut node);
+    let height = node.chain.height();
+    let epoch = node.chain.epoch();
+
+    // A macro block without the reward.
+    let block = create_macro_block_with_reward(&node, 0);
+    let e = node.apply_new_block(Block::MacroBlock(block)).unwrap_err();
+    match e.downcast::<NodeBlockError>().unwrap() {
+        NodeBlockError::InvalidBlockReward(block_height, _hash, got, expected) => {
+            assert_eq!(block_height, height);
+            assert_eq!(got, 0);
+            assert_eq!(expected, cfg.macro_block_reward);
+        }
+        e => panic!("Unexpected error: {}", e),
+    }
+    assert_eq!(node.chain.height(), height);
+
+    // A macro block with an excessive reward.
+    let block = create_macro_block_with_reward(&node, 2 * cfg.macro_block_reward);
+    let e = node.apply_new_block(Block::MacroBlock(block)).unwrap_err();
+    match e.downcast::<NodeBlockError>().unwrap() {
+        NodeBlockError::InvalidBlockReward(..) => {}
+        e => panic!("Unexpected error: {}", e),
+    }
+    assert_eq!(node.chain.height(), height);
+
+    // The expected reward is paid to the leader.
+    let block = create_macro_block_with_reward(&node, cfg.macro_block_reward);
+    assert_eq!(block.body.outputs.leafs().len(), 1);
+    let output: Output = (**block.body.outputs.leafs()[0].0).clone();
+    let output_hash = Hash::digest(&output);
+    let (tx, mut rx) = unbounded();
+    node.handle_subscribe_outputs(tx).unwrap();
+    node.apply_new_block(Block::MacroBlock(block)).unwrap();
+    assert_eq!(node.chain.height(), height + 1);
+    assert_eq!(node.chain.epoch(), epoch + 1);
+    match node.chain.output_by_hash(&output_hash).unwrap().unwrap() {
+        Output::PaymentOutput(o) => {
+            let payload = o.decrypt_payload(&keys.wallet_skey).unwrap();
+            assert_eq!(payload.amount, cfg.macro_block_reward);
+        }
+        _ => panic!("Expected a payment output"),
+    }
+
+    // Wallets are notified about the reward.
+    let changed = (&mut rx).wait().next().unwrap().unwrap();
+    assert_eq!(changed.epoch, epoch);
+    assert!(changed.inputs.is_empty());
+    assert_eq!(changed.outputs.len(), 1);
+    assert_eq!(Hash::digest(&changed.outputs[0]), output_hash);
+}
+
+#[test]
+pub fn unbonding_period() {
+    let mut cfg: ChainConfig = Default::default();
+    cfg.blocks_in_epoch = 2;
+    let (mut node, _node_api, _loopback) = new_node(cfg.clone());
+    let keys = node.keys.clone();
+
+    let advance_epoch = |node: &mut NodeService| {
+        let epoch = node.chain.epoch();
+        simulate_consensus(node);
+        let block = create_macro_block_with_reward(node, cfg.macro_block_reward);
+        node.apply_new_block(Block::MacroBlock(block)).unwrap();
+        assert_eq!(node.chain.epoch(), epoch + 1);
+    };
+    let is_active = |node: &NodeService, utxo: &Hash| -> bool {
+        node.chain
+            .escrow_info()
+            .validators
+            .iter()
+            .flat_map(|v| v.stakes.iter())
+            .find(|s| &s.utxo == utxo)
+            .expect("stake exists")
+            .is_active
+    };
+
+    let genesis_stake = node
+        .chain
+        .unspent()
+        .map(|hash| node.chain.output_by_hash(&hash).unwrap().unwrap())
+        .find(|output| match output {
+            Output::StakeOutput(_) => true,
+            _ => false,
+        })
+        .expect("genesis has a stake");
+    let genesis_stake_hash = Hash::digest(&genesis_stake);
+
+    // Stake more money in the next epoch, so it expires after the genesis stake.
+    advance_epoch(&mut node);
+    let mut inputs: Vec<Output> = Vec::new();
+    let mut amount: i64 = 0;
+    for hash in node.chain.unspent() {
+        let output = node.chain.output_by_hash(&hash).unwrap().unwrap();
+        if let Output::PaymentOutput(ref o) = output {
+            amount += o.decrypt_payload(&keys.wallet_skey).unwrap().amount;
+            inputs.push(output);
+        }
+    }
+    let stake = cfg.min_stake_amount;
+    let fee = cfg.payment_fee + cfg.stake_fee;
+    let (change, gamma) = Output::new_payment(&keys.wallet_pkey, amount - stake - fee).unwrap();
+    let new_stake = Output::new_stake(
+        &keys.wallet_pkey,
+        &keys.network_skey,
+        &keys.network_pkey,
+        stake,
+    )
+    .unwrap();
+    let new_stake_hash = Hash::digest(&new_stake);
+    let tx: Transaction = PaymentTransaction::new(
+        &keys.wallet_skey,
+        &inputs,
+        &[change, new_stake.clone()],
+        gamma,
+        fee,
+    )
+    .unwrap()
+    .into();
+    node.handle_transaction(tx).unwrap();
+    advance_epoch(&mut node);
+    assert!(is_active(&node, &new_stake_hash));
+
+    // Wait until the genesis stake expires.
+    while is_active(&node, &genesis_stake_hash) {
+        assert!(is_active(&node, &new_stake_hash));
+        advance_epoch(&mut node);
+    }
+    assert!(is_active(&node, &new_stake_hash));
+
+    // The active stake can't be withdrawn, even if expired stakes cover its amount.
+    let fee = cfg.payment_fee;
+    let (output, gamma) = Output::new_payment(&keys.wallet_pkey, stake - fee).unwrap();
+    let tx: Transaction =
+        PaymentTransaction::new(&keys.wallet_skey, &[new_stake], &[output], gamma, fee)
+            .unwrap()
+            .into();
+    let e = node.handle_transaction(tx).unwrap_err();
+    match e.downcast::<BlockchainError>().unwrap() {
+        BlockchainError::StakeIsNotMatured(utxo, active_until_epoch, epoch) => {
+            assert_eq!(utxo, new_stake_hash);
+            assert_eq!(epoch, node.chain.epoch());
+            assert!(active_until_epoch >= epoch);
+        }
+        e => panic!("Unexpected error: {}", e),
+    }
+    assert_eq!(node.mempool.len(), 0);
+
+    // The matured stake can be withdrawn.
+    let (output, gamma) = Output::new_payment(&keys.wallet_pkey, stake - fee).unwrap();
+    let tx: Transaction =
+        PaymentTransaction::new(&keys.wallet_skey, &[genesis_stake], &[output], gamma, fee)
+            .unwrap()
+            .into();
+    let tx_hash = Hash::digest(&tx);
+    node.handle_transaction(tx).unwrap();
+    assert!(node.mempool.contains(&tx_hash));
+}
+
+#[test]
+pub fn strict_reward_validation() {
+    let cfg: ChainConfig = Default::default();
+    assert!(cfg.strict_reward_validation);
+    let (mut node, _node_api, _loopback) = new_node(cfg.clone());
+    let height = node.chain.height();
+
+    let block = create_micro_block_with_reward(&node, SystemTime::now(), 2 * cfg.block_reward);
+    let e = node.apply_new_block(block).unwrap_err();
+    match e.downcast::<NodeBlockError>().unwrap() {
+        NodeBlockError::InvalidBlockReward(block_height, _hash, got, expected) => {
+            assert_eq!(block_height, height);
+            assert_eq!(got, 2 * cfg.block_reward);
+            assert_eq!(expected, cfg.block_reward);
+        }
+        e => panic!("Unexpected error: {}", e),
+    }
+    assert_eq!(node.chain.height(), height);
+    assert!(node.reward_violations.is_empty());
+}
+
+#[test]
+pub fn lenient_reward_validation() {
+    let mut cfg: ChainConfig = Default::default();
+    cfg.strict_reward_validation = false;
+    let (mut node, _node_api, _loopback) = new_node(cfg.clone());
+    let keys = node.keys.clone();
+    let height = node.chain.height();
+
+    // The block is accepted and the violation is recorded.
+    let block = create_micro_block_with_reward(&node, SystemTime::now(), 2 * cfg.block_reward);
+    let hash = Hash::digest(&block);
+    node.apply_new_block(block).unwrap();
+    assert_eq!(node.chain.height(), height + 1);
+    let violation = RewardViolation {
+        height,
+        hash,
+        leader: keys.network_pkey,
+        block_reward: 2 * cfg.block_reward,
+        expected_reward: cfg.block_reward,
+    };
+    assert_eq!(node.reward_violations().violations, vec![violation.clone()]);
+
+    // Blocks with the expected reward are not recorded.
+    let block = create_micro_block_with_timestamp(&node, SystemTime::now());
+    node.apply_new_block(block).unwrap();
+    assert_eq!(node.chain.height(), height + 2);
+    assert_eq!(node.reward_violations.len(), 1);
+
+    // The evidence is discarded with the block.
+    node.pop_micro_block().unwrap();
+    node.pop_micro_block().unwrap();
+    assert_eq!(node.chain.height(), height);
+    assert!(node.reward_violations.is_empty());
+
+    // Only recent violations are kept.
+    for i in 0..MAX_REWARD_VIOLATIONS + 1 {
+        let mut violation = violation.clone();
+        violation.height = i as u64;
+        node.register_reward_violation(violation);
+    }
+    let violations = node.reward_violations().violations;
+    assert_eq!(violations.len(), MAX_REWARD_VIOLATIONS);
+    assert_eq!(violations[0].height, 1);
+}
+
+#[test]
+pub fn auditor_only() {
+    for &auditor_only in &[false, true] {
+        let mut cfg: ChainConfig = Default::default();
+        cfg.blocks_in_epoch = 1;
+        cfg.auditor_only = auditor_only;
+        let (mut node, _node_api, _loopback) = new_node(cfg);
+        let keys = node.keys.clone();
+        // The only validator of the network.
+        assert_eq!(node.chain.leader(), keys.network_pkey);
+        assert_eq!(node.is_leader(), !auditor_only);
+
+        // The next block is a macro block.
+        let block = create_micro_block_with_timestamp(&node, SystemTime::now());
+        node.apply_new_block(block).unwrap();
+        assert_eq!(node.consensus.is_some(), !auditor_only);
+    }
+}
+
+#[test]
+pub fn orphan_blocks() {
+    let cfg: ChainConfig = Default::default();
+    let (mut node, _node_api, _loopback) = new_node(cfg);
+    let height = node.chain.height();
+    assert!(node.orphan_blocks().blocks.is_empty());
+
+    // Create three blocks and revert them.
+    let mut blocks: Vec<Block> = Vec::new();
+    for _ in 0..3 {
+        simulate_consensus(&mut node);
+        blocks.push(node.chain.block_by_height(node.chain.height() - 1).unwrap());
+    }
+    for _ in 0..3 {
+        node.pop_micro_block().unwrap();
+    }
+    assert_eq!(node.chain.height(), height);
+
+    // The first block is missing, the others are buffered.
+    let hashes: Vec<Hash> = blocks.iter().map(Hash::digest).collect();
+    node.handle_sealed_block(None, blocks[2].clone()).unwrap();
+    node.handle_sealed_block(None, blocks[1].clone()).unwrap();
+    assert_eq!(node.chain.height(), height);
+    let orphans = node.orphan_blocks();
+    assert_eq!(orphans.height, height);
+    assert_eq!(orphans.last_block, node.chain.last_block_hash());
+    assert_eq!(
+        orphans.blocks,
+        vec![
+            (height + 1, hashes[1], hashes[0]),
+            (height + 2, hashes[2], hashes[1]),
+        ]
+    );
+
+    // The missing block unlocks the queue.
+    node.handle_sealed_block(None, blocks[0].clone()).unwrap();
+    assert_eq!(node.chain.height(), height + 3);
+    assert!(node.orphan_blocks().blocks.is_empty());
+}
+
+#[test]
+pub fn revert_micro_blocks() {
+    let cfg: ChainConfig = Default::default();
+    let (mut node, _node_api, _loopback) = new_node(cfg);
+    let macro_block_height = node.chain.last_macro_block_height();
+    let height = node.chain.height();
+    simulate_consensus(&mut node);
+    simulate_consensus(&mut node);
+    let last_block_hash = node.chain.last_block_hash();
+
+    // The macro block can't be popped - the chain is left intact.
+    let e = node.revert_micro_blocks(macro_block_height).unwrap_err();
+    match e.downcast::<NodeBlockError>().unwrap() {
+        NodeBlockError::ExpectedMicroBlock(block_height, _hash) => {
+            assert_eq!(block_height, macro_block_height);
+        }
+        e => panic!("Unexpected error: {}", e),
+    }
+    assert_eq!(node.chain.height(), height + 2);
+    assert_eq!(node.chain.last_block_hash(), last_block_hash);
+    assert!(!node.chain_inconsistent);
+    assert!(node.is_leader());
+
+    // Micro blocks are reverted, the latest first.
+    let hashes = node.revert_micro_blocks(height).unwrap();
+    assert_eq!(hashes.len(), 2);
+    assert_eq!(hashes[0], last_block_hash);
+    assert_eq!(node.chain.height(), height);
+    assert!(!node.chain_inconsistent);
+}
+
+#[test]
+pub fn mempool_changed() {
+    let cfg: ChainConfig = Default::default();
+    let (mut node, _node_api, _loopback) = new_node(cfg);
+    let (tx, mut rx) = unbounded();
+    node.handle_subscribe_mempool_changed(tx).unwrap();
+
+    // Nothing is sent without changes.
+    node.flush_mempool_changes();
+
+    // Added.
+    let tx_hash = simulate_payment(&mut node, 100).unwrap();
+    node.flush_mempool_changes();
+    let msg = (&mut rx).wait().next().unwrap().unwrap();
+    assert_eq!(msg.added, vec![tx_hash]);
+    assert!(msg.removed.is_empty());
+
+    // Removed by a block.
+    simulate_consensus(&mut node);
+    node.flush_mempool_changes();
+    let msg = (&mut rx).wait().next().unwrap().unwrap();
+    assert!(msg.added.is_empty());
+    assert_eq!(msg.removed, vec![tx_hash]);
+
+    // Added and cancelled in one batch.
+    let tx_hash = simulate_payment(&mut node, 100).unwrap();
+    node.handle_cancel_transaction(tx_hash).unwrap();
+    node.flush_mempool_changes();
+    let msg = (&mut rx).wait().next().unwrap().unwrap();
+    assert_eq!(msg.added, vec![tx_hash]);
+    assert_eq!(msg.removed, vec![tx_hash]);
+}
+
+#[test]
+pub fn fork_outcomes() {
+    let cfg: ChainConfig = Default::default();
+    let (mut node, _node_api, _loopback) = new_node(cfg);
+    let keys = node.keys.clone();
+    let (tx, mut rx) = unbounded();
+    node.handle_subscribe_fork_resolved(tx).unwrap();
+    simulate_consensus(&mut node);
+    let height = node.chain.height();
+
+    // A block without view change.
+    let block0 = create_micro_block_with_timestamp(&node, SystemTime::now());
+
+    // A local block after a view change.
+    let msg = node
+        .optimistic
+        .handle_timeout(&node.chain)
+        .unwrap()
+        .unwrap();
+    node.handle_view_change(msg).unwrap();
+    assert_eq!(node.chain.height(), height + 1);
+    let block1 = node.chain.block_by_height(height).unwrap();
+    assert_eq!(block1.base_header().view_change, 1);
+    let proof = match block1 {
+        Block::MicroBlock(ref block) => block.view_change_proof.clone().unwrap(),
+        _ => panic!("Expected a micro block"),
+    };
+
+    // The remote block has a lesser view_change.
+    node.handle_sealed_block(None, block0.clone()).unwrap();
+    let msg = (&mut rx).wait().next().unwrap().unwrap();
+    assert_eq!(msg.height, height);
+    assert_eq!(msg.outcome, ForkOutcome::LocalWon);
+
+    // A duplicate of the local block.
+    node.handle_sealed_block(None, block1.clone()).unwrap();
+    let msg = (&mut rx).wait().next().unwrap().unwrap();
+    assert_eq!(msg.outcome, ForkOutcome::Duplicate);
+    assert_eq!(node.chain.height(), height + 1);
+
+    // A proof from an unknown branch.
+    let sealed_proof = SealedViewChangeProof {
+        chain: ChainInfo {
+            height,
+            view_change: 1,
+            last_block: Hash::digest("unknown"),
+        },
+        proof: proof.clone(),
+    };
+    node.handle_view_change_direct(sealed_proof, keys.network_pkey)
+        .unwrap();
+    let msg = (&mut rx).wait().next().unwrap().unwrap();
+    assert_eq!(msg.outcome, ForkOutcome::NeedHistory);
+    assert_eq!(node.chain.height(), height + 1);
+
+    // The remote block has a greater view_change.
+    node.pop_micro_block().unwrap();
+    node.handle_sealed_block(None, block0.clone()).unwrap();
+    assert_eq!(node.chain.last_block_hash(), Hash::digest(&block0));
+    node.handle_sealed_block(None, block1.clone()).unwrap();
+    let msg = (&mut rx).wait().next().unwrap().unwrap();
+    assert_eq!(msg.height, height);
+    assert_eq!(msg.outcome, ForkOutcome::RemoteWon { rolled_back: 1 });
+    assert_eq!(node.chain.height(), height + 1);
+    assert_eq!(node.chain.last_block_hash(), Hash::digest(&block1));
+
+    // Two different blocks for the same slot.
+    let timestamp = SystemTime::now();
+    let block2 = create_micro_block_with_timestamp(&node, timestamp);
+    let block3 = create_micro_block_with_timestamp(&node, timestamp + Duration::from_millis(1));
+    node.handle_sealed_block(None, block2.clone()).unwrap();
+    node.handle_sealed_block(None, block3.clone()).unwrap();
+    let msg = (&mut rx).wait().next().unwrap().unwrap();
+    assert_eq!(msg.height, height + 1);
+    assert_eq!(
+        msg.outcome,
+        ForkOutcome::CheatDetected {
+            offender: keys.network_pkey
+        }
+    );
+    assert_eq!(node.chain.last_block_hash(), Hash::digest(&block2));
+}
+
+fn coinbase_amount(block: &MicroBlock, skey: &curve1174::SecretKey) -> i64 {
+    let mut mined: i64 = 0;
+    for output in &block.coinbase.outputs {
+        match output {
+            Output::PaymentOutput(o) => {
+                let PaymentPayload { amount, .. } = o.decrypt_payload(skey).unwrap();
+                mined += amount;
+            }
+            _ => panic!("Unexpected output in coinbase"),
+        }
+    }
+    mined
+}
+
+#[test]
+pub fn fee_policy_reward_leader() {
+    let mut cfg: ChainConfig = Default::default();
+    cfg.fee_policy = FeePolicy::RewardLeader;
+    let (mut node, _node_api, _loopback) = new_node(cfg);
+    let keys = node.keys.clone();
+
+    simulate_payment(&mut node, 100).unwrap();
+    simulate_consensus(&mut node);
+    let block = match node.chain.last_block().unwrap() {
+        Block::MicroBlock(block) => block,
+        Block::MacroBlock(_) => panic!("Expected a micro block"),
+    };
+    assert_eq!(block.transactions.len(), 1);
+    assert!(block.coinbase.block_fee > 0);
+    assert_eq!(
+        coinbase_amount(&block, &keys.wallet_skey),
+        block.coinbase.block_reward + block.coinbase.block_fee
+    );
+}
+
+#[test]
+pub fn fee_policy_burn() {
+    let mut cfg: ChainConfig = Default::default();
+    cfg.fee_policy = FeePolicy::Burn;
+    let (mut node, _node_api, _loopback) = new_node(cfg.clone());
+    let keys = node.keys.clone();
+
+    simulate_payment(&mut node, 100).unwrap();
+    let height = node.chain.height();
+
+    // A block which pays fees to the leader is rejected.
+    let mut block = node.mempool.create_block(
+        node.chain.last_block_hash(),
+        VERSION,
+        height,
+        cfg.block_reward,
+        FeePolicy::RewardLeader,
+        &node.keys,
+        node.chain.last_random(),
+        node.chain.view_change(),
+        None,
+        cfg.max_utxo_in_block,
+        cfg.max_block_bytes,
+    );
+    block.sign(&keys.network_skey, &keys.network_pkey);
+    let e = node.apply_new_block(Block::MicroBlock(block)).unwrap_err();
+    match e.downcast::<BlockError>().unwrap() {
+        BlockError::InvalidBlockBalance(block_height, _hash) => {
+            assert_eq!(block_height, height);
+        }
+        e => panic!("Unexpected error: {}", e),
+    }
+    assert_eq!(node.chain.height(), height);
+
+    // Fees are recorded in the coinbase, but only the reward is paid.
+    simulate_consensus(&mut node);
+    assert_eq!(node.chain.height(), height + 1);
+    let block = match node.chain.last_block().unwrap() {
+        Block::MicroBlock(block) => block,
+        Block::MacroBlock(_) => panic!("Expected a micro block"),
+    };
+    assert_eq!(block.transactions.len(), 1);
+    assert!(block.coinbase.block_fee > 0);
+    assert_eq!(
+        coinbase_amount(&block, &keys.wallet_skey),
+        block.coinbase.block_reward
+    );
+}
+
+#[test]
+pub fn request_history_backoff() {
+    let peer = KeyChain::new_mem().network_pkey;
+
+    let mut cfg: ChainConfig = Default::default();
+    cfg.loader_timeout = Duration::from_secs(10);
+    cfg.max_loader_timeout = Duration::from_secs(35);
+    let (mut node, _node_api, mut loopback) = new_node(cfg.clone());
+    assert_eq!(node.loader_backoff(), cfg.loader_timeout);
+
+    // Rapid repeated calls send only one request.
+    node.last_sync_clock = clock::now() - cfg.max_loader_timeout;
+    for _ in 0..10 {
+        node.request_history_from(peer).unwrap();
+    }
+    let _msg: ChainLoaderMessage = loopback.get_unicast(loader::CHAIN_LOADER_TOPIC, &peer);
+    loopback.assert_empty_queue();
+    assert_eq!(node.loader_backoff(), cfg.loader_timeout);
+
+    // The interval doubles while requests make no progress.
+    node.last_sync_clock = clock::now() - cfg.loader_timeout;
+    node.request_history_from(peer).unwrap();
+    let _msg: ChainLoaderMessage = loopback.get_unicast(loader::CHAIN_LOADER_TOPIC, &peer);
+    assert_eq!(node.loader_backoff(), 2 * cfg.loader_timeout);
+    node.last_sync_clock = clock::now() - cfg.loader_timeout;
+    node.request_history_from(peer).unwrap();
+    loopback.assert_empty_queue();
+
+    // The interval is bounded by max_loader_timeout.
+    for _ in 0..3 {
+        node.last_sync_clock = clock::now() - cfg.max_loader_timeout;
+        node.request_history_from(peer).unwrap();
+        let _msg: ChainLoaderMessage = loopback.get_unicast(loader::CHAIN_LOADER_TOPIC, &peer);
+    }
+    assert_eq!(node.loader_backoff(), cfg.max_loader_timeout);
+
+    // A new block resets the backoff.
+    simulate_consensus(&mut node);
+    assert_eq!(node.loader_backoff(), cfg.loader_timeout);
+}
+
+#[test]
+pub fn request_history_rotation() {
+    let keys = KeyChain::new_mem();
+    let keys1 = KeyChain::new_mem();
+    let keys2 = KeyChain::new_mem();
+
+    let cfg: ChainConfig = Default::default();
+    assert_eq!(cfg.loader_requests_per_peer, 1);
+    let (mut node, _node_api, mut loopback) =
+        new_node_with_validators(cfg.clone(), &[keys, keys1.clone(), keys2.clone()]);
+    let peer1 = keys1.network_pkey;
+    let peer2 = keys2.network_pkey;
+
+    // The first request goes to the requested peer.
+    node.last_sync_clock = clock::now() - cfg.max_loader_timeout;
+    node.request_history_from(peer1).unwrap();
+    let _msg: ChainLoaderMessage = loopback.get_unicast(loader::CHAIN_LOADER_TOPIC, &peer1);
+    loopback.assert_empty_queue();
+
+    // The peer doesn't respond - the retry goes to another validator.
+    node.last_sync_clock = clock::now() - cfg.max_loader_timeout;
+    node.request_history_from(peer1).unwrap();
+    let _msg: ChainLoaderMessage = loopback.get_unicast(loader::CHAIN_LOADER_TOPIC, &peer2);
+    loopback.assert_empty_queue();
+    assert_eq!(node.loader_backoff(), 2 * cfg.loader_timeout);
+
+    // All validators have been tried - start over.
+    node.last_sync_clock = clock::now() - cfg.max_loader_timeout;
+    node.request_history_from(peer1).unwrap();
+    let _msg: ChainLoaderMessage = loopback.get_unicast(loader::CHAIN_LOADER_TOPIC, &peer1);
+    loopback.assert_empty_queue();
+}
+
+#[test]
+pub fn resync() {
+    let cfg: ChainConfig = Default::default();
+    let (mut node, _node_api, mut loopback) = new_node(cfg.clone());
+    let leader = node.chain.leader();
+
+    // The loader throttling is bypassed.
+    let info = node.resync().unwrap();
+    assert!(info.requested);
+    assert_eq!(info.height, node.chain.height());
+    let _msg: ChainLoaderMessage = loopback.get_unicast(loader::CHAIN_LOADER_TOPIC, &leader);
+    loopback.assert_empty_queue();
+
+    // Repeated requests are throttled.
+    let info = node.resync().unwrap();
+    assert!(!info.requested);
+    loopback.assert_empty_queue();
+
+    // Resync is possible again after resync_timeout.
+    node.last_resync_clock = Some(clock::now() - cfg.resync_timeout);
+    let info = node.resync().unwrap();
+    assert!(info.requested);
+    let _msg: ChainLoaderMessage = loopback.get_unicast(loader::CHAIN_LOADER_TOPIC, &leader);
+}
+
+#[test]
+pub fn orphan_blocks_request_sender() {
+    let peer = KeyChain::new_mem().network_pkey;
+
+    let cfg: ChainConfig = Default::default();
+    let (mut node, _node_api, mut loopback) = new_node(cfg.clone());
+    let height = node.chain.height();
+
+    // Create two blocks and revert them.
+    let mut blocks: Vec<Block> = Vec::new();
+    for _ in 0..2 {
+        simulate_consensus(&mut node);
+        blocks.push(node.chain.block_by_height(node.chain.height() - 1).unwrap());
+    }
+    for _ in 0..2 {
+        node.pop_micro_block().unwrap();
+    }
+    assert_eq!(node.chain.height(), height);
+    loopback.filter_broadcast(&[SEALED_BLOCK_TOPIC]);
+    loopback.assert_empty_queue();
+
+    // History is requested from the node which sent the orphan.
+    node.last_sync_clock = clock::now() - cfg.max_loader_timeout;
+    node.handle_sealed_block(Some(peer), blocks[1].clone())
+        .unwrap();
+    assert_eq!(node.chain.height(), height);
+    let _msg: ChainLoaderMessage = loopback.get_unicast(loader::CHAIN_LOADER_TOPIC, &peer);
+    loopback.assert_empty_queue();
+
+    // The missing block unlocks the queue.
+    node.handle_sealed_block(None, blocks[0].clone()).unwrap();
+    assert_eq!(node.chain.height(), height + 2);
+}
+
+#[test]
+pub fn blocks_in_epoch_mismatch() {
+    simple_logger::init_with_level(log::Level::Debug).unwrap_or_default();
+    let keys = KeyChain::new_mem();
+
+    let mut cfg: ChainConfig = Default::default();
+    cfg.blocks_in_epoch = 1;
+    let timestamp = SystemTime::now();
+    let genesis = genesis(
+        &[keys.clone()],
+        cfg.min_stake_amount,
+        1000 * cfg.min_stake_amount,
+        timestamp,
+    );
+
+    // Zero is never valid.
+    let mut bad_cfg = cfg.clone();
+    bad_cfg.blocks_in_epoch = 0;
+    let chain = Blockchain::testing(cfg.clone().into(), genesis.clone(), timestamp)
+        .expect("Failed to create blockchain");
+    let (_loopback, network) = Loopback::new();
+    let e = NodeService::new(bad_cfg, chain, keys.clone(), network)
+        .err()
+        .unwrap();
+    assert_eq!(
+        e.downcast::<NodeConfigError>().unwrap(),
+        NodeConfigError::ZeroBlocksInEpoch
+    );
+
+    // Any positive value agrees with the genesis.
+    let chain = Blockchain::testing(cfg.clone().into(), genesis.clone(), timestamp)
+        .expect("Failed to create blockchain");
+    let (_loopback, network) = Loopback::new();
+    let (mut node, _node_api) =
+        NodeService::new(cfg.clone(), chain, keys.clone(), network).unwrap();
+    let mut bad_cfg = cfg.clone();
+    bad_cfg.blocks_in_epoch = 2;
+    validate_blocks_in_epoch(&bad_cfg, &node.chain).unwrap();
+
+    // A macro block at height 1 contradicts blocks_in_epoch = 2.
+    let block = Block::MacroBlock(create_macro_block_with_reward(&node, 0));
+    node.apply_new_block(block.clone()).unwrap();
+    validate_blocks_in_epoch(&cfg, &node.chain).unwrap();
+    match validate_blocks_in_epoch(&bad_cfg, &node.chain).unwrap_err() {
+        NodeConfigError::BlocksInEpochMismatch(
+            blocks_in_epoch,
+            epoch,
+            last_macro_block_height,
+            _,
+        ) => {
+            assert_eq!(blocks_in_epoch, 2);
+            assert_eq!(epoch, node.chain.epoch());
+            assert_eq!(
+                last_macro_block_height,
+                node.chain.last_macro_block_height()
+            );
+        }
+        e => panic!("Unexpected error: {}", e),
+    }
+
+    // The same block is fine as a part of the genesis.
+    let mut genesis = genesis;
+    genesis.push(block);
+    let chain = Blockchain::testing(cfg.clone().into(), genesis, timestamp)
+        .expect("Failed to create blockchain");
+    assert_eq!(chain.genesis_epochs(), 2);
+    assert_eq!(chain.last_genesis_macro_block_height(), 1);
+    validate_blocks_in_epoch(&cfg, &chain).unwrap();
+    validate_blocks_in_epoch(&bad_cfg, &chain).unwrap();
+}
+
+#[test]
+pub fn invalid_bft_threshold() {
+    let cfg: ChainConfig = Default::default();
+    validate_bft_threshold(&cfg).unwrap();
+
+    for &(numerator, denominator) in &[(1, 0), (-1, 3), (3, 3), (4, 3), (1, -3)] {
+        let mut bad_cfg = cfg.clone();
+        bad_cfg.bft_threshold = BftThreshold {
+            numerator,
+            denominator,
+        };
+        assert_eq!(
+            validate_bft_threshold(&bad_cfg).unwrap_err(),
+            NodeConfigError::InvalidBftThreshold(numerator, denominator)
+        );
+    }
+
+    // The node refuses to start.
+    let keys = KeyChain::new_mem();
+    let timestamp = SystemTime::now();
+    let genesis = genesis(
+        &[keys.clone()],
+        cfg.min_stake_amount,
+        1000 * cfg.min_stake_amount,
+        timestamp,
+    );
+    let chain = Blockchain::testing(cfg.clone().into(), genesis, timestamp)
+        .expect("Failed to create blockchain");
+    let (_loopback, network) = Loopback::new();
+    let mut bad_cfg = cfg.clone();
+    bad_cfg.bft_threshold.numerator = bad_cfg.bft_threshold.denominator;
+    let e = NodeService::new(bad_cfg, chain, keys, network)
+        .err()
+        .unwrap();
+    assert_eq!(
+        e.downcast::<NodeConfigError>().unwrap(),
+        NodeConfigError::InvalidBftThreshold(3, 3)
+    );
+}
+
+#[test]
+pub fn invalid_timeouts() {
+    let mut cfg: ChainConfig = Default::default();
+    validate_timeouts(&cfg).unwrap();
+
+    // empty_block_timeout is checked only if empty blocks are skipped.
+    cfg.empty_block_timeout = cfg.micro_block_timeout;
+    validate_timeouts(&cfg).unwrap();
+    cfg.skip_empty_blocks = true;
+    assert_eq!(
+        validate_timeouts(&cfg).unwrap_err(),
+        NodeConfigError::EmptyBlockTimeoutTooLong(cfg.micro_block_timeout, cfg.micro_block_timeout)
+    );
+    cfg.empty_block_timeout = cfg.micro_block_timeout - Duration::from_millis(1);
+    validate_timeouts(&cfg).unwrap();
+
+    // The leader of a view change must propose before the next view change.
+    let mut bad_cfg = cfg.clone();
+    bad_cfg.view_change_tx_wait_timeout = cfg.micro_block_timeout;
+    assert_eq!(
+        validate_timeouts(&bad_cfg).unwrap_err(),
+        NodeConfigError::ViewChangeTxWaitTimeoutTooLong(
+            cfg.micro_block_timeout,
+            cfg.micro_block_timeout
+        )
+    );
+    bad_cfg.view_change_tx_wait_timeout = cfg.micro_block_timeout - Duration::from_millis(1);
+    validate_timeouts(&bad_cfg).unwrap();
+
+    // The node refuses to start.
+    cfg.empty_block_timeout = cfg.micro_block_timeout + Duration::from_millis(1);
+    let keys = KeyChain::new_mem();
+    let timestamp = SystemTime::now();
+    let genesis = genesis(
+        &[keys.clone()],
+        cfg.min_stake_amount,
+        1000 * cfg.min_stake_amount,
+        timestamp,
+    );
+    let chain = Blockchain::testing(cfg.clone().into(), genesis, timestamp)
+        .expect("Failed to create blockchain");
+    let (_loopback, network) = Loopback::new();
+    let e = NodeService::new(cfg.clone(), chain, keys, network)
+        .err()
+        .unwrap();
+    assert_eq!(
+        e.downcast::<NodeConfigError>().unwrap(),
+        NodeConfigError::EmptyBlockTimeoutTooLong(cfg.empty_block_timeout, cfg.micro_block_timeout)
+    );
+}
+
+#[test]
+pub fn block_rate_limit() {
+    let peer1 = KeyChain::new_mem().network_pkey;
+    let peer2 = KeyChain::new_mem().network_pkey;
+
+    let mut cfg: ChainConfig = Default::default();
+    cfg.max_blocks_per_second = 1;
+    cfg.max_blocks_burst = 1;
+    let (mut node, _node_api, mut loopback) = new_node(cfg);
+    let height = node.chain.height();
+
+    // Create two blocks and revert them.
+    let mut blocks: Vec<Block> = Vec::new();
+    for _ in 0..2 {
+        simulate_consensus(&mut node);
+        blocks.push(node.chain.block_by_height(node.chain.height() - 1).unwrap());
+    }
+    for _ in 0..2 {
+        node.pop_micro_block().unwrap();
+    }
+    assert_eq!(node.chain.height(), height);
+
+    // Blocks with invalid signatures don't consume the budget of the producer.
+    let mut forged = blocks[1].clone();
+    if let Block::MicroBlock(ref mut forged) = forged {
+        forged.sig = pbc::Signature::zero();
+    }
+    node.handle_broadcast_block(Some(peer1), forged)
+        .unwrap_err();
+
+    // The first block from the producer is queued.
+    node.handle_broadcast_block(Some(peer1), blocks[1].clone())
+        .unwrap();
+    assert!(node.future_blocks.contains_key(&(height + 1)));
+
+    // The next block from the same producer is dropped, whoever has sent it.
+    let limited = metrics::RATE_LIMITED_BLOCKS.get();
+    node.handle_broadcast_block(Some(peer2), blocks[0].clone())
+        .unwrap();
+    assert!(metrics::RATE_LIMITED_BLOCKS.get() > limited);
+    assert_eq!(node.chain.height(), height);
+
+    // Forged blocks of a rate limited producer are dropped before signature checks.
+    let mut forged = blocks[0].clone();
+    if let Block::MicroBlock(ref mut forged) = forged {
+        forged.sig = pbc::Signature::zero();
+    }
+    let sealed_hash = sealed_block_hash(&forged);
+    node.handle_broadcast_block(Some(peer1), forged).unwrap();
+    assert!(!node.rejected_blocks.contains_key(&sealed_hash));
+
+    // Blocks from the chain loader are not limited.
+    node.handle_sealed_block(Some(peer2), blocks[0].clone())
+        .unwrap();
+    assert_eq!(node.chain.height(), height + 2);
+
+    loopback.filter_broadcast(&[SEALED_BLOCK_TOPIC]);
+    loopback.filter_unicast(&[loader::CHAIN_LOADER_TOPIC]);
+    loopback.assert_empty_queue();
+}
+
+#[test]
+pub fn transaction_by_hash() {
+    let mut cfg: ChainConfig = Default::default();
+    cfg.blocks_in_epoch = 100;
+    cfg.confirmation_depth = 1;
+    let (mut node, _node_api, _loopback) = new_node(cfg);
+    let height = node.chain.height();
+
+    // Unknown.
+    let location = node.transaction_by_hash(Hash::digest("unknown")).unwrap();
+    assert_eq!(location.status, TransactionLocationStatus::Unknown);
+    assert_eq!(location.height, None);
+
+    // Pending.
+    let tx_hash = simulate_payment(&mut node, 100).unwrap();
+    let location = node.transaction_by_hash(tx_hash).unwrap();
+    assert_eq!(location.tx_hash, tx_hash);
+    assert_eq!(location.status, TransactionLocationStatus::Pending);
+    assert_eq!(location.height, None);
+
+    // Included into a block.
+    simulate_consensus(&mut node);
+    let location = node.transaction_by_hash(tx_hash).unwrap();
+    assert_eq!(location.status, TransactionLocationStatus::InBlock);
+    assert_eq!(location.height, Some(height));
+
+    // Buried under confirmation_depth blocks.
+    simulate_consensus(&mut node);
+    let location = node.transaction_by_hash(tx_hash).unwrap();
+    assert_eq!(location.status, TransactionLocationStatus::Committed);
+    assert_eq!(location.height, Some(height));
+}
+
+#[test]
+pub fn transaction_pending() {
+    let cfg: ChainConfig = Default::default();
+    let (mut node, _node_api, _loopback) = new_node(cfg);
+
+    // Pushed to the mempool.
+    let tx_hash = simulate_payment(&mut node, 100).unwrap();
+    let info = node.transaction_pending(tx_hash);
+    assert_eq!(info.tx_hash, tx_hash);
+    assert!(info.pending);
+    assert!(node.mempool.get(&tx_hash).is_some());
+
+    // Pruned by a block.
+    simulate_consensus(&mut node);
+    let info = node.transaction_pending(tx_hash);
+    assert!(!info.pending);
+    assert!(node.mempool.get(&tx_hash).is_none());
+}
+
+#[test]
+pub fn duplicate_transaction() {
+    let cfg: ChainConfig = Default::default();
+    let (mut node, _node_api, _loopback) = new_node(cfg);
+
+    let tx_hash = simulate_payment(&mut node, 100).unwrap();
+    let tx = node.mempool.get(&tx_hash).unwrap();
+    assert_eq!(node.mempool.len(), 1);
+
+    // The second identical submission is a no-op.
+    node.handle_transaction(tx.clone()).unwrap();
+    assert_eq!(node.mempool.len(), 1);
+
+    // Without revalidation.
+    node.handle_transaction_async(tx.clone()).unwrap();
+    assert!(node.pending_transactions.is_empty());
+    assert_eq!(node.mempool.len(), 1);
+
+    // A pruned transaction is accepted again.
+    node.mempool.remove_tx(&tx_hash).unwrap();
+    assert!(!node.mempool.contains(&tx_hash));
+    node.handle_transaction(tx).unwrap();
+    assert!(node.mempool.contains(&tx_hash));
+}
+
+#[test]
+pub fn merge_mempool() {
+    let cfg: ChainConfig = Default::default();
+    let (mut node, _node_api, _loopback) = new_node(cfg);
+    let (tx, mut rx) = unbounded();
+    node.handle_subscribe_transaction_status(tx).unwrap();
+
+    let tx_hash1 = simulate_payment(&mut node, 100).unwrap();
+    let tx1 = node.mempool.get(&tx_hash1).unwrap();
+    let msg = (&mut rx).wait().next().unwrap().unwrap();
+    assert_eq!(msg.status, TransactionStatus::Accepted {});
+
+    // Duplicates are skipped.
+    let mut other = Mempool::new();
+    other.push_tx(tx_hash1, tx1);
+    assert!(node.merge_mempool(&other).is_empty());
+    assert_eq!(node.mempool.len(), 1);
+
+    // A conflict which doesn't pay more is rejected.
+    let tx2 = create_payment(&node, 200, 0).unwrap();
+    let tx_hash2 = Hash::digest(&tx2);
+    let mut other = Mempool::new();
+    other.push_tx(tx_hash2, tx2.clone());
+    assert!(node.merge_mempool(&other).is_empty());
+    assert!(node.mempool.contains(&tx_hash1));
+    assert!(!node.mempool.contains(&tx_hash2));
+    let e = node.merge_transaction(tx2).unwrap_err();
+    match e.downcast::<NodeTransactionError>().unwrap() {
+        NodeTransactionError::ConflictsWithMempool(tx_hash, fee, conflicts_fee) => {
+            assert_eq!(tx_hash, tx_hash2);
+            assert_eq!(fee, conflicts_fee);
+        }
+        e => panic!("{}", e),
+    }
+
+    // A conflict which pays more replaces the transaction.
+    let tx3 = create_payment(&node, 300, node.cfg.payment_fee).unwrap();
+    let tx_hash3 = Hash::digest(&tx3);
+    let mut other = Mempool::new();
+    other.push_tx(tx_hash3, tx3);
+    assert_eq!(node.merge_mempool(&other), vec![tx_hash3]);
+    assert!(!node.mempool.contains(&tx_hash1));
+    assert!(node.mempool.contains(&tx_hash3));
+    assert_eq!(node.mempool.len(), 1);
+    let msg = (&mut rx).wait().next().unwrap().unwrap();
+    assert_eq!(msg.tx_hash, tx_hash1);
+    assert_eq!(msg.status, TransactionStatus::Replaced { by: tx_hash3 });
+    let msg = (&mut rx).wait().next().unwrap().unwrap();
+    assert_eq!(msg.tx_hash, tx_hash3);
+    assert_eq!(msg.status, TransactionStatus::Accepted {});
+
+    // The replacement is mined.
+    simulate_consensus(&mut node);
+    assert_eq!(node.mempool.len(), 0);
+}
+
+#[test]
+pub fn consensus_message_version() {
+    let cfg: ChainConfig = Default::default();
+    let (mut node, _node_api, _loopback) = new_node(cfg);
+    let keys = node.keys.clone();
+
+    let height = node.chain.height();
+    let mut msg: BlockConsensusMessage = ConsensusMessage::new(
+        height,
+        0,
+        Hash::digest("test"),
+        &keys.network_skey,
+        &keys.network_pkey,
+        ConsensusMessageBody::Prevote {},
+    );
+    msg.version = CONSENSUS_PROTOCOL_VERSION + 1;
+    let e = node.handle_consensus_message(msg).unwrap_err();
+    match e.downcast::<ConsensusError>().unwrap() {
+        ConsensusError::UnsupportedVersion(version, supported) => {
+            assert_eq!(version, CONSENSUS_PROTOCOL_VERSION + 1);
+            assert_eq!(supported, CONSENSUS_PROTOCOL_VERSION);
+        }
+        e => panic!("unexpected error: {}", e),
+    }
+    assert_eq!(node.future_consensus_messages.len(), 0);
+}
+
+#[test]
+pub fn future_consensus_message_bounds() {
+    let cfg: ChainConfig = Default::default();
+    let (mut node, _node_api, _loopback) = new_node(cfg.clone());
+    let keys = node.keys.clone();
+    assert!(node.consensus.is_none());
+
+    let prevote = |height: u64, round: u32| -> BlockConsensusMessage {
+        ConsensusMessage::new(
+            height,
+            round,
+            Hash::digest("test"),
+            &keys.network_skey,
+            &keys.network_pkey,
+            ConsensusMessageBody::Prevote {},
+        )
+    };
+
+    // An absurd height is not queued.
+    let e = node
+        .handle_consensus_message(prevote(u64::max_value(), 0))
+        .unwrap_err();
+    match e.downcast::<ConsensusError>().unwrap() {
+        ConsensusError::HeightOutOfBounds(height, _max_height) => {
+            assert_eq!(height, u64::max_value());
+        }
+        e => panic!("unexpected error: {}", e),
+    }
+    assert_eq!(node.future_consensus_messages.len(), 0);
+
+    // An absurd round is not queued.
+    let height = node.chain.last_macro_block_height() + cfg.blocks_in_epoch;
+    let e = node
+        .handle_consensus_message(prevote(height, u32::max_value()))
+        .unwrap_err();
+    match e.downcast::<ConsensusError>().unwrap() {
+        ConsensusError::RoundOutOfBounds(round, max_round) => {
+            assert_eq!(round, u32::max_value());
+            assert_eq!(max_round, MAX_CONSENSUS_ROUNDS_AHEAD);
+        }
+        e => panic!("unexpected error: {}", e),
+    }
+    assert_eq!(node.future_consensus_messages.len(), 0);
+
+    // A message for the next macro block is queued.
+    node.handle_consensus_message(prevote(height, 0)).unwrap();
+    assert_eq!(node.future_consensus_messages.len(), 1);
+}
+
+#[test]
+pub fn underpaid_transactions() {
+    let mut cfg: ChainConfig = Default::default();
+    // Distinct fees, so the reported minimum tells which one was applied.
+    cfg.stake_fee = 2 * cfg.payment_fee;
+    let (mut node, _node_api, _loopback) = new_node(cfg.clone());
+    let keys = node.keys.clone();
+
+    let mut inputs: Vec<Output> = Vec::new();
+    let mut amount: i64 = 0;
+    for hash in node.chain.unspent() {
+        let output = node.chain.output_by_hash(&hash).unwrap().unwrap();
+        if let Output::PaymentOutput(ref o) = output {
+            amount += o.decrypt_payload(&keys.wallet_skey).unwrap().amount;
+            inputs.push(output);
+        }
+    }
+
+    // Underpaid and negative fees for a payment.
+    for fee in &[cfg.payment_fee - 1, -1] {
+        let fee = *fee;
+        let (output, gamma) = Output::new_payment(&keys.wallet_pkey, amount - fee).unwrap();
+        let tx: Transaction =
+            PaymentTransaction::unchecked(&keys.wallet_skey, &inputs, &[output], gamma, fee)
+                .unwrap()
+                .into();
+        let e = node.handle_transaction(tx.clone()).unwrap_err();
+        match e.downcast::<NodeTransactionError>().unwrap() {
+            NodeTransactionError::TooLowFee(tx_hash, min, got) => {
+                assert_eq!(tx_hash, Hash::digest(&tx));
+                assert_eq!(min, cfg.payment_fee);
+                assert_eq!(got, fee);
+            }
+            e => panic!("Unexpected error: {}", e),
+        }
+        assert_eq!(node.mempool.len(), 0);
+    }
+
+    // A stake paying only the payment fee is underpaid.
+    let fee = cfg.payment_fee;
+    let output = Output::new_stake(
+        &keys.wallet_pkey,
+        &keys.network_skey,
+        &keys.network_pkey,
+        amount - fee,
+    )
+    .unwrap();
+    let tx: Transaction =
+        PaymentTransaction::unchecked(&keys.wallet_skey, &inputs, &[output], Fr::zero(), fee)
+            .unwrap()
+            .into();
+    let e = node.handle_transaction(tx.clone()).unwrap_err();
+    match e.downcast::<NodeTransactionError>().unwrap() {
+        NodeTransactionError::TooLowFee(tx_hash, min, got) => {
+            assert_eq!(tx_hash, Hash::digest(&tx));
+            assert_eq!(min, cfg.stake_fee);
+            assert_eq!(got, fee);
+        }
+        e => panic!("Unexpected error: {}", e),
+    }
+    assert_eq!(node.mempool.len(), 0);
+}
+
+#[test]
+pub fn fee_params() {
+    // Fees of the wallet are out of date.
+    let stale_payment_fee: i64 = 1;
+    let mut cfg: ChainConfig = Default::default();
+    cfg.payment_fee = 10 * stale_payment_fee;
+    let (mut node, _node_api, _loopback) = new_node(cfg.clone());
+    let keys = node.keys.clone();
+
+    let params = node.fee_params();
+    assert_eq!(params.payment_fee, cfg.payment_fee);
+    assert_eq!(params.stake_fee, cfg.stake_fee);
+
+    let mut inputs: Vec<Output> = Vec::new();
+    let mut amount: i64 = 0;
+    for hash in node.chain.unspent() {
+        let output = node.chain.output_by_hash(&hash).unwrap().unwrap();
+        if let Output::PaymentOutput(ref o) = output {
+            amount += o.decrypt_payload(&keys.wallet_skey).unwrap().amount;
+            inputs.push(output);
+        }
+    }
+
+    // Stale fee is rejected.
+    let fee = stale_payment_fee;
+    let (output, gamma) = Output::new_payment(&keys.wallet_pkey, amount - fee).unwrap();
+    let tx: Transaction =
+        PaymentTransaction::new(&keys.wallet_skey, &inputs, &[output], gamma, fee)
+            .unwrap()
+            .into();
+    let e = node.handle_transaction(tx).unwrap_err();
+    match e.downcast::<NodeTransactionError>().unwrap() {
+        NodeTransactionError::TooLowFee(_tx_hash, min, got) => {
+            assert_eq!(min, params.payment_fee);
+            assert_eq!(got, stale_payment_fee);
+        }
+        e => panic!("Unexpected error: {}", e),
+    }
+    assert_eq!(node.mempool.len(), 0);
+
+    // Queried fee is accepted.
+    let fee = params.payment_fee;
+    let (output, gamma) = Output::new_payment(&keys.wallet_pkey, amount - fee).unwrap();
+    let tx: Transaction =
+        PaymentTransaction::new(&keys.wallet_skey, &inputs, &[output], gamma, fee)
+            .unwrap()
+            .into();
+    let tx_hash = Hash::digest(&tx);
+    node.handle_transaction(tx).expect("transaction is valid");
+    assert!(node.mempool.contains(&tx_hash));
+}
+
+#[test]
+pub fn health() {
+    let mut cfg: ChainConfig = Default::default();
+    // One payment with change fills the mempool.
+    cfg.max_utxo_in_mempool = 3;
+    let (mut node, _node_api, _loopback) = new_node(cfg);
+
+    let health = node.health();
+    assert!(health.synchronized);
+    assert_eq!(health.height, node.chain.height());
+    assert!(health.mempool_ok);
+    assert!(!health.network_ready);
+    assert!(health.validator_active);
+
+    node.is_network_ready = true;
+    assert!(node.health().network_ready);
+
+    simulate_payment(&mut node, 10).unwrap();
+    assert_eq!(node.mempool.inputs_len() + node.mempool.outputs_len(), 3);
+    assert!(!node.health().mempool_ok);
+
+    simulate_consensus(&mut node);
+    let health = node.health();
+    assert!(health.mempool_ok);
+    assert_eq!(health.height, node.chain.height());
+}
+
+#[test]
+pub fn epoch_finalized() {
+    let mut cfg: ChainConfig = Default::default();
+    cfg.blocks_in_epoch = 2;
+    cfg.macro_block_reward = 1_000_000;
+    let (mut node, _node_api, _loopback) = new_node(cfg.clone());
+    let (tx, rx) = unbounded();
+    node.handle_subscribe_epoch_finalized(tx).unwrap();
+    let epoch = node.chain.epoch();
+
+    // Micro blocks don't finalize the epoch.
+    simulate_consensus(&mut node);
+    let micro_block_reward = cfg.reward_at_height(node.chain.height() - 1);
+
+    // The macro block does.
+    let block = create_macro_block_with_reward(&node, cfg.macro_block_reward);
+    node.apply_new_block(Block::MacroBlock(block)).unwrap();
+    assert_eq!(node.chain.epoch(), epoch + 1);
+    let validators = node.chain.validators().clone();
+
+    drop(node);
+    let events: Vec<EpochFinalized> = rx.wait().map(Result::unwrap).collect();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].epoch, epoch);
+    assert_eq!(
+        events[0].total_reward,
+        micro_block_reward + cfg.macro_block_reward
+    );
+    assert_eq!(events[0].validators, validators);
+}
+
+#[test]
+pub fn coinbase_maturity() {
+    let mut cfg: ChainConfig = Default::default();
+    cfg.coinbase_maturity = 2;
+    let (mut node, _node_api, _loopback) = new_node(cfg.clone());
+    let keys = node.keys.clone();
+
+    // Outputs of the genesis block are not block rewards.
+    for hash in node.chain.unspent() {
+        assert_eq!(node.chain.coinbase_height(hash), None);
+    }
+
+    // Create a block with a reward.
+    let height = node.chain.height();
+    simulate_consensus(&mut node);
+    assert_eq!(node.chain.height(), height + 1);
+    let (reward, amount) = node
+        .chain
+        .unspent()
+        .filter(|hash| node.chain.coinbase_height(hash) == Some(height))
+        .map(|hash| node.chain.output_by_hash(&hash).unwrap().unwrap())
+        .find_map(|output| match output {
+            Output::PaymentOutput(ref o) => {
+                let amount = o.decrypt_payload(&keys.wallet_skey).unwrap().amount;
+                Some((output, amount))
+            }
+            _ => None,
+        })
+        .expect("block has a reward");
+    let reward_hash = Hash::digest(&reward);
+
+    let fee = cfg.payment_fee;
+    let (output, gamma) = Output::new_payment(&keys.wallet_pkey, amount - fee).unwrap();
+    let tx: Transaction =
+        PaymentTransaction::new(&keys.wallet_skey, &[reward], &[output], gamma, fee)
+            .unwrap()
+            .into();
+    let tx_hash = Hash::digest(&tx);
+
+    // The reward is spent too early.
+    let e = node.handle_transaction(tx.clone()).unwrap_err();
+    match e.downcast::<NodeTransactionError>().unwrap() {
+        NodeTransactionError::ImmatureCoinbase(e_tx_hash, utxo, confirmations, required) => {
+            assert_eq!(e_tx_hash, tx_hash);
+            assert_eq!(utxo, reward_hash);
+            assert_eq!(confirmations, 1);
+            assert_eq!(required, cfg.coinbase_maturity);
+        }
+        e => panic!("Unexpected error: {}", e),
+    }
+    assert_eq!(node.mempool.len(), 0);
+
+    // Blocks can't spend the reward too early either.
+    let mut block = match create_micro_block_with_timestamp(&node, SystemTime::now()) {
+        Block::MicroBlock(block) => block,
+        Block::MacroBlock(_) => unreachable!(),
+    };
+    block.transactions.push(tx.clone());
+    block.sign(&keys.network_skey, &keys.network_pkey);
+    let e = node
+        .chain
+        .validate_micro_block(&block, SystemTime::now())
+        .unwrap_err();
+    match e {
+        BlockchainError::TransactionError(TransactionError::ImmatureCoinbase(
+            e_tx_hash,
+            utxo,
+            confirmations,
+            required,
+        )) => {
+            assert_eq!(e_tx_hash, tx_hash);
+            assert_eq!(utxo, reward_hash);
+            assert_eq!(confirmations, 1);
+            assert_eq!(required, cfg.coinbase_maturity);
+        }
+        e => panic!("Unexpected error: {}", e),
+    }
+
+    // The reward is spent after maturity.
+    simulate_consensus(&mut node);
+    assert_eq!(node.chain.height(), height + 2);
+    node.handle_transaction(tx).expect("transaction is valid");
+    assert!(node.mempool.contains(&tx_hash));
+}
+
+#[test]
+pub fn inclusion_estimate() {
+    let cfg: ChainConfig = Default::default();
+    let (mut node, _node_api, _loopback) = new_node(cfg.clone());
+    let keys = node.keys.clone();
+
+    // Empty mempool.
+    let estimate = node.inclusion_estimate(cfg.payment_fee);
+    assert_eq!(estimate.blocks, Some(1));
+    assert_eq!(estimate.utxo_ahead, 0);
+    assert_eq!(estimate.block_capacity, cfg.max_utxo_in_block - 3);
+    assert_eq!(node.inclusion_estimate(cfg.payment_fee - 1).blocks, None);
+
+    // Queue a transaction.
+    let mut inputs: Vec<Output> = Vec::new();
+    let mut amount: i64 = 0;
+    for hash in node.chain.unspent() {
+        let output = node.chain.output_by_hash(&hash).unwrap().unwrap();
+        if let Output::PaymentOutput(ref o) = output {
+            amount += o.decrypt_payload(&keys.wallet_skey).unwrap().amount;
+            inputs.push(output);
+        }
+    }
+    let fee = cfg.payment_fee;
+    let (output, gamma) = Output::new_payment(&keys.wallet_pkey, amount - fee).unwrap();
+    let tx: Transaction =
+        PaymentTransaction::new(&keys.wallet_skey, &inputs, &[output.clone()], gamma, fee)
+            .unwrap()
+            .into();
+    node.handle_transaction(tx).expect("transaction is valid");
+    assert_eq!(node.mempool.len(), 1);
+
+    // Any fee waits for the queued transaction.
+    for fee in &[fee, fee + 1] {
+        let estimate = node.inclusion_estimate(*fee);
+        assert_eq!(estimate.blocks, Some(1));
+        assert_eq!(estimate.utxo_ahead, inputs.len() + 1);
+    }
+
+    // A transaction which spends the queued one is skipped by the block builder.
+    let (output2, gamma2) = Output::new_payment(&keys.wallet_pkey, amount - 2 * fee).unwrap();
+    let tx2: Transaction =
+        PaymentTransaction::new(&keys.wallet_skey, &[output], &[output2], gamma2, fee)
+            .unwrap()
+            .into();
+    node.mempool.push_tx(Hash::digest(&tx2), tx2);
+    assert_eq!(node.mempool.len(), 2);
+    let estimate = node.inclusion_estimate(fee);
+    assert_eq!(estimate.blocks, Some(1));
+    assert_eq!(estimate.utxo_ahead, inputs.len() + 1);
+}
@@ -26,8 +26,11 @@ use crate::error::*;
 use crate::mempool::Mempool;
 use failure::Error;
 use log::*;
+use std::collections::HashSet;
 use std::time::SystemTime;
-use stegos_blockchain::{Blockchain, MacroBlock, Output, Transaction, TransactionError};
+use stegos_blockchain::{
+    BftThreshold, Block, Blockchain, MacroBlock, Output, Transaction, TransactionError,
+};
 use stegos_crypto::hash::Hash;
 
 ///
@@ -40,11 +43,42 @@ pub(crate) fn validate_transaction(
     _timestamp: SystemTime,
     payment_fee: i64,
     stake_fee: i64,
+    coinbase_maturity: u64,
 ) -> Result<(), Error> {
+    let inputs = validate_transaction_state(
+        tx,
+        mempool,
+        chain,
+        payment_fee,
+        stake_fee,
+        coinbase_maturity,
+    )?;
+
+    // Check the monetary balance, Bulletpoofs/amounts and signature.
+    let staking_balance = tx.validate(&inputs)?;
+
+    // Checks staking balance.
+    chain.validate_staking_balance(&inputs, &staking_balance)?;
+
+    Ok(())
+}
+
+///
+/// Validate transaction against the current state of mempool and blockchain.
+/// Returns resolved inputs. Cryptographic checks are left to tx.validate().
+///
+pub(crate) fn validate_transaction_state(
+    tx: &Transaction,
+    mempool: &Mempool,
+    chain: &Blockchain,
+    payment_fee: i64,
+    stake_fee: i64,
+    coinbase_maturity: u64,
+) -> Result<Vec<Output>, Error> {
     let tx_hash = Hash::digest(tx);
 
     // Check that transaction exists in the mempool.
-    if mempool.contains_tx(&tx_hash) {
+    if mempool.contains(&tx_hash) {
         return Err(NodeTransactionError::AlreadyExists(tx_hash).into());
     }
 
@@ -62,12 +96,18 @@ pub(crate) fn validate_transaction(
     }
 
     let mut inputs: Vec<Output> = Vec::new();
+    let mut input_hashes: HashSet<Hash> = HashSet::new();
 
     // TODO: allow transaction with overlapping inputs/outputs in mempool.
     // See https://github.com/stegos/stegos/issues/826.
 
     // Check for overlapping inputs in mempool.
     for input_hash in tx.txins() {
+        // Check that the input is not spent twice by this transaction.
+        if !input_hashes.insert(*input_hash) {
+            return Err(NodeTransactionError::DuplicateInput(tx_hash, *input_hash).into());
+        }
+
         // Check that the input can be resolved.
         let input = match chain.output_by_hash(input_hash)? {
             Some(input) => input,
@@ -76,6 +116,22 @@ pub(crate) fn validate_transaction(
             }
         };
 
+        // Check that block rewards are matured.
+        if coinbase_maturity > 0 {
+            if let Some(height) = chain.coinbase_height(input_hash) {
+                let confirmations = chain.height() - height;
+                if confirmations < coinbase_maturity {
+                    return Err(NodeTransactionError::ImmatureCoinbase(
+                        tx_hash,
+                        *input_hash,
+                        confirmations,
+                        coinbase_maturity,
+                    )
+                    .into());
+                }
+            }
+        }
+
         // Check that the input is not claimed by other transactions.
         if mempool.contains_input(input_hash) {
             return Err(TransactionError::MissingInput(tx_hash, input_hash.clone()).into());
@@ -93,13 +149,7 @@ pub(crate) fn validate_transaction(
         }
     }
 
-    // Check the monetary balance, Bulletpoofs/amounts and signature.
-    let staking_balance = tx.validate(&inputs)?;
-
-    // Checks staking balance.
-    chain.validate_staking_balance(staking_balance.iter())?;
-
-    Ok(())
+    Ok(inputs)
 }
 
 fn vetted_timestamp(
@@ -137,6 +187,99 @@ fn vetted_timestamp(
     Ok(())
 }
 
+///
+/// Check that the block is not stamped too far in the future
+/// and not earlier than the previous block.
+///
+pub(crate) fn validate_block_timestamp(
+    cfg: &ChainConfig,
+    block: &Block,
+    previous_timestamp: SystemTime,
+) -> Result<(), Error> {
+    let header = block.base_header();
+    if header.timestamp < previous_timestamp {
+        return Err(NodeBlockError::NonMonotonicTimestamp(
+            header.height,
+            previous_timestamp,
+            header.timestamp,
+        )
+        .into());
+    }
+
+    let timestamp = SystemTime::now();
+    if let Ok(drift) = header.timestamp.duration_since(timestamp) {
+        if drift > cfg.max_clock_drift {
+            return Err(NodeBlockError::TimestampInFuture(
+                header.height,
+                Hash::digest(block),
+                drift,
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+///
+/// Check that the configured epoch length agrees with the chain.
+/// Macro blocks are expected every blocks_in_epoch blocks, starting from
+/// the last macro block of the genesis. Epochs of the genesis can be of any length.
+///
+pub(crate) fn validate_blocks_in_epoch(
+    cfg: &ChainConfig,
+    chain: &Blockchain,
+) -> Result<(), NodeConfigError> {
+    if cfg.blocks_in_epoch == 0 {
+        return Err(NodeConfigError::ZeroBlocksInEpoch);
+    }
+    let epoch = chain.epoch();
+    let last_macro_block_height = chain.last_macro_block_height();
+    let expected_height = chain.last_genesis_macro_block_height()
+        + epoch.saturating_sub(chain.genesis_epochs()) * cfg.blocks_in_epoch;
+    if last_macro_block_height != expected_height || chain.blocks_in_epoch() > cfg.blocks_in_epoch {
+        return Err(NodeConfigError::BlocksInEpochMismatch(
+            cfg.blocks_in_epoch,
+            epoch,
+            last_macro_block_height,
+            chain.height(),
+        ));
+    }
+    Ok(())
+}
+
+///
+/// Check that the configured BFT threshold is a fraction in [0, 1).
+///
+pub(crate) fn validate_bft_threshold(cfg: &ChainConfig) -> Result<(), NodeConfigError> {
+    let BftThreshold {
+        numerator,
+        denominator,
+    } = cfg.bft_threshold;
+    if denominator <= 0 || numerator < 0 || numerator >= denominator {
+        return Err(NodeConfigError::InvalidBftThreshold(numerator, denominator));
+    }
+    Ok(())
+}
+
+///
+/// Check that the configured timeouts of the leader don't cause view changes.
+///
+pub(crate) fn validate_timeouts(cfg: &ChainConfig) -> Result<(), NodeConfigError> {
+    if cfg.skip_empty_blocks && cfg.empty_block_timeout >= cfg.micro_block_timeout {
+        return Err(NodeConfigError::EmptyBlockTimeoutTooLong(
+            cfg.empty_block_timeout,
+            cfg.micro_block_timeout,
+        ));
+    }
+    if cfg.view_change_tx_wait_timeout >= cfg.micro_block_timeout {
+        return Err(NodeConfigError::ViewChangeTxWaitTimeoutTooLong(
+            cfg.view_change_tx_wait_timeout,
+            cfg.micro_block_timeout,
+        ));
+    }
+    Ok(())
+}
+
 ///
 /// Validate proposed macro block.
 ///
@@ -159,6 +302,15 @@ pub(crate) fn validate_proposed_macro_block(
         )
         .into());
     }
+    if block.header.block_reward != cfg.macro_block_reward {
+        return Err(NodeBlockError::InvalidBlockReward(
+            block.header.base.height,
+            block_hash,
+            block.header.block_reward,
+            cfg.macro_block_reward,
+        )
+        .into());
+    }
     vetted_timestamp(block, cfg, chain.last_macro_block_timestamp())?;
     chain.validate_macro_block(block, block.header.base.timestamp, true)?;
 
@@ -226,6 +378,7 @@ mod test {
                 timestamp,
                 payment_fee,
                 stake_fee,
+                0,
             )
             .expect("transaction is valid");
         }
@@ -239,7 +392,7 @@ mod test {
             let tx = PaymentTransaction::new(&skey, &inputs, &[output], gamma, fee)
                 .unwrap()
                 .into();
-            validate_transaction(&tx, &mempool, &chain, timestamp, payment_fee, stake_fee)
+            validate_transaction(&tx, &mempool, &chain, timestamp, payment_fee, stake_fee, 0)
                 .expect("transaction is valid");
         }
 
@@ -252,8 +405,9 @@ mod test {
             let tx = PaymentTransaction::unchecked(&skey, &inputs, &[output], gamma, fee)
                 .unwrap()
                 .into();
-            let e = validate_transaction(&tx, &mempool, &chain, timestamp, payment_fee, stake_fee)
-                .expect_err("transaction is not valid");
+            let e =
+                validate_transaction(&tx, &mempool, &chain, timestamp, payment_fee, stake_fee, 0)
+                    .expect_err("transaction is not valid");
             match e.downcast::<NodeTransactionError>().unwrap() {
                 NodeTransactionError::TooLowFee(tx_hash, min, got) => {
                     assert_eq!(tx_hash, Hash::digest(&tx));
@@ -275,8 +429,9 @@ mod test {
             let tx = PaymentTransaction::new(&skey, &[input], &[output], outputs_gamma, fee)
                 .unwrap()
                 .into();
-            let e = validate_transaction(&tx, &mempool, &chain, timestamp, payment_fee, stake_fee)
-                .expect_err("transaction is not valid");
+            let e =
+                validate_transaction(&tx, &mempool, &chain, timestamp, payment_fee, stake_fee, 0)
+                    .expect_err("transaction is not valid");
             match e.downcast::<TransactionError>().unwrap() {
                 TransactionError::MissingInput(_tx_hash, hash) => {
                     assert_eq!(hash, missing);
@@ -302,8 +457,9 @@ mod test {
             mempool.push_tx(Hash::digest(&tx), tx.clone());
 
             // TX hash is unique.
-            let e = validate_transaction(&tx, &mempool, &chain, timestamp, payment_fee, stake_fee)
-                .expect_err("transaction is not valid");
+            let e =
+                validate_transaction(&tx, &mempool, &chain, timestamp, payment_fee, stake_fee, 0)
+                    .expect_err("transaction is not valid");
             match e.downcast::<NodeTransactionError>().expect("proper error") {
                 NodeTransactionError::AlreadyExists(tx_hash) => {
                     assert_eq!(tx_hash, Hash::digest(&tx));
@@ -318,8 +474,9 @@ mod test {
                     .unwrap()
                     .into()
             };
-            let e = validate_transaction(&tx2, &mempool, &chain, timestamp, payment_fee, stake_fee)
-                .expect_err("transaction is not valid");
+            let e =
+                validate_transaction(&tx2, &mempool, &chain, timestamp, payment_fee, stake_fee, 0)
+                    .expect_err("transaction is not valid");
             match e.downcast::<TransactionError>().expect("proper error") {
                 TransactionError::MissingInput(_tx_hash, hash) => {
                     assert_eq!(hash, input_hashes[0]);
@@ -328,9 +485,9 @@ mod test {
             }
 
             mempool.prune(&input_hashes, &output_hashes);
-            validate_transaction(&tx, &mempool, &chain, timestamp, payment_fee, stake_fee)
+            validate_transaction(&tx, &mempool, &chain, timestamp, payment_fee, stake_fee, 0)
                 .expect("transaction is valid");
-            validate_transaction(&tx2, &mempool, &chain, timestamp, payment_fee, stake_fee)
+            validate_transaction(&tx2, &mempool, &chain, timestamp, payment_fee, stake_fee, 0)
                 .expect("transaction is valid");
         }
 
@@ -345,7 +502,7 @@ mod test {
             let tx = PaymentTransaction::new(&skey, &inputs, &[output], Fr::zero(), fee)
                 .unwrap()
                 .into();
-            validate_transaction(&tx, &mempool, &chain, timestamp, payment_fee, stake_fee)
+            validate_transaction(&tx, &mempool, &chain, timestamp, payment_fee, stake_fee, 0)
                 .expect("transaction is valid");
         }
 
@@ -365,8 +522,9 @@ mod test {
             let tx = PaymentTransaction::unchecked(&skey, &inputs, &outputs, outputs_gamma, fee)
                 .unwrap()
                 .into();
-            let e = validate_transaction(&tx, &mempool, &chain, timestamp, payment_fee, stake_fee)
-                .expect_err("transaction is not valid");
+            let e =
+                validate_transaction(&tx, &mempool, &chain, timestamp, payment_fee, stake_fee, 0)
+                    .expect_err("transaction is not valid");
             match e.downcast::<OutputError>().expect("proper error") {
                 OutputError::InvalidStake(_output_hash) => {}
                 _ => panic!(),
@@ -383,8 +541,9 @@ mod test {
             let tx = PaymentTransaction::unchecked(&skey, &stakes, &[output], outputs_gamma, fee)
                 .unwrap()
                 .into();
-            let e = validate_transaction(&tx, &mempool, &chain, timestamp, payment_fee, stake_fee)
-                .expect_err("transaction is not valid");
+            let e =
+                validate_transaction(&tx, &mempool, &chain, timestamp, payment_fee, stake_fee, 0)
+                    .expect_err("transaction is not valid");
             match e.downcast::<BlockchainError>().expect("proper error") {
                 BlockchainError::StakeIsLocked(
                     validator_pkey2,
@@ -410,10 +569,60 @@ mod test {
             let tx = PaymentTransaction::unchecked(&skey, &stakes, &[output], Fr::zero(), 0)
                 .unwrap()
                 .into();
-            validate_transaction(&tx, &mempool, &chain, timestamp, payment_fee, 0)
+            validate_transaction(&tx, &mempool, &chain, timestamp, payment_fee, 0, 0)
                 .expect("transaction is valid");
         }
 
+        //
+        // Duplicate input.
+        //
+        {
+            let fee = payment_fee;
+            let input = inputs[0].clone();
+            let input_hash = Hash::digest(&input);
+            let (output, outputs_gamma) = Output::new_payment(&pkey, amount - fee).unwrap();
+            let tx = PaymentTransaction::unchecked(
+                &skey,
+                &[input.clone(), input],
+                &[output],
+                outputs_gamma,
+                fee,
+            )
+            .unwrap()
+            .into();
+            let e =
+                validate_transaction(&tx, &mempool, &chain, timestamp, payment_fee, stake_fee, 0)
+                    .expect_err("transaction is not valid");
+            match e.downcast::<NodeTransactionError>().expect("proper error") {
+                NodeTransactionError::DuplicateInput(tx_hash, hash) => {
+                    assert_eq!(tx_hash, Hash::digest(&tx));
+                    assert_eq!(hash, input_hash);
+                }
+                _ => panic!(),
+            }
+        }
+
+        //
+        // Output hash collision in blockchain.
+        //
+        {
+            let fee = payment_fee;
+            let output = inputs[0].clone();
+            let output_hash = Hash::digest(&output);
+            let tx = PaymentTransaction::unchecked(&skey, &inputs, &[output], Fr::zero(), fee)
+                .unwrap()
+                .into();
+            let e =
+                validate_transaction(&tx, &mempool, &chain, timestamp, payment_fee, stake_fee, 0)
+                    .expect_err("transaction is not valid");
+            match e.downcast::<TransactionError>().expect("proper error") {
+                TransactionError::OutputHashCollision(_tx_hash, hash) => {
+                    assert_eq!(hash, output_hash);
+                }
+                _ => panic!(),
+            }
+        }
+
         //
         // Output hash collision in mempool.
         //
@@ -431,8 +640,9 @@ mod test {
             let tx = PaymentTransaction::unchecked(&skey, &inputs, &outputs, outputs_gamma, fee)
                 .unwrap()
                 .into();
-            let e = validate_transaction(&tx, &mempool, &chain, timestamp, payment_fee, stake_fee)
-                .expect_err("transaction is not valid");
+            let e =
+                validate_transaction(&tx, &mempool, &chain, timestamp, payment_fee, stake_fee, 0)
+                    .expect_err("transaction is not valid");
             match e.downcast::<TransactionError>().expect("proper error") {
                 TransactionError::OutputHashCollision(_tx_hash, hash) => {
                     assert_eq!(hash, output_hashes[0]);
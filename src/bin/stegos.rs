@@ -25,7 +25,7 @@ use atty;
 use clap;
 use clap::{App, Arg, ArgMatches};
 use dirs;
-use failure::Error;
+use failure::{format_err, Error};
 use futures::Future;
 use hyper::server::Server;
 use hyper::service::service_fn_ok;
@@ -34,7 +34,7 @@ use log::*;
 use prometheus::{self, Encoder};
 use std::path::PathBuf;
 use std::process;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 use stegos_api::WebSocketAPI;
 use stegos_blockchain::Blockchain;
 use stegos_keychain::*;
@@ -45,6 +45,7 @@ use stegos_wallet::WalletService;
 use tokio::runtime::Runtime;
 
 use crate::console::*;
+use crate::seed_pool::{refresh_pool, DnsSeedPoolSource, SeedPoolResolver};
 
 fn load_configuration_file(args: &ArgMatches<'_>) -> Result<config::Config, Error> {
     // Use --config argument for configuration.
@@ -85,7 +86,7 @@ pub fn load_configuration(args: &ArgMatches<'_>) -> Result<config::Config, Error
         cfg.general.chain = chain.to_string();
     }
     // Use default SRV record for the chain
-    if cfg.general.chain != "dev" && cfg.network.seed_pool == "" {
+    if cfg.general.chain != "dev" && cfg.general.chain != "custom" && cfg.network.seed_pool == "" {
         cfg.network.seed_pool =
             format!("_stegos._tcp.{}.aws.stegos.com", cfg.general.chain).to_string();
     }
@@ -100,6 +101,12 @@ pub fn load_configuration(args: &ArgMatches<'_>) -> Result<config::Config, Error
         cfg.keychain.recovery_file = recovery_file.to_string();
     }
 
+    if cfg.network.seed_pool_refresh_interval == 0 {
+        return Err(format_err!(
+            "network.seed_pool_refresh_interval must be greater than zero"
+        ));
+    }
+
     Ok(cfg)
 }
 
@@ -179,7 +186,7 @@ fn run() -> Result<(), Error> {
                 .short("n")
                 .long("chain")
                 .value_name("NAME")
-                .help("Specify chain to use: testnet or dev")
+                .help("Specify chain to use: testnet, dev or custom")
                 .takes_value(true),
         )
         .get_matches();
@@ -188,7 +195,7 @@ fn run() -> Result<(), Error> {
     let mut cfg = load_configuration(&args)?;
 
     // Initialize logger
-    initialize_logger(&cfg)?;
+    let log_levels = initialize_logger(&cfg)?;
 
     // Print welcome message
     info!("{} {}", name, version);
@@ -204,6 +211,13 @@ fn run() -> Result<(), Error> {
     let (network, network_service) = Libp2pNetwork::new(&cfg.network, &keychain)?;
     rt.spawn(network_service);
 
+    // Keep looking for new nodes in seed pool
+    if cfg.network.seed_pool != "" {
+        let resolver = SeedPoolResolver::new(&cfg.network, DnsSeedPoolSource);
+        let interval = Duration::from_secs(cfg.network.seed_pool_refresh_interval);
+        rt.spawn(refresh_pool(resolver, network.clone(), interval)?);
+    }
+
     // Start metrics exporter
     if cfg.general.prometheus_endpoint != "" {
         // Prepare HTTP service to export Prometheus metrics
@@ -235,21 +249,28 @@ fn run() -> Result<(), Error> {
 
     // Initialize Wallet.
     let (wallet_service, wallet) = WalletService::new(
+        cfg.wallet.clone(),
         keychain.clone(),
         network.clone(),
         node.clone(),
         cfg.chain.payment_fee,
         cfg.chain.stake_fee,
         cfg.chain.stake_epochs,
+        cfg.chain.max_utxo_in_tx,
         wallet_persistent_state,
-    );
+    )?;
     rt.spawn(wallet_service);
 
     // Don't initialize REPL if stdin is not a TTY device
     if atty::is(atty::Stream::Stdin) {
         // Initialize console
-        let console_service =
-            ConsoleService::new(&cfg.general, network.clone(), wallet.clone(), node.clone())?;
+        let console_service = ConsoleService::new(
+            &cfg.general,
+            network.clone(),
+            wallet.clone(),
+            node.clone(),
+            log_levels,
+        )?;
         rt.spawn(console_service);
     }
 
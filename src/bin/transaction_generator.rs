@@ -51,7 +51,7 @@ pub fn load_configuration(folder: &str) -> Result<config::Config, Error> {
     }
 
     // Use default SRV record for the chain
-    if cfg.general.chain != "dev" && cfg.network.seed_pool == "" {
+    if cfg.general.chain != "dev" && cfg.general.chain != "custom" && cfg.network.seed_pool == "" {
         cfg.network.seed_pool =
             format!("_stegos._tcp.{}.aws.stegos.com", cfg.general.chain).to_string();
     }
@@ -218,14 +218,16 @@ fn run() -> Result<(), Error> {
         info!("Starting wallet with generator.");
         // Initialize Wallet.
         let (wallet_service, wallet) = WalletService::new(
+            cfg.wallet.clone(),
             keychain.clone(),
             network.clone(),
             node.clone(),
             cfg.chain.payment_fee,
             cfg.chain.stake_fee,
             cfg.chain.stake_epochs,
+            cfg.chain.max_utxo_in_tx,
             wallet_persistent_state,
-        );
+        )?;
         rt.spawn(wallet_service);
 
         let bot = Generator::new(wallet, cfg.general.generate_txs, mode, true);
@@ -35,6 +35,7 @@ use stegos_crypto::curve1174::PublicKey;
 use stegos_keychain::KeyChainConfig;
 use stegos_network::NetworkConfig;
 use stegos_node::ChainConfig;
+use stegos_wallet::WalletConfig;
 use toml;
 
 /// Configuration root
@@ -59,6 +60,8 @@ pub struct Config {
     pub storage: StorageConfig,
     /// WebSocket API configuration.
     pub api: WebSocketConfig,
+    /// Wallet configuration.
+    pub wallet: WalletConfig,
 }
 
 /// Default values for global configuration.
@@ -71,6 +74,7 @@ impl Default for Config {
             keychain: Default::default(),
             storage: Default::default(),
             api: Default::default(),
+            wallet: Default::default(),
         }
     }
 }
@@ -81,6 +85,8 @@ impl Default for Config {
 pub struct GeneralConfig {
     /// Chain name.
     pub chain: String,
+    /// Path to the genesis block for "custom" chain.
+    pub genesis_path: String,
     /// Log4RS configuration file
     pub log4rs_config: String,
     /// Prometheus exporter endpoint
@@ -93,6 +99,7 @@ impl Default for GeneralConfig {
     fn default() -> Self {
         GeneralConfig {
             chain: "testnet".to_string(),
+            genesis_path: "".to_string(),
             log4rs_config: "stegos-log4rs.toml".to_string(),
             prometheus_endpoint: "".to_string(),
             generate_txs: Vec::new(),
@@ -23,6 +23,7 @@ use crate::config::GeneralConfig;
 use crate::consts;
 use crate::generator::{Generator, GeneratorMode};
 use crate::money::{format_money, parse_money};
+use crate::LogLevels;
 use dirs;
 use failure::Error;
 use futures::sync::mpsc::UnboundedReceiver;
@@ -37,6 +38,7 @@ use std::fmt;
 use std::path::PathBuf;
 use std::thread;
 use stegos_crypto::curve1174::PublicKey;
+use stegos_crypto::hash::Hash;
 use stegos_crypto::pbc;
 use stegos_network::Network;
 use stegos_network::UnicastMessage;
@@ -66,6 +68,8 @@ lazy_static! {
     static ref PUBLISH_COMMAND_RE: Regex = Regex::new(r"\s*(?P<topic>[0-9A-Za-z]+)\s+(?P<msg>.*)$").unwrap();
     /// Regex to parse "send" command.
     static ref SEND_COMMAND_RE: Regex = Regex::new(r"\s*(?P<recipient>[0-9a-f]+)\s+(?P<msg>.+)$").unwrap();
+    /// Regex to parse "log level" command.
+    static ref LOG_LEVEL_COMMAND_RE: Regex = Regex::new(r"\s*(?P<target>[0-9A-Za-z_:]+)\s+(?P<level>[A-Za-z]+)\s*$").unwrap();
 }
 
 const CONSOLE_PROTOCOL_ID: &'static str = "console";
@@ -78,6 +82,8 @@ pub struct ConsoleService {
     wallet: Wallet,
     /// Node API.
     node: Node,
+    /// Log levels.
+    log_levels: LogLevels,
     /// Generator.
     generator: Option<Generator>,
     /// Wallet events.
@@ -101,6 +107,7 @@ impl ConsoleService {
         network: Network,
         wallet: Wallet,
         node: Node,
+        log_levels: LogLevels,
     ) -> Result<ConsoleService, Error> {
         let (tx, rx) = channel::<String>(1);
         let wallet_notifications = wallet.subscribe();
@@ -130,6 +137,7 @@ impl ConsoleService {
             stdin_th,
             unicast_rx,
             node,
+            log_levels,
         };
         Ok(service)
     }
@@ -194,10 +202,23 @@ impl ConsoleService {
         println!("show utxo - print unspent outputs");
         println!("show election - print leader election state");
         println!("show escrow - print escrow");
+        println!("show fee - print recommended fee per output");
+        println!("show fee params - print minimal fees accepted by the node");
+        println!("show inclusion FEE - print blocks until a transaction paying FEE per output is included");
+        println!("show participation - print validators participation in macro blocks");
+        println!("show violations - print recent blocks accepted with an unexpected reward");
+        println!("show leaders - print leaders of the next view changes");
+        println!("show validators - print validators of the current epoch");
+        println!("show viewchange - print the proof of the last view change");
+        println!("show orphans - print blocks waiting for their predecessors");
+        println!("show tx TX_HASH - print the status of a transaction");
         println!("show recovery - print recovery information");
+        println!("show health - print health of the node");
         println!("net publish TOPIC MESSAGE - publish a network message via floodsub");
         println!("net send NETWORK_PUBKEY MESSAGE - send a network message via unicast");
         println!("db pop block - revert the latest block");
+        println!("db resync - request missing blocks from peers");
+        println!("log level TARGET LEVEL - change log level of the target");
         println!("generator start LIST_OF_WALLETS_ADDRESSES - start transaction generator");
         println!("generator stop - stop transaction generator");
         println!();
@@ -253,6 +274,13 @@ impl ConsoleService {
         println!();
     }
 
+    fn help_log_level() {
+        println!("Usage: log level TARGET LEVEL");
+        println!(" - TARGET log target, e.g. stegos_node");
+        println!(" - LEVEL one of [off, error, warn, info, debug, trace]");
+        println!();
+    }
+
     fn help_generator() {
         println!("Usage: generator SUBCOMMAND");
         println!(" - start or stop transaction generator");
@@ -527,10 +555,59 @@ impl ConsoleService {
             self.wallet_response = Some(self.wallet.request(request));
         } else if msg == "show election" {
             let request = NodeRequest::ElectionInfo {};
-            self.node_response = Some(self.node.request(request));
+            return self.node_request(request);
         } else if msg == "show escrow" {
             let request = NodeRequest::EscrowInfo {};
-            self.node_response = Some(self.node.request(request));
+            return self.node_request(request);
+        } else if msg == "show health" {
+            let request = NodeRequest::Health {};
+            return self.node_request(request);
+        } else if msg == "show fee params" {
+            let request = NodeRequest::FeeParams {};
+            return self.node_request(request);
+        } else if msg == "show fee" {
+            let request = NodeRequest::FeeEstimate {};
+            return self.node_request(request);
+        } else if msg.starts_with("show inclusion ") {
+            let fee = msg[15..].trim();
+            let fee = match parse_money(fee) {
+                Ok(fee) => fee,
+                Err(e) => {
+                    println!("Invalid fee '{}': {}", fee, e);
+                    return true;
+                }
+            };
+            let request = NodeRequest::InclusionEstimate { fee };
+            return self.node_request(request);
+        } else if msg == "show participation" {
+            let request = NodeRequest::ParticipationInfo {};
+            return self.node_request(request);
+        } else if msg == "show violations" {
+            let request = NodeRequest::RewardViolations {};
+            return self.node_request(request);
+        } else if msg == "show leaders" {
+            let request = NodeRequest::LeaderSchedule { count: 10 };
+            return self.node_request(request);
+        } else if msg == "show validators" {
+            let request = NodeRequest::ValidatorSet {};
+            return self.node_request(request);
+        } else if msg == "show viewchange" {
+            let request = NodeRequest::ViewChangeProof {};
+            return self.node_request(request);
+        } else if msg == "show orphans" {
+            let request = NodeRequest::OrphanBlocks {};
+            return self.node_request(request);
+        } else if msg.starts_with("show tx ") {
+            let hash = msg[8..].trim();
+            let hash = match Hash::try_from_hex(hash) {
+                Ok(h) => h,
+                Err(e) => {
+                    println!("Invalid transaction hash '{}': {}", hash, e);
+                    return true;
+                }
+            };
+            let request = NodeRequest::TransactionByHash { hash };
+            return self.node_request(request);
         } else if msg == "show utxo" {
             let request = WalletRequest::UnspentInfo {};
             self.wallet_response = Some(self.wallet.request(request));
@@ -538,7 +615,27 @@ impl ConsoleService {
             let request = WalletRequest::GetRecovery {};
             self.wallet_response = Some(self.wallet.request(request));
         } else if msg == "db pop block" {
-            self.node.pop_block();
+            if let Err(e) = self.node.pop_block() {
+                println!("Failed to pop block: {}", e);
+            }
+            return true;
+        } else if msg == "db resync" {
+            let request = NodeRequest::Resync {};
+            return self.node_request(request);
+        } else if msg.starts_with("log level ") {
+            let caps = match LOG_LEVEL_COMMAND_RE.captures(&msg[10..]) {
+                Some(c) => c,
+                None => {
+                    Self::help_log_level();
+                    return true;
+                }
+            };
+            let target = caps.name("target").unwrap().as_str();
+            let level = caps.name("level").unwrap().as_str();
+            if let Err(e) = self.log_levels.set(target, level) {
+                println!("{}", e);
+                Self::help_log_level();
+            }
             return true;
         } else {
             Self::help();
@@ -547,6 +644,20 @@ impl ConsoleService {
         return false; // keep stdin parked until result is received.
     }
 
+    /// Send a request to the node and wait for the response.
+    fn node_request(&mut self, request: NodeRequest) -> bool {
+        match self.node.request(request) {
+            Ok(rx) => {
+                self.node_response = Some(rx);
+                false // keep stdin parked until result is received.
+            }
+            Err(e) => {
+                println!("Failed to execute node request: {}", e);
+                true
+            }
+        }
+    }
+
     fn on_exit(&self) {
         std::process::exit(0);
     }
@@ -555,6 +666,23 @@ impl ConsoleService {
         let output = match info {
             NodeResponse::EscrowInfo(info) => serde_yaml::to_string(&[info]),
             NodeResponse::ElectionInfo(info) => serde_yaml::to_string(&[info]),
+            NodeResponse::FeeEstimate(info) => serde_yaml::to_string(&[info]),
+            NodeResponse::ParticipationInfo(info) => serde_yaml::to_string(&[info]),
+            NodeResponse::LeaderSchedule(info) => serde_yaml::to_string(&[info]),
+            NodeResponse::ValidatorSet(info) => serde_yaml::to_string(&[info]),
+            NodeResponse::UtxoSnapshot(info) => serde_yaml::to_string(&[info]),
+            NodeResponse::ViewChangeProof(info) => serde_yaml::to_string(&[info]),
+            NodeResponse::EpochHistory(info) => serde_yaml::to_string(&[info]),
+            NodeResponse::RawBlock(info) => serde_yaml::to_string(&[info]),
+            NodeResponse::OrphanBlocks(info) => serde_yaml::to_string(&[info]),
+            NodeResponse::Resync(info) => serde_yaml::to_string(&[info]),
+            NodeResponse::TransactionLocation(info) => serde_yaml::to_string(&[info]),
+            NodeResponse::Pending(info) => serde_yaml::to_string(&[info]),
+            NodeResponse::FeeParams(info) => serde_yaml::to_string(&[info]),
+            NodeResponse::Health(info) => serde_yaml::to_string(&[info]),
+            NodeResponse::InclusionEstimate(info) => serde_yaml::to_string(&[info]),
+            NodeResponse::RewardViolations(info) => serde_yaml::to_string(&[info]),
+            NodeResponse::Error { error } => Ok(format!("Error: {}", error)),
         }
         .map_err(|_| fmt::Error)
         .unwrap();
@@ -24,26 +24,124 @@ pub mod console;
 pub mod consts;
 pub mod generator;
 pub mod money;
+pub mod seed_pool;
 
+use crate::seed_pool::{DnsSeedPoolSource, SeedPoolResolver};
 use failure::format_err;
 use failure::Error;
 use log::*;
 use log4rs::append::console::ConsoleAppender;
+use log4rs::append::Append;
 use log4rs::config::{Appender, Config as LogConfig, Logger, Root};
 use log4rs::encode::pattern::PatternEncoder;
 use log4rs::{Error as LogError, Handle as LogHandle};
-use resolve::{config::DnsConfig, record::Srv, resolver};
+use std::collections::BTreeMap;
+use std::fs;
 use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
 use stegos_blockchain::Block;
 use stegos_crypto::hash::Hash;
 use stegos_serialization::traits::*;
 
-pub fn initialize_logger(cfg: &config::Config) -> Result<LogHandle, LogError> {
+/// Targets which are logged at Info level by default.
+const LOG_TARGETS: &[&str] = &[
+    "stegos",
+    "stegos_blockchain",
+    "stegos_crypto",
+    "stegos_consensus",
+    "stegos_keychain",
+    "stegos_node",
+    "stegos_network",
+    "stegos_txpool",
+    "stegos_wallet",
+];
+
+fn default_log_levels() -> BTreeMap<String, LevelFilter> {
+    LOG_TARGETS
+        .iter()
+        .map(|target| (target.to_string(), LevelFilter::Info))
+        .collect()
+}
+
+fn console_appender() -> Box<dyn Append> {
+    let stdout = ConsoleAppender::builder()
+        .encoder(Box::new(PatternEncoder::new(
+            "{d(%Y-%m-%d %H:%M:%S)(local)} {h({l})} [{M}] {m}{n}",
+        )))
+        .build();
+    Box::new(stdout)
+}
+
+fn log_config(appender: Box<dyn Append>, levels: &BTreeMap<String, LevelFilter>) -> LogConfig {
+    let mut config = LogConfig::builder().appender(Appender::builder().build("stdout", appender));
+    for (target, level) in levels {
+        config = config.logger(Logger::builder().build(target.as_str(), *level));
+    }
+    config
+        .build(Root::builder().appender("stdout").build(LevelFilter::Warn))
+        .expect("console logger should never fail")
+}
+
+/// Runtime control over log levels.
+pub struct LogLevels {
+    handle: LogHandle,
+    appender: fn() -> Box<dyn Append>,
+    /// Current per-target levels, None if the logger was configured from log4rs config file.
+    levels: Option<Arc<Mutex<BTreeMap<String, LevelFilter>>>>,
+}
+
+impl LogLevels {
+    fn new(handle: LogHandle, appender: fn() -> Box<dyn Append>) -> Self {
+        let levels = Some(Arc::new(Mutex::new(default_log_levels())));
+        LogLevels {
+            handle,
+            appender,
+            levels,
+        }
+    }
+
+    fn from_config_file(handle: LogHandle) -> Self {
+        LogLevels {
+            handle,
+            appender: console_appender,
+            levels: None,
+        }
+    }
+
+    ///
+    /// Change the log level for the target.
+    ///
+    pub fn set(&self, target: &str, level: &str) -> Result<(), Error> {
+        let level = LevelFilter::from_str(level)
+            .map_err(|_| format_err!("Unknown log level: {}", level))?;
+        if target.is_empty() {
+            return Err(format_err!("Empty log target"));
+        }
+        let levels = match &self.levels {
+            Some(levels) => levels,
+            None => {
+                return Err(format_err!("Log levels are managed by log4rs config file"));
+            }
+        };
+        let mut levels = levels.lock().unwrap();
+        levels.insert(target.to_string(), level);
+        self.handle
+            .set_config(log_config((self.appender)(), &levels));
+        info!("Changed log level: target={}, level={}", target, level);
+        Ok(())
+    }
+}
+
+pub fn initialize_logger(cfg: &config::Config) -> Result<LogLevels, LogError> {
     // Try to load log4rs config file
     let path = Path::new(&cfg.general.log4rs_config);
     if !cfg.general.log4rs_config.is_empty() && path.is_file() {
         match log4rs::load_config_file(path, Default::default()) {
-            Ok(config) => return Ok(log4rs::init_config(config)?),
+            Ok(config) => {
+                let handle = log4rs::init_config(config)?;
+                return Ok(LogLevels::from_config_file(handle));
+            }
             Err(e) => {
                 error!("Failed to read log4rs config file: {}", e);
                 println!("Failed to read log4rs config file: {}", e);
@@ -51,41 +149,43 @@ pub fn initialize_logger(cfg: &config::Config) -> Result<LogHandle, LogError> {
         }
     };
 
-    let stdout = ConsoleAppender::builder()
-        .encoder(Box::new(PatternEncoder::new(
-            "{d(%Y-%m-%d %H:%M:%S)(local)} {h({l})} [{M}] {m}{n}",
-        )))
-        .build();
-    let config = LogConfig::builder()
-        .appender(Appender::builder().build("stdout", Box::new(stdout)))
-        .logger(Logger::builder().build("stegos", LevelFilter::Info))
-        .logger(Logger::builder().build("stegos_blockchain", LevelFilter::Info))
-        .logger(Logger::builder().build("stegos_crypto", LevelFilter::Info))
-        .logger(Logger::builder().build("stegos_consensus", LevelFilter::Info))
-        .logger(Logger::builder().build("stegos_keychain", LevelFilter::Info))
-        .logger(Logger::builder().build("stegos_node", LevelFilter::Info))
-        .logger(Logger::builder().build("stegos_network", LevelFilter::Info))
-        .logger(Logger::builder().build("stegos_txpool", LevelFilter::Info))
-        .logger(Logger::builder().build("stegos_wallet", LevelFilter::Info))
-        .build(Root::builder().appender("stdout").build(LevelFilter::Warn))
-        .expect("console logger should never fail");
-
-    Ok(log4rs::init_config(config)?)
+    let config = log_config(console_appender(), &default_log_levels());
+    let handle = log4rs::init_config(config)?;
+    Ok(LogLevels::new(handle, console_appender))
 }
 
+/// SHA3-256 of embedded genesis files.
+const DEV_GENESIS_DIGEST: &'static str =
+    "d14dabde9cf0d3342ae3a6a679dee29ca0c4a27d98e1e248721789ea66e2c278";
+const TESTNET_GENESIS_DIGEST: &'static str =
+    "3e51e8cb3fb03e7a85a044bcef333150c8dc804687ae5f5ba2e323322d2e88c9";
+const DEVNET_GENESIS_DIGEST: &'static str =
+    "7770f6f8cafc5b19f81e4a8da6f93325557541ca837d697f7d80a909f053091c";
+
 pub fn initialize_genesis(cfg: &config::Config) -> Result<Vec<Block>, Error> {
-    let block1: &[u8] = match cfg.general.chain.as_ref() {
-        "dev" => include_bytes!("../chains/dev/genesis0.bin"),
-        "testnet" => include_bytes!("../chains/testnet/genesis0.bin"),
-        "devnet" => include_bytes!("../chains/devnet/genesis0.bin"),
+    let buffers: Vec<Vec<u8>> = match cfg.general.chain.as_ref() {
+        "dev" => embedded_genesis(&[(
+            &include_bytes!("../chains/dev/genesis0.bin")[..],
+            DEV_GENESIS_DIGEST,
+        )])?,
+        "testnet" => embedded_genesis(&[(
+            &include_bytes!("../chains/testnet/genesis0.bin")[..],
+            TESTNET_GENESIS_DIGEST,
+        )])?,
+        "devnet" => embedded_genesis(&[(
+            &include_bytes!("../chains/devnet/genesis0.bin")[..],
+            DEVNET_GENESIS_DIGEST,
+        )])?,
+        "custom" => load_genesis_files(&cfg.general.genesis_path)?,
         chain @ _ => {
             return Err(format_err!("Unknown chain: {}", chain));
         }
     };
     info!("Using genesis for '{}' chain", cfg.general.chain);
-    let mut blocks = Vec::<Block>::new();
-    for (i, block) in [block1.as_ref()].iter().enumerate() {
-        let block = Block::from_buffer(&block)?;
+    let mut blocks = Vec::<Block>::with_capacity(buffers.len());
+    for (i, buffer) in buffers.iter().enumerate() {
+        let block = Block::from_buffer(buffer)
+            .map_err(|e| format_err!("Invalid genesis block #{}: {}", i, e))?;
         let header = block.base_header();
         info!(
             "Block #{}: hash={}, version={}",
@@ -93,34 +193,220 @@ pub fn initialize_genesis(cfg: &config::Config) -> Result<Vec<Block>, Error> {
             Hash::digest(&block),
             header.version,
         );
+        // Check that blocks are linked together.
+        if let Some(previous) = blocks.last() {
+            let previous_hash = Hash::digest(previous);
+            let previous_height = previous.base_header().height;
+            if header.previous != previous_hash || header.height != previous_height + 1 {
+                return Err(format_err!(
+                    "Genesis block #{} doesn't follow the previous block: previous={}, expected_previous={}, height={}, expected_height={}",
+                    i,
+                    header.previous,
+                    previous_hash,
+                    header.height,
+                    previous_height + 1
+                ));
+            }
+        }
         blocks.push(block);
     }
     Ok(blocks)
 }
 
-pub fn resolve_pool(cfg: &mut config::Config) -> Result<(), Error> {
-    if cfg.network.seed_pool == "" {
-        return Ok(());
-    }
-
-    let config = DnsConfig::load_default()?;
-    let resolver = resolver::DnsResolver::new(config)?;
-
-    let rrs: Vec<Srv> = resolver.resolve_record(&cfg.network.seed_pool)?;
-
-    for r in rrs.iter() {
-        if let Ok(addrs) = resolver.resolve_host(&r.target) {
-            for a in addrs {
-                let maddr = format!("/ip4/{}/tcp/{}", a.to_string(), r.port);
-                // don't try to connect to ourselves or already configured seed nodes
-                if cfg.network.advertised_addresses.iter().all(|a| *a != maddr)
-                    && cfg.network.seed_nodes.iter().all(|a| *a != maddr)
-                {
-                    info!(target: "stegos_network::ncp", "Adding node from seed pool: {}", maddr);
-                    cfg.network.seed_nodes.push(maddr);
-                }
-            }
+/// Verifies embedded genesis blocks.
+fn embedded_genesis(blocks: &[(&[u8], &str)]) -> Result<Vec<Vec<u8>>, Error> {
+    let mut buffers = Vec::with_capacity(blocks.len());
+    for (block, digest) in blocks {
+        verify_genesis(block, digest)?;
+        buffers.push(block.to_vec());
+    }
+    Ok(buffers)
+}
+
+/// Reads genesis for "custom" chain.
+/// `path` is either a single block or a directory with genesis0.bin, genesis1.bin, etc.
+fn load_genesis_files(path: &str) -> Result<Vec<Vec<u8>>, Error> {
+    if path == "" {
+        return Err(format_err!(
+            "general.genesis_path is required for custom chain"
+        ));
+    }
+    let path = Path::new(path);
+    if !path.is_dir() {
+        return Ok(vec![load_genesis_file(path)?]);
+    }
+    let mut buffers = Vec::new();
+    loop {
+        let file = path.join(format!("genesis{}.bin", buffers.len()));
+        if !file.exists() {
+            break;
         }
+        buffers.push(load_genesis_file(&file)?);
+    }
+    if buffers.is_empty() {
+        return Err(format_err!("No genesis0.bin found in '{}'", path.display()));
     }
+    Ok(buffers)
+}
+
+fn load_genesis_file(path: &Path) -> Result<Vec<u8>, Error> {
+    fs::read(path)
+        .map_err(|e| format_err!("Failed to read genesis file '{}': {}", path.display(), e))
+}
+
+/// Checks that embedded genesis hasn't been corrupted.
+fn verify_genesis(block: &[u8], digest: &str) -> Result<(), Error> {
+    let expected = Hash::try_from_hex(digest)?;
+    let got = Hash::digest(block);
+    if got != expected {
+        return Err(format_err!(
+            "Invalid genesis: expected_digest={}, got_digest={}",
+            expected,
+            got
+        ));
+    }
+    Ok(())
+}
+
+pub fn resolve_pool(cfg: &mut config::Config) -> Result<(), Error> {
+    let mut resolver = SeedPoolResolver::new(&cfg.network, DnsSeedPoolSource);
+    let seed_nodes = resolver.refresh()?;
+    cfg.network.seed_nodes.extend(seed_nodes);
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use lazy_static::lazy_static;
+    use std::time::SystemTime;
+    use stegos_blockchain::{genesis, BaseBlockHeader, MacroBlock};
+    use stegos_keychain::KeyChain;
+
+    #[test]
+    fn genesis() {
+        for chain in &["dev", "testnet", "devnet"] {
+            let mut cfg: config::Config = Default::default();
+            cfg.general.chain = chain.to_string();
+            let blocks = initialize_genesis(&cfg).expect("valid genesis");
+            assert_eq!(blocks.len(), 1);
+        }
+
+        let block: &[u8] = include_bytes!("../chains/dev/genesis0.bin");
+        verify_genesis(block, DEV_GENESIS_DIGEST).expect("valid genesis");
+        let mut mutated = block.to_vec();
+        let last = mutated.len() - 1;
+        mutated[last] ^= 1;
+        assert!(verify_genesis(&mutated, DEV_GENESIS_DIGEST).is_err());
+        assert!(verify_genesis(block, TESTNET_GENESIS_DIGEST).is_err());
+    }
+
+    #[test]
+    fn custom_genesis() {
+        let mut cfg: config::Config = Default::default();
+        cfg.general.chain = "custom".to_string();
+        assert!(initialize_genesis(&cfg).is_err());
+
+        let path = std::env::temp_dir().join(format!("stegos-genesis-{}.bin", std::process::id()));
+        cfg.general.genesis_path = path.to_str().unwrap().to_string();
+        assert!(initialize_genesis(&cfg).is_err());
+
+        // Valid block.
+        let block: &[u8] = include_bytes!("../chains/dev/genesis0.bin");
+        fs::write(&path, block).unwrap();
+        let blocks = initialize_genesis(&cfg).expect("valid genesis");
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(
+            Hash::digest(&blocks[0]),
+            Hash::digest(&Block::from_buffer(block).unwrap())
+        );
+
+        // Garbage.
+        fs::write(&path, b"garbage").unwrap();
+        assert!(initialize_genesis(&cfg).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+    #[test]
+    fn multi_block_genesis() {
+        let keys = KeyChain::new_mem();
+        let timestamp = SystemTime::now();
+        let mut blocks = genesis(&[keys.clone()], 1_000_000, 1_000_000_000, timestamp);
+        assert_eq!(blocks.len(), 1);
+        let header = blocks[0].base_header().clone();
+        let base = BaseBlockHeader::new(
+            header.version,
+            Hash::digest(&blocks[0]),
+            header.height + 1,
+            header.view_change,
+            header.timestamp,
+            header.random,
+        );
+        blocks.push(Block::MacroBlock(MacroBlock::empty(
+            base,
+            keys.network_pkey,
+        )));
+
+        let dir = std::env::temp_dir().join(format!("stegos-genesis-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for (i, block) in blocks.iter().enumerate() {
+            let path = dir.join(format!("genesis{}.bin", i));
+            fs::write(&path, block.into_buffer().unwrap()).unwrap();
+        }
+
+        let mut cfg: config::Config = Default::default();
+        cfg.general.chain = "custom".to_string();
+        cfg.general.genesis_path = dir.to_str().unwrap().to_string();
+        let loaded = initialize_genesis(&cfg).expect("valid genesis");
+        assert_eq!(loaded.len(), 2);
+        for (block, loaded) in blocks.iter().zip(loaded.iter()) {
+            assert_eq!(Hash::digest(block), Hash::digest(loaded));
+        }
+
+        // Blocks must be linked together.
+        fs::copy(dir.join("genesis0.bin"), dir.join("genesis1.bin")).unwrap();
+        assert!(initialize_genesis(&cfg).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    lazy_static! {
+        static ref CAPTURED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+    }
+
+    #[derive(Debug)]
+    struct CaptureAppender;
+
+    impl Append for CaptureAppender {
+        fn append(&self, record: &Record) -> Result<(), Box<dyn std::error::Error + Sync + Send>> {
+            CAPTURED.lock().unwrap().push(format!("{}", record.args()));
+            Ok(())
+        }
+
+        fn flush(&self) {}
+    }
+
+    fn capture_appender() -> Box<dyn Append> {
+        Box::new(CaptureAppender)
+    }
+
+    #[test]
+    fn log_levels() {
+        let config = log_config(capture_appender(), &default_log_levels());
+        let handle = log4rs::init_config(config).expect("logger initialized");
+        let log_levels = LogLevels::new(handle, capture_appender);
+
+        debug!(target: "stegos_node", "hidden");
+        assert!(!CAPTURED.lock().unwrap().iter().any(|m| m == "hidden"));
+
+        log_levels.set("stegos_node", "debug").expect("valid level");
+        debug!(target: "stegos_node", "shown");
+        assert!(CAPTURED.lock().unwrap().iter().any(|m| m == "shown"));
+
+        log_levels.set("stegos_node", "info").expect("valid level");
+        debug!(target: "stegos_node", "hidden again");
+        assert!(!CAPTURED.lock().unwrap().iter().any(|m| m == "hidden again"));
+
+        assert!(log_levels.set("stegos_node", "loud").is_err());
+        assert!(log_levels.set("", "debug").is_err());
+    }
+}
@@ -0,0 +1,180 @@
+//
+// Copyright (c) 2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use failure::Error;
+use futures::sync::mpsc::unbounded;
+use futures::{Future, Stream};
+use log::*;
+use resolve::{config::DnsConfig, record::Srv, resolver};
+use std::collections::HashSet;
+use std::thread;
+use std::time::Duration;
+use stegos_network::{Network, NetworkConfig};
+
+/// Source of seed node addresses.
+pub trait SeedPoolSource {
+    /// Returns multiaddrs of all nodes in the seed pool.
+    fn resolve(&mut self, seed_pool: &str) -> Result<Vec<String>, Error>;
+}
+
+/// Resolves the seed pool using DNS SRV records.
+pub struct DnsSeedPoolSource;
+
+impl SeedPoolSource for DnsSeedPoolSource {
+    fn resolve(&mut self, seed_pool: &str) -> Result<Vec<String>, Error> {
+        let config = DnsConfig::load_default()?;
+        let resolver = resolver::DnsResolver::new(config)?;
+
+        let rrs: Vec<Srv> = resolver.resolve_record(seed_pool)?;
+
+        let mut maddrs = Vec::new();
+        for r in rrs.iter() {
+            if let Ok(addrs) = resolver.resolve_host(&r.target) {
+                for a in addrs {
+                    maddrs.push(format!("/ip4/{}/tcp/{}", a.to_string(), r.port));
+                }
+            }
+        }
+        Ok(maddrs)
+    }
+}
+
+/// Keeps track of seed nodes discovered from the seed pool.
+pub struct SeedPoolResolver<S: SeedPoolSource> {
+    /// DNS name of pool of seed nodes.
+    seed_pool: String,
+    /// Source of seed node addresses.
+    source: S,
+    /// Our own addresses and already known seed nodes.
+    known: HashSet<String>,
+}
+
+impl<S: SeedPoolSource> SeedPoolResolver<S> {
+    pub fn new(cfg: &NetworkConfig, source: S) -> Self {
+        let seed_pool = cfg.seed_pool.clone();
+        let known: HashSet<String> = cfg
+            .advertised_addresses
+            .iter()
+            .chain(cfg.seed_nodes.iter())
+            .cloned()
+            .collect();
+        SeedPoolResolver {
+            seed_pool,
+            source,
+            known,
+        }
+    }
+
+    /// Resolves the seed pool and returns newly discovered seed nodes.
+    pub fn refresh(&mut self) -> Result<Vec<String>, Error> {
+        if self.seed_pool == "" {
+            return Ok(Vec::new());
+        }
+
+        let mut seed_nodes = Vec::new();
+        for maddr in self.source.resolve(&self.seed_pool)? {
+            // don't try to connect to ourselves or already known seed nodes
+            if self.known.insert(maddr.clone()) {
+                info!(target: "stegos_network::ncp", "Adding node from seed pool: {}", maddr);
+                seed_nodes.push(maddr);
+            }
+        }
+        Ok(seed_nodes)
+    }
+}
+
+/// Periodically resolves the seed pool and dials newly discovered seed nodes.
+/// DNS lookups are blocking, so they are made on a separate thread,
+/// which stops when the returned future is dropped.
+pub fn refresh_pool<S: SeedPoolSource + Send + 'static>(
+    mut resolver: SeedPoolResolver<S>,
+    network: Network,
+    interval: Duration,
+) -> Result<impl Future<Item = (), Error = ()>, Error> {
+    let (tx, rx) = unbounded::<String>();
+    thread::Builder::new()
+        .name("seed-pool".to_string())
+        .spawn(move || loop {
+            thread::sleep(interval);
+            match resolver.refresh() {
+                Ok(seed_nodes) => {
+                    for maddr in seed_nodes {
+                        if tx.unbounded_send(maddr).is_err() {
+                            return;
+                        }
+                    }
+                }
+                Err(e) => error!("Failed to resolve seed pool: {}", e),
+            }
+        })?;
+
+    Ok(rx.for_each(move |maddr| {
+        if let Err(e) = network.dial_address(maddr) {
+            error!("Failed to dial seed node: {}", e);
+        }
+        Ok(())
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct StubSource {
+        records: Vec<Vec<String>>,
+    }
+
+    impl SeedPoolSource for StubSource {
+        fn resolve(&mut self, _seed_pool: &str) -> Result<Vec<String>, Error> {
+            Ok(self.records.remove(0))
+        }
+    }
+
+    #[test]
+    fn refresh() {
+        let mut cfg: NetworkConfig = Default::default();
+        cfg.seed_pool = "_stegos._tcp.example.com".to_string();
+        cfg.advertised_addresses = vec!["/ip4/10.0.0.1/tcp/10203".to_string()];
+        cfg.seed_nodes = vec!["/ip4/10.0.0.2/tcp/10203".to_string()];
+        let source = StubSource {
+            records: vec![
+                vec![
+                    "/ip4/10.0.0.1/tcp/10203".to_string(),
+                    "/ip4/10.0.0.2/tcp/10203".to_string(),
+                    "/ip4/10.0.0.3/tcp/10203".to_string(),
+                ],
+                vec![
+                    "/ip4/10.0.0.3/tcp/10203".to_string(),
+                    "/ip4/10.0.0.4/tcp/10203".to_string(),
+                ],
+            ],
+        };
+        let mut resolver = SeedPoolResolver::new(&cfg, source);
+        assert_eq!(
+            resolver.refresh().unwrap(),
+            vec!["/ip4/10.0.0.3/tcp/10203".to_string()]
+        );
+        assert_eq!(
+            resolver.refresh().unwrap(),
+            vec!["/ip4/10.0.0.4/tcp/10203".to_string()]
+        );
+    }
+}
@@ -106,7 +106,7 @@ impl TransactionPoolService {
 
             // Epoch Changes
             let epoch_changes = node
-                .subscribe_epoch_changed()
+                .subscribe_epoch_changed()?
                 .map(|m| PoolEvent::EpochChanged(m));
             streams.push(Box::new(epoch_changes));
 
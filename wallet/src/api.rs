@@ -33,6 +33,7 @@ use stegos_crypto::curve1174::PublicKey;
 use stegos_crypto::hash::Hash;
 use stegos_crypto::pbc;
 use stegos_node::EpochChanged;
+use stegos_node::FeeParams;
 use stegos_node::OutputsChanged;
 
 #[derive(Serialize, Clone, Debug, PartialEq, Eq)]
@@ -85,6 +86,13 @@ pub enum WalletRequest {
     },
     UnstakeAll {},
     RestakeAll {},
+    Consolidate {
+        max_inputs: usize,
+    },
+    Split {
+        parts: usize,
+        amount_each: i64,
+    },
     KeysInfo {},
     BalanceInfo {},
     UnspentInfo {},
@@ -156,6 +164,7 @@ pub(crate) enum WalletEvent {
     //
     NodeOutputsChanged(OutputsChanged),
     NodeEpochChanged(EpochChanged),
+    NodeFeeParams(FeeParams),
 }
 
 #[derive(Debug, Clone)]
@@ -23,8 +23,45 @@
 
 use crate::error::*;
 
+/// Result of coin selection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoinSelection<'a, T> {
+    /// Selected UTXOs.
+    pub inputs: Vec<&'a T>,
+    /// Effective fee, including the fee for the change output if any.
+    pub fee: i64,
+    /// Amount of change, zero if the change output isn't needed.
+    pub change: i64,
+}
+
+///
+/// Select UTXOs to pay `amount`, without creating a transaction.
+///
+/// `unspent_iter` yields candidate UTXOs with their amounts,
+/// `fee` is the fee for a transaction without a change,
+/// `fee_change` is the fee for a transaction with a change output.
+///
+pub fn select_coins<'a, I, T>(
+    unspent_iter: I,
+    amount: i64,
+    fee: i64,
+    fee_change: i64,
+) -> Result<CoinSelection<'a, T>, WalletError>
+where
+    I: IntoIterator<Item = (&'a T, i64)>,
+{
+    let (inputs, fee, change) = find_utxo(unspent_iter, amount, fee, fee_change)?;
+    Ok(CoinSelection {
+        inputs,
+        fee,
+        change,
+    })
+}
+
 /// Find appropriate inputs.
-pub(crate) fn find_utxo<'a, I, T>(
+/// Returns selected UTXOs, effective fee and change.
+/// Fails with NegativeAmount if `sum`, `fee`, `fee_change` or an UTXO amount is negative.
+pub fn find_utxo<'a, I, T>(
     unspent_iter: I,
     sum: i64,
     fee: i64,
@@ -33,12 +70,21 @@ pub(crate) fn find_utxo<'a, I, T>(
 where
     I: IntoIterator<Item = (&'a T, i64)>,
 {
-    assert!(sum >= 0);
-    assert!(fee >= 0);
-    assert!(fee_change >= 0);
+    for &value in &[sum, fee, fee_change] {
+        if value < 0 {
+            return Err(WalletError::NegativeAmount(value));
+        }
+    }
+    let sum_fee = sum.checked_add(fee).ok_or(WalletError::AmountOverflow)?;
+    let sum_fee_change = sum
+        .checked_add(fee_change)
+        .ok_or(WalletError::AmountOverflow)?;
     let mut sorted: Vec<(i64, &T)> = Vec::new();
     for (output, amount) in unspent_iter {
-        if amount == sum + fee {
+        if amount < 0 {
+            return Err(WalletError::NegativeAmount(amount));
+        }
+        if amount == sum_fee {
             return Ok((vec![output], fee, 0i64));
         }
         sorted.push((amount, output));
@@ -50,12 +96,12 @@ where
     // Naive algorithm - try to spent as much UTXO as possible.
     //
 
-    // Sort in ascending order to eliminate as much outputs as possible
-    sorted.sort_by_key(|(amount, _output)| *amount);
+    sort_by_amount(&mut sorted);
 
     // Try to spend without a change.
+    // Amounts are non-negative, so `change` can't overflow.
     let mut spent: Vec<&T> = Vec::new();
-    let mut change: i64 = sum + fee;
+    let mut change: i64 = sum_fee;
     for (amount, output) in sorted.iter() {
         change -= *amount;
         spent.push(*output);
@@ -69,7 +115,7 @@ where
 
     // Try to spend with a change.
     spent.clear();
-    let mut change: i64 = sum + fee_change;
+    let mut change: i64 = sum_fee_change;
     for (amount, output) in sorted.iter() {
         change -= *amount;
         spent.push(*output);
@@ -85,6 +131,37 @@ where
     return Ok((spent, fee_change, -change));
 }
 
+/// Find up to `max_inputs` UTXOs with the smallest amounts.
+/// Returns selected UTXOs and their total amount.
+pub(crate) fn find_smallest_utxo<'a, I, T>(
+    unspent_iter: I,
+    max_inputs: usize,
+) -> Result<(Vec<&'a T>, i64), WalletError>
+where
+    I: IntoIterator<Item = (&'a T, i64)>,
+{
+    let mut sorted: Vec<(i64, &T)> = unspent_iter
+        .into_iter()
+        .map(|(output, amount)| (amount, output))
+        .collect();
+    sort_by_amount(&mut sorted);
+
+    let mut spent: Vec<&T> = Vec::new();
+    let mut total: i64 = 0;
+    for (amount, output) in sorted.into_iter().take(max_inputs) {
+        total = total
+            .checked_add(amount)
+            .ok_or(WalletError::AmountOverflow)?;
+        spent.push(output);
+    }
+    Ok((spent, total))
+}
+
+/// Sort in ascending order to eliminate as much outputs as possible.
+fn sort_by_amount<T>(sorted: &mut [(i64, &T)]) {
+    sorted.sort_by_key(|(amount, _output)| *amount);
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::*;
@@ -176,5 +253,102 @@ pub mod tests {
             Err(WalletError::NotEnoughMoney) => {}
             _ => panic!(),
         };
+
+        // NegativeAmount
+        let unspent_iter = unspent.iter().map(|(h, a)| (h, *a));
+        match find_utxo(unspent_iter, -1, FEE, FEE_CHANGE) {
+            Err(WalletError::NegativeAmount(-1)) => {}
+            _ => panic!(),
+        };
+        let unspent_iter = unspent.iter().map(|(h, a)| (h, *a));
+        match find_utxo(unspent_iter, 5, -FEE, FEE_CHANGE) {
+            Err(WalletError::NegativeAmount(_)) => {}
+            _ => panic!(),
+        };
+        let unspent_iter = unspent.iter().map(|(h, a)| (h, *a));
+        match find_utxo(unspent_iter, 5, FEE, -FEE_CHANGE) {
+            Err(WalletError::NegativeAmount(_)) => {}
+            _ => panic!(),
+        };
+        let negative = Hash::digest(&-1i64);
+        let unspent_iter = unspent
+            .iter()
+            .map(|(h, a)| (h, *a))
+            .chain(Some((&negative, -1)));
+        match find_utxo(unspent_iter, 5, FEE, FEE_CHANGE) {
+            Err(WalletError::NegativeAmount(-1)) => {}
+            _ => panic!(),
+        };
+
+        // AmountOverflow
+        let unspent_iter = unspent.iter().map(|(h, a)| (h, *a));
+        match find_utxo(unspent_iter, i64::max_value(), FEE, FEE_CHANGE) {
+            Err(WalletError::AmountOverflow) => {}
+            _ => panic!(),
+        };
+        let unspent_iter = unspent.iter().map(|(h, a)| (h, *a));
+        match find_utxo(unspent_iter, i64::max_value() - FEE, FEE, FEE_CHANGE) {
+            Err(WalletError::AmountOverflow) => {}
+            _ => panic!(),
+        };
+    }
+
+    #[test]
+    pub fn test_find_smallest_utxo() {
+        let mut unspent: Vec<(Hash, i64)> = Vec::new();
+        let amounts: [i64; 5] = [100, 50, 10, 2, 1];
+        for amount in amounts.iter() {
+            let hash = Hash::digest(amount);
+            unspent.push((hash, *amount));
+        }
+
+        let unspent_iter = unspent.iter().map(|(h, a)| (h, *a));
+        let (spent, total) = find_smallest_utxo(unspent_iter, 3).unwrap();
+        assert_eq!(
+            spent,
+            vec![
+                &Hash::digest(&1i64),
+                &Hash::digest(&2i64),
+                &Hash::digest(&10i64)
+            ]
+        );
+        assert_eq!(total, 13);
+
+        let unspent_iter = unspent.iter().map(|(h, a)| (h, *a));
+        let (spent, total) = find_smallest_utxo(unspent_iter, 10).unwrap();
+        assert_eq!(spent.len(), 5);
+        assert_eq!(total, 163);
+    }
+
+    #[test]
+    pub fn test_select_coins() {
+        let mut unspent: Vec<(Hash, i64)> = Vec::new();
+        let amounts: [i64; 5] = [100, 50, 10, 2, 1];
+        for amount in amounts.iter() {
+            let hash = Hash::digest(amount);
+            unspent.push((hash, *amount));
+        }
+
+        const FEE: i64 = 1;
+        const FEE_CHANGE: i64 = 2 * FEE;
+
+        let unspent_iter = unspent.iter().map(|(h, a)| (h, *a));
+        let selection = select_coins(unspent_iter, 5, FEE, FEE_CHANGE).unwrap();
+        assert_eq!(
+            selection,
+            CoinSelection {
+                inputs: vec![
+                    &Hash::digest(&1i64),
+                    &Hash::digest(&2i64),
+                    &Hash::digest(&10i64),
+                ],
+                fee: FEE_CHANGE,
+                change: 6,
+            }
+        );
+
+        let unspent_iter = unspent.iter().map(|(h, a)| (h, *a));
+        let e = select_coins(unspent_iter, 1000, FEE, FEE_CHANGE).unwrap_err();
+        assert_eq!(e, WalletError::NotEnoughMoney);
     }
 }
@@ -34,6 +34,20 @@ pub enum WalletError {
     IncorrectTXINType,
     #[fail(display = "Incorrect UTXO data")]
     InvalidUTXOData,
+    #[fail(display = "Stake is locked: unlock_epoch={}", _0)]
+    StakeIsLocked(u64),
     #[fail(display = "Nothing to re-stake")]
     NothingToRestake,
+    #[fail(display = "Re-staking doesn't pay fees: stake_fee={}", _0)]
+    RestakeFeeRequired(i64),
+    #[fail(display = "Nothing to consolidate: inputs={}", _0)]
+    NothingToConsolidate(usize),
+    #[fail(display = "Nothing to split: parts={}", _0)]
+    NothingToSplit(usize),
+    #[fail(display = "Too many UTXO in transaction: got={}, max={}", _0, _1)]
+    TooManyUTXO(usize, usize),
+    #[fail(display = "Amount overflow")]
+    AmountOverflow,
+    #[fail(display = "Invalid number of change outputs: change_outputs={}", _0)]
+    InvalidChangeOutputs(usize),
 }
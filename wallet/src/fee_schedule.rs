@@ -0,0 +1,110 @@
+//! Wallet - Fee Schedules.
+
+//
+// Copyright (c) 2019 Stegos AG
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::error::*;
+
+/// Fees passed to coin selection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeSchedule {
+    /// The fee if inputs match the amount exactly.
+    pub fee: i64,
+    /// The fee if the transaction also has change outputs.
+    pub fee_change: i64,
+}
+
+/// Calculate the fee for the given number of payment and stake outputs.
+fn outputs_fee(
+    payment_outputs: usize,
+    payment_fee: i64,
+    stake_outputs: usize,
+    stake_fee: i64,
+) -> Result<i64, WalletError> {
+    (payment_outputs as i64)
+        .checked_mul(payment_fee)
+        .and_then(|x| {
+            (stake_outputs as i64)
+                .checked_mul(stake_fee)
+                .and_then(|y| x.checked_add(y))
+        })
+        .ok_or(WalletError::AmountOverflow)
+}
+
+/// Payment: one output to the recipient and up to `change_outputs` change outputs.
+pub fn payment(payment_fee: i64, change_outputs: usize) -> Result<FeeSchedule, WalletError> {
+    let fee = outputs_fee(1, payment_fee, 0, 0)?;
+    let fee_change = outputs_fee(1 + change_outputs, payment_fee, 0, 0)?;
+    Ok(FeeSchedule { fee, fee_change })
+}
+
+/// Staking: one stake output and one payment output for change.
+pub fn staking(payment_fee: i64, stake_fee: i64) -> Result<FeeSchedule, WalletError> {
+    let fee = outputs_fee(0, payment_fee, 1, stake_fee)?;
+    let fee_change = outputs_fee(1, payment_fee, 1, stake_fee)?;
+    Ok(FeeSchedule { fee, fee_change })
+}
+
+/// Unstaking: one payment output and one stake output for the remaining stake.
+pub fn unstaking(payment_fee: i64, stake_fee: i64) -> Result<FeeSchedule, WalletError> {
+    let fee = outputs_fee(1, payment_fee, 0, stake_fee)?;
+    let fee_change = outputs_fee(1, payment_fee, 1, stake_fee)?;
+    Ok(FeeSchedule { fee, fee_change })
+}
+
+/// Split: `parts` payment outputs and one change output.
+pub fn split(payment_fee: i64, parts: usize) -> Result<FeeSchedule, WalletError> {
+    let fee = outputs_fee(parts, payment_fee, 0, 0)?;
+    let fee_change = outputs_fee(parts + 1, payment_fee, 0, 0)?;
+    Ok(FeeSchedule { fee, fee_change })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    fn schedule(fee: i64, fee_change: i64) -> Result<FeeSchedule, WalletError> {
+        Ok(FeeSchedule { fee, fee_change })
+    }
+
+    #[test]
+    fn schedules() {
+        let payment_fee: i64 = 10;
+        let stake_fee: i64 = 3;
+        assert_eq!(payment(payment_fee, 1), schedule(10, 20));
+        assert_eq!(payment(payment_fee, 3), schedule(10, 40));
+        assert_eq!(staking(payment_fee, stake_fee), schedule(3, 13));
+        assert_eq!(staking(payment_fee, 0), schedule(0, 10));
+        assert_eq!(unstaking(payment_fee, stake_fee), schedule(10, 13));
+        assert_eq!(unstaking(payment_fee, 0), schedule(10, 10));
+        assert_eq!(split(payment_fee, 2), schedule(20, 30));
+        assert_eq!(split(payment_fee, 5), schedule(50, 60));
+    }
+
+    #[test]
+    fn overflow() {
+        let fee = i64::max_value() / 2 + 1;
+        assert_eq!(payment(fee, 1), Err(WalletError::AmountOverflow));
+        assert_eq!(staking(fee, fee), Err(WalletError::AmountOverflow));
+        assert_eq!(unstaking(fee, fee), Err(WalletError::AmountOverflow));
+        assert_eq!(split(fee, 2), Err(WalletError::AmountOverflow));
+    }
+}
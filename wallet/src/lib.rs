@@ -25,7 +25,9 @@
 
 mod api;
 mod change;
+mod config;
 mod error;
+mod fee_schedule;
 mod transaction;
 mod valueshuffle;
 
@@ -33,7 +35,9 @@ mod valueshuffle;
 mod tests;
 
 pub use crate::api::*;
-use crate::error::WalletError;
+pub use crate::change::{find_utxo, select_coins, CoinSelection};
+pub use crate::config::WalletConfig;
+pub use crate::error::WalletError;
 use crate::transaction::*;
 use crate::valueshuffle::ValueShuffle;
 use failure::Error;
@@ -53,7 +57,10 @@ use stegos_crypto::hash::{Hash, Hashable, Hasher};
 use stegos_keychain::KeyChain;
 use stegos_network::Network;
 use stegos_node::EpochChanged;
+use stegos_node::FeeParams;
 use stegos_node::Node;
+use stegos_node::NodeRequest;
+use stegos_node::NodeResponse;
 use stegos_node::OutputsChanged;
 
 struct PaymentValue {
@@ -127,6 +134,8 @@ impl From<Transaction> for SavedTransaction {
 }
 
 pub struct WalletService {
+    /// Configuration.
+    cfg: WalletConfig,
     /// Keys.
     keys: KeyChain,
     /// Current Epoch.
@@ -144,6 +153,8 @@ pub struct WalletService {
     stake_fee: i64,
     /// Lifetime of stake.
     stake_epochs: u64,
+    /// Maximal number of inputs and outputs in a transaction.
+    max_utxo_in_tx: usize,
 
     /// Node API.
     node: Node,
@@ -165,14 +176,16 @@ pub struct WalletService {
 impl WalletService {
     /// Create a new wallet.
     pub fn new(
+        cfg: WalletConfig,
         keys: KeyChain,
         network: Network,
         node: Node,
         payment_fee: i64,
         stake_fee: i64,
         stake_epochs: u64,
+        max_utxo_in_tx: usize,
         persistent_state: Vec<(Output, u64)>,
-    ) -> (Self, Wallet) {
+    ) -> Result<(Self, Wallet), Error> {
         info!("My wallet key: {}", keys.wallet_pkey.to_hex());
         debug!("My network key: {}", keys.network_pkey.to_hex());
 
@@ -188,7 +201,7 @@ impl WalletService {
             keys.network_pkey.clone(),
             network.clone(),
             node.clone(),
-        );
+        )?;
 
         let transactions_interest = HashMap::new();
         let unprocessed_transactions = HashMap::new();
@@ -209,19 +222,31 @@ impl WalletService {
 
         // Monetary blocks from node.
         let node_outputs = node
-            .subscribe_outputs_changed()
+            .subscribe_outputs_changed()?
             .map(|outputs| WalletEvent::NodeOutputsChanged(outputs));
         events.push(Box::new(node_outputs));
 
         // Key blocks from node.
         let node_epochs = node
-            .subscribe_epoch_changed()
+            .subscribe_epoch_changed()?
             .map(|epoch| WalletEvent::NodeEpochChanged(epoch));
         events.push(Box::new(node_epochs));
 
+        // Fees accepted by node, overriding the configured ones.
+        let node_fee_params = node
+            .request(NodeRequest::FeeParams {})?
+            .into_stream()
+            .map_err(|_| ())
+            .filter_map(|response| match response {
+                NodeResponse::FeeParams(params) => Some(WalletEvent::NodeFeeParams(params)),
+                _ => None,
+            });
+        events.push(Box::new(node_fee_params));
+
         let events = select_all(events);
 
         let mut service = WalletService {
+            cfg,
             epoch,
             keys,
             payments,
@@ -230,6 +255,7 @@ impl WalletService {
             payment_fee,
             stake_fee,
             stake_epochs,
+            max_utxo_in_tx,
             node,
             subscribers,
             events,
@@ -244,7 +270,7 @@ impl WalletService {
 
         let api = Wallet { outbox };
 
-        (service, api)
+        Ok((service, api))
     }
 
     /// Send money.
@@ -263,6 +289,7 @@ impl WalletService {
             amount,
             self.payment_fee,
             data,
+            self.cfg.change_outputs,
         )?;
 
         // Transaction TXINs can generally have different keying for each one
@@ -300,6 +327,44 @@ impl WalletService {
         }
     }
 
+    /// Merge the smallest payment UTXOs into one.
+    fn consolidate(&mut self, max_inputs: usize) -> Result<(Hash, i64), Error> {
+        let unspent_iter = self.payments.values().map(|v| (&v.output, v.amount));
+        let tx = create_consolidation_transaction(
+            &self.keys.wallet_skey,
+            &self.keys.wallet_pkey,
+            unspent_iter,
+            max_inputs,
+            self.payment_fee,
+        )?;
+        let tx_hash = Hash::digest(&tx);
+        let fee = tx.fee;
+        let tx: Transaction = tx.into();
+        self.node.send_transaction(tx.clone())?;
+        self.add_transaction_interest(tx.into());
+        Ok((tx_hash, fee))
+    }
+
+    /// Split money into `parts` equal payment UTXOs.
+    fn split(&mut self, parts: usize, amount_each: i64) -> Result<(Hash, i64), Error> {
+        let unspent_iter = self.payments.values().map(|v| (&v.output, v.amount));
+        let tx = create_split_transaction(
+            &self.keys.wallet_skey,
+            &self.keys.wallet_pkey,
+            unspent_iter,
+            parts,
+            amount_each,
+            self.payment_fee,
+            self.max_utxo_in_tx,
+        )?;
+        let tx_hash = Hash::digest(&tx);
+        let fee = tx.fee;
+        let tx: Transaction = tx.into();
+        self.node.send_transaction(tx.clone())?;
+        self.add_transaction_interest(tx.into());
+        Ok((tx_hash, fee))
+    }
+
     /// Send money using value shuffle.
     fn secure_payment(
         &mut self,
@@ -345,7 +410,25 @@ impl WalletService {
     /// Unstake money from the escrow.
     /// NOTE: amount must include PAYMENT_FEE.
     fn unstake(&self, amount: i64) -> Result<(Hash, i64), Error> {
-        let unspent_iter = self.stakes.values().map(|v| &v.output);
+        // Only expired stakes can be withdrawn.
+        let epoch = self.epoch;
+        let matured: i64 = self
+            .stakes
+            .values()
+            .filter(|v| v.active_until_epoch < epoch)
+            .map(|v| v.output.amount)
+            .sum();
+        if matured < amount {
+            if let Some(unlock_epoch) = self.unlock_epoch(amount) {
+                return Err(WalletError::StakeIsLocked(unlock_epoch).into());
+            }
+        }
+
+        let unspent_iter = self
+            .stakes
+            .values()
+            .filter(|v| v.active_until_epoch < epoch)
+            .map(|v| &v.output);
         let tx = create_unstaking_transaction(
             &self.keys.wallet_skey,
             &self.keys.wallet_pkey,
@@ -366,14 +449,37 @@ impl WalletService {
     fn unstake_all(&self) -> Result<(Hash, i64), Error> {
         let mut amount: i64 = 0;
         for val in self.stakes.values() {
-            amount += val.output.amount;
+            if val.active_until_epoch < self.epoch {
+                amount += val.output.amount;
+            }
+        }
+        if amount == 0 && !self.stakes.is_empty() {
+            let unlock_epoch = self.unlock_epoch(1).expect("stakes exist");
+            return Err(WalletError::StakeIsLocked(unlock_epoch).into());
         }
         self.unstake(amount)
     }
 
+    /// Returns the first epoch when at least `amount` of stake can be withdrawn,
+    /// None if the wallet doesn't have enough stake.
+    fn unlock_epoch(&self, amount: i64) -> Option<u64> {
+        let mut stakes: Vec<&StakeValue> = self.stakes.values().collect();
+        stakes.sort_by_key(|v| v.active_until_epoch);
+        let mut unlocked: i64 = 0;
+        for val in stakes {
+            unlocked += val.output.amount;
+            if unlocked >= amount {
+                return Some(val.active_until_epoch + 1);
+            }
+        }
+        None
+    }
+
     /// Restake all available stakes (even if not expired).
     fn restake_all(&mut self) -> Result<(Hash, i64), Error> {
-        assert_eq!(self.stake_fee, 0);
+        if self.stake_fee != 0 {
+            return Err(WalletError::RestakeFeeRequired(self.stake_fee).into());
+        }
         if self.stakes.is_empty() {
             return Err(WalletError::NothingToRestake.into());
         }
@@ -393,7 +499,6 @@ impl WalletService {
 
     /// Re-stake expiring stakes.
     fn restake_expiring(&mut self) -> Result<(), Error> {
-        assert_eq!(self.stake_fee, 0);
         let epoch = self.epoch;
         let stakes: Vec<&StakeOutput> = self.stakes.iter().filter_map(|(hash, val)|
                 // Re-stake in the last epoch where stake is valid.
@@ -409,6 +514,9 @@ impl WalletService {
         if stakes.is_empty() {
             return Ok(()); // Nothing to re-stake.
         }
+        if self.stake_fee != 0 {
+            return Err(WalletError::RestakeFeeRequired(self.stake_fee).into());
+        }
 
         let tx = create_restaking_transaction(
             &self.keys.wallet_skey,
@@ -431,11 +539,20 @@ impl WalletService {
     }
 
     /// Called when outputs registered and/or pruned.
-    fn on_outputs_changed(&mut self, epoch: u64, inputs: Vec<Output>, outputs: Vec<Output>) {
+    fn on_outputs_changed(
+        &mut self,
+        epoch: u64,
+        inputs: Vec<Output>,
+        outputs: Vec<Output>,
+        rolled_back: bool,
+    ) {
         assert_eq!(self.epoch, epoch);
         let saved_balance = self.balance();
 
-        self.find_commited_txs(&inputs);
+        // Reverted outputs were never spent by a transaction.
+        if !rolled_back {
+            self.find_commited_txs(&inputs);
+        }
         for input in inputs {
             self.on_output_pruned(epoch, input);
         }
@@ -594,6 +711,17 @@ impl WalletService {
         }
     }
 
+    fn on_fee_params(&mut self, params: FeeParams) {
+        if params.payment_fee != self.payment_fee || params.stake_fee != self.stake_fee {
+            info!(
+                "Using fees from node: payment_fee={}, stake_fee={}",
+                params.payment_fee, params.stake_fee
+            );
+        }
+        self.payment_fee = params.payment_fee;
+        self.stake_fee = params.stake_fee;
+    }
+
     fn notify(&mut self, notification: WalletNotification) {
         self.subscribers
             .retain(move |tx| tx.unbounded_send(notification.clone()).is_ok());
@@ -653,6 +781,12 @@ impl Future for WalletService {
                             WalletRequest::Unstake { amount } => self.unstake(amount).into(),
                             WalletRequest::UnstakeAll {} => self.unstake_all().into(),
                             WalletRequest::RestakeAll {} => self.restake_all().into(),
+                            WalletRequest::Consolidate { max_inputs } => {
+                                self.consolidate(max_inputs).into()
+                            }
+                            WalletRequest::Split { parts, amount_each } => {
+                                self.split(parts, amount_each).into()
+                            }
                             WalletRequest::KeysInfo {} => WalletResponse::KeysInfo {
                                 wallet_pkey: self.keys.wallet_pkey,
                                 network_pkey: self.keys.network_pkey,
@@ -690,12 +824,16 @@ impl Future for WalletService {
                         epoch,
                         inputs,
                         outputs,
+                        rolled_back,
                     }) => {
-                        self.on_outputs_changed(epoch, inputs, outputs);
+                        self.on_outputs_changed(epoch, inputs, outputs, rolled_back);
                     }
                     WalletEvent::NodeEpochChanged(EpochChanged { epoch, .. }) => {
                         self.on_epoch_changed(epoch);
                     }
+                    WalletEvent::NodeFeeParams(params) => {
+                        self.on_fee_params(params);
+                    }
                 },
                 Async::Ready(None) => unreachable!(), // never happens
                 Async::NotReady => return Ok(Async::NotReady),
@@ -25,11 +25,19 @@
 
 #![allow(warnings)]
 
-use crate::{TransactionCommitted, WalletNotification, WalletRequest, WalletResponse};
+use crate::{
+    TransactionCommitted, WalletConfig, WalletError, WalletNotification, WalletRequest,
+    WalletResponse, WalletService,
+};
 use pretty_assertions::assert_eq;
 use serde::Serialize;
 use serde_json::{json, Value};
+use std::time::SystemTime;
+use stegos_blockchain::{genesis, Blockchain, Output};
 use stegos_crypto::hash::Hash;
+use stegos_keychain::KeyChain;
+use stegos_network::loopback::Loopback;
+use stegos_node::{ChainConfig, FeeParams, NodeService};
 
 fn compare<T: Serialize>(val: T, expected: Value) {
     let actual = serde_json::to_value(val).expect("Cannot serialize value");
@@ -85,6 +93,91 @@ fn response_committed_tx() {
     );
 }
 
+/// Creates a wallet connected to a node which is the only validator of a new chain.
+fn new_wallet(payment_fee: i64, stake_fee: i64) -> (WalletService, NodeService, Loopback) {
+    let (loopback, network) = Loopback::new();
+    let keys = KeyChain::new_mem();
+    let cfg: ChainConfig = Default::default();
+    let timestamp = SystemTime::now();
+    let genesis = genesis(
+        &[keys.clone()],
+        cfg.min_stake_amount,
+        1000 * cfg.min_stake_amount,
+        timestamp,
+    );
+    let chain = Blockchain::testing(cfg.clone().into(), genesis, timestamp)
+        .expect("Failed to create blockchain");
+    let (node_service, node) =
+        NodeService::new(cfg.clone(), chain, keys.clone(), network.clone()).unwrap();
+    let (wallet_service, _wallet) = WalletService::new(
+        WalletConfig::default(),
+        keys,
+        network,
+        node,
+        payment_fee,
+        stake_fee,
+        cfg.stake_epochs,
+        cfg.max_utxo_in_tx,
+        Vec::new(),
+    )
+    .unwrap();
+    (wallet_service, node_service, loopback)
+}
+
+#[test]
+fn fee_params() {
+    // Fees of the wallet are out of date.
+    let (mut wallet, _node, _loopback) = new_wallet(1, 0);
+    let params = FeeParams {
+        payment_fee: 10,
+        stake_fee: 5,
+    };
+    wallet.on_fee_params(params);
+    assert_eq!(wallet.payment_fee, 10);
+    assert_eq!(wallet.stake_fee, 5);
+
+    // Re-staking can't pay the stake fee.
+    let e = wallet.restake_all().unwrap_err();
+    assert_eq!(
+        e.downcast::<WalletError>().unwrap(),
+        WalletError::RestakeFeeRequired(5)
+    );
+}
+
+#[test]
+fn unstake_locked() {
+    let (mut wallet, _node, _loopback) = new_wallet(1_000, 0);
+    let keys = wallet.keys.clone();
+    let amount: i64 = 1_000_000;
+    let output = Output::new_stake(
+        &keys.wallet_pkey,
+        &keys.network_skey,
+        &keys.network_pkey,
+        amount,
+    )
+    .unwrap();
+    wallet.on_output_created(0, output);
+    let active_until_epoch = wallet.stake_epochs;
+
+    // The stake is still active.
+    wallet.epoch = active_until_epoch;
+    let e = wallet.unstake(amount).unwrap_err();
+    assert_eq!(
+        e.downcast::<WalletError>().unwrap(),
+        WalletError::StakeIsLocked(active_until_epoch + 1)
+    );
+    let e = wallet.unstake_all().unwrap_err();
+    assert_eq!(
+        e.downcast::<WalletError>().unwrap(),
+        WalletError::StakeIsLocked(active_until_epoch + 1)
+    );
+
+    // The stake has expired.
+    wallet.epoch = active_until_epoch + 1;
+    wallet.unstake(amount).unwrap();
+    wallet.unstake_all().unwrap();
+}
+
 /*
 BalanceInfo {
     balance: i64,
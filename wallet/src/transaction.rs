@@ -23,9 +23,13 @@
 
 use crate::change::*;
 use crate::error::*;
+use crate::fee_schedule::{self, FeeSchedule};
 use crate::valueshuffle::ProposedUTXO;
 use failure::Error;
 use log::*;
+use rand::{thread_rng, Rng};
+use std::cmp;
+use std::convert::TryFrom;
 use stegos_blockchain::*;
 use stegos_crypto::curve1174::Fr;
 use stegos_crypto::curve1174::PublicKey;
@@ -33,6 +37,36 @@ use stegos_crypto::curve1174::SecretKey;
 use stegos_crypto::hash::Hash;
 use stegos_crypto::pbc;
 
+/// Calculate the total amount withdrawn from the wallet.
+fn withdrawn_amount(amount: i64, change: i64, fee: i64) -> Result<i64, WalletError> {
+    amount
+        .checked_add(change)
+        .and_then(|x| x.checked_add(fee))
+        .ok_or(WalletError::AmountOverflow)
+}
+
+/// Split the change into up to `count` non-zero parts with random weights.
+fn split_change(change: i64, count: usize) -> Result<Vec<i64>, WalletError> {
+    assert!(change > 0);
+    if count == 0 {
+        return Err(WalletError::InvalidChangeOutputs(count));
+    }
+    let count = cmp::min(count as i64, change) as usize;
+    let mut rng = thread_rng();
+    let weights: Vec<i64> = (0..count).map(|_| rng.gen_range(1, 101)).collect();
+    let total_weight: i64 = weights.iter().sum();
+
+    // Each part gets at least one unit, the rest is distributed by weights.
+    let rest = change - count as i64;
+    let mut parts: Vec<i64> = weights
+        .iter()
+        .map(|w| 1 + ((rest as i128) * (*w as i128) / (total_weight as i128)) as i64)
+        .collect();
+    let distributed: i64 = parts.iter().sum();
+    *parts.last_mut().unwrap() += change - distributed;
+    Ok(parts)
+}
+
 /// Create a new ValueShuffle payment transaction. (no data)
 pub(crate) fn create_vs_payment_transaction<'a, UnspentIter>(
     sender_pkey: &PublicKey,
@@ -63,9 +97,13 @@ where
     //
 
     trace!("Checking for available funds in the wallet...");
-    let fee = payment_fee;
-    let fee_change = fee + payment_fee;
-    let (inputs, fee, change) = find_utxo(unspent_iter, amount, fee, fee_change)?;
+    let FeeSchedule { fee, fee_change } = fee_schedule::payment(payment_fee, 1)?;
+    let CoinSelection {
+        inputs,
+        fee,
+        change,
+    } = select_coins(unspent_iter, amount, fee, fee_change)?;
+    let withdrawn = withdrawn_amount(amount, change, fee)?;
     let inputs: Vec<Output> = inputs
         .into_iter()
         .map(|o| Output::PaymentOutput(o.clone()))
@@ -74,11 +112,7 @@ where
 
     debug!(
         "Transaction preview: recipient={}, amount={}, withdrawn={}, change={}, fee={}",
-        recipient,
-        amount,
-        amount + change + fee,
-        change,
-        fee
+        recipient, amount, withdrawn, change, fee
     );
     let mut inputs_pairs = Vec::<(Hash, PaymentOutput)>::new();
     for input in &inputs {
@@ -132,11 +166,7 @@ where
 
     info!(
         "Created payment transaction: recipient={}, amount={}, withdrawn={}, change={}, fee={}",
-        recipient,
-        amount,
-        amount + change + fee,
-        change,
-        fee
+        recipient, amount, withdrawn, change, fee
     );
 
     Ok((inputs_pairs, outputs, fee))
@@ -150,6 +180,7 @@ pub(crate) fn create_payment_transaction<'a, UnspentIter>(
     amount: i64,
     payment_fee: i64,
     data: PaymentPayloadData,
+    change_outputs: usize,
 ) -> Result<(Vec<Output>, Vec<Output>, Fr, i64), Error>
 where
     UnspentIter: Iterator<Item = (&'a PaymentOutput, i64)>,
@@ -170,9 +201,13 @@ where
     //
 
     trace!("Checking for available funds in the wallet...");
-    let fee = payment_fee;
-    let fee_change = fee + payment_fee;
-    let (inputs, fee, change) = find_utxo(unspent_iter, amount, fee, fee_change)?;
+    let FeeSchedule { fee, fee_change } = fee_schedule::payment(payment_fee, change_outputs)?;
+    let CoinSelection {
+        inputs,
+        fee,
+        change,
+    } = select_coins(unspent_iter, amount, fee, fee_change)?;
+    let withdrawn = withdrawn_amount(amount, change, fee)?;
     let inputs: Vec<Output> = inputs
         .into_iter()
         .map(|o| Output::PaymentOutput(o.clone()))
@@ -181,11 +216,7 @@ where
 
     debug!(
         "Transaction preview: recipient={:?}, amount={}, withdrawn={}, change={}, fee={}",
-        recipient,
-        amount,
-        amount + change + fee,
-        change,
-        fee
+        recipient, amount, withdrawn, change, fee
     );
     for input in &inputs {
         debug!("Use UTXO: hash={}", Hash::digest(input));
@@ -195,7 +226,7 @@ where
     // Create outputs
     //
 
-    let mut outputs: Vec<Output> = Vec::<Output>::with_capacity(2);
+    let mut outputs: Vec<Output> = Vec::<Output>::with_capacity(1 + change_outputs);
 
     // Create an output for payment
     trace!("Creating change UTXO...");
@@ -209,28 +240,26 @@ where
     let mut gamma = gamma1;
 
     if change > 0 {
-        // Create an output for change
-        trace!("Creating change UTXO...");
-        let data = PaymentPayloadData::Comment("Change".to_string());
-        let (output2, gamma2) = PaymentOutput::with_payload(sender_pkey, change, data.clone())?;
-        info!(
-            "Created change UTXO: hash={}, recipient={}, change={}, data={:?}",
-            Hash::digest(&output2),
-            sender_pkey,
-            change,
-            data
-        );
-        outputs.push(Output::PaymentOutput(output2));
-        gamma += gamma2;
+        // Create outputs for change
+        for change in split_change(change, change_outputs)? {
+            trace!("Creating change UTXO...");
+            let data = PaymentPayloadData::Comment("Change".to_string());
+            let (output2, gamma2) = PaymentOutput::with_payload(sender_pkey, change, data.clone())?;
+            info!(
+                "Created change UTXO: hash={}, recipient={}, change={}, data={:?}",
+                Hash::digest(&output2),
+                sender_pkey,
+                change,
+                data
+            );
+            outputs.push(Output::PaymentOutput(output2));
+            gamma += gamma2;
+        }
     }
 
     info!(
         "Created payment transaction: recipient={}, amount={}, withdrawn={}, change={}, fee={}",
-        recipient,
-        amount,
-        amount + change + fee,
-        change,
-        fee
+        recipient, amount, withdrawn, change, fee
     );
 
     Ok((inputs, outputs, gamma, fee))
@@ -267,9 +296,13 @@ where
     //
 
     trace!("Checking for available funds in the wallet...");
-    let fee = stake_fee;
-    let fee_change = fee + payment_fee;
-    let (inputs, fee, change) = find_utxo(unspent_iter, amount, fee, fee_change)?;
+    let FeeSchedule { fee, fee_change } = fee_schedule::staking(payment_fee, stake_fee)?;
+    let CoinSelection {
+        inputs,
+        fee,
+        change,
+    } = select_coins(unspent_iter, amount, fee, fee_change)?;
+    let withdrawn = withdrawn_amount(amount, change, fee)?;
     let inputs: Vec<Output> = inputs
         .into_iter()
         .map(|o| Output::PaymentOutput(o.clone()))
@@ -281,7 +314,7 @@ where
         sender_pkey,
         validator_pkey,
         amount,
-        amount + change + fee,
+        withdrawn,
         change,
         fee
     );
@@ -330,7 +363,7 @@ where
         tx_hash,
         validator_pkey,
         amount,
-        amount + change + fee,
+        withdrawn,
         change,
         fee
     );
@@ -353,14 +386,16 @@ pub(crate) fn create_unstaking_transaction<'a, UnspentIter>(
 where
     UnspentIter: Iterator<Item = &'a StakeOutput>,
 {
-    if amount <= payment_fee {
-        return Err(WalletError::NegativeAmount(amount - payment_fee).into());
-    }
-
     debug!(
         "Creating a unstaking transaction: recipient={:?}, validator={:?}, amount={}",
         sender_pkey, validator_pkey, amount
     );
+    let amount = amount
+        .checked_sub(payment_fee)
+        .ok_or(WalletError::AmountOverflow)?;
+    if amount <= 0 {
+        return Err(WalletError::NegativeAmount(amount).into());
+    }
 
     //
     // Find inputs
@@ -368,9 +403,12 @@ where
 
     trace!("Checking for staked money in the wallet...");
     let unspent_iter = unspent_iter.map(|o| (o, o.amount));
-    let amount = amount - payment_fee;
-    let (inputs, fee, change) =
-        find_utxo(unspent_iter, amount, payment_fee, payment_fee + stake_fee)?;
+    let FeeSchedule { fee, fee_change } = fee_schedule::unstaking(payment_fee, stake_fee)?;
+    let CoinSelection {
+        inputs,
+        fee,
+        change,
+    } = select_coins(unspent_iter, amount, fee, fee_change)?;
     let inputs: Vec<Output> = inputs
         .into_iter()
         .map(|o| Output::StakeOutput(o.clone()))
@@ -431,6 +469,201 @@ where
     Ok(tx)
 }
 
+/// Create a transaction which merges the smallest payment UTXOs into one output.
+pub(crate) fn create_consolidation_transaction<'a, UnspentIter>(
+    sender_skey: &SecretKey,
+    sender_pkey: &PublicKey,
+    unspent_iter: UnspentIter,
+    max_inputs: usize,
+    payment_fee: i64,
+) -> Result<PaymentTransaction, Error>
+where
+    UnspentIter: Iterator<Item = (&'a PaymentOutput, i64)>,
+{
+    debug!(
+        "Creating a consolidation transaction: recipient={}, max_inputs={}",
+        sender_pkey, max_inputs
+    );
+
+    //
+    // Find inputs
+    //
+
+    trace!("Checking for available funds in the wallet...");
+    let (inputs, withdrawn) = find_smallest_utxo(unspent_iter, max_inputs)?;
+    if inputs.len() < 2 {
+        return Err(WalletError::NothingToConsolidate(inputs.len()).into());
+    }
+    let fee = payment_fee;
+    let amount = withdrawn
+        .checked_sub(fee)
+        .ok_or(WalletError::AmountOverflow)?;
+    if amount <= 0 {
+        return Err(WalletError::NotEnoughMoney.into());
+    }
+    let inputs: Vec<Output> = inputs
+        .into_iter()
+        .map(|o| Output::PaymentOutput(o.clone()))
+        .collect();
+
+    debug!(
+        "Transaction preview: recipient={}, amount={}, withdrawn={}, fee={}",
+        sender_pkey, amount, withdrawn, fee
+    );
+    for input in &inputs {
+        debug!("Use UTXO: hash={}", Hash::digest(input));
+    }
+
+    //
+    // Create outputs
+    //
+
+    trace!("Creating change UTXO...");
+    let data = PaymentPayloadData::Comment("Change".to_string());
+    let (output, gamma) = PaymentOutput::with_payload(sender_pkey, amount, data.clone())?;
+    info!(
+        "Created change UTXO: hash={}, recipient={}, change={}, data={:?}",
+        Hash::digest(&output),
+        sender_pkey,
+        amount,
+        data
+    );
+    let outputs = vec![Output::PaymentOutput(output)];
+
+    trace!("Signing transaction...");
+    let tx = PaymentTransaction::new(&sender_skey, &inputs, &outputs, gamma, fee)?;
+    let tx_hash = Hash::digest(&tx);
+    info!(
+        "Signed consolidation transaction: hash={}, inputs={}, amount={}, withdrawn={}, fee={}",
+        tx_hash,
+        inputs.len(),
+        amount,
+        withdrawn,
+        fee
+    );
+
+    Ok(tx)
+}
+
+/// Create a transaction which splits money into `parts` equal outputs to self.
+/// The transaction is limited by `max_utxo_in_tx` inputs and outputs.
+pub(crate) fn create_split_transaction<'a, UnspentIter>(
+    sender_skey: &SecretKey,
+    sender_pkey: &PublicKey,
+    unspent_iter: UnspentIter,
+    parts: usize,
+    amount_each: i64,
+    payment_fee: i64,
+    max_utxo_in_tx: usize,
+) -> Result<PaymentTransaction, Error>
+where
+    UnspentIter: Iterator<Item = (&'a PaymentOutput, i64)>,
+{
+    if parts < 2 {
+        return Err(WalletError::NothingToSplit(parts).into());
+    }
+    // At least one input is needed.
+    if parts >= max_utxo_in_tx {
+        return Err(WalletError::TooManyUTXO(parts + 1, max_utxo_in_tx).into());
+    }
+    if amount_each <= 0 {
+        return Err(WalletError::NegativeAmount(amount_each).into());
+    }
+
+    debug!(
+        "Creating a split transaction: recipient={}, parts={}, amount_each={}",
+        sender_pkey, parts, amount_each
+    );
+
+    //
+    // Find inputs
+    //
+
+    trace!("Checking for available funds in the wallet...");
+    let amount = i64::try_from(parts)
+        .ok()
+        .and_then(|parts| parts.checked_mul(amount_each))
+        .ok_or(WalletError::AmountOverflow)?;
+    let FeeSchedule { fee, fee_change } = fee_schedule::split(payment_fee, parts)?;
+    let CoinSelection {
+        inputs,
+        fee,
+        change,
+    } = select_coins(unspent_iter, amount, fee, fee_change)?;
+    let utxo_count = inputs.len() + parts + if change > 0 { 1 } else { 0 };
+    if utxo_count > max_utxo_in_tx {
+        return Err(WalletError::TooManyUTXO(utxo_count, max_utxo_in_tx).into());
+    }
+    let withdrawn = withdrawn_amount(amount, change, fee)?;
+    let inputs: Vec<Output> = inputs
+        .into_iter()
+        .map(|o| Output::PaymentOutput(o.clone()))
+        .collect();
+    assert!(!inputs.is_empty());
+
+    debug!(
+        "Transaction preview: recipient={}, amount={}, withdrawn={}, change={}, fee={}",
+        sender_pkey, amount, withdrawn, change, fee
+    );
+    for input in &inputs {
+        debug!("Use UTXO: hash={}", Hash::digest(input));
+    }
+
+    //
+    // Create outputs
+    //
+
+    let mut outputs: Vec<Output> = Vec::<Output>::with_capacity(parts + 1);
+    let mut gamma = Fr::zero();
+    for _ in 0..parts {
+        trace!("Creating split UTXO...");
+        let data = PaymentPayloadData::Comment("Split".to_string());
+        let (output, output_gamma) =
+            PaymentOutput::with_payload(sender_pkey, amount_each, data.clone())?;
+        info!(
+            "Created split UTXO: hash={}, recipient={}, amount={}, data={:?}",
+            Hash::digest(&output),
+            sender_pkey,
+            amount_each,
+            data
+        );
+        outputs.push(Output::PaymentOutput(output));
+        gamma += output_gamma;
+    }
+
+    if change > 0 {
+        trace!("Creating change UTXO...");
+        let data = PaymentPayloadData::Comment("Change".to_string());
+        let (output, output_gamma) =
+            PaymentOutput::with_payload(sender_pkey, change, data.clone())?;
+        info!(
+            "Created change UTXO: hash={}, recipient={}, change={}, data={:?}",
+            Hash::digest(&output),
+            sender_pkey,
+            change,
+            data
+        );
+        outputs.push(Output::PaymentOutput(output));
+        gamma += output_gamma;
+    }
+
+    trace!("Signing transaction...");
+    let tx = PaymentTransaction::new(&sender_skey, &inputs, &outputs, gamma, fee)?;
+    let tx_hash = Hash::digest(&tx);
+    info!(
+        "Signed split transaction: hash={}, inputs={}, parts={}, amount_each={}, withdrawn={}, change={}, fee={}",
+        tx_hash,
+        inputs.len(),
+        parts,
+        amount_each,
+        withdrawn,
+        change,
+        fee
+    );
+
+    Ok(tx)
+}
+
 /// Create a restaking transaction.
 pub(crate) fn create_restaking_transaction<'a, UnspentIter>(
     _sender_skey: &SecretKey,
@@ -488,6 +721,325 @@ pub mod tests {
     use stegos_crypto::curve1174::make_random_keys;
     use stegos_crypto::pbc;
 
+    /// Check that amounts near i64::MAX don't overflow.
+    #[test]
+    fn payment_amount_overflow() {
+        simple_logger::init_with_level(log::Level::Debug).unwrap_or_default();
+        let (_skey, pkey) = make_random_keys();
+        let (_recipient_skey, recipient_pkey) = make_random_keys();
+        let (output, _gamma) = PaymentOutput::new(&pkey, 100).expect("keys are valid");
+        let unspent: Vec<(PaymentOutput, i64)> = vec![(output, 100)];
+
+        // amount + fee overflows.
+        let e = create_payment_transaction(
+            &pkey,
+            &recipient_pkey,
+            unspent.iter().map(|(o, a)| (o, *a)),
+            i64::max_value() - 1,
+            2,
+            PaymentPayloadData::Comment("Test".to_string()),
+            1,
+        )
+        .unwrap_err();
+        assert_eq!(
+            e.downcast::<WalletError>().unwrap(),
+            WalletError::AmountOverflow
+        );
+
+        // fee + fee for change overflows.
+        let e = create_payment_transaction(
+            &pkey,
+            &recipient_pkey,
+            unspent.iter().map(|(o, a)| (o, *a)),
+            10,
+            i64::max_value() / 2 + 1,
+            PaymentPayloadData::Comment("Test".to_string()),
+            1,
+        )
+        .unwrap_err();
+        assert_eq!(
+            e.downcast::<WalletError>().unwrap(),
+            WalletError::AmountOverflow
+        );
+
+        // Still works for regular amounts.
+        create_payment_transaction(
+            &pkey,
+            &recipient_pkey,
+            unspent.iter().map(|(o, a)| (o, *a)),
+            10,
+            2,
+            PaymentPayloadData::Comment("Test".to_string()),
+            1,
+        )
+        .expect("tx is created");
+    }
+
+    /// Check that the change is split into several outputs.
+    #[test]
+    fn payment_change_split() {
+        simple_logger::init_with_level(log::Level::Debug).unwrap_or_default();
+        let payment_fee: i64 = 1;
+        let (skey, pkey) = make_random_keys();
+        let (_recipient_skey, recipient_pkey) = make_random_keys();
+        let (output, _gamma) = PaymentOutput::new(&pkey, 1000).expect("keys are valid");
+        let inputs = [Output::PaymentOutput(output.clone())];
+        let unspent: Vec<(PaymentOutput, i64)> = vec![(output, 1000)];
+
+        let amount: i64 = 100;
+        let change_outputs: usize = 3;
+        let (inputs2, outputs, gamma, fee) = create_payment_transaction(
+            &pkey,
+            &recipient_pkey,
+            unspent.iter().map(|(o, a)| (o, *a)),
+            amount,
+            payment_fee,
+            PaymentPayloadData::Comment("Test".to_string()),
+            change_outputs,
+        )
+        .expect("tx is created");
+        assert_eq!(fee, payment_fee * (1 + change_outputs as i64));
+        assert_eq!(outputs.len(), 1 + change_outputs);
+        let tx =
+            PaymentTransaction::new(&skey, &inputs2, &outputs, gamma, fee).expect("tx is signed");
+        tx.validate(&inputs).expect("tx is valid");
+
+        let mut change: i64 = 0;
+        for output in &outputs[1..] {
+            match output {
+                Output::PaymentOutput(o) => {
+                    let PaymentPayload { amount, .. } =
+                        o.decrypt_payload(&skey).expect("key is valid");
+                    assert!(amount > 0);
+                    change += amount;
+                }
+                _ => panic!("invalid tx"),
+            }
+        }
+        assert_eq!(change, 1000 - amount - fee);
+
+        // At least one change output is required.
+        let e = create_payment_transaction(
+            &pkey,
+            &recipient_pkey,
+            unspent.iter().map(|(o, a)| (o, *a)),
+            amount,
+            payment_fee,
+            PaymentPayloadData::Comment("Test".to_string()),
+            0,
+        )
+        .unwrap_err();
+        assert_eq!(
+            e.downcast::<WalletError>().unwrap(),
+            WalletError::InvalidChangeOutputs(0)
+        );
+    }
+
+    /// Check that small UTXOs are merged into one.
+    #[test]
+    fn consolidation_transaction() {
+        simple_logger::init_with_level(log::Level::Debug).unwrap_or_default();
+        let payment_fee: i64 = 1;
+        let (skey, pkey) = make_random_keys();
+
+        let mut inputs: Vec<Output> = Vec::new();
+        let mut unspent: Vec<(PaymentOutput, i64)> = Vec::new();
+        for amount in 1..=5 {
+            let amount = amount * 10;
+            let (output, _gamma) = PaymentOutput::new(&pkey, amount).expect("keys are valid");
+            inputs.push(Output::PaymentOutput(output.clone()));
+            unspent.push((output, amount));
+        }
+        let total: i64 = unspent.iter().map(|(_o, a)| *a).sum();
+
+        // Consolidate all outputs.
+        let tx = create_consolidation_transaction(
+            &skey,
+            &pkey,
+            unspent.iter().map(|(o, a)| (o, *a)),
+            10,
+            payment_fee,
+        )
+        .expect("tx is created");
+        tx.validate(&inputs).expect("tx is valid");
+        assert_eq!(tx.txins.len(), 5);
+        assert_eq!(tx.fee, payment_fee);
+        assert_eq!(tx.txouts.len(), 1);
+        match &tx.txouts[0] {
+            Output::PaymentOutput(o) => {
+                let PaymentPayload { amount, .. } = o.decrypt_payload(&skey).expect("key is valid");
+                assert_eq!(amount, total - payment_fee);
+            }
+            _ => panic!("invalid tx"),
+        }
+
+        // Consolidate the smallest outputs.
+        let tx = create_consolidation_transaction(
+            &skey,
+            &pkey,
+            unspent.iter().map(|(o, a)| (o, *a)),
+            2,
+            payment_fee,
+        )
+        .expect("tx is created");
+        tx.validate(&inputs[0..2]).expect("tx is valid");
+        assert_eq!(
+            tx.txins,
+            vec![Hash::digest(&inputs[0]), Hash::digest(&inputs[1])]
+        );
+
+        // Not enough inputs.
+        let e = create_consolidation_transaction(
+            &skey,
+            &pkey,
+            unspent.iter().map(|(o, a)| (o, *a)),
+            1,
+            payment_fee,
+        )
+        .unwrap_err();
+        assert_eq!(
+            e.downcast::<WalletError>().unwrap(),
+            WalletError::NothingToConsolidate(1)
+        );
+
+        // Fee is not covered.
+        let e = create_consolidation_transaction(
+            &skey,
+            &pkey,
+            unspent.iter().map(|(o, a)| (o, *a)),
+            2,
+            30,
+        )
+        .unwrap_err();
+        assert_eq!(
+            e.downcast::<WalletError>().unwrap(),
+            WalletError::NotEnoughMoney
+        );
+    }
+
+    /// Check that a UTXO is split into equal parts.
+    #[test]
+    fn split_transaction() {
+        simple_logger::init_with_level(log::Level::Debug).unwrap_or_default();
+        let payment_fee: i64 = 1;
+        let max_utxo_in_tx: usize = 10;
+        let (skey, pkey) = make_random_keys();
+        let (output, _gamma) = PaymentOutput::new(&pkey, 1000).expect("keys are valid");
+        let inputs = [Output::PaymentOutput(output.clone())];
+        let unspent: Vec<(PaymentOutput, i64)> = vec![(output, 1000)];
+
+        // Split with change.
+        let parts: usize = 5;
+        let amount_each: i64 = 100;
+        let tx = create_split_transaction(
+            &skey,
+            &pkey,
+            unspent.iter().map(|(o, a)| (o, *a)),
+            parts,
+            amount_each,
+            payment_fee,
+            max_utxo_in_tx,
+        )
+        .expect("tx is created");
+        tx.validate(&inputs).expect("tx is valid");
+        assert_eq!(tx.txouts.len(), parts + 1);
+        assert_eq!(tx.fee, payment_fee * (parts as i64 + 1));
+        let mut amounts: Vec<i64> = Vec::new();
+        for output in &tx.txouts {
+            match output {
+                Output::PaymentOutput(o) => {
+                    let PaymentPayload { amount, .. } =
+                        o.decrypt_payload(&skey).expect("key is valid");
+                    amounts.push(amount);
+                }
+                _ => panic!("invalid tx"),
+            }
+        }
+        assert_eq!(amounts.iter().filter(|a| **a == amount_each).count(), parts);
+        let change: i64 = amounts.iter().sum::<i64>() - amount_each * parts as i64;
+        assert_eq!(change, 1000 - amount_each * parts as i64 - tx.fee);
+
+        // Split without change.
+        let amount_each: i64 = (1000 - payment_fee * parts as i64) / parts as i64;
+        let tx = create_split_transaction(
+            &skey,
+            &pkey,
+            unspent.iter().map(|(o, a)| (o, *a)),
+            parts,
+            amount_each,
+            payment_fee,
+            max_utxo_in_tx,
+        )
+        .expect("tx is created");
+        tx.validate(&inputs).expect("tx is valid");
+        assert_eq!(tx.txouts.len(), parts);
+        assert_eq!(tx.fee, payment_fee * parts as i64);
+
+        // Too few parts.
+        let e = create_split_transaction(
+            &skey,
+            &pkey,
+            unspent.iter().map(|(o, a)| (o, *a)),
+            1,
+            100,
+            payment_fee,
+            max_utxo_in_tx,
+        )
+        .unwrap_err();
+        assert_eq!(
+            e.downcast::<WalletError>().unwrap(),
+            WalletError::NothingToSplit(1)
+        );
+
+        // Fee is not covered.
+        let e = create_split_transaction(
+            &skey,
+            &pkey,
+            unspent.iter().map(|(o, a)| (o, *a)),
+            parts,
+            200,
+            payment_fee,
+            max_utxo_in_tx,
+        )
+        .unwrap_err();
+        assert_eq!(
+            e.downcast::<WalletError>().unwrap(),
+            WalletError::NotEnoughMoney
+        );
+
+        // Too many parts for a single input.
+        let e = create_split_transaction(
+            &skey,
+            &pkey,
+            unspent.iter().map(|(o, a)| (o, *a)),
+            max_utxo_in_tx,
+            1,
+            payment_fee,
+            max_utxo_in_tx,
+        )
+        .unwrap_err();
+        assert_eq!(
+            e.downcast::<WalletError>().unwrap(),
+            WalletError::TooManyUTXO(max_utxo_in_tx + 1, max_utxo_in_tx)
+        );
+
+        // The change output doesn't fit.
+        let e = create_split_transaction(
+            &skey,
+            &pkey,
+            unspent.iter().map(|(o, a)| (o, *a)),
+            parts,
+            100,
+            payment_fee,
+            parts + 1,
+        )
+        .unwrap_err();
+        assert_eq!(
+            e.downcast::<WalletError>().unwrap(),
+            WalletError::TooManyUTXO(parts + 2, parts + 1)
+        );
+    }
+
     /// Check transaction signing and validation.
     #[test]
     fn unstaking_transactions() {
@@ -594,6 +1146,23 @@ pub mod tests {
             _ => panic!(),
         }
 
+        // amount - PAYMENT_FEE overflows.
+        let e = create_unstaking_transaction(
+            &skey,
+            &pkey,
+            &validator_pkey,
+            &validator_skey,
+            unspent.iter(),
+            i64::min_value(),
+            payment_fee,
+            stake_fee,
+        )
+        .unwrap_err();
+        assert_eq!(
+            e.downcast::<WalletError>().unwrap(),
+            WalletError::AmountOverflow
+        );
+
         // Try to re-stake zero.
         let unstake = stake - stake_fee;
         let e = create_unstaking_transaction(
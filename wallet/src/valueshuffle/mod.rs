@@ -328,7 +328,7 @@ impl ValueShuffle {
         participant_pkey: ParticipantID,
         network: Network,
         node: Node,
-    ) -> ValueShuffle {
+    ) -> Result<ValueShuffle, Error> {
         //
         // State.
         //
@@ -344,21 +344,19 @@ impl ValueShuffle {
 
         // Network.
         let pool_formed = network
-            .subscribe_unicast(VALUE_SHUFFLE_TOPIC)
-            .expect("connected")
+            .subscribe_unicast(VALUE_SHUFFLE_TOPIC)?
             .map(|m| ValueShuffleEvent::MessageReceived(m.from, m.data));
         events.push(Box::new(pool_formed));
 
         // Facilitator elections.
         let facilitator_changed = node
-            .subscribe_epoch_changed()
+            .subscribe_epoch_changed()?
             .map(|epoch| ValueShuffleEvent::FacilitatorChanged(epoch.facilitator));
         events.push(Box::new(facilitator_changed));
 
         // Pool formation.
         let pool_formed = network
-            .subscribe_unicast(POOL_ANNOUNCE_TOPIC)
-            .expect("connected")
+            .subscribe_unicast(POOL_ANNOUNCE_TOPIC)?
             .map(|m| ValueShuffleEvent::PoolFormed(m.from, m.data));
         events.push(Box::new(pool_formed));
 
@@ -371,7 +369,7 @@ impl ValueShuffle {
 
         let events = select_all(events);
 
-        ValueShuffle {
+        Ok(ValueShuffle {
             skey: skey.clone(),
             facilitator_pkey,
             state,
@@ -415,7 +413,7 @@ impl ValueShuffle {
             serialized_utxo_size: None,
             dicemix_nbr_utxo_chunks: None,
             pending_removals: Vec::new(),
-        }
+        })
     }
 
     /// Called by Wallet.